@@ -1,25 +1,530 @@
-use std::path::Path;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::env;
 
+#[path = "tools/mdrs-rom/src/rom.rs"]
+mod rom;
+#[path = "tools/mdrs-rom/src/toolchain.rs"]
+mod toolchain;
+
+use rom::HeaderPatch;
+use toolchain::Toolchain;
+
+/// Where `include_image!` paths are relative to.
+const IMAGE_DIR: &str = "src/assets";
+
+/// Lists the assets `include_assets!` turns into consts, with paths relative to the crate root.
+const ASSET_MANIFEST: &str = "assets.toml";
+
+/// When set to a directory, every example is also built and written there as `<name>.bin`.
+const EXAMPLE_ROMS_VAR: &str = "MDRS_EXAMPLE_ROMS";
+
 pub fn main() {
     let out_dir = env::var("OUT_DIR").unwrap();
 
-    // Note that there are a number of downsides to this approach, the comments
-    // below detail how to improve the portability of these commands.
-    Command::new("m68k-linux-gnu-gcc").args(&["src/header.S", "-c", "-o"])
-        .arg(&format!("{}/header.o", out_dir))
-        .status().unwrap();
-    Command::new("m68k-linux-gnu-gcc").args(&["src/sys/libc.S", "-c", "-o"])
-        .arg(&format!("{}/libc.o", out_dir))
-        .status().unwrap();
-    Command::new("m68k-linux-gnu-ar").args(&["crus", "libheader.a", "header.o", "libc.o"])
-        .current_dir(&Path::new(&out_dir))
-        .status().unwrap();
+    let toolchain = Toolchain::find();
+    toolchain.run("gcc", |gcc| {
+        gcc.args(["src/header.S", "-c", "-o"]).arg(Path::new(&out_dir).join("header.o"));
+    });
+    toolchain.run("ar", |ar| {
+        ar.args(["crus", "libheader.a", "header.o"]).current_dir(&out_dir);
+    });
+
+    convert_images(Path::new(IMAGE_DIR), &Path::new(&out_dir).join("images"));
+    build_assets(Path::new(ASSET_MANIFEST), Path::new(&out_dir));
+    Layout::load(Path::new("Cargo.toml")).write(Path::new(&out_dir));
 
     println!("cargo::rustc-link-search=native={}", out_dir);
     println!("cargo::rustc-link-lib=static=header");
     println!("cargo::rerun-if-changed=src/header.S");
-    println!("cargo::rerun-if-changed=src/sys/libc_a.S");
+    println!("cargo::rerun-if-changed={}", IMAGE_DIR);
+    println!("cargo::rerun-if-changed={}", ASSET_MANIFEST);
+    println!("cargo::rerun-if-changed=Cargo.toml");
     println!("cargo::rerun-if-changed=build.rs");
-}
\ No newline at end of file
+    println!("cargo::rerun-if-changed=tools/mdrs-rom/src");
+    println!("cargo::rerun-if-env-changed={}", toolchain::PREFIX_VAR);
+    println!("cargo::rerun-if-env-changed=PATH");
+
+    for var in [EXAMPLE_ROMS_VAR, rom::TITLE_VAR, rom::REGION_VAR] {
+        println!("cargo::rerun-if-env-changed={}", var);
+    }
+    if let Some(rom_dir) = env::var_os(EXAMPLE_ROMS_VAR) {
+        println!("cargo::rerun-if-changed=examples");
+        let patch = HeaderPatch::from_env();
+        build_example_roms(&toolchain, &patch, Path::new(&rom_dir), &Path::new(&out_dir).join("examples"));
+    }
+}
+
+/// The cartridge's ROM space without a mapper, and work RAM, which ends at the top of the address
+/// space.
+const ROM_SIZE: u32 = 0x400000;
+const RAM_START: u32 = 0xFF0000;
+const RAM_END: u32 = 0x1000000;
+
+/// How work RAM is divided up, from `[package.metadata.mdrs]` in Cargo.toml:
+///
+/// ```toml
+/// [package.metadata.mdrs]
+/// stack-size = 0x2000       # The default
+/// heap-size = 0x4000        # Everything between .noinit and the stack if left out
+/// persistent-size = 0x100   # RAM kept across resets, after the .noinit statics
+/// ```
+struct Layout {
+    stack_size: u32,
+    heap_size: Option<u32>,
+    persistent_size: u32,
+}
+
+impl Layout {
+    fn load(manifest: &Path) -> Self {
+        let text = fs::read_to_string(manifest).unwrap();
+        let cargo: toml::Table = text.parse().unwrap_or_else(|err| panic!("{}: {}", manifest.display(), err));
+        let config = cargo.get("package")
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| metadata.get("mdrs"))
+            .and_then(toml::Value::as_table);
+
+        let mut layout = Self { stack_size: 0x2000, heap_size: None, persistent_size: 0 };
+        for (key, value) in config.into_iter().flatten() {
+            let size = value.as_integer()
+                .and_then(|size| u32::try_from(size).ok())
+                .filter(|&size| size <= RAM_END - RAM_START)
+                .unwrap_or_else(|| panic!("package.metadata.mdrs.{}: expected a size in bytes, up to 64KB", key));
+            match key.as_str() {
+                "stack-size" => layout.stack_size = size,
+                "heap-size" => layout.heap_size = Some(size),
+                "persistent-size" => layout.persistent_size = size,
+                _ => panic!("package.metadata.mdrs.{}: unknown setting", key),
+            }
+        }
+
+        if layout.stack_size < 0x100 || layout.stack_size % 4 != 0 {
+            panic!("package.metadata.mdrs.stack-size: has to be a multiple of 4, and at least 256 bytes");
+        }
+        if layout.heap_size.is_some_and(|size| size == 0 || size % 2 != 0) {
+            panic!("package.metadata.mdrs.heap-size: has to be a non-zero multiple of 2");
+        }
+        if layout.persistent_size % 4 != 0 {
+            panic!("package.metadata.mdrs.persistent-size: has to be a multiple of 4");
+        }
+        let reserved = layout.stack_size + layout.heap_size.unwrap_or(0) + layout.persistent_size;
+        if reserved > RAM_END - RAM_START {
+            panic!("package.metadata.mdrs: the stack, heap and persistent RAM add up to more than 64KB");
+        }
+        layout
+    }
+
+    /// Writes `memory.ld`, which `megadrive.ld` includes, and `layout.rs` for `sys::layout`.
+    ///
+    /// Whether the sections fit is left to the linker script's asserts, since only the linker knows
+    /// how big .data and .bss are.
+    fn write(&self, out_dir: &Path) {
+        let mut script = String::new();
+        writeln!(script, "/* Generated by build.rs from [package.metadata.mdrs] in Cargo.toml. */").unwrap();
+        writeln!(script, "MEMORY\n{{").unwrap();
+        writeln!(script, "    ROM (rx) : ORIGIN = 0, LENGTH = {:#X}", ROM_SIZE).unwrap();
+        writeln!(script, "    RAM (rwx) : ORIGIN = {:#X}, LENGTH = {:#X}", RAM_START, RAM_END - RAM_START).unwrap();
+        writeln!(script, "}}\n").unwrap();
+        writeln!(script, "_stack_top = {:#X};", RAM_END).unwrap();
+        writeln!(script, "_stack_bottom = _stack_top - {:#X};", self.stack_size).unwrap();
+        writeln!(script, "_persistent_size = {:#X};", self.persistent_size).unwrap();
+        if let Some(heap_size) = self.heap_size {
+            writeln!(script, "_heap_size = {:#X};", heap_size).unwrap();
+        }
+        fs::write(out_dir.join("memory.ld"), script).unwrap();
+
+        let consts = format!(
+            "pub(super) const STACK_SIZE: usize = {:#X};\n\
+             pub(super) const HEAP_SIZE: Option<usize> = {:?};\n\
+             pub(super) const PERSISTENT_SIZE: usize = {:#X};\n",
+            self.stack_size, self.heap_size, self.persistent_size,
+        );
+        fs::write(out_dir.join("layout.rs"), consts).unwrap();
+    }
+}
+
+/// Builds each example as a finished ROM in `rom_dir`.
+fn build_example_roms(toolchain: &Toolchain, patch: &HeaderPatch, rom_dir: &Path, target_dir: &Path) {
+    fs::create_dir_all(rom_dir).unwrap();
+
+    let mut names: Vec<_> = fs::read_dir("examples").unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rs"))
+        .map(|path| path.file_stem().unwrap().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+
+    for name in names {
+        build_rom(toolchain, patch, &["--example", &name], &rom_dir.join(format!("{}.bin", name)), target_dir);
+    }
+}
+
+/// Builds one target, picked by `target` such as `["--example", "sprites"]`, with a nested cargo,
+/// then strips it down to a raw ROM at `rom` and finishes it with `finish_rom`.
+///
+/// The nested build gets its own target directory, since this build holds the lock on the main one.
+fn build_rom(toolchain: &Toolchain, patch: &HeaderPatch, target: &[&str; 2], rom: &Path, target_dir: &Path) {
+    let cargo = env::var("CARGO").unwrap();
+    let release = env::var("PROFILE").unwrap() == "release";
+    let profile_dir = if release { "release" } else { "debug" };
+
+    let mut build = Command::new(&cargo);
+    build.arg("build").args(target).arg("--target-dir").arg(target_dir);
+    build.env_remove(EXAMPLE_ROMS_VAR);
+    if release {
+        build.arg("--release");
+    }
+    if !build.status().unwrap().success() {
+        panic!("failed to build {}", target[1]);
+    }
+
+    let mut elf = target_dir.join("m68k-none-eabi").join(profile_dir);
+    if target[0] == "--example" {
+        elf.push("examples");
+    }
+    elf.push(target[1]);
+    if let Some(dir) = rom.parent() {
+        fs::create_dir_all(dir).unwrap();
+    }
+    toolchain.run("objcopy", |objcopy| {
+        objcopy.args(["-O", "binary"]).arg(&elf).arg(rom);
+    });
+
+    let mut data = fs::read(rom).unwrap();
+    rom::finish_rom(&mut data, patch).unwrap_or_else(|err| panic!("{}: {}", rom.display(), err));
+    fs::write(rom, data).unwrap();
+}
+
+/// Converts every PNG under `src` to 4bpp tiles and a palette for `include_image!`.
+///
+/// For `src/assets/foo.png`, this writes `foo.png.tiles`, the raw tile data in row-major order,
+/// and `foo.png.pal`, the width and height in tiles followed by the 16 palette colors, all as
+/// big-endian words.
+fn convert_images(src: &Path, dst: &Path) {
+    let Ok(entries) = fs::read_dir(src) else { return };
+    fs::create_dir_all(dst).unwrap();
+
+    for entry in entries {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap();
+        if path.is_dir() {
+            convert_images(&path, &dst.join(name));
+            continue;
+        }
+        if !path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")) {
+            continue;
+        }
+
+        println!("cargo::rerun-if-changed={}", path.display());
+        let image = ConvertedImage::load(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+
+        let mut tiles_name = name.to_owned();
+        tiles_name.push(".tiles");
+        let mut pal_name = name.to_owned();
+        pal_name.push(".pal");
+        image.write(&dst.join(tiles_name), &dst.join(pal_name));
+    }
+}
+
+/// Converts the assets listed in the manifest, and writes `assets.rs` to `out_dir`, with a const
+/// for each of them, for `include_assets!`.
+///
+/// The manifest has a table for each kind of asset, naming each one and giving its file:
+///
+/// ```toml
+/// [images]
+/// player = "src/assets/player.png"
+///
+/// [palettes]
+/// title = "src/assets/title.png"  # The palette the image converts to
+/// hud = "src/assets/hud.pal"      # 16 big-endian CRAM words
+///
+/// [maps]
+/// level1 = { path = "src/assets/level1.map", width = 128, height = 32 }
+///
+/// [audio]
+/// theme = "src/assets/theme.vgm"  # VGM files become a `Song`, anything else stays bytes
+/// ```
+///
+/// A missing or malformed asset fails the build with its name and path, rather than leaving it to
+/// `include_bytes!`. Without a manifest, `assets.rs` is empty.
+fn build_assets(manifest: &Path, out_dir: &Path) {
+    let mut code = String::new();
+    if manifest.exists() {
+        let text = fs::read_to_string(manifest).unwrap();
+        let tables: toml::Table = text.parse().unwrap_or_else(|err| panic!("{}: {}", manifest.display(), err));
+        let converted = out_dir.join("assets");
+        fs::create_dir_all(&converted).unwrap();
+
+        for (kind, entries) in &tables {
+            let entries = entries.as_table()
+                .unwrap_or_else(|| panic!("{}: [{}] should be a table of assets", manifest.display(), kind));
+            for (name, entry) in entries {
+                let asset = Asset::parse(manifest, kind, name, entry);
+                println!("cargo::rerun-if-changed={}", asset.path.display());
+                asset.generate(kind, &converted, &mut code).unwrap_or_else(|err| panic!("{}: {}", asset.context, err));
+            }
+        }
+    }
+    fs::write(out_dir.join("assets.rs"), code).unwrap();
+}
+
+/// One entry in the asset manifest.
+struct Asset {
+    /// The const the asset becomes.
+    ident: String,
+    /// The file, as an absolute path, since `include_bytes!` in the generated code is relative to
+    /// `OUT_DIR`.
+    path: PathBuf,
+    /// The width and height of a map, in tiles.
+    size: Option<(u16, u16)>,
+    /// Where the entry is in the manifest, for errors.
+    context: String,
+}
+
+impl Asset {
+    fn parse(manifest: &Path, kind: &str, name: &str, entry: &toml::Value) -> Self {
+        let context = format!("{}: {}.{}", manifest.display(), kind, name);
+        if name.is_empty()
+            || name.starts_with(|c: char| c.is_ascii_digit())
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            panic!("{}: asset names have to be valid identifiers", context);
+        }
+
+        let (path, table) = match entry {
+            toml::Value::String(path) => (path.as_str(), None),
+            toml::Value::Table(table) => match table.get("path").and_then(toml::Value::as_str) {
+                Some(path) => (path, Some(table)),
+                None => panic!("{}: missing `path`", context),
+            },
+            _ => panic!("{}: expected a path, or a table with one", context),
+        };
+        let dimension = |key: &str| -> u16 {
+            table.and_then(|table| table.get(key)).and_then(toml::Value::as_integer)
+                .and_then(|value| u16::try_from(value).ok())
+                .unwrap_or_else(|| panic!("{}: maps need a `{}` in tiles", context, key))
+        };
+        let size = (kind == "maps").then(|| (dimension("width"), dimension("height")));
+
+        let path = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join(path);
+        if !path.is_file() {
+            panic!("{}: {} doesn't exist", context, path.display());
+        }
+        Self { ident: name.to_ascii_uppercase(), path, size, context }
+    }
+
+    /// Converts the asset if it needs to be, and appends its const to `code`.
+    fn generate(&self, kind: &str, converted: &Path, code: &mut String) -> Result<(), String> {
+        let ident = &self.ident;
+        let is_png = self.path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+        match kind {
+            "images" => {
+                if !is_png {
+                    return Err("images have to be PNGs".into());
+                }
+                let tiles = converted.join(format!("{}.tiles", ident));
+                let meta = converted.join(format!("{}.meta", ident));
+                ConvertedImage::load(&self.path)?.write(&tiles, &meta);
+                writeln!(
+                    code,
+                    "pub const {}: mdrs::sys::vdp::Image = mdrs::sys::vdp::Image::from_parts(\
+                     mdrs::include_bytes_aligned_as!(mdrs::sys::vdp::Tile, {:?}), include_bytes!({:?}));",
+                    ident, tiles.display(), meta.display(),
+                )
+            }
+            "palettes" => {
+                let palette = if is_png {
+                    let palette = converted.join(format!("{}.pal", ident));
+                    let colors: Vec<u8> = ConvertedImage::load(&self.path)?.palette.iter()
+                        .flat_map(|color| color.to_be_bytes())
+                        .collect();
+                    fs::write(&palette, colors).unwrap();
+                    palette
+                } else {
+                    check_size(&self.path, 32, "a palette of 16 colors")?;
+                    self.path.clone()
+                };
+                writeln!(code, "pub const {}: &mdrs::sys::vdp::Palette = mdrs::include_palette!({:?});", ident, palette.display())
+            }
+            "maps" => {
+                let (width, height) = self.size.unwrap();
+                check_size(&self.path, width as u64 * height as u64 * 2, &format!("{} by {} tiles", width, height))?;
+                writeln!(
+                    code,
+                    "pub const {}: mdrs::gfx::TileMap = mdrs::gfx::TileMap::new(\
+                     mdrs::include_bytes_aligned_as!(mdrs::sys::vdp::TileFlags, {:?}), {}, {});",
+                    ident, self.path.display(), width, height,
+                )
+            }
+            "audio" if self.path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("vgm")) => {
+                if env::var_os("CARGO_FEATURE_AUDIO").is_none() {
+                    return Err("VGM files need the `audio` feature".into());
+                }
+                let data = fs::read(&self.path).unwrap();
+                if !data.starts_with(b"Vgm ") || data.len() <= 0x40 {
+                    return Err(format!("{} isn't a VGM file", self.path.display()));
+                }
+                writeln!(
+                    code,
+                    "pub const {}: mdrs::sys::audio::music::Song = match mdrs::sys::audio::music::Song::from_vgm(include_bytes!({:?})) {{ \
+                     Some(song) => song, None => panic!(\"bad VGM file\") }};",
+                    ident, self.path.display(),
+                )
+            }
+            "audio" => writeln!(code, "pub const {}: &[u8] = include_bytes!({:?});", ident, self.path.display()),
+            _ => return Err(format!("unknown kind of asset `{}`, expected images, palettes, maps or audio", kind)),
+        }
+        .unwrap();
+        Ok(())
+    }
+}
+
+fn check_size(path: &Path, expected: u64, what: &str) -> Result<(), String> {
+    let len = fs::metadata(path).unwrap().len();
+    if len != expected {
+        return Err(format!("{} is {} bytes, which isn't {}", path.display(), len, what));
+    }
+    Ok(())
+}
+
+struct ConvertedImage {
+    width_tiles: u16,
+    height_tiles: u16,
+    palette: [u16; 16],
+    tiles: Vec<u8>,
+}
+
+/// Reduces an 8 bit per component color to CRAM's format.
+fn to_cram([r, g, b, _]: [u8; 4]) -> u16 {
+    (((b >> 5) as u16) << 9) | (((g >> 5) as u16) << 5) | (((r >> 5) as u16) << 1)
+}
+
+fn cram_distance(a: u16, b: u16) -> u32 {
+    (0..3).map(|i| {
+        let shift = 1 + i * 4;
+        let d = ((a >> shift) & 7) as i32 - ((b >> shift) & 7) as i32;
+        (d * d) as u32
+    }).sum()
+}
+
+fn is_transparent(pixel: [u8; 4]) -> bool {
+    pixel[3] < 0x80
+}
+
+impl ConvertedImage {
+    /// Writes the raw tile data to `tiles`, and the width and height in tiles followed by the 16
+    /// palette colors to `meta`, all as big-endian words.
+    fn write(&self, tiles: &Path, meta: &Path) {
+        fs::write(tiles, &self.tiles).unwrap();
+
+        let mut data = Vec::with_capacity(36);
+        data.extend_from_slice(&self.width_tiles.to_be_bytes());
+        data.extend_from_slice(&self.height_tiles.to_be_bytes());
+        for color in self.palette {
+            data.extend_from_slice(&color.to_be_bytes());
+        }
+        fs::write(meta, data).unwrap();
+    }
+
+    fn load(path: &Path) -> Result<Self, String> {
+        let mut decoder = png::Decoder::new(File::open(path).map_err(|err| err.to_string())?);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(|err| err.to_string())?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buf).map_err(|err| err.to_string())?;
+        let (width, height) = (frame.width as usize, frame.height as usize);
+
+        let pixels: Vec<[u8; 4]> = match frame.color_type {
+            png::ColorType::Rgba => buf.chunks_exact(4).map(|p| [p[0], p[1], p[2], p[3]]).collect(),
+            png::ColorType::Rgb => buf.chunks_exact(3).map(|p| [p[0], p[1], p[2], 0xFF]).collect(),
+            png::ColorType::GrayscaleAlpha => buf.chunks_exact(2).map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+            png::ColorType::Grayscale => buf.iter().map(|&p| [p, p, p, 0xFF]).collect(),
+            png::ColorType::Indexed => return Err("unexpected indexed output".into()),
+        };
+        let pixels = &pixels[..width * height];
+
+        // Indexed images with 16 colors or fewer keep their palette order, so index 0 stays the
+        // transparent color the artist chose. Anything else gets quantized.
+        let info = reader.info();
+        let (palette, indices) = match (&info.palette, info.color_type) {
+            (Some(source), png::ColorType::Indexed) if source.len() <= 16 * 3 => {
+                let trns = info.trns.as_deref().unwrap_or(&[]);
+                let entries: Vec<[u8; 4]> = source.chunks_exact(3).enumerate()
+                    .map(|(i, c)| [c[0], c[1], c[2], trns.get(i).copied().unwrap_or(0xFF)])
+                    .collect();
+                let mut palette = [0u16; 16];
+                for (slot, &entry) in palette.iter_mut().zip(&entries) {
+                    *slot = to_cram(entry);
+                }
+                let indices = pixels.iter()
+                    .map(|&p| entries.iter().position(|&e| e == p).unwrap_or(0) as u8)
+                    .collect();
+                (palette, indices)
+            }
+            _ => Self::quantize(pixels),
+        };
+
+        let width_tiles = width.div_ceil(8);
+        let height_tiles = height.div_ceil(8);
+        if width_tiles > u16::MAX as usize || height_tiles > u16::MAX as usize {
+            return Err("image is too big".into());
+        }
+
+        // Pixels outside the image, when it isn't a whole number of tiles, are transparent.
+        let index_at = |x: usize, y: usize| -> u8 {
+            if x < width && y < height { indices[y * width + x] } else { 0 }
+        };
+
+        let mut tiles = Vec::with_capacity(width_tiles * height_tiles * 32);
+        for ty in 0..height_tiles {
+            for tx in 0..width_tiles {
+                for row in 0..8 {
+                    for col in (0..8).step_by(2) {
+                        let (x, y) = (tx * 8 + col, ty * 8 + row);
+                        tiles.push((index_at(x, y) << 4) | index_at(x + 1, y));
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            width_tiles: width_tiles as u16,
+            height_tiles: height_tiles as u16,
+            palette,
+            tiles,
+        })
+    }
+
+    /// Picks the 15 most used colors, after reducing them to CRAM's format, and maps every other
+    /// color to the nearest of them. Index 0 is kept for transparent pixels.
+    fn quantize(pixels: &[[u8; 4]]) -> ([u16; 16], Vec<u8>) {
+        let mut counts: HashMap<u16, usize> = HashMap::new();
+        for &pixel in pixels.iter().filter(|&&p| !is_transparent(p)) {
+            *counts.entry(to_cram(pixel)).or_default() += 1;
+        }
+
+        let mut colors: Vec<(u16, usize)> = counts.into_iter().collect();
+        colors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        colors.truncate(15);
+
+        let mut palette = [0u16; 16];
+        for (slot, &(color, _)) in palette[1..].iter_mut().zip(&colors) {
+            *slot = color;
+        }
+
+        let indices = pixels.iter().map(|&pixel| {
+            if is_transparent(pixel) || colors.is_empty() {
+                return 0;
+            }
+            let color = to_cram(pixel);
+            let nearest = (0..colors.len()).min_by_key(|&i| cram_distance(colors[i].0, color)).unwrap();
+            nearest as u8 + 1
+        }).collect();
+
+        (palette, indices)
+    }
+}
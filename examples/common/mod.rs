@@ -0,0 +1,37 @@
+//! Setup shared by the example ROMs.
+
+use mdrs::include_tiles;
+use mdrs::sys::vdp;
+
+/// An ASCII font, with each character at its own tile index.
+pub const FONT: &[vdp::Tile] = include_tiles!("../../src/assets/font4bpp.bin");
+
+pub const PALETTE: &vdp::Palette = &[
+    vdp::Color::BLACK, vdp::Color::BLUE, vdp::Color::GREEN, vdp::Color::RED,
+    vdp::Color::new(0, 7, 7), vdp::Color::new(7, 0, 7), vdp::Color::new(7, 7, 0),
+    vdp::Color::new(0, 0, 4), vdp::Color::new(0, 4, 0), vdp::Color::new(4, 0, 0),
+    vdp::Color::new(0, 4, 4), vdp::Color::new(4, 0, 4), vdp::Color::new(4, 4, 0),
+    vdp::Color::new(3, 3, 3), vdp::Color::new(5, 5, 5), vdp::Color::WHITE,
+];
+
+/// Clears VRAM and loads the font and palette, applying `settings` first.
+pub fn init(settings: vdp::Settings) -> vdp::Settings {
+    settings.apply::<true>();
+
+    vdp::DMACommand::new_fill(vdp::VRAMAddress::from_word_addr(0), 0x10000, 0, None).schedule().map_err(|_| ()).unwrap();
+    vdp::VDP::wait_for_vblank(None);
+
+    vdp::DMACommand::new_transfer(PALETTE, vdp::Address::CRAM(0), None).schedule().map_err(|_| ()).unwrap();
+    vdp::DMACommand::new_transfer(FONT, vdp::Address::VRAM(vdp::VRAMAddress::from_tile_index(0)), None).schedule().map_err(|_| ()).unwrap();
+    vdp::VDP::wait_for_vblank(None);
+
+    settings
+}
+
+/// Writes `text` to plane A, starting at the tile `x`, `y`.
+pub fn print(settings: &vdp::Settings, x: u8, y: u8, text: &[u8]) {
+    for (i, &c) in text.iter().enumerate() {
+        vdp::Writer::new(vdp::Address::VRAM(settings.plane_a_tile(x + i as u8, y)))
+            .write([vdp::TileFlags::for_tile(c as u16, 0)]);
+    }
+}
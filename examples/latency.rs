@@ -0,0 +1,110 @@
+//! Measures input latency. The screen flashes once a second; press B as the flash appears, and the
+//! average time from the flash to the press is shown. The gap between the controllers being polled
+//! and the game reading them is shown too, and A switches between polling at vblank and from the
+//! H-int handler, so the two can be compared.
+//!
+//! A steady offset from the flash is latency in the emulator or display, which a rhythm game can
+//! take off its judgements.
+
+#![no_std]
+#![no_main]
+
+mod common;
+
+use mdrs::sys::io::{self, manager};
+use mdrs::sys::{fmt, timing, vdp};
+
+mdrs::entry!(run);
+
+/// Frames between flashes.
+const BEAT: u32 = 60;
+/// How many presses the average is taken over.
+const SAMPLES: usize = 8;
+/// The line H-int polling happens on, late in the active display.
+const POLL_LINE: u8 = 200;
+
+/// Lines from the start of vblank to when `stamp` was taken, counting from `frame`.
+fn lines_since(frame: u32, stamp: manager::Timestamp) -> i32 {
+    let lines = timing::lines_per_frame() as i32;
+    stamp.frame.wrapping_sub(frame) as i32 * lines + stamp.line as i32
+}
+
+/// Turns lines into milliseconds, at about 15.7 thousand lines a second.
+fn lines_to_ms(lines: i32) -> i32 {
+    let per_second = timing::lines_per_frame() as i32 * if timing::is_pal() { 50 } else { 60 };
+    lines * 1000 / per_second
+}
+
+fn print_value(settings: &vdp::Settings, y: u8, lines: i32) {
+    let mut buf = fmt::IntBuffer::new();
+    common::print(settings, 16, y, b"                  ");
+    common::print(settings, 16, y, buf.format(lines).as_bytes());
+    common::print(settings, 24, y, buf.format(lines_to_ms(lines)).as_bytes());
+    common::print(settings, 30, y, b"MS");
+}
+
+fn run() -> ! {
+    let settings = common::init(vdp::Settings::DEFAULT);
+    common::print(&settings, 1, 1, b"INPUT LATENCY");
+    common::print(&settings, 1, 3, b"PRESS B ON THE FLASH");
+    common::print(&settings, 1, 4, b"A CHANGES POLLING");
+    common::print(&settings, 16, 6, b"LINES   TIME");
+    common::print(&settings, 1, 7, b"POLLING");
+    common::print(&settings, 1, 8, b"TO FLASH");
+    common::print(&settings, 1, 9, b"POLL TO READ");
+
+    // Active display starts this many lines after vblank does, which is when a flash shows up.
+    let display_start = (timing::lines_per_frame() - 224) as i32;
+
+    let mut samples = [0i32; SAMPLES];
+    let mut count = 0usize;
+    let mut flash_frame = 0u32;
+
+    loop {
+        let frame = timing::elapsed_frames();
+
+        // Flash for two frames on every beat. The new color shows from this frame's active display.
+        let flashing = frame % BEAT < 2;
+        if frame % BEAT == 0 {
+            flash_frame = frame;
+        }
+        let color = if flashing { vdp::Color::WHITE } else { vdp::Color::BLACK };
+        vdp::Writer::new(vdp::Address::CRAM(0)).write([color]);
+
+        let mode = manager::poll_mode();
+        common::print(&settings, 16, 7, match mode {
+            manager::PollMode::VBlank => b"VBLANK    ",
+            manager::PollMode::Line(_) => b"LINE 200  ",
+        });
+
+        if let Some(pad) = manager::player(0) {
+            let pressed = pad.just_pressed();
+            if pressed.contains(io::Buttons::A) {
+                manager::set_poll_mode(match mode {
+                    manager::PollMode::VBlank => manager::PollMode::Line(POLL_LINE),
+                    manager::PollMode::Line(_) => manager::PollMode::VBlank,
+                });
+            }
+
+            if pressed.contains(io::Buttons::B) {
+                let stamp = manager::latched_at();
+
+                // Presses just before the flash count against the coming one, not the last.
+                let mut offset = lines_since(flash_frame, stamp) - display_start;
+                let beat_lines = (BEAT * timing::lines_per_frame() as u32) as i32;
+                if offset > beat_lines / 2 {
+                    offset -= beat_lines;
+                }
+                samples[count % SAMPLES] = offset;
+                count += 1;
+                let taken = &samples[..count.min(SAMPLES)];
+                print_value(&settings, 8, taken.iter().sum::<i32>() / taken.len() as i32);
+
+                let now = manager::Timestamp { frame: timing::elapsed_frames(), line: timing::lines_since_vblank() };
+                print_value(&settings, 9, lines_since(stamp.frame, now) - stamp.line as i32);
+            }
+        }
+
+        vdp::VDP::wait_for_vblank(None);
+    }
+}
@@ -0,0 +1,81 @@
+//! A menu ROM holding a few small programs, picked with up, down and START. Each goes back to the
+//! menu when START is pressed again.
+
+#![no_std]
+#![no_main]
+
+mod common;
+
+use mdrs::sys::launcher::{self, Program};
+use mdrs::sys::{self, io, vdp};
+
+mdrs::entry!(menu);
+
+const PROGRAMS: [Program; 2] = [
+    Program::new("BLUE SCREEN", blue),
+    Program::new("COUNTER", counter),
+];
+
+#[inline]
+fn pad() -> io::ControllerState<io::Player1> {
+    sys::with_cs::<1, 7, _>(|cs| io::P1_CONTROLLER.borrow(cs).get())
+}
+
+/// Goes back to the menu if START was just pressed.
+fn check_exit() {
+    if pad().just_pressed().contains(io::Buttons::START) {
+        launcher::launch(menu);
+    }
+}
+
+fn menu() -> ! {
+    let settings = common::init(vdp::Settings::DEFAULT);
+    common::print(&settings, 1, 1, b"PICK A PROGRAM");
+    for (i, program) in PROGRAMS.iter().enumerate() {
+        common::print(&settings, 3, 3 + i as u8, program.name.as_bytes());
+    }
+
+    let mut selected = 0;
+    loop {
+        let pressed = pad().just_pressed();
+        if pressed.contains(io::Buttons::START) {
+            PROGRAMS[selected].launch();
+        }
+
+        common::print(&settings, 1, 3 + selected as u8, b" ");
+        if pressed.contains(io::Buttons::UP) {
+            selected = selected.checked_sub(1).unwrap_or(PROGRAMS.len() - 1);
+        }
+        if pressed.contains(io::Buttons::DOWN) {
+            selected = (selected + 1) % PROGRAMS.len();
+        }
+        common::print(&settings, 1, 3 + selected as u8, b">");
+
+        vdp::VDP::wait_for_vblank(None);
+    }
+}
+
+fn blue() -> ! {
+    let settings = common::init(vdp::Settings::DEFAULT);
+    common::print(&settings, 1, 1, b"START GOES BACK");
+    vdp::Writer::new(vdp::Address::CRAM(0)).write([vdp::Color::BLUE]);
+
+    loop {
+        check_exit();
+        vdp::VDP::wait_for_vblank(None);
+    }
+}
+
+fn counter() -> ! {
+    let settings = common::init(vdp::Settings::DEFAULT);
+    common::print(&settings, 1, 1, b"START GOES BACK");
+
+    let mut buf = sys::fmt::IntBuffer::new();
+    let mut frames = 0u32;
+    loop {
+        check_exit();
+        common::print(&settings, 1, 3, buf.format(frames).as_bytes());
+        frames += 1;
+        vdp::VDP::wait_for_vblank(None);
+    }
+}
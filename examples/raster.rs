@@ -0,0 +1,38 @@
+//! Changes the backdrop color on every scanline from the H-int handler, for a scrolling gradient.
+
+#![no_std]
+#![no_main]
+
+mod common;
+
+use core::ptr;
+
+use mdrs::sys::{timing, vdp};
+
+mdrs::entry!(run);
+
+/// The first line of the gradient, advanced each frame. Only written outside of H-int.
+static mut OFFSET: u8 = 0;
+
+/// Runs at the start of every line's hblank.
+fn raster_line() {
+    let line = (vdp::VDP::hv_counter() >> 8) as u8;
+    let shade = (line.wrapping_add(unsafe { ptr::read_volatile(&raw const OFFSET) }) >> 2) & 0x0F;
+    let level = if shade < 8 { shade } else { 15 - shade };
+    vdp::Writer::new(vdp::Address::CRAM(0)).write([vdp::Color::new(0, 0, level)]);
+}
+
+fn run() -> ! {
+    let mut settings = vdp::Settings::DEFAULT;
+    settings.enable_interrupts(true, true, false);
+    settings.set_hint_interval(0);
+    let settings = common::init(settings);
+    common::print(&settings, 1, 1, b"RASTER EFFECTS");
+
+    vdp::VDP::set_hblank_handler(Some(raster_line));
+
+    loop {
+        unsafe { ptr::write_volatile(&raw mut OFFSET, timing::elapsed_frames() as u8) }
+        vdp::VDP::wait_for_vblank(None);
+    }
+}
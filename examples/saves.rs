@@ -0,0 +1,55 @@
+//! Keeps a few flags in battery backed SRAM, toggled with A, B and C.
+
+#![no_std]
+#![no_main]
+
+mod common;
+
+use mdrs::define_flags;
+use mdrs::sys::flags::FlagSet;
+use mdrs::sys::{self, io, sram, vdp};
+
+mdrs::entry!(run);
+
+define_flags! {
+    mod unlocks {
+        RED = 0,
+        GREEN = 1,
+        BLUE = 2,
+    }
+}
+
+const BUTTONS: [io::Buttons; 3] = [io::Buttons::A, io::Buttons::B, io::Buttons::C];
+
+fn run() -> ! {
+    let settings = common::init(vdp::Settings::DEFAULT);
+    common::print(&settings, 1, 1, b"SAVES: A, B AND C TOGGLE");
+
+    let mut flags = FlagSet::<1>::new();
+    if !sram::with_sram(|sram| flags.load(sram, 0)) {
+        common::print(&settings, 1, 2, b"NO SAVE FOUND");
+    }
+
+    loop {
+        let pad = sys::with_cs::<1, 7, _>(|cs| io::P1_CONTROLLER.borrow(cs).get());
+
+        let mut changed = false;
+        for (&button, &flag) in BUTTONS.iter().zip(unlocks::ALL) {
+            if pad.just_pressed().contains(button) {
+                flags.toggle(flag);
+                changed = true;
+            }
+        }
+        if changed {
+            sram::with_sram(|sram| flags.save(sram, 0));
+        }
+
+        for (row, &flag) in unlocks::ALL.iter().enumerate() {
+            let state: &[u8] = if flags.get(flag) { b"ON " } else { b"OFF" };
+            common::print(&settings, 1, 4 + row as u8, flag.name().as_bytes());
+            common::print(&settings, 8, 4 + row as u8, state);
+        }
+
+        vdp::VDP::wait_for_vblank(None);
+    }
+}
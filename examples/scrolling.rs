@@ -0,0 +1,48 @@
+//! Scrolls bands of text at different speeds with a line scroll table, steered with the pad.
+
+#![no_std]
+#![no_main]
+
+mod common;
+
+use fixed::types::I8F8;
+use mdrs::sys::{self, io, vdp};
+
+mdrs::entry!(run);
+
+const SETTINGS: vdp::Settings = vdp::Settings::builder()
+    .with_scroll_mode(vdp::HScrollMode::Lines, vdp::VScrollMode::Screen)
+    .build();
+
+/// The line scroll table is sent with queued DMA, so the layers live in a static rather than on
+/// the stack.
+static mut LAYERS: vdp::ParallaxLayers<3> = vdp::ParallaxLayers::new(vdp::Plane::A, [
+    vdp::ParallaxBand::new(0, 64, I8F8::lit("0.25")),
+    vdp::ParallaxBand::new(64, 160, I8F8::lit("0.5")),
+    vdp::ParallaxBand::new(160, 224, I8F8::ONE),
+]);
+
+fn run() -> ! {
+    let settings = common::init(SETTINGS);
+
+    for y in 0..28 {
+        common::print(&settings, 0, y, b"PARALLAX SCROLLING WITH LINE SCROLL ");
+    }
+
+    let layers = unsafe { &mut *(&raw mut LAYERS) };
+    let mut camera_x = 0i16;
+
+    loop {
+        let pad = sys::with_cs::<1, 7, _>(|cs| io::P1_CONTROLLER.borrow(cs).get());
+        if pad.left() {
+            camera_x -= 2;
+        }
+        if pad.right() {
+            camera_x += 2;
+        }
+
+        layers.update(camera_x);
+        layers.schedule().map_err(|_| ()).unwrap();
+        vdp::VDP::wait_for_vblank(None);
+    }
+}
@@ -0,0 +1,50 @@
+//! Plays PSG tones on A, B and C, and draws each channel's level as a bar.
+
+#![no_std]
+#![no_main]
+
+mod common;
+
+use mdrs::sys::audio::levels::{self, Channel};
+use mdrs::sys::audio::psg;
+use mdrs::sys::{self, io, vdp};
+
+mdrs::entry!(run);
+
+/// PSG tone periods for C, E and G.
+const NOTES: [(io::Buttons, u16); 3] = [
+    (io::Buttons::A, 0x1AC),
+    (io::Buttons::B, 0x153),
+    (io::Buttons::C, 0x11D),
+];
+
+fn run() -> ! {
+    let settings = common::init(vdp::Settings::DEFAULT);
+    common::print(&settings, 1, 1, b"SOUND: PRESS A, B OR C");
+    psg::silence();
+
+    loop {
+        let pad = sys::with_cs::<1, 7, _>(|cs| io::P1_CONTROLLER.borrow(cs).get());
+
+        for (channel, &(button, period)) in NOTES.iter().enumerate() {
+            let level_channel = Channel::ALL[Channel::Psg1 as usize + channel];
+            if pad.just_pressed().contains(button) {
+                psg::write(0x80 | ((channel as u8) << 5) | (period & 0x0F) as u8);
+                psg::write((period >> 4) as u8);
+                psg::set_attenuation(channel as u8, 0);
+                levels::key_on(level_channel, levels::MAX_LEVEL);
+            } else if pad.just_released().contains(button) {
+                psg::set_attenuation(channel as u8, 0x0F);
+                levels::key_off(level_channel);
+            }
+        }
+
+        for (row, level) in levels::levels().iter().enumerate() {
+            let mut bar = [b' '; levels::MAX_LEVEL as usize];
+            bar[..level.level() as usize].fill(b'#');
+            common::print(&settings, 1, 4 + row as u8, &bar);
+        }
+
+        vdp::VDP::wait_for_vblank(None);
+    }
+}
@@ -0,0 +1,59 @@
+//! Bounces a handful of sprites around the screen.
+
+#![no_std]
+#![no_main]
+
+mod common;
+
+use mdrs::sys::{rand, vdp};
+
+mdrs::entry!(run);
+
+const COUNT: usize = 16;
+
+/// Sprite coordinates start 128 pixels up and to the left of the screen.
+const ORIGIN: i16 = 128;
+
+#[derive(Clone, Copy)]
+struct Ball {
+    x: i16,
+    y: i16,
+    dx: i16,
+    dy: i16,
+}
+
+fn run() -> ! {
+    let settings = common::init(vdp::Settings::DEFAULT);
+    common::print(&settings, 1, 1, b"SPRITES");
+
+    rand::seed_from_hardware();
+    let mut balls: [Ball; COUNT] = core::array::from_fn(|_| Ball {
+        x: rand::range(0..312) as i16,
+        y: rand::range(0..216) as i16,
+        dx: if rand::random_u16() & 1 == 0 { 1 } else { -1 },
+        dy: if rand::random_u16() & 1 == 0 { 1 } else { -1 },
+    });
+    let mut sprites = [vdp::Sprite::ZEROED; COUNT];
+
+    loop {
+        for (i, (ball, sprite)) in balls.iter_mut().zip(sprites.iter_mut()).enumerate() {
+            ball.x += ball.dx;
+            ball.y += ball.dy;
+            if ball.x <= 0 || ball.x >= 312 {
+                ball.dx = -ball.dx;
+            }
+            if ball.y <= 0 || ball.y >= 216 {
+                ball.dy = -ball.dy;
+            }
+
+            *sprite = vdp::Sprite::with_flags(vdp::TileFlags::for_tile(b'O' as u16, 0), vdp::SpriteSize::Size1x1);
+            sprite.x = (ball.x + ORIGIN) as u16;
+            sprite.y = (ball.y + ORIGIN) as u16;
+            // Sprites are drawn as a linked list, which ends at the link back to sprite 0.
+            sprite.link = if i + 1 < COUNT { i as u8 + 1 } else { 0 };
+        }
+
+        vdp::VDP::wait_for_vblank(None);
+        vdp::Writer::new(vdp::Address::VRAM(settings.sprites_base())).write(sprites);
+    }
+}
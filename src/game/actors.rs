@@ -0,0 +1,298 @@
+use crate::gfx::SpriteTable;
+use crate::sys::timing;
+
+use super::pause;
+
+/// What an actor wants after its update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Alive,
+    /// The actor is removed from its pool, and its handle stops working.
+    Dead,
+}
+
+/// Something in the game world that runs every frame, such as the player, an enemy or a bullet.
+///
+/// A pool holds one type of actor, which is usually an enum of every kind in the game, so they
+/// don't need to be allocated:
+///
+/// ```ignore
+/// enum Thing {
+///     Player(Player),
+///     Bullet(Bullet),
+/// }
+///
+/// impl Actor for Thing {
+///     fn update(&mut self, ctx: &mut Context<Self>) -> Status {
+///         match self {
+///             Thing::Player(player) => player.update(ctx),
+///             Thing::Bullet(bullet) => bullet.update(ctx),
+///         }
+///     }
+/// }
+/// ```
+pub trait Actor: Sized {
+    /// Runs once a frame, while the game isn't paused.
+    fn update(&mut self, ctx: &mut Context<'_, Self>) -> Status;
+
+    /// Adds the actor's sprites to the frame's sprite table. Runs every frame, even while paused.
+    #[inline]
+    fn draw<const N: usize>(&self, sprites: &mut SpriteTable<N>) {
+        let _ = sprites;
+    }
+}
+
+/// Refers to an actor in a pool. Once the actor is gone, its handle doesn't find anything, even if
+/// the slot has been reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handle {
+    index: u8,
+    generation: u8,
+}
+
+impl Handle {
+    /// The actor's slot, which stays the same for as long as it lives.
+    #[inline]
+    pub const fn index(&self) -> u8 {
+        self.index
+    }
+}
+
+struct Slot<A> {
+    actor: Option<A>,
+    /// Counts up every time the slot empties, so old handles miss.
+    generation: u8,
+    /// Set for actors spawned during this frame's update, which wait for the next.
+    fresh: bool,
+}
+
+impl<A> Slot<A> {
+    const EMPTY: Self = Self { actor: None, generation: 0, fresh: false };
+}
+
+/// A fixed number of actor slots, and the loop that runs them.
+///
+/// ```ignore
+/// let mut actors: Pool<Thing, 48> = Pool::new();
+/// actors.spawn(Thing::Player(Player::new())).ok();
+///
+/// loop {
+///     actors.update();
+///     sprites.clear();
+///     actors.draw(&mut sprites);
+///     sprites.flush().ok();
+///     VDP::wait_for_vblank(None);
+/// }
+/// ```
+pub struct Pool<A: Actor, const N: usize> {
+    slots: [Slot<A>; N],
+    last_frame: Option<u32>,
+}
+
+impl<A: Actor, const N: usize> Pool<A, N> {
+    /// # Panics
+    ///
+    /// Panics if `N` is more than 256.
+    pub const fn new() -> Self {
+        if N > 256 {
+            panic!("actor pools hold at most 256 actors");
+        }
+        Self {
+            slots: [const { Slot::EMPTY }; N],
+            last_frame: None,
+        }
+    }
+
+    /// The number of live actors.
+    pub fn len(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.actor.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(|slot| slot.actor.is_none())
+    }
+
+    /// Puts `actor` in the first free slot. It's updated from the next `update`.
+    ///
+    /// Returns the actor back if every slot is taken.
+    #[inline]
+    pub fn spawn(&mut self, actor: A) -> Result<Handle, A> {
+        spawn(&mut self.slots, None, actor)
+    }
+
+    /// Removes an actor, and returns it if it was still there.
+    #[inline]
+    pub fn despawn(&mut self, handle: Handle) -> Option<A> {
+        despawn(&mut self.slots, handle)
+    }
+
+    #[inline]
+    pub fn get(&self, handle: Handle) -> Option<&A> {
+        get(&self.slots, handle)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut A> {
+        get_mut(&mut self.slots, handle)
+    }
+
+    /// Removes every actor.
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            if slot.actor.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+            }
+        }
+    }
+
+    /// The live actors, in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (Handle, &A)> {
+        self.slots.iter().enumerate().filter_map(|(i, slot)| {
+            let handle = Handle { index: i as u8, generation: slot.generation };
+            slot.actor.as_ref().map(|actor| (handle, actor))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Handle, &mut A)> {
+        self.slots.iter_mut().enumerate().filter_map(|(i, slot)| {
+            let handle = Handle { index: i as u8, generation: slot.generation };
+            slot.actor.as_mut().map(|actor| (handle, actor))
+        })
+    }
+
+    /// Runs every actor's update, in slot order, and removes those that die.
+    ///
+    /// Does nothing while the game is paused, and returns false. If frames were dropped since the
+    /// last update, the context's `delta` says how many, so movement can keep up.
+    pub fn update(&mut self) -> bool {
+        if pause::is_paused() {
+            // The frames spent paused aren't made up for afterwards.
+            self.last_frame = None;
+            return false;
+        }
+        let frame = timing::elapsed_frames();
+        let delta = self.last_frame.map_or(1, |last| frame.wrapping_sub(last).max(1));
+        self.last_frame = Some(frame);
+
+        for slot in &mut self.slots {
+            slot.fresh = false;
+        }
+        for i in 0..N {
+            if self.slots[i].fresh {
+                continue;
+            }
+            // The actor is taken out while it runs, so it can reach the rest of the pool.
+            let Some(mut actor) = self.slots[i].actor.take() else { continue };
+            let handle = Handle { index: i as u8, generation: self.slots[i].generation };
+            let mut ctx = Context { slots: &mut self.slots, handle, frame, delta };
+            match actor.update(&mut ctx) {
+                Status::Alive => self.slots[i].actor = Some(actor),
+                Status::Dead => self.slots[i].generation = self.slots[i].generation.wrapping_add(1),
+            }
+        }
+        true
+    }
+
+    /// Runs every actor's draw, in slot order, so lower slots' sprites go in front.
+    pub fn draw<const S: usize>(&self, sprites: &mut SpriteTable<S>) {
+        for slot in &self.slots {
+            if let Some(actor) = &slot.actor {
+                actor.draw(sprites);
+            }
+        }
+    }
+}
+
+impl<A: Actor, const N: usize> Default for Pool<A, N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What an actor's update can see and do besides itself: the frame, and the rest of its pool.
+pub struct Context<'a, A> {
+    slots: &'a mut [Slot<A>],
+    handle: Handle,
+    frame: u32,
+    delta: u32,
+}
+
+impl<A> Context<'_, A> {
+    /// The updating actor's own handle.
+    #[inline]
+    pub fn handle(&self) -> Handle {
+        self.handle
+    }
+
+    /// The frame number, from `timing::elapsed_frames`.
+    #[inline]
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// The number of frames since the pool's last update, which is 1 unless frames were dropped.
+    #[inline]
+    pub fn delta(&self) -> u32 {
+        self.delta
+    }
+
+    /// Spawns another actor, such as a bullet. It's updated from the next frame.
+    #[inline]
+    pub fn spawn(&mut self, actor: A) -> Result<Handle, A> {
+        spawn(self.slots, Some(self.handle.index as usize), actor)
+    }
+
+    /// Removes another actor. The updating actor removes itself by returning `Status::Dead`.
+    #[inline]
+    pub fn despawn(&mut self, handle: Handle) -> Option<A> {
+        despawn(self.slots, handle)
+    }
+
+    /// Another actor. The updating actor isn't found, since it's out of its slot while it runs.
+    #[inline]
+    pub fn get(&self, handle: Handle) -> Option<&A> {
+        get(self.slots, handle)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut A> {
+        get_mut(self.slots, handle)
+    }
+}
+
+/// Puts `actor` in the first free slot other than `skip`.
+fn spawn<A>(slots: &mut [Slot<A>], skip: Option<usize>, actor: A) -> Result<Handle, A> {
+    let free = (0..slots.len()).find(|&i| slots[i].actor.is_none() && Some(i) != skip);
+    let Some(index) = free else { return Err(actor) };
+    let slot = &mut slots[index];
+    slot.actor = Some(actor);
+    slot.fresh = true;
+    Ok(Handle { index: index as u8, generation: slot.generation })
+}
+
+fn despawn<A>(slots: &mut [Slot<A>], handle: Handle) -> Option<A> {
+    let slot = slots.get_mut(handle.index as usize)?;
+    if slot.generation != handle.generation {
+        return None;
+    }
+    let actor = slot.actor.take()?;
+    slot.generation = slot.generation.wrapping_add(1);
+    Some(actor)
+}
+
+fn get<A>(slots: &[Slot<A>], handle: Handle) -> Option<&A> {
+    let slot = slots.get(handle.index as usize)?;
+    if slot.generation != handle.generation {
+        return None;
+    }
+    slot.actor.as_ref()
+}
+
+fn get_mut<A>(slots: &mut [Slot<A>], handle: Handle) -> Option<&mut A> {
+    let slot = slots.get_mut(handle.index as usize)?;
+    if slot.generation != handle.generation {
+        return None;
+    }
+    slot.actor.as_mut()
+}
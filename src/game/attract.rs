@@ -0,0 +1,131 @@
+/// One of the screens an attract mode rotates through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttractStage {
+    /// The title screen. This is the only interactive stage; input here just keeps it on screen.
+    Title,
+    /// A recorded demo, identified by its index.
+    Demo(u8),
+    /// The high score table.
+    Scores,
+}
+
+/// Arcade-style attract mode sequencing.
+///
+/// The title screen is shown until the player stays idle for the configured time, after which the
+/// demos and the high score table are cycled through. Any input outside of the title screen jumps
+/// straight back to it. Every stage is left to the game to draw, usually through input playback for
+/// the demos; this only decides which one should be on screen.
+#[derive(Debug, Clone, Copy)]
+pub struct AttractMode {
+    stage: AttractStage,
+    timer: u16,
+    title_frames: u16,
+    demo_frames: u16,
+    scores_frames: u16,
+    demo_count: u8,
+    next_demo: u8,
+}
+
+impl AttractMode {
+    /// Creates an attract mode that idles for 10 seconds on the title, then plays a single 30 second
+    /// demo and shows the scores for 5 seconds, assuming 60 frames per second.
+    pub const fn new() -> Self {
+        Self {
+            stage: AttractStage::Title,
+            timer: 0,
+            title_frames: 600,
+            demo_frames: 1800,
+            scores_frames: 300,
+            demo_count: 1,
+            next_demo: 0,
+        }
+    }
+
+    /// Sets how many idle frames the title screen waits before the rotation starts.
+    pub const fn with_title_timeout(mut self, frames: u16) -> Self {
+        self.title_frames = frames;
+        self
+    }
+
+    /// Sets how many frames each demo runs for.
+    pub const fn with_demo_length(mut self, frames: u16) -> Self {
+        self.demo_frames = frames;
+        self
+    }
+
+    /// Sets how many frames the high score table is shown for.
+    pub const fn with_scores_length(mut self, frames: u16) -> Self {
+        self.scores_frames = frames;
+        self
+    }
+
+    /// Sets how many demos there are to cycle through. With no demos, the rotation skips straight
+    /// to the scores.
+    pub const fn with_demo_count(mut self, count: u8) -> Self {
+        self.demo_count = count;
+        self
+    }
+
+    #[inline]
+    pub const fn stage(&self) -> AttractStage {
+        self.stage
+    }
+
+    /// The number of frames the current stage has been on screen for.
+    #[inline]
+    pub const fn stage_frames(&self) -> u16 {
+        self.timer
+    }
+
+    /// Goes back to the title screen, e.g. after a game over.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.stage = AttractStage::Title;
+        self.timer = 0;
+    }
+
+    /// Advances the attract mode by a frame. `input` should be true if the player pressed anything
+    /// this frame.
+    ///
+    /// Returns the new stage if the game should switch screens.
+    pub fn update(&mut self, input: bool) -> Option<AttractStage> {
+        if input {
+            self.timer = 0;
+            return if self.stage != AttractStage::Title {
+                self.stage = AttractStage::Title;
+                Some(self.stage)
+            } else {
+                None
+            };
+        }
+
+        self.timer = self.timer.saturating_add(1);
+
+        let length = match self.stage {
+            AttractStage::Title => self.title_frames,
+            AttractStage::Demo(_) => self.demo_frames,
+            AttractStage::Scores => self.scores_frames,
+        };
+        if self.timer < length {
+            return None;
+        }
+
+        self.timer = 0;
+        self.stage = match self.stage {
+            AttractStage::Title if self.demo_count > 0 => {
+                let demo = self.next_demo;
+                self.next_demo = if demo + 1 >= self.demo_count { 0 } else { demo + 1 };
+                AttractStage::Demo(demo)
+            },
+            AttractStage::Title | AttractStage::Demo(_) => AttractStage::Scores,
+            AttractStage::Scores => AttractStage::Title,
+        };
+        Some(self.stage)
+    }
+}
+
+impl Default for AttractMode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
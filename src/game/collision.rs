@@ -0,0 +1,293 @@
+use fixed::types::I16F16;
+
+use crate::sys::math::Vec2F16;
+
+/// The size of a grid cell, in pixels.
+pub const CELL_SIZE: i16 = 32;
+/// The size of the grid's world, which is one screen. Boxes outside it are kept in the edge cells.
+pub const GRID_WIDTH: i16 = 320;
+pub const GRID_HEIGHT: i16 = 224;
+/// The most boxes a `Grid` holds, which is the number of bits in a cell's mask.
+pub const MAX_ENTRIES: usize = 32;
+
+const COLS: usize = ((GRID_WIDTH + CELL_SIZE - 1) / CELL_SIZE) as usize;
+const ROWS: usize = ((GRID_HEIGHT + CELL_SIZE - 1) / CELL_SIZE) as usize;
+
+/// An axis-aligned box, by its top left corner and its size.
+///
+/// Boxes touching along an edge don't count as intersecting, so a box resting on the floor isn't
+/// colliding with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Aabb {
+    pub pos: Vec2F16,
+    pub size: Vec2F16,
+}
+
+/// Where a moving box first touches another, from `Aabb::sweep`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hit {
+    /// How far along the movement the boxes touch, from 0 to 1.
+    pub time: I16F16,
+    /// The side of the other box that was hit, pointing out of it, such as (0, -1) for its top.
+    pub normal: Vec2F16,
+}
+
+impl Aabb {
+    #[inline]
+    pub const fn new(pos: Vec2F16, size: Vec2F16) -> Self {
+        Self { pos, size }
+    }
+
+    /// A box in whole pixels.
+    #[inline]
+    pub const fn from_pixels(x: i16, y: i16, width: i16, height: i16) -> Self {
+        Self::new(
+            Vec2F16::new(I16F16::const_from_int(x as i32), I16F16::const_from_int(y as i32)),
+            Vec2F16::new(I16F16::const_from_int(width as i32), I16F16::const_from_int(height as i32)),
+        )
+    }
+
+    #[inline]
+    pub fn left(&self) -> I16F16 {
+        self.pos.x
+    }
+
+    #[inline]
+    pub fn top(&self) -> I16F16 {
+        self.pos.y
+    }
+
+    #[inline]
+    pub fn right(&self) -> I16F16 {
+        self.pos.x + self.size.x
+    }
+
+    #[inline]
+    pub fn bottom(&self) -> I16F16 {
+        self.pos.y + self.size.y
+    }
+
+    #[inline]
+    pub fn center(&self) -> Vec2F16 {
+        Vec2F16::new(self.pos.x + (self.size.x >> 1), self.pos.y + (self.size.y >> 1))
+    }
+
+    /// The box moved by `offset`.
+    #[inline]
+    pub fn translated(&self, offset: Vec2F16) -> Self {
+        Self::new(self.pos + offset, self.size)
+    }
+
+    #[inline]
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.left() < other.right()
+            && other.left() < self.right()
+            && self.top() < other.bottom()
+            && other.top() < self.bottom()
+    }
+
+    /// Returns true if `point` is inside the box, counting its top and left edges but not its
+    /// bottom and right, like a pixel.
+    #[inline]
+    pub fn contains(&self, point: Vec2F16) -> bool {
+        point.x >= self.left() && point.x < self.right() && point.y >= self.top() && point.y < self.bottom()
+    }
+
+    /// The shortest move that pushes this box out of `other`, along whichever axis is shallower,
+    /// or `None` if they don't intersect.
+    pub fn penetration(&self, other: &Self) -> Option<Vec2F16> {
+        if !self.intersects(other) {
+            return None;
+        }
+        let push = |start: I16F16, end: I16F16, other_start: I16F16, other_end: I16F16| {
+            let (back, forward) = (other_start - end, other_end - start);
+            if -back < forward { back } else { forward }
+        };
+        let x = push(self.left(), self.right(), other.left(), other.right());
+        let y = push(self.top(), self.bottom(), other.top(), other.bottom());
+        Some(if x.abs() < y.abs() {
+            Vec2F16::new(x, I16F16::ZERO)
+        } else {
+            Vec2F16::new(I16F16::ZERO, y)
+        })
+    }
+
+    /// Where this box, moving by `velocity`, first touches `other`, or `None` if it doesn't during
+    /// the move. Boxes that already intersect aren't a hit, which `penetration` is for.
+    ///
+    /// This stops fast objects passing through thin ones between frames, which checking where they
+    /// end up can miss.
+    pub fn sweep(&self, velocity: Vec2F16, other: &Self) -> Option<Hit> {
+        // The times the box enters and leaves the other's span on one axis.
+        let axis = |start: I16F16, end: I16F16, v: I16F16, other_start: I16F16, other_end: I16F16| {
+            if v == I16F16::ZERO {
+                return if start < other_end && other_start < end {
+                    Some((I16F16::MIN, I16F16::MAX))
+                } else {
+                    None
+                };
+            }
+            let (near, far) = if v > I16F16::ZERO {
+                (other_start - end, other_end - start)
+            } else {
+                (other_end - start, other_start - end)
+            };
+            Some((near.saturating_div(v), far.saturating_div(v)))
+        };
+
+        let (enter_x, exit_x) = axis(self.left(), self.right(), velocity.x, other.left(), other.right())?;
+        let (enter_y, exit_y) = axis(self.top(), self.bottom(), velocity.y, other.top(), other.bottom())?;
+        let (enter, exit) = (enter_x.max(enter_y), exit_x.min(exit_y));
+        if enter >= exit || enter < I16F16::ZERO || enter > I16F16::ONE {
+            return None;
+        }
+
+        let normal = if enter_x > enter_y {
+            Vec2F16::new(if velocity.x > I16F16::ZERO { -I16F16::ONE } else { I16F16::ONE }, I16F16::ZERO)
+        } else {
+            Vec2F16::new(I16F16::ZERO, if velocity.y > I16F16::ZERO { -I16F16::ONE } else { I16F16::ONE })
+        };
+        Some(Hit { time: enter, normal })
+    }
+}
+
+/// A broadphase over one screen, in cells of `CELL_SIZE` pixels, that narrows down which boxes
+/// could be touching before they're tested properly.
+///
+/// Boxes are put in under ids from 0 to `MAX_ENTRIES - 1`, which can be anything the game likes,
+/// such as actor slots. Each cell keeps a bit for every id in it, so finding what's near a box is
+/// a few ORs. Moving objects are usually handled by clearing the grid and inserting everything
+/// again each frame.
+///
+/// ```ignore
+/// grid.clear();
+/// for (id, enemy) in enemies.iter().enumerate() {
+///     grid.insert(id as u8, enemy.hitbox());
+/// }
+/// for id in grid.query(&player.hitbox()) {
+///     hurt_player(&enemies[id as usize]);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Grid {
+    cells: [u32; COLS * ROWS],
+    boxes: [Aabb; MAX_ENTRIES],
+    used: u32,
+}
+
+impl Grid {
+    pub const fn new() -> Self {
+        Self {
+            cells: [0; COLS * ROWS],
+            boxes: [Aabb::from_pixels(0, 0, 0, 0); MAX_ENTRIES],
+            used: 0,
+        }
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.cells = [0; COLS * ROWS];
+        self.used = 0;
+    }
+
+    /// Puts `aabb` in under `id`, replacing whatever was there.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` isn't less than `MAX_ENTRIES`.
+    pub fn insert(&mut self, id: u8, aabb: Aabb) {
+        if id as usize >= MAX_ENTRIES {
+            panic!("grid id out of range");
+        }
+        self.remove(id);
+        self.boxes[id as usize] = aabb;
+        self.used |= 1 << id;
+        let (x0, y0, x1, y1) = Self::span(&aabb);
+        for y in y0..=y1 {
+            for cell in &mut self.cells[y * COLS + x0..=y * COLS + x1] {
+                *cell |= 1 << id;
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: u8) {
+        if id as usize >= MAX_ENTRIES || self.used & (1 << id) == 0 {
+            return;
+        }
+        let (x0, y0, x1, y1) = Self::span(&self.boxes[id as usize]);
+        for y in y0..=y1 {
+            for cell in &mut self.cells[y * COLS + x0..=y * COLS + x1] {
+                *cell &= !(1 << id);
+            }
+        }
+        self.used &= !(1 << id);
+    }
+
+    #[inline]
+    pub fn get(&self, id: u8) -> Option<&Aabb> {
+        if (id as usize) < MAX_ENTRIES && self.used & (1 << id) != 0 {
+            Some(&self.boxes[id as usize])
+        } else {
+            None
+        }
+    }
+
+    /// The ids of the boxes that intersect `aabb`, in order.
+    pub fn query(&self, aabb: &Aabb) -> impl Iterator<Item = u8> + '_ {
+        let aabb = *aabb;
+        ids(self.near(&aabb)).filter(move |&id| self.boxes[id as usize].intersects(&aabb))
+    }
+
+    /// Every pair of ids whose boxes intersect, each once, with the lower id first.
+    pub fn pairs(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        ids(self.used).flat_map(move |a| {
+            let boxed = self.boxes[a as usize];
+            let later = (u32::MAX << a) << 1;
+            ids(self.near(&boxed) & later)
+                .filter(move |&b| self.boxes[b as usize].intersects(&boxed))
+                .map(move |b| (a, b))
+        })
+    }
+
+    /// The ids in the cells `aabb` covers.
+    fn near(&self, aabb: &Aabb) -> u32 {
+        let (x0, y0, x1, y1) = Self::span(aabb);
+        let mut mask = 0;
+        for y in y0..=y1 {
+            for &cell in &self.cells[y * COLS + x0..=y * COLS + x1] {
+                mask |= cell;
+            }
+        }
+        mask
+    }
+
+    /// The first and last columns and rows of cells `aabb` covers, clamped to the grid.
+    fn span(aabb: &Aabb) -> (usize, usize, usize, usize) {
+        let cell = |pos: I16F16, len: usize| {
+            (pos.to_num::<i32>() / CELL_SIZE as i32).clamp(0, len as i32 - 1) as usize
+        };
+        // The right and bottom edges aren't part of the box.
+        let right = (aabb.right() - I16F16::DELTA).max(aabb.left());
+        let bottom = (aabb.bottom() - I16F16::DELTA).max(aabb.top());
+        (cell(aabb.left(), COLS), cell(aabb.top(), ROWS), cell(right, COLS), cell(bottom, ROWS))
+    }
+}
+
+impl Default for Grid {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The ids set in `mask`, lowest first.
+fn ids(mut mask: u32) -> impl Iterator<Item = u8> {
+    core::iter::from_fn(move || {
+        if mask == 0 {
+            return None;
+        }
+        let id = mask.trailing_zeros() as u8;
+        mask &= mask - 1;
+        Some(id)
+    })
+}
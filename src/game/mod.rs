@@ -0,0 +1,8 @@
+pub mod actors;
+pub mod attract;
+pub mod collision;
+pub mod pause;
+pub mod scenes;
+pub mod timeline;
+#[cfg(feature = "audio")]
+pub mod rhythm;
@@ -0,0 +1,235 @@
+use core::cell;
+
+use critical_section as cs;
+
+use crate::sys::vdp::{Settings, WindowClip};
+use crate::sys::{self, io};
+#[cfg(feature = "audio")]
+use crate::sys::audio::{levels::Channel, music, sfx};
+
+/// What happens to the audio while the game is paused. Sound effects are stopped either way,
+/// unless it's `Keep`.
+#[cfg(feature = "audio")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseAudio {
+    /// Pauses the music, and resumes it where it left off. The default.
+    Pause,
+    /// Keeps the music playing with every channel ducked, so it's silent but still in time when
+    /// the game resumes.
+    Duck,
+    /// Leaves the audio alone, for pause menus with their own music.
+    Keep,
+}
+
+/// Callbacks run when the game is paused and resumed, and what pausing does to the audio and
+/// the screen.
+///
+/// `on_pause` runs after the audio is paused and the overlay is shown, so it can draw the pause
+/// menu, and `on_resume` runs after both are restored. `on_frame` runs once per paused frame,
+/// which is where the pause menu is navigated.
+#[derive(Clone, Copy)]
+pub struct PauseHooks {
+    on_pause: Option<fn()>,
+    on_resume: Option<fn()>,
+    on_frame: Option<fn()>,
+    #[cfg(feature = "audio")]
+    audio: PauseAudio,
+    overlay: Option<(WindowClip, WindowClip)>,
+}
+
+impl PauseHooks {
+    pub const NONE: Self = Self {
+        on_pause: None,
+        on_resume: None,
+        on_frame: None,
+        #[cfg(feature = "audio")]
+        audio: PauseAudio::Pause,
+        overlay: None,
+    };
+
+    pub const fn with_on_pause(mut self, f: fn()) -> Self {
+        self.on_pause = Some(f);
+        self
+    }
+
+    pub const fn with_on_resume(mut self, f: fn()) -> Self {
+        self.on_resume = Some(f);
+        self
+    }
+
+    pub const fn with_on_frame(mut self, f: fn()) -> Self {
+        self.on_frame = Some(f);
+        self
+    }
+
+    #[cfg(feature = "audio")]
+    pub const fn with_audio(mut self, audio: PauseAudio) -> Self {
+        self.audio = audio;
+        self
+    }
+
+    /// Shows the window plane over plane A with these clips while paused, as the pause menu's
+    /// overlay. The menu is drawn into the window's table, and the clips the game had are put
+    /// back on resume.
+    pub const fn with_overlay(mut self, x_clip: WindowClip, y_clip: WindowClip) -> Self {
+        self.overlay = Some((x_clip, y_clip));
+        self
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PauseState {
+    paused: bool,
+    enabled: bool,
+    hooks: PauseHooks,
+    /// What `pause` changed, so `resume` only undoes that.
+    #[cfg(feature = "audio")]
+    music: Option<PauseAudio>,
+    window_clip: Option<(WindowClip, WindowClip)>,
+}
+
+static PAUSE_STATE: cs::Mutex<cell::Cell<PauseState>> = cs::Mutex::new(cell::Cell::new(PauseState {
+    paused: false,
+    enabled: true,
+    hooks: PauseHooks::NONE,
+    #[cfg(feature = "audio")]
+    music: None,
+    window_clip: None,
+}));
+
+#[inline]
+fn state() -> PauseState {
+    sys::with_cs::<1, 7, _>(|cs| PAUSE_STATE.borrow(cs).get())
+}
+
+#[inline]
+fn modify(f: impl FnOnce(&mut PauseState)) {
+    sys::with_cs::<1, 7, _>(|cs| {
+        let cell = PAUSE_STATE.borrow(cs);
+        let mut state = cell.get();
+        f(&mut state);
+        cell.set(state);
+    })
+}
+
+/// Returns true while the game is paused.
+///
+/// Gameplay systems (actors, animation, physics) skip their updates while this is set. Vblank work
+/// such as DMA, palette effects and input polling keeps running.
+#[inline]
+pub fn is_paused() -> bool {
+    state().paused
+}
+
+#[inline]
+pub fn set_hooks(hooks: PauseHooks) {
+    modify(|state| state.hooks = hooks);
+}
+
+/// Allows or disallows pausing, e.g. during cutscenes or on the title screen.
+///
+/// Disabling pausing while paused resumes the game.
+pub fn set_enabled(enabled: bool) {
+    modify(|state| state.enabled = enabled);
+    if !enabled {
+        resume();
+    }
+}
+
+pub fn pause() {
+    let state = state();
+    if state.paused || !state.enabled {
+        return;
+    }
+
+    #[cfg(feature = "audio")]
+    let music = pause_audio(state.hooks.audio);
+    let window_clip = state.hooks.overlay.map(|(x_clip, y_clip)| set_window_clip(x_clip, y_clip));
+    modify(|state| {
+        state.paused = true;
+        #[cfg(feature = "audio")]
+        {
+            state.music = music;
+        }
+        state.window_clip = window_clip;
+    });
+    if let Some(on_pause) = state.hooks.on_pause {
+        on_pause();
+    }
+}
+
+pub fn resume() {
+    let state = state();
+    if !state.paused {
+        return;
+    }
+
+    modify(|state| state.paused = false);
+    if let Some((x_clip, y_clip)) = state.window_clip {
+        set_window_clip(x_clip, y_clip);
+    }
+    #[cfg(feature = "audio")]
+    match state.music {
+        Some(PauseAudio::Pause) => music::resume(),
+        Some(PauseAudio::Duck) => Channel::ALL.into_iter().for_each(music::unduck),
+        Some(PauseAudio::Keep) | None => {}
+    }
+    if let Some(on_resume) = state.hooks.on_resume {
+        on_resume();
+    }
+}
+
+/// Stops the sound effects and pauses or ducks the music, returning what was done to the music
+/// for `resume` to undo.
+#[cfg(feature = "audio")]
+fn pause_audio(audio: PauseAudio) -> Option<PauseAudio> {
+    if audio == PauseAudio::Keep {
+        return None;
+    }
+    sfx::stop_all();
+    if music::state() != music::State::Playing {
+        return None;
+    }
+    match audio {
+        PauseAudio::Pause => music::pause(),
+        PauseAudio::Duck => Channel::ALL.into_iter().for_each(music::duck),
+        PauseAudio::Keep => {}
+    }
+    Some(audio)
+}
+
+/// Sets the window's clips, returning the ones it had.
+fn set_window_clip(x_clip: WindowClip, y_clip: WindowClip) -> (WindowClip, WindowClip) {
+    let mut settings = Settings::current();
+    let old = (settings.window_x_clip(), settings.window_y_clip());
+    settings.set_window_clip(x_clip, y_clip);
+    settings.apply::<false>();
+    old
+}
+
+#[inline]
+pub fn toggle() {
+    if is_paused() {
+        resume();
+    } else {
+        pause();
+    }
+}
+
+/// Advances the pause system by a frame, toggling pause whenever Start goes down on `pad`.
+///
+/// Returns true if the game should run its gameplay update this frame.
+pub fn update<P: io::IOPort>(pad: &io::ControllerState<P>) -> bool {
+    if pad.just_pressed().contains(io::Buttons::START) {
+        toggle();
+    }
+
+    let state = state();
+    if state.paused {
+        if let Some(on_frame) = state.hooks.on_frame {
+            on_frame();
+        }
+    }
+
+    !state.paused
+}
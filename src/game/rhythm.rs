@@ -0,0 +1,203 @@
+use crate::sys::RingBuffer;
+use crate::sys::audio::music::{self, Marker, Markers, Song};
+use crate::sys::io::manager;
+
+/// How many notes past the next unjudged one can be hit early. Notes closer together than this
+/// in the same stretch of song are judged in order.
+const TRACKED: usize = 32;
+
+/// A note in a beatmap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Note {
+    /// When the note should be hit, as a `music::position`.
+    pub time: u32,
+    /// Which of the game's lanes, or buttons, the note is in.
+    pub lane: u8,
+}
+
+impl Note {
+    #[inline]
+    pub const fn new(time: u32, lane: u8) -> Self {
+        Self { time, lane }
+    }
+}
+
+impl From<Marker> for Note {
+    /// A marker's id is the lane of its note.
+    #[inline]
+    fn from(marker: Marker) -> Self {
+        Self::new(marker.time, marker.id)
+    }
+}
+
+/// The notes for a song, which are the markers placed in its VGM file, with each marker's id as
+/// the note's lane. See `music::MARKER_COMMAND`.
+///
+/// Notes are read from the song as the judge gets to them, so they can be as long as the song,
+/// and go on for as long as it loops.
+#[derive(Debug, Clone, Copy)]
+pub struct Beatmap {
+    song: Song,
+}
+
+impl Beatmap {
+    #[inline]
+    pub const fn new(song: Song) -> Self {
+        Self { song }
+    }
+
+    /// The notes, in the order they're hit.
+    #[inline]
+    pub fn notes(&self) -> impl Iterator<Item = Note> {
+        self.song.markers().map(Note::from)
+    }
+}
+
+/// How far from a note, either way, a press can be and still get each judgement, in samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Windows {
+    pub perfect: u32,
+    pub good: u32,
+    /// Presses this close, but outside the good window, use up the note as a miss.
+    pub miss: u32,
+}
+
+impl Windows {
+    /// Roughly 2, 5 and 8 frames at 60 Hz.
+    pub const DEFAULT: Self = Self::from_millis(33, 83, 133);
+
+    pub const fn from_millis(perfect: u16, good: u16, miss: u16) -> Self {
+        Self {
+            perfect: perfect as u32 * 441 / 10,
+            good: good as u32 * 441 / 10,
+            miss: miss as u32 * 441 / 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Judgement {
+    Perfect,
+    Good,
+    Miss,
+}
+
+/// A judged press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hit {
+    pub judgement: Judgement,
+    pub note: Note,
+    /// How far off the press was, in samples. Negative presses were early.
+    pub offset: i32,
+}
+
+/// Judges presses against a beatmap as the music plays.
+///
+/// Polling the controllers on a scanline (see `io::manager::PollMode::Line`) timestamps each press
+/// more finely than once per frame, and `press` turns that timestamp into a song position with
+/// `music::position_at`, so judgements aren't skewed by where in the frame the game reads its input.
+pub struct Judge {
+    beatmap: Beatmap,
+    windows: Windows,
+    /// The notes still to come from the beatmap.
+    markers: Markers,
+    /// The notes read from the beatmap that haven't been judged or gone by yet, in order.
+    upcoming: RingBuffer<Note, TRACKED>,
+    /// The upcoming notes that have already been judged, as bits indexed from the first.
+    judged: u32,
+}
+
+impl Judge {
+    pub const fn new(beatmap: Beatmap, windows: Windows) -> Self {
+        Self {
+            beatmap,
+            windows,
+            markers: beatmap.song.markers(),
+            upcoming: RingBuffer::new(),
+            judged: 0,
+        }
+    }
+
+    /// Starts judging from the first note again, for when the song is restarted.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.markers = self.beatmap.song.markers();
+        self.upcoming.clear();
+        self.judged = 0;
+    }
+
+    /// Reads notes from the beatmap until `TRACKED` of them are upcoming, or there are no more.
+    fn fill(&mut self) {
+        while !self.upcoming.is_full() {
+            let Some(marker) = self.markers.next() else { break };
+            let _ = self.upcoming.push_back(marker.into());
+        }
+    }
+
+    /// Judges a press in `lane` at the time the controllers were last read.
+    #[inline]
+    pub fn press(&mut self, lane: u8) -> Option<Hit> {
+        let stamp = manager::latched_at();
+        self.judge(lane, music::position_at(stamp.frame, stamp.line))
+    }
+
+    /// Judges a press in `lane` at song position `time`. Returns `None` if there's no note in the
+    /// lane close enough for the press to count.
+    pub fn judge(&mut self, lane: u8, time: u32) -> Option<Hit> {
+        self.fill();
+        for (i, &note) in self.upcoming.iter().enumerate() {
+            if note.time > time.saturating_add(self.windows.miss) {
+                break;
+            }
+            let distance = time.abs_diff(note.time);
+            if note.lane != lane || self.judged & (1 << i) != 0 || distance > self.windows.miss {
+                continue;
+            }
+
+            self.judged |= 1 << i;
+            let judgement = if distance <= self.windows.perfect {
+                Judgement::Perfect
+            } else if distance <= self.windows.good {
+                Judgement::Good
+            } else {
+                Judgement::Miss
+            };
+            return Some(Hit { judgement, note, offset: time.wrapping_sub(note.time) as i32 });
+        }
+        None
+    }
+
+    /// Moves past the notes that can no longer be hit at song position `time`, and returns how many
+    /// of them were never pressed.
+    pub fn expire(&mut self, time: u32) -> u16 {
+        let mut missed = 0;
+        self.fill();
+        while let Some(note) = self.upcoming.front() {
+            if note.time.saturating_add(self.windows.miss) >= time {
+                break;
+            }
+            if self.judged & 1 == 0 {
+                missed += 1;
+            }
+            self.upcoming.pop_front();
+            self.judged >>= 1;
+            self.fill();
+        }
+        missed
+    }
+
+    /// Expires notes up to the current song position. Call this once per frame, after judging the
+    /// frame's presses.
+    #[inline]
+    pub fn update(&mut self) -> u16 {
+        self.expire(music::position())
+    }
+
+    /// Returns true once every note has been judged or gone by. This never happens for songs
+    /// that loop, unless they have no notes.
+    #[inline]
+    pub fn is_finished(&mut self) -> bool {
+        self.fill();
+        self.upcoming.is_empty()
+    }
+}
@@ -0,0 +1,221 @@
+use heapless::Vec;
+
+use crate::sys::vdp::palette::{Fader, Palette, LINES};
+
+/// How long fades between scenes take by default, in frames.
+pub const DEFAULT_FADE_FRAMES: u8 = 16;
+
+/// A change of scene, returned by `Scene::update`.
+pub enum Transition<S> {
+    /// Puts a scene on top of the current one, which is kept to go back to.
+    Push(S),
+    /// Ends the current scene, and goes back to the one under it.
+    Pop,
+    /// Ends the current scene, and puts another in its place.
+    Replace(S),
+}
+
+/// One screen's worth of the game, such as the title screen, a level, or a pause menu.
+///
+/// Like actors, the scenes a game has are usually variants of one enum.
+pub trait Scene: Sized {
+    /// Runs when the scene starts, while the screen is black, so it's where tiles, planes and the
+    /// like are loaded.
+    fn enter(&mut self);
+
+    /// Runs once a frame while the scene is on top, including while it fades in. Returns a
+    /// transition to change scene.
+    fn update(&mut self) -> Option<Transition<Self>>;
+
+    /// Runs when the scene ends, once the screen has faded to black.
+    #[inline]
+    fn exit(&mut self) {}
+
+    /// Runs when a scene is pushed on top of this one.
+    #[inline]
+    fn suspend(&mut self) {}
+
+    /// Runs when this scene is back on top, after the one pushed on it is popped.
+    #[inline]
+    fn resume(&mut self) {}
+
+    /// The palettes the scene fades in to after `enter`, or `None` if it sets its own.
+    #[inline]
+    fn palettes(&self) -> Option<&[Palette; LINES]> {
+        None
+    }
+
+    /// Returns true for scenes drawn over the one below, such as a pause menu, which are pushed
+    /// and popped without fading, and leave the palettes alone.
+    #[inline]
+    fn is_overlay(&self) -> bool {
+        false
+    }
+}
+
+/// A stack of scenes, of which the top one runs, with fades to and from black between them.
+///
+/// ```ignore
+/// static mut SCENES: SceneManager<Screen, 4> = SceneManager::new();
+///
+/// let scenes = unsafe { &mut *(&raw mut SCENES) };
+/// scenes.start(Screen::Title(Title::new()));
+/// loop {
+///     scenes.update();
+///     VDP::wait_for_vblank(None);
+/// }
+/// ```
+///
+/// The manager keeps the palette `Fader` the fades go through, which sends its colors with queued
+/// DMA, so it must stay alive until the next vblank after every `update`. In practice this means
+/// keeping it in a static.
+pub struct SceneManager<S: Scene, const N: usize> {
+    stack: Vec<S, N>,
+    fader: Fader,
+    fade_frames: u8,
+    /// A transition waiting for the fade out to finish.
+    pending: Option<Transition<S>>,
+}
+
+impl<S: Scene, const N: usize> SceneManager<S, N> {
+    pub const fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            fader: Fader::new(),
+            fade_frames: DEFAULT_FADE_FRAMES,
+            pending: None,
+        }
+    }
+
+    #[inline]
+    pub const fn with_fade_frames(mut self, frames: u8) -> Self {
+        self.fade_frames = frames;
+        self
+    }
+
+    #[inline]
+    pub fn set_fade_frames(&mut self, frames: u8) {
+        self.fade_frames = frames;
+    }
+
+    /// The fader scenes' palettes go through, for effects of their own between transitions.
+    #[inline]
+    pub fn fader(&mut self) -> &mut Fader {
+        &mut self.fader
+    }
+
+    /// The scene on top.
+    #[inline]
+    pub fn current(&self) -> Option<&S> {
+        self.stack.last()
+    }
+
+    #[inline]
+    pub fn current_mut(&mut self) -> Option<&mut S> {
+        self.stack.last_mut()
+    }
+
+    /// The number of scenes on the stack.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Returns true while the screen is fading between scenes.
+    #[inline]
+    pub fn is_transitioning(&self) -> bool {
+        self.pending.is_some() || self.fader.is_fading()
+    }
+
+    /// Ends every scene, and enters `scene`, fading in from whatever's on screen.
+    pub fn start(&mut self, scene: S) {
+        while let Some(mut old) = self.stack.pop() {
+            old.exit();
+        }
+        self.pending = None;
+        self.enter(scene);
+    }
+
+    /// Runs the frame: the top scene's update, or the next step of a transition. Call this once a
+    /// frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a scene is pushed onto a full stack.
+    pub fn update(&mut self) {
+        if self.pending.is_some() {
+            if !self.fader.is_fading() {
+                if let Some(transition) = self.pending.take() {
+                    self.apply(transition);
+                }
+            }
+        } else if let Some(transition) = self.stack.last_mut().and_then(Scene::update) {
+            if self.is_instant(&transition) {
+                self.apply(transition);
+            } else {
+                self.fader.fade_to_black(self.fade_frames);
+                self.pending = Some(transition);
+            }
+        }
+        self.fader.step();
+    }
+
+    /// Whether a transition skips the fade, which is the case for overlays coming and going.
+    fn is_instant(&self, transition: &Transition<S>) -> bool {
+        match transition {
+            Transition::Push(scene) => scene.is_overlay(),
+            Transition::Pop => self.stack.last().is_some_and(Scene::is_overlay),
+            Transition::Replace(_) => false,
+        }
+    }
+
+    fn apply(&mut self, transition: Transition<S>) {
+        match transition {
+            Transition::Push(scene) => {
+                if let Some(top) = self.stack.last_mut() {
+                    top.suspend();
+                }
+                self.enter(scene);
+            }
+            Transition::Pop => {
+                let overlay = self.stack.last().is_some_and(Scene::is_overlay);
+                if let Some(mut old) = self.stack.pop() {
+                    old.exit();
+                }
+                if let Some(top) = self.stack.last_mut() {
+                    top.resume();
+                    if !overlay {
+                        if let Some(palettes) = top.palettes() {
+                            self.fader.fade_to(palettes, self.fade_frames);
+                        }
+                    }
+                }
+            }
+            Transition::Replace(scene) => {
+                if let Some(mut old) = self.stack.pop() {
+                    old.exit();
+                }
+                self.enter(scene);
+            }
+        }
+    }
+
+    fn enter(&mut self, mut scene: S) {
+        scene.enter();
+        if !scene.is_overlay() {
+            if let Some(palettes) = scene.palettes() {
+                self.fader.fade_to(palettes, self.fade_frames);
+            }
+        }
+        if self.stack.push(scene).is_err() {
+            panic!("scene stack is full");
+        }
+    }
+}
+
+impl<S: Scene, const N: usize> Default for SceneManager<S, N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,169 @@
+use super::pause;
+
+/// An event in a cutscene, `frame` frames after it starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cue<E> {
+    pub frame: u16,
+    pub event: E,
+    /// Whether the event still runs when the cutscene is skipped, for events that leave things
+    /// the way the game expects to find them afterwards, such as spawning an actor or moving the
+    /// camera. Sounds and dialog are left out.
+    pub on_skip: bool,
+}
+
+impl<E> Cue<E> {
+    #[inline]
+    pub const fn at(frame: u16, event: E) -> Self {
+        Self { frame, event, on_skip: false }
+    }
+
+    /// Makes the event run when the cutscene is skipped too.
+    #[inline]
+    pub const fn on_skip(mut self) -> Self {
+        self.on_skip = true;
+        self
+    }
+}
+
+/// A cutscene's events, in the order they happen. The events are the game's own type, usually an
+/// enum with a variant for each kind of thing a cutscene does:
+///
+/// ```ignore
+/// enum Event { Spawn(ActorKind, i16, i16), Camera(i16, i16), Sfx(Sound), Dialog(&'static str), FadeOut }
+///
+/// const INTRO: Script<Event> = Script::new(&[
+///     Cue::at(0, Event::Spawn(ActorKind::Hero, 40, 160)).on_skip(),
+///     Cue::at(30, Event::Dialog("Where am I?")),
+///     Cue::at(90, Event::Camera(256, 0)).on_skip(),
+///     Cue::at(90, Event::Sfx(Sound::Rumble)),
+///     Cue::at(180, Event::FadeOut),
+/// ]);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Script<E: 'static> {
+    cues: &'static [Cue<E>],
+    length: u16,
+}
+
+impl<E> Script<E> {
+    /// A script that ends on the frame of its last cue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cues aren't sorted by frame, which fails the build when used in a const.
+    pub const fn new(cues: &'static [Cue<E>]) -> Self {
+        let mut i = 1;
+        while i < cues.len() {
+            if cues[i].frame < cues[i - 1].frame {
+                panic!("cutscene cues must be sorted by frame");
+            }
+            i += 1;
+        }
+        let length = if let Some(last) = cues.last() { last.frame } else { 0 };
+        Self { cues, length }
+    }
+
+    /// Carries the cutscene on past its last cue, until `frames` frames after it started.
+    #[inline]
+    pub const fn with_length(mut self, frames: u16) -> Self {
+        if frames > self.length {
+            self.length = frames;
+        }
+        self
+    }
+
+    #[inline]
+    pub const fn cues(&self) -> &'static [Cue<E>] {
+        self.cues
+    }
+
+    #[inline]
+    pub const fn length(&self) -> u16 {
+        self.length
+    }
+}
+
+/// Plays a `Script`, handing back each event on its frame for the game to carry out.
+///
+/// The timeline stops while the game is paused, and can be held, for instance while a line of
+/// dialog waits for a button, so events stay in step with what the player has seen however long
+/// that takes.
+pub struct Timeline<E: 'static> {
+    script: Script<E>,
+    frame: u16,
+    /// The first cue that hasn't run.
+    next: usize,
+    held: bool,
+}
+
+impl<E> Timeline<E> {
+    pub const fn new(script: Script<E>) -> Self {
+        Self {
+            script,
+            frame: 0,
+            next: 0,
+            held: false,
+        }
+    }
+
+    /// Starts the cutscene over.
+    #[inline]
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.next = 0;
+        self.held = false;
+    }
+
+    /// How many frames the cutscene has run for, not counting frames paused or held.
+    #[inline]
+    pub const fn frame(&self) -> u16 {
+        self.frame
+    }
+
+    /// Stops the timeline until `release` is called.
+    #[inline]
+    pub fn hold(&mut self) {
+        self.held = true;
+    }
+
+    #[inline]
+    pub fn release(&mut self) {
+        self.held = false;
+    }
+
+    #[inline]
+    pub const fn is_held(&self) -> bool {
+        self.held
+    }
+
+    /// Returns true once every cue has run and the script's length has gone by.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.script.cues.len() && self.frame > self.script.length
+    }
+
+    /// Advances the timeline by a frame, and returns the events due on it, in order. Call this once
+    /// per frame. Nothing happens while the timeline is held or the game is paused.
+    pub fn update(&mut self) -> impl Iterator<Item = &'static E> {
+        let cues = self.script.cues;
+        let start = self.next;
+        if !self.held && !pause::is_paused() && !self.is_finished() {
+            while cues.get(self.next).is_some_and(|cue| cue.frame <= self.frame) {
+                self.next += 1;
+            }
+            self.frame += 1;
+        }
+        cues[start..self.next].iter().map(|cue| &cue.event)
+    }
+
+    /// Skips to the end of the cutscene, and returns the events that haven't run yet but should
+    /// still run when it's skipped, in order.
+    pub fn skip(&mut self) -> impl Iterator<Item = &'static E> {
+        let cues = self.script.cues;
+        let start = self.next;
+        self.next = cues.len();
+        self.frame = self.script.length + 1;
+        self.held = false;
+        cues[start..].iter().filter(|cue| cue.on_skip).map(|cue| &cue.event)
+    }
+}
@@ -0,0 +1,222 @@
+use fixed::types::I16F16;
+
+use crate::sys::math::Vec2F16;
+use crate::sys::vdp::Settings;
+
+/// A box on screen the camera's target can move around in without the camera following.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadzone {
+    pub x: i16,
+    pub y: i16,
+    pub width: i16,
+    pub height: i16,
+}
+
+impl Deadzone {
+    #[inline]
+    pub const fn new(x: i16, y: i16, width: i16, height: i16) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// A deadzone of `width` by `height` in the middle of a `view_width` by `view_height` screen.
+    #[inline]
+    pub const fn centered(view_width: u16, view_height: u16, width: i16, height: i16) -> Self {
+        Self::new((view_width as i16 - width) / 2, (view_height as i16 - height) / 2, width, height)
+    }
+}
+
+/// The part of a level the camera is kept inside, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub left: i16,
+    pub top: i16,
+    pub right: i16,
+    pub bottom: i16,
+}
+
+impl Bounds {
+    #[inline]
+    pub const fn new(left: i16, top: i16, right: i16, bottom: i16) -> Self {
+        Self { left, top, right, bottom }
+    }
+
+    /// The whole of a level `width` by `height` tiles.
+    #[inline]
+    pub const fn for_tiles(width: u16, height: u16) -> Self {
+        Self::new(0, 0, (width << 3) as i16, (height << 3) as i16)
+    }
+}
+
+/// Where the camera ended up after `Camera::follow`, in the forms the rest of a frame needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CameraFrame {
+    /// The level position at the screen's top left, in whole pixels, for
+    /// `TileMapStreamer::update`.
+    pub x: i32,
+    pub y: i32,
+    /// How far the camera moved since the last frame, in whole pixels, for parallax layers and
+    /// anything else that moves with the screen.
+    pub dx: i32,
+    pub dy: i32,
+    /// The values for the plane's entries in the scroll tables.
+    pub hscroll: i16,
+    pub vscroll: i16,
+}
+
+/// Follows a target around a level, such as the player.
+///
+/// Each frame, `follow` is given the target's position. The camera only moves once the target
+/// leaves the deadzone, and then eases towards keeping it inside, closing a fraction of the gap
+/// each frame set by the smoothing. It never shows anything outside the bounds.
+///
+/// ```ignore
+/// let mut camera = Camera::for_settings(&settings)
+///     .with_deadzone(Deadzone::centered(320, 224, 32, 48))
+///     .with_smoothing(2)
+///     .with_bounds(Bounds::for_tiles(map.width(), map.height()));
+/// camera.snap_to(player.pos);
+/// streamer.redraw(camera.frame().x, camera.frame().y);
+///
+/// // Every frame:
+/// let frame = camera.follow(player.pos);
+/// streamer.update(frame.x, frame.y);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    /// The level position at the screen's top left.
+    pos: Vec2F16,
+    view_width: u16,
+    view_height: u16,
+    deadzone: Deadzone,
+    /// Each frame the camera closes `1 / 2^smoothing` of the gap to where it wants to be.
+    smoothing: u8,
+    bounds: Option<Bounds>,
+    /// The whole pixel position as of the last frame, for the deltas.
+    last: (i32, i32),
+}
+
+impl Camera {
+    /// Creates a camera for a `width` by `height` screen, that keeps its target in the middle of
+    /// the screen with no smoothing or bounds.
+    pub const fn new(width: u16, height: u16) -> Self {
+        Self {
+            pos: Vec2F16::new(I16F16::ZERO, I16F16::ZERO),
+            view_width: width,
+            view_height: height,
+            deadzone: Deadzone::centered(width, height, 0, 0),
+            smoothing: 0,
+            bounds: None,
+            last: (0, 0),
+        }
+    }
+
+    /// Creates a camera for the display size in `settings`.
+    #[inline]
+    pub fn for_settings(settings: &Settings) -> Self {
+        Self::new(settings.display_width().pixels, settings.display_height().pixels)
+    }
+
+    #[inline]
+    pub const fn with_deadzone(mut self, deadzone: Deadzone) -> Self {
+        self.deadzone = deadzone;
+        self
+    }
+
+    /// Makes the camera close `1 / 2^shift` of the gap each frame, rather than all of it. 0 snaps,
+    /// and every step up halves the speed.
+    #[inline]
+    pub const fn with_smoothing(mut self, shift: u8) -> Self {
+        self.smoothing = shift;
+        self
+    }
+
+    #[inline]
+    pub const fn with_bounds(mut self, bounds: Bounds) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    #[inline]
+    pub fn set_deadzone(&mut self, deadzone: Deadzone) {
+        self.deadzone = deadzone;
+    }
+
+    #[inline]
+    pub fn set_smoothing(&mut self, shift: u8) {
+        self.smoothing = shift;
+    }
+
+    /// Changes the bounds, for example when a boss arena closes off. The camera is kept inside the
+    /// new bounds from the next `follow`, easing there with the smoothing.
+    #[inline]
+    pub fn set_bounds(&mut self, bounds: Option<Bounds>) {
+        self.bounds = bounds;
+    }
+
+    /// The level position at the screen's top left.
+    #[inline]
+    pub fn position(&self) -> Vec2F16 {
+        self.pos
+    }
+
+    /// Where the camera is, without moving it. The deltas are from the last frame.
+    #[inline]
+    pub fn frame(&self) -> CameraFrame {
+        let (x, y) = (self.pos.x.to_num::<i32>(), self.pos.y.to_num::<i32>());
+        CameraFrame {
+            x,
+            y,
+            dx: x - self.last.0,
+            dy: y - self.last.1,
+            hscroll: -x as i16,
+            vscroll: y as i16,
+        }
+    }
+
+    /// Moves the camera straight to where it would settle for `target`, such as at the start of
+    /// a level or after a teleport. The next frame's deltas are from here.
+    pub fn snap_to(&mut self, target: Vec2F16) {
+        self.pos = self.clamp(self.wanted(target));
+        self.last = (self.pos.x.to_num(), self.pos.y.to_num());
+    }
+
+    /// Moves the camera towards `target` for this frame. Call this once a frame.
+    pub fn follow(&mut self, target: Vec2F16) -> CameraFrame {
+        let wanted = self.clamp(self.wanted(target));
+        let step = |from: I16F16, to: I16F16| from + ((to - from) >> self.smoothing as u32);
+        self.pos = self.clamp(Vec2F16::new(step(self.pos.x, wanted.x), step(self.pos.y, wanted.y)));
+
+        let frame = self.frame();
+        self.last = (frame.x, frame.y);
+        frame
+    }
+
+    /// Where the camera has to be to bring `target` back inside the deadzone.
+    fn wanted(&self, target: Vec2F16) -> Vec2F16 {
+        let axis = |pos: I16F16, target: I16F16, start: i16, len: i16| {
+            let (start, end) = (pos + I16F16::from_num(start), pos + I16F16::from_num(start + len));
+            if target < start {
+                pos + (target - start)
+            } else if target > end {
+                pos + (target - end)
+            } else {
+                pos
+            }
+        };
+        let dz = self.deadzone;
+        Vec2F16::new(axis(self.pos.x, target.x, dz.x, dz.width), axis(self.pos.y, target.y, dz.y, dz.height))
+    }
+
+    /// Keeps the screen inside the bounds. A level smaller than the screen stays at its top left.
+    fn clamp(&self, pos: Vec2F16) -> Vec2F16 {
+        let Some(bounds) = self.bounds else { return pos };
+        let axis = |pos: I16F16, min: i16, max: i16, view: u16| {
+            let max = (max - view as i16).max(min);
+            pos.clamp(I16F16::from_num(min), I16F16::from_num(max))
+        };
+        Vec2F16::new(
+            axis(pos.x, bounds.left, bounds.right, self.view_width),
+            axis(pos.y, bounds.top, bounds.bottom, self.view_height),
+        )
+    }
+}
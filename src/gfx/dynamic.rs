@@ -0,0 +1,143 @@
+use crate::sys::timing;
+use crate::sys::vdp::{Address, DMACommand, Tile, TileAllocator, TileHandle, VRAMAddress};
+
+/// Why a frame couldn't be queued. The slot keeps showing its last frame either way, and the
+/// upload can be tried again next frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadError {
+    /// The words already queued for these slots this frame plus this frame's would be over the
+    /// budget set with `set_budget`.
+    OverBudget,
+    /// The DMA queue is full.
+    QueueFull,
+}
+
+/// `N` slots of VRAM tiles which animated sprites stream their frames into, instead of keeping
+/// every frame in VRAM.
+///
+/// Each slot is the size of the biggest frame that goes in it. When a sprite's animation moves
+/// on, `set_frame` queues a DMA of the new frame's tiles over the slot, and the sprite keeps
+/// drawing from the slot's tiles as before.
+///
+/// ```ignore
+/// static mut ALLOCATOR: TileAllocator<16> = TileAllocator::new(0x100, 0x200);
+///
+/// let allocator = unsafe { &mut *(&raw mut ALLOCATOR) };
+/// let mut slots = DynamicTiles::<4>::alloc(allocator, 16).unwrap();
+/// loop {
+///     let _ = slots.set_frame(allocator, 0, PLAYER_FRAMES[player.frame()]);
+///     let base = slots.tile_index(allocator, 0);
+///     // Draw the player's sprite from `base`.
+///     VDP::wait_for_vblank(None);
+/// }
+/// ```
+///
+/// The slots are one range from a `TileAllocator`, so defragmenting can move them. The tile index
+/// should be looked up with `tile_index` whenever the sprite is drawn, as with the allocator's own
+/// ranges.
+pub struct DynamicTiles<const N: usize> {
+    handle: TileHandle,
+    slot_tiles: u16,
+    /// The frame each slot was last given, to skip uploading it again.
+    current: [Option<*const Tile>; N],
+    budget: u16,
+    /// The words queued so far in `frame`.
+    queued: u16,
+    frame: u32,
+}
+
+impl<const N: usize> DynamicTiles<N> {
+    /// Allocates `N` slots of `slot_tiles` tiles each. Returns `None` if the allocator hasn't got
+    /// room for them.
+    pub fn alloc<const M: usize>(allocator: &mut TileAllocator<M>, slot_tiles: u16) -> Option<Self> {
+        let handle = allocator.alloc(slot_tiles.checked_mul(N as u16)?)?;
+        Some(Self {
+            handle,
+            slot_tiles,
+            current: [None; N],
+            budget: u16::MAX,
+            queued: 0,
+            frame: 0,
+        })
+    }
+
+    /// Gives the slots back to the allocator.
+    #[inline]
+    pub fn free<const M: usize>(self, allocator: &mut TileAllocator<M>) {
+        allocator.free(self.handle);
+    }
+
+    /// Limits the words of DMA these slots queue in one frame, so streaming animation can't take
+    /// more than its share of `VDP::dma_budget`. There's no limit to begin with.
+    #[inline]
+    pub fn set_budget(&mut self, words: u16) {
+        self.budget = words;
+    }
+
+    /// The number of tiles in each slot.
+    #[inline]
+    pub fn slot_tiles(&self) -> u16 {
+        self.slot_tiles
+    }
+
+    /// The first tile index of `slot`.
+    #[inline]
+    pub fn tile_index<const M: usize>(&self, allocator: &TileAllocator<M>, slot: usize) -> u16 {
+        allocator.base(&self.handle) + (slot % N) as u16 * self.slot_tiles
+    }
+
+    /// Where `slot` starts in VRAM. Slots are `slot_tiles` tiles of 32 bytes apart, so the
+    /// address goes through `from_tile_index` like every other tile.
+    #[inline]
+    fn slot_address<const M: usize>(&self, allocator: &TileAllocator<M>, slot: usize) -> VRAMAddress {
+        VRAMAddress::from_tile_index(self.tile_index(allocator, slot))
+    }
+
+    /// Queues `tiles` to be uploaded into `slot` in the next vblank. Nothing is queued if the slot
+    /// already has these tiles.
+    ///
+    /// The tiles are sent straight from where they are, so they have to be in ROM or a static.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more tiles than fit in a slot.
+    pub fn set_frame<const M: usize>(
+        &mut self,
+        allocator: &TileAllocator<M>,
+        slot: usize,
+        tiles: &'static [Tile],
+    ) -> Result<(), UploadError> {
+        if tiles.len() > self.slot_tiles as usize {
+            panic!("frame is bigger than the slot");
+        }
+        let slot = slot % N;
+        if self.current[slot] == Some(tiles.as_ptr()) {
+            return Ok(());
+        }
+
+        let frame = timing::elapsed_frames();
+        if frame != self.frame {
+            self.frame = frame;
+            self.queued = 0;
+        }
+        let words = (tiles.len() * size_of::<Tile>() / 2) as u16;
+        if self.queued.saturating_add(words) > self.budget {
+            return Err(UploadError::OverBudget);
+        }
+
+        let addr = self.slot_address(allocator, slot);
+        DMACommand::new_static_transfer(tiles, Address::VRAM(addr), None)
+            .schedule()
+            .map_err(|_| UploadError::QueueFull)?;
+        self.queued += words;
+        self.current[slot] = Some(tiles.as_ptr());
+        Ok(())
+    }
+
+    /// Forgets what's in every slot, so the next `set_frame` uploads whatever it's given. This is
+    /// needed if something else has written over the slots.
+    #[inline]
+    pub fn invalidate(&mut self) {
+        self.current = [None; N];
+    }
+}
@@ -0,0 +1,171 @@
+use crate::sys::vdp::{Sprite, SpriteSize, TileFlags};
+
+use super::sprites::ORIGIN;
+
+/// The area sprites can be seen in, which is the biggest the screen gets.
+const SCREEN_WIDTH: i16 = 320;
+const SCREEN_HEIGHT: i16 = 240;
+
+/// One hardware sprite in a meta-sprite's frame, placed relative to the meta-sprite's anchor.
+#[derive(Debug, Clone, Copy)]
+pub struct Piece {
+    pub x: i16,
+    pub y: i16,
+    pub size: SpriteSize,
+    /// The piece's tiles and flips. The tile index counts from the meta-sprite's first tile.
+    pub flags: TileFlags,
+}
+
+impl Piece {
+    #[inline]
+    pub const fn new(x: i16, y: i16, size: SpriteSize, tile: u16) -> Self {
+        Self { x, y, size, flags: TileFlags::for_tile(tile, 0) }
+    }
+
+    #[inline]
+    pub const fn with_flags(mut self, flags: TileFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+}
+
+/// A character or object too big for one hardware sprite, drawn as several pieces that move and
+/// flip together.
+///
+/// Frames are lists of pieces, which usually live in ROM:
+///
+/// ```ignore
+/// const BOSS_IDLE: &[Piece] = &[
+///     Piece::new(-16, -48, SpriteSize::Size4x4, 0),
+///     Piece::new(-16, -16, SpriteSize::Size4x2, 16),
+/// ];
+///
+/// let mut boss = MetaSprite::new(BOSS_IDLE).with_tile_base(allocator.base(&boss_tiles));
+/// boss.set_position(160, 200);
+/// boss.set_flip(facing_left, false);
+/// table.push_meta(&boss);
+/// ```
+///
+/// Flipping mirrors the pieces around the anchor, which is usually put at the character's feet.
+/// Each piece's tiles are flipped too, on top of any flips the piece has of its own.
+#[derive(Debug, Clone, Copy)]
+pub struct MetaSprite {
+    pieces: &'static [Piece],
+    x: i16,
+    y: i16,
+    tile_base: u16,
+    flip_h: bool,
+    flip_v: bool,
+    palette: Option<u8>,
+    priority: Option<bool>,
+}
+
+impl MetaSprite {
+    pub const fn new(pieces: &'static [Piece]) -> Self {
+        Self {
+            pieces,
+            x: 0,
+            y: 0,
+            tile_base: 0,
+            flip_h: false,
+            flip_v: false,
+            palette: None,
+            priority: None,
+        }
+    }
+
+    /// Makes piece tile indices count from `tile`, where the meta-sprite's tiles were loaded.
+    #[inline]
+    pub const fn with_tile_base(mut self, tile: u16) -> Self {
+        self.tile_base = tile;
+        self
+    }
+
+    /// Draws every piece with `palette`, rather than the palettes in their flags.
+    #[inline]
+    pub const fn with_palette(mut self, palette: Option<u8>) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Draws every piece with or without priority, rather than as in their flags.
+    #[inline]
+    pub const fn with_priority(mut self, priority: Option<bool>) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    #[inline]
+    pub const fn pieces(&self) -> &'static [Piece] {
+        self.pieces
+    }
+
+    /// Switches to another frame, keeping the position, flips and overrides.
+    #[inline]
+    pub fn set_pieces(&mut self, pieces: &'static [Piece]) {
+        self.pieces = pieces;
+    }
+
+    /// The anchor's position on screen.
+    #[inline]
+    pub const fn position(&self) -> (i16, i16) {
+        (self.x, self.y)
+    }
+
+    #[inline]
+    pub fn set_position(&mut self, x: i16, y: i16) {
+        self.x = x;
+        self.y = y;
+    }
+
+    #[inline]
+    pub fn set_flip(&mut self, h: bool, v: bool) {
+        self.flip_h = h;
+        self.flip_v = v;
+    }
+
+    #[inline]
+    pub const fn flip(&self) -> (bool, bool) {
+        (self.flip_h, self.flip_v)
+    }
+
+    #[inline]
+    pub fn set_palette(&mut self, palette: Option<u8>) {
+        self.palette = palette;
+    }
+
+    #[inline]
+    pub fn set_priority(&mut self, priority: Option<bool>) {
+        self.priority = priority;
+    }
+
+    /// The hardware sprites for the pieces that are at least partly on screen, in order, with
+    /// their links left at 0.
+    pub fn sprites(&self) -> impl Iterator<Item = Sprite> + '_ {
+        self.pieces.iter().filter_map(move |piece| {
+            let (w, h) = ((piece.size.width() as i16) << 3, (piece.size.height() as i16) << 3);
+            let dx = if self.flip_h { -piece.x - w } else { piece.x };
+            let dy = if self.flip_v { -piece.y - h } else { piece.y };
+            let (x, y) = (self.x.wrapping_add(dx), self.y.wrapping_add(dy));
+            if x + w <= 0 || x >= SCREEN_WIDTH || y + h <= 0 || y >= SCREEN_HEIGHT {
+                return None;
+            }
+
+            let mut flags = piece.flags
+                .with_tile_index(piece.flags.tile_index().wrapping_add(self.tile_base))
+                .with_flip_h(piece.flags.flip_h() != self.flip_h)
+                .with_flip_v(piece.flags.flip_v() != self.flip_v);
+            if let Some(palette) = self.palette {
+                flags.set_palette(palette);
+            }
+            if let Some(priority) = self.priority {
+                flags.set_priority(priority);
+            }
+
+            let mut sprite = Sprite::with_flags(flags, piece.size);
+            sprite.x = (x + ORIGIN) as u16;
+            sprite.y = (y + ORIGIN) as u16;
+            Some(sprite)
+        })
+    }
+}
@@ -0,0 +1,12 @@
+mod camera;
+mod dynamic;
+mod metasprite;
+mod sprites;
+mod streamer;
+pub mod tileops;
+
+pub use camera::{Bounds, Camera, CameraFrame, Deadzone};
+pub use dynamic::{DynamicTiles, UploadError};
+pub use metasprite::{MetaSprite, Piece};
+pub use sprites::SpriteTable;
+pub use streamer::{TileMap, TileMapStreamer, MAX_STEP};
@@ -0,0 +1,212 @@
+use crate::sys::game_loop::Commit;
+use crate::sys::vdp::{Address, DMACommand, Settings, Sprite, VRAMAddress, VRAMData};
+
+use super::MetaSprite;
+
+/// Sprite positions are offset by this much, so the screen's top left is at (128, 128).
+pub(super) const ORIGIN: i16 = 128;
+
+/// The most separate writes a partial update is split into before a full upload is cheaper. Each
+/// one takes a slot in the DMA queue.
+const MAX_PARTIAL_WRITES: usize = 8;
+
+/// A RAM copy of the sprite table, filled from scratch each frame.
+///
+/// Sprites are pushed in the order they're drawn, frontmost first, and `flush` links them up and
+/// sends the table to VRAM using queued DMA. Like other queued DMA sources, the table must stay
+/// alive until the next vblank after flushing, which in practice means keeping it in a static.
+///
+/// `N` is the number of sprites the table holds, which is 80 in H40 and 64 in H32.
+///
+/// With partial updates on, the table remembers what it last sent, and when only a few words have
+/// changed, such as the positions of a couple of sprites, it sends just those words. That costs a
+/// second copy of the table in RAM.
+pub struct SpriteTable<const N: usize = 80> {
+    base: VRAMAddress,
+    sprites: [Sprite; N],
+    len: usize,
+    partial: bool,
+    /// What VRAM holds, as of the last flush, once `synced` is set.
+    sent: [Sprite; N],
+    sent_len: usize,
+    synced: bool,
+}
+
+impl<const N: usize> SpriteTable<N> {
+    /// Creates an empty table for the sprite table at `base`.
+    pub const fn new(base: VRAMAddress) -> Self {
+        Self {
+            base,
+            sprites: [Sprite::ZEROED; N],
+            len: 0,
+            partial: false,
+            sent: [Sprite::ZEROED; N],
+            sent_len: 0,
+            synced: false,
+        }
+    }
+
+    /// Turns on partial updates. See the type's documentation.
+    #[inline]
+    pub const fn with_partial_updates(mut self) -> Self {
+        self.partial = true;
+        self
+    }
+
+    #[inline]
+    pub fn set_partial_updates(&mut self, enable: bool) {
+        self.partial = enable;
+        self.synced = false;
+    }
+
+    /// Makes the next flush send the whole table, for when something else has written to the
+    /// sprite table in VRAM.
+    #[inline]
+    pub fn invalidate(&mut self) {
+        self.synced = false;
+    }
+
+    /// Creates an empty table for the sprite table configured in `settings`.
+    #[inline]
+    pub fn for_settings(settings: &Settings) -> Self {
+        Self::new(settings.sprites_base())
+    }
+
+    #[inline]
+    pub fn base(&self) -> VRAMAddress {
+        self.base
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns true once no more sprites fit.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len >= N
+    }
+
+    /// The sprites pushed so far.
+    #[inline]
+    pub fn sprites(&self) -> &[Sprite] {
+        &self.sprites[..self.len]
+    }
+
+    /// Removes every sprite, to start the next frame.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Adds a sprite behind those already pushed. Its link is filled in by `flush`.
+    ///
+    /// Returns false if the table is full.
+    #[inline]
+    pub fn push(&mut self, sprite: Sprite) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.sprites[self.len] = sprite;
+        self.len += 1;
+        true
+    }
+
+    /// Adds a sprite with its top left corner at (`x`, `y`) on screen.
+    ///
+    /// Returns false if the table is full.
+    #[inline]
+    pub fn push_at(&mut self, mut sprite: Sprite, x: i16, y: i16) -> bool {
+        sprite.x = (x + ORIGIN) as u16;
+        sprite.y = (y + ORIGIN) as u16;
+        self.push(sprite)
+    }
+
+    /// Adds every piece of `meta` that's on screen, in the meta-sprite's order. Pieces that don't
+    /// fit are left out.
+    ///
+    /// Returns the number of sprites added.
+    pub fn push_meta(&mut self, meta: &MetaSprite) -> usize {
+        let start = self.len;
+        for sprite in meta.sprites() {
+            if !self.push(sprite) {
+                break;
+            }
+        }
+        self.len - start
+    }
+
+    /// Links the sprites up and queues the table to be sent to VRAM during the next vblank.
+    ///
+    /// An empty table still sends one sprite, off screen, so nothing from the last frame stays up.
+    pub fn flush(&mut self) -> Result<(), DMACommand> {
+        if self.len == 0 {
+            self.sprites[0] = Sprite::ZEROED;
+        }
+        let len = self.len.max(1);
+        // The list ends at the sprite that links back to sprite 0.
+        for (i, sprite) in self.sprites[..len].iter_mut().enumerate() {
+            sprite.link = if i + 1 < len { i as u8 + 1 } else { 0 };
+        }
+
+        if self.partial && self.synced && len <= self.sent_len {
+            if let Some((writes, count)) = self.changed_words(len) {
+                return self.flush_partial(len, &writes[..count]);
+            }
+        }
+
+        DMACommand::new_transfer(&self.sprites[..len], Address::VRAM(self.base), None).schedule()?;
+        if self.partial {
+            self.sent[..len].copy_from_slice(&self.sprites[..len]);
+            self.sent_len = len;
+            self.synced = true;
+        }
+        Ok(())
+    }
+
+    /// The range of words that changed in each sprite that did, or `None` if there are too many to
+    /// be worth sending separately. Sprites past `len` are left as they were, since the list ends
+    /// before them.
+    fn changed_words(&self, len: usize) -> Option<([(u8, u8, u8); MAX_PARTIAL_WRITES], usize)> {
+        let mut writes = [(0, 0, 0); MAX_PARTIAL_WRITES];
+        let mut count = 0;
+        for i in 0..len {
+            let (new, old) = (self.sprites[i].as_words(), self.sent[i].as_words());
+            let Some(first) = (0..4).find(|&w| new[w] != old[w]) else { continue };
+            let last = (first..4).rfind(|&w| new[w] != old[w]).unwrap_or(first);
+            if count == MAX_PARTIAL_WRITES {
+                return None;
+            }
+            writes[count] = (i as u8, first as u8, last as u8 + 1);
+            count += 1;
+        }
+        Some((writes, count))
+    }
+
+    /// Queues the changed words of each sprite. Sprites that don't fit in the DMA queue are sent by
+    /// a later flush.
+    fn flush_partial(&mut self, len: usize, writes: &[(u8, u8, u8)]) -> Result<(), DMACommand> {
+        for &(i, first, end) in writes {
+            let (i, first, end) = (i as usize, first as usize, end as usize);
+            let words = &self.sprites[i].as_words()[first..end];
+            let addr = VRAMAddress::from_word_addr(self.base.word_addr() + (i as u16) * 4 + first as u16);
+            DMACommand::new_transfer(words, Address::VRAM(addr), None).schedule()?;
+            self.sent[i] = self.sprites[i];
+        }
+        self.sent_len = self.sent_len.max(len);
+        Ok(())
+    }
+}
+
+impl<const N: usize> Commit for SpriteTable<N> {
+    #[inline]
+    fn commit(&mut self) -> Result<(), DMACommand> {
+        self.flush()
+    }
+}
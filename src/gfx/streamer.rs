@@ -0,0 +1,216 @@
+use core::num::NonZero;
+
+use crate::sys;
+use crate::sys::vdp::{Address, DMACommand, Plane, PlaneSize, Settings, TileFlags, VRAMAddress, Writer};
+
+/// The most columns or rows `TileMapStreamer::update` sends in a frame. Moving further than this
+/// redraws the whole plane.
+pub const MAX_STEP: usize = 2;
+
+/// A level's tiles, bigger than a plane, in row-major order.
+#[derive(Debug, Clone, Copy)]
+pub struct TileMap {
+    tiles: &'static [TileFlags],
+    width: u16,
+    height: u16,
+}
+
+impl TileMap {
+    /// # Panics
+    ///
+    /// Panics if `tiles` doesn't hold `width` by `height` tiles, which fails the build when used in
+    /// a const.
+    pub const fn new(tiles: &'static [TileFlags], width: u16, height: u16) -> Self {
+        if tiles.len() != width as usize * height as usize {
+            panic!("tile map is the wrong size");
+        }
+        Self { tiles, width, height }
+    }
+
+    #[inline]
+    pub const fn width(&self) -> u16 {
+        self.width
+    }
+
+    #[inline]
+    pub const fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// The tile at (`x`, `y`), or a blank tile outside the map.
+    #[inline]
+    pub fn get(&self, x: i32, y: i32) -> TileFlags {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return TileFlags::ZEROED;
+        }
+        self.tiles[y as usize * self.width as usize + x as usize]
+    }
+}
+
+/// Scrolls a plane across a `TileMap`, sending only the columns and rows of tiles that come into
+/// view.
+///
+/// The plane is used as a ring: each map column goes into the plane column it's congruent to,
+/// and the same for rows, so the plane always holds the tiles around the camera, and scrolling
+/// it by the camera's position shows them in the right place. The plane has to be bigger than the
+/// screen by at least a tile each way, and no more than 64 tiles wide.
+///
+/// New columns and rows are sent with queued DMA from buffers in the streamer, so it must stay
+/// alive until the next vblank after `update`, which in practice means keeping it in a static.
+pub struct TileMapStreamer {
+    map: TileMap,
+    base: VRAMAddress,
+    size: PlaneSize,
+    view_cols: i32,
+    view_rows: i32,
+    /// The camera position, in pixels.
+    x: i32,
+    y: i32,
+    /// The map tile at the top left of the screen.
+    col: i32,
+    row: i32,
+    /// Set when something couldn't be queued, so the next update redraws everything.
+    stale: bool,
+    columns: [[TileFlags; 128]; MAX_STEP],
+    rows: [[TileFlags; 64]; MAX_STEP],
+}
+
+impl TileMapStreamer {
+    /// Creates a streamer for `plane` as configured in `settings`. Call `redraw` to fill the
+    /// plane before the first `update`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the plane is wider than 64 tiles, or not bigger than the screen.
+    pub fn new(map: TileMap, settings: &Settings, plane: Plane) -> Self {
+        let size = settings.plane_size();
+        let base = match plane {
+            Plane::A => settings.plane_a_base(),
+            Plane::B => settings.plane_b_base(),
+        };
+        let view_cols = settings.display_width().tiles as i32 + 1;
+        let view_rows = settings.display_height().tiles as i32 + 1;
+        if size.width_tiles() > 64 {
+            panic!("tile map streaming needs a plane at most 64 tiles wide");
+        }
+        if (size.width_tiles() as i32) < view_cols || (size.height_tiles() as i32) < view_rows {
+            panic!("tile map streaming needs a plane bigger than the screen");
+        }
+
+        Self {
+            map,
+            base,
+            size,
+            view_cols,
+            view_rows,
+            x: 0,
+            y: 0,
+            col: 0,
+            row: 0,
+            stale: true,
+            columns: [[TileFlags::ZEROED; 128]; MAX_STEP],
+            rows: [[TileFlags::ZEROED; 64]; MAX_STEP],
+        }
+    }
+
+    #[inline]
+    pub fn map(&self) -> &TileMap {
+        &self.map
+    }
+
+    /// The camera position the plane was last brought up to date for.
+    #[inline]
+    pub fn position(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+    /// The horizontal and vertical scroll values that show the camera's view, for the plane's
+    /// entries in the scroll tables.
+    #[inline]
+    pub fn scroll(&self) -> (i16, i16) {
+        (-self.x as i16, self.y as i16)
+    }
+
+    /// Fills the whole plane for a camera at (`x`, `y`), writing straight to VRAM. This is slow,
+    /// so it's for when a level starts or the camera jumps, ideally with the display off.
+    pub fn redraw(&mut self, x: i32, y: i32) {
+        self.set_position(x, y);
+        let (width, height) = (self.size.width_tiles() as i32, self.size.height_tiles() as i32);
+        for r in self.row..self.row + height {
+            let plane_row = r.rem_euclid(height);
+            let buf = &mut self.rows[0];
+            for c in self.col..self.col + width {
+                buf[c.rem_euclid(width) as usize] = self.map.get(c, r);
+            }
+            let addr = self.size.tile_offset_from(self.base, 0, plane_row as u8);
+            sys::with_cs::<1, 7, _>(|_| {
+                Writer::new(Address::VRAM(addr)).with_autoinc(2).write::<[TileFlags]>(&buf[..width as usize]);
+            });
+        }
+        self.stale = false;
+    }
+
+    /// Moves the camera to (`x`, `y`), and queues the columns and rows that came into view. Call
+    /// this once a frame.
+    ///
+    /// Moving more than `MAX_STEP` tiles either way since the last update redraws the whole plane,
+    /// as does a column or row that didn't fit in the DMA queue last time.
+    pub fn update(&mut self, x: i32, y: i32) {
+        let (col, row) = (self.col, self.row);
+        let (new_col, new_row) = (x >> 3, y >> 3);
+        let far = |from: i32, to: i32| (to - from).unsigned_abs() as usize > MAX_STEP;
+        if self.stale || far(col, new_col) || far(row, new_row) {
+            self.redraw(x, y);
+            return;
+        }
+        self.set_position(x, y);
+
+        // Columns go first, with the new rows, and the rows after, with the new columns, so the
+        // corner both uncover is sent either way.
+        let columns = if new_col > col { col + self.view_cols..new_col + self.view_cols } else { new_col..col };
+        for (slot, c) in columns.enumerate() {
+            self.stream_column(slot, c);
+        }
+        let rows = if new_row > row { row + self.view_rows..new_row + self.view_rows } else { new_row..row };
+        for (slot, r) in rows.enumerate() {
+            self.stream_row(slot, r);
+        }
+    }
+
+    #[inline]
+    fn set_position(&mut self, x: i32, y: i32) {
+        self.x = x;
+        self.y = y;
+        self.col = x >> 3;
+        self.row = y >> 3;
+    }
+
+    /// Queues map column `c` into its plane column, filled with the map rows around the camera.
+    fn stream_column(&mut self, slot: usize, c: i32) {
+        let (width, height) = (self.size.width_tiles() as i32, self.size.height_tiles() as i32);
+        let buf = &mut self.columns[slot];
+        for r in self.row..self.row + height {
+            buf[r.rem_euclid(height) as usize] = self.map.get(c, r);
+        }
+        let addr = self.size.tile_offset_from(self.base, c.rem_euclid(width) as u8, 0);
+        // Each tile of a column is a row further on in VRAM.
+        let autoinc = NonZero::new((width * 2) as u8);
+        let cmd = DMACommand::new_transfer(&buf[..height as usize], Address::VRAM(addr), autoinc);
+        if cmd.schedule().is_err() {
+            self.stale = true;
+        }
+    }
+
+    /// Queues map row `r` into its plane row, filled with the map columns around the camera.
+    fn stream_row(&mut self, slot: usize, r: i32) {
+        let (width, height) = (self.size.width_tiles() as i32, self.size.height_tiles() as i32);
+        let buf = &mut self.rows[slot];
+        for c in self.col..self.col + width {
+            buf[c.rem_euclid(width) as usize] = self.map.get(c, r);
+        }
+        let addr = self.size.tile_offset_from(self.base, 0, r.rem_euclid(height) as u8);
+        if DMACommand::new_transfer(&buf[..width as usize], Address::VRAM(addr), None).schedule().is_err() {
+            self.stale = true;
+        }
+    }
+}
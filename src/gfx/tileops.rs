@@ -0,0 +1,138 @@
+use crate::sys::vdp::Tile;
+
+/// The width and height of a tile, in pixels.
+const SIZE: usize = 8;
+
+/// The color index of the pixel at (`x`, `y`). The leftmost pixel is in the top nibble of its row.
+#[inline]
+pub const fn pixel(tile: &Tile, x: usize, y: usize) -> u8 {
+    ((tile[y] >> ((SIZE - 1 - x) * 4)) & 0xF) as u8
+}
+
+#[inline]
+pub const fn set_pixel(tile: &mut Tile, x: usize, y: usize, index: u8) {
+    let shift = (SIZE - 1 - x) * 4;
+    tile[y] = (tile[y] & !(0xF << shift)) | (((index & 0xF) as u32) << shift);
+}
+
+/// Mirrors a tile left to right.
+///
+/// A whole sprite or plane tile can be flipped for free with its `TileFlags`, so this is for when
+/// only part of a picture is flipped, such as a tile about to be merged into another.
+pub const fn flip_h(tile: &Tile) -> Tile {
+    let mut out = [0; SIZE];
+    let mut y = 0;
+    while y < SIZE {
+        // Swapping the nibbles in each byte, then the bytes, reverses the pixels.
+        let row = tile[y];
+        out[y] = (((row & 0x0F0F0F0F) << 4) | ((row >> 4) & 0x0F0F0F0F)).swap_bytes();
+        y += 1;
+    }
+    out
+}
+
+/// Mirrors a tile top to bottom.
+pub const fn flip_v(tile: &Tile) -> Tile {
+    let mut out = [0; SIZE];
+    let mut y = 0;
+    while y < SIZE {
+        out[y] = tile[SIZE - 1 - y];
+        y += 1;
+    }
+    out
+}
+
+/// Turns a tile a quarter turn clockwise.
+pub const fn rotate_cw(tile: &Tile) -> Tile {
+    let mut out = [0; SIZE];
+    let mut y = 0;
+    while y < SIZE {
+        let mut x = 0;
+        while x < SIZE {
+            set_pixel(&mut out, x, y, pixel(tile, y, SIZE - 1 - x));
+            x += 1;
+        }
+        y += 1;
+    }
+    out
+}
+
+/// Turns a tile a quarter turn anticlockwise.
+pub const fn rotate_ccw(tile: &Tile) -> Tile {
+    let mut out = [0; SIZE];
+    let mut y = 0;
+    while y < SIZE {
+        let mut x = 0;
+        while x < SIZE {
+            set_pixel(&mut out, x, y, pixel(tile, SIZE - 1 - y, x));
+            x += 1;
+        }
+        y += 1;
+    }
+    out
+}
+
+/// Turns a tile half a turn, which is the same as flipping it both ways.
+#[inline]
+pub const fn rotate_180(tile: &Tile) -> Tile {
+    flip_v(&flip_h(tile))
+}
+
+/// Replaces every color index `i` in a tile with `map[i]`, for recoloring art within a palette
+/// line, such as a second player's sprite, or an enemy that's been hit.
+pub const fn remap(tile: &Tile, map: &[u8; 16]) -> Tile {
+    let mut out = [0; SIZE];
+    let mut y = 0;
+    while y < SIZE {
+        let mut row = 0;
+        let mut shift = 0;
+        while shift < 32 {
+            let index = (tile[y] >> shift) & 0xF;
+            row |= ((map[index as usize] & 0xF) as u32) << shift;
+            shift += 4;
+        }
+        out[y] = row;
+        y += 1;
+    }
+    out
+}
+
+/// A row with every nibble that's not 0 in `row` set to 0xF.
+#[inline]
+const fn opaque(row: u32) -> u32 {
+    ((row | (row >> 1) | (row >> 2) | (row >> 3)) & 0x11111111) * 0xF
+}
+
+/// Takes each pixel from `top` where `mask`'s pixel isn't 0, and from `base` everywhere else.
+pub const fn merge(base: &Tile, top: &Tile, mask: &Tile) -> Tile {
+    let mut out = [0; SIZE];
+    let mut y = 0;
+    while y < SIZE {
+        let mask = opaque(mask[y]);
+        out[y] = (base[y] & !mask) | (top[y] & mask);
+        y += 1;
+    }
+    out
+}
+
+/// Draws `top` over `base`, with `top`'s color 0 left transparent as the VDP does.
+#[inline]
+pub const fn overlay(base: &Tile, top: &Tile) -> Tile {
+    merge(base, top, top)
+}
+
+/// Applies `f` to each tile in `tiles`, in place, such as to flip a whole set of tiles.
+///
+/// ```ignore
+/// static mut MIRRORED: [Tile; 16] = [[0; 8]; 16];
+///
+/// let mirrored = unsafe { &mut *(&raw mut MIRRORED) };
+/// mirrored.copy_from_slice(&PLAYER_TILES[..16]);
+/// tileops::apply(mirrored, tileops::flip_h);
+/// ```
+#[inline]
+pub fn apply(tiles: &mut [Tile], f: impl Fn(&Tile) -> Tile) {
+    for tile in tiles {
+        *tile = f(tile);
+    }
+}
@@ -0,0 +1,84 @@
+#![no_std]
+#![feature(asm_experimental_arch)]
+#![feature(ptr_metadata)]
+#![feature(bigint_helper_methods)]
+#![feature(likely_unlikely)]
+#![feature(const_option_ops)]
+#![feature(const_trait_impl)]
+#![feature(const_convert)]
+#![feature(const_ops)]
+#![feature(slice_ptr_get)]
+#![feature(allocator_api)]
+#![feature(maybe_uninit_array_assume_init)]
+
+extern crate alloc;
+
+pub mod sys;
+pub mod gfx;
+#[cfg(feature = "game")]
+pub mod game;
+
+/// Defines the program's entry point, which runs once `sys` has set up the console, and places the
+/// ROM header built from a `sys::header::RomConfig`, or `RomConfig::DEFAULT` if none is given.
+///
+/// Every ROM built with this crate, including the examples, names its main function here:
+///
+/// ```ignore
+/// mdrs::entry!(run);
+///
+/// fn run() -> ! {
+///     loop {
+///         mdrs::sys::vdp::VDP::wait_for_vblank(None);
+///     }
+/// }
+///
+/// const CONFIG: mdrs::sys::header::RomConfig = mdrs::sys::header::RomConfig::DEFAULT.with_title("MY GAME");
+/// mdrs::entry!(run, CONFIG);
+/// ```
+#[macro_export]
+macro_rules! entry {
+    ($main:path) => {
+        $crate::entry!($main, $crate::sys::header::RomConfig::DEFAULT);
+    };
+    ($main:path, $config:expr) => {
+        $crate::rom_header!($config);
+
+        #[no_mangle]
+        pub fn main() -> ! {
+            let main: fn() -> ! = $main;
+            main()
+        }
+    };
+}
+
+/// Declares a module with a const for each asset in the crate's `assets.toml`, converted by the
+/// build script. Images become a `sys::vdp::Image`, palettes a `&sys::vdp::Palette`, maps a
+/// `gfx::TileMap`, VGM files a `sys::audio::music::Song`, and other audio a `&[u8]`, each named
+/// after its key in upper case.
+///
+/// ```toml
+/// [images]
+/// player = "src/assets/player.png"
+///
+/// [maps]
+/// level1 = { path = "src/assets/level1.map", width = 128, height = 32 }
+/// ```
+///
+/// ```ignore
+/// mdrs::include_assets!(mod assets);
+///
+/// let streamer = TileMapStreamer::new(assets::LEVEL1, &settings, Plane::A);
+/// ```
+///
+/// Files that are missing or the wrong size fail the build, naming the manifest entry.
+#[macro_export]
+macro_rules! include_assets {
+    ($vis:vis mod $name:ident) => {
+        $vis mod $name {
+            #[allow(unused_imports)]
+            use $crate as mdrs;
+
+            include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+        }
+    };
+}
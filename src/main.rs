@@ -1,37 +1,28 @@
 #![no_std]
 #![no_main]
-#![feature(asm_experimental_arch)]
-#![feature(ptr_metadata)]
-#![feature(bigint_helper_methods)]
-#![feature(likely_unlikely)]
-#![feature(const_option_ops)]
-#![feature(const_trait_impl)]
-#![feature(const_convert)]
-#![feature(const_ops)]
-#![feature(slice_ptr_get)]
-#![feature(allocator_api)]
 #![feature(maybe_uninit_array_assume_init)]
 
-use core::num::NonZero;
+use mdrs::include_tiles;
+use mdrs::sys::{self, io, vdp};
 
-use fixed::types::{I8F8, I16F16};
-
-use crate::sys::{io, vdp};
-
-extern crate alloc;
-
-pub mod sys;
+mdrs::entry!(demo);
 
 const FONT_DATA: &[vdp::Tile] = include_tiles!("assets/font4bpp.bin");
 
-const PALETTE: &[u16] = &[
-    0xF000, 0xFF00, 0xF0F0, 0xF00F, 0xFFF0, 0xFF0F, 0xF0FF,
-    0xF800, 0xF080, 0xF008, 0xF880, 0xF808, 0xF088, 0xF666, 0xFBBB, 0xFFFF,
+const PALETTE: &vdp::Palette = &[
+    vdp::Color::BLACK, vdp::Color::BLUE, vdp::Color::GREEN, vdp::Color::RED,
+    vdp::Color::new(0, 7, 7), vdp::Color::new(7, 0, 7), vdp::Color::new(7, 7, 0),
+    vdp::Color::new(0, 0, 4), vdp::Color::new(0, 4, 0), vdp::Color::new(4, 0, 0),
+    vdp::Color::new(0, 4, 4), vdp::Color::new(4, 0, 4), vdp::Color::new(4, 4, 0),
+    vdp::Color::new(3, 3, 3), vdp::Color::new(5, 5, 5), vdp::Color::WHITE,
 ];
 
-#[no_mangle]
-pub fn main() -> ! {
-    
+/// The scroll values are sent with queued DMA, so they live in a static rather than on the stack.
+static mut SCROLLER: vdp::Scroller = vdp::Scroller::new();
+
+fn demo() -> ! {
+    mdrs::info!("demo starting");
+
     let mut settings = vdp::Settings::DEFAULT;
     settings.set_scroll_mode(vdp::HScrollMode::Screen, vdp::VScrollMode::Screen);
     settings.apply::<true>();
@@ -71,30 +62,29 @@ pub fn main() -> ! {
         }
     }
 
+    let scroller = unsafe { &mut *(&raw mut SCROLLER) };
     let mut hscroll = 0i16;
     let mut vscroll = 0i16;
 
-    loop {
-        let p1 = core::hint::black_box(sys::with_cs::<1, 7, _>(|cs| core::hint::black_box(io::P1_CONTROLLER.borrow(cs).get())));
+    sys::run_game_loop(|frame| {
+        let buttons = frame.p1.buttons();
 
-        if p1.left() {
+        if buttons.contains(io::Buttons::LEFT) {
             hscroll += 1;
         }
-        if p1.right() {
+        if buttons.contains(io::Buttons::RIGHT) {
             hscroll -= 1;
         }
 
-        if p1.up() {
+        if buttons.contains(io::Buttons::UP) {
             vscroll -= 1;
         }
-        if p1.down() {
+        if buttons.contains(io::Buttons::DOWN) {
             vscroll += 1;
         }
 
-        vdp::Writer::new(vdp::Address::VRAM(settings.hscroll_base())).with_autoinc(2).write([hscroll, hscroll]);
-
-        vdp::Writer::new(vdp::Address::VSRAM(0)).with_autoinc(2).write([vscroll, vscroll]);
-
-        vdp::VDP::wait_for_vblank(None);
-    }
+        scroller.set_plane_scroll(vdp::Plane::A, hscroll, vscroll);
+        scroller.set_plane_scroll(vdp::Plane::B, hscroll, vscroll);
+        frame.commit(scroller).ok();
+    })
 }
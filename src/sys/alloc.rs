@@ -1,4 +1,6 @@
-use core::{alloc::Layout, ptr::NonNull};
+use core::{alloc::{AllocError, Allocator, Layout}, cell::UnsafeCell, num::NonZero, ptr::NonNull};
+
+use critical_section as cs;
 
 
 extern "C" {
@@ -6,20 +8,38 @@ extern "C" {
     static mut _heap_end: u8;
 }
 
-#[inline]
-const fn heap_size() -> usize {
-    unsafe { (&raw const _heap_end).offset_from(&raw const _heap_start) as usize }
-}
-
 /// A specialized allocator, taking advantage of the fact that RAM is only 64 kB, and can be addressed fully with a u16, rather than a usize.
 /// 
 /// As a result, block headers are tiny; only a single word!
-pub struct MDSpecializeAlloc;
+///
+/// Frees are deferred: `dealloc` only pushes the block onto a list, which is drained by the next
+/// allocation or by `drain_deferred`, so freeing keeps interrupts masked for as short a time as
+/// possible. Freed blocks are merged with free blocks after them straight away, and `realloc`
+/// grows or shrinks a block in place when it can, rather than copying it.
+///
+/// Besides the global heap, instances can manage other regions of RAM as arenas (see `new_in`),
+/// which are used through `allocator_api` collections, and can be freed wholesale with `reset`.
+pub struct MDSpecializeAlloc {
+    name: &'static str,
+    start: *mut u8,
+    end: *mut u8,
+    /// The low word of the address of the most recently deferred block's data, or 0 if there are
+    /// none. Each deferred block holds the link to the next one in its first two data bytes.
+    deferred: UnsafeCell<u16>,
+}
+
+// The region bounds never change, and the deferred list is only ever touched with interrupts masked.
+unsafe impl Sync for MDSpecializeAlloc {}
 
 impl MDSpecializeAlloc {
     #[inline]
     const fn root_block(&self) -> NonNull<BlockHeader> {
-        unsafe { NonNull::new_unchecked((&raw mut _heap_start).cast()) }
+        unsafe { NonNull::new_unchecked(self.start.cast()) }
+    }
+
+    #[inline]
+    const fn region_end(&self) -> NonNull<u8> {
+        unsafe { NonNull::new_unchecked(self.end) }
     }
 
     #[inline]
@@ -27,48 +47,158 @@ impl MDSpecializeAlloc {
         let mut current = Some(self.root_block());
         while let Some(mut curr_ptr) = current {
             let curr_block = curr_ptr.as_mut();
+            curr_block.validate();
             if curr_block.is_free() {
-                // Try combining consecutive free blocks.
-                while let Some(next_ptr) = curr_block.next() {
-                    // Current block isnt at the end, so start checking the next block.
-                    let next_block = next_ptr.as_ref();
-                    if next_block.is_free() {
-                        // Combine the current block with the next block.
-                        curr_block.size += next_block.size & !BlockHeader::FREE_BIT;
-                    } else {
-                        // Hit a used block, break
-                        break;
-                    }
-                }
+                // Freeing only merges a block with the ones after it, so a block freed before the
+                // one in front of it is merged here.
+                self.absorb_free(curr_block);
 
                 if curr_block.satisfies_layout(layout) {
                     // Current block has a suitable size, so break
                     break;
                 } else {
-                    current = curr_block.next();
+                    current = curr_block.next(self.region_end());
                 }
             } else {
-                current = curr_block.next();
+                current = curr_block.next(self.region_end());
             }
         }
         current
     }
 
+    /// The allocator for the global heap, which lies between `_heap_start` and `_heap_end`.
     #[inline]
     pub const fn new() -> Self {
-        Self
+        Self {
+            name: "heap",
+            start: &raw mut _heap_start,
+            end: &raw mut _heap_end,
+            deferred: UnsafeCell::new(0),
+        }
+    }
+
+    /// An allocator managing `region`, which is usually a static buffer.
+    ///
+    /// `init` must be called before allocating from it.
+    ///
+    /// # Safety
+    ///
+    /// `region` must be in work RAM, word aligned, no more than 0xFFFE bytes long, and not used for
+    /// anything else for as long as the allocator is.
+    #[inline]
+    pub const unsafe fn new_in(name: &'static str, region: *mut [u8]) -> Self {
+        let start = region.cast::<u8>();
+        Self {
+            name,
+            start,
+            end: start.add(region.len() & !1),
+            deferred: UnsafeCell::new(0),
+        }
+    }
+
+    /// The name the allocator was given, for diagnostics.
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The size of the region the allocator manages, in bytes.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        unsafe { self.end.offset_from_unsigned(self.start) }
+    }
+
+    #[inline]
+    fn link_to_ptr(&self, link: u16) -> NonNull<u8> {
+        let root = self.root_block().cast::<u8>();
+        unsafe { root.with_addr(NonZero::new_unchecked((root.addr().get() & !0xFFFF) | link as usize)) }
+    }
+
+    /// Pushes a block onto the deferred free list.
+    ///
+    /// The 68000 has no compare-and-swap, so the push masks interrupts, but only for the two words
+    /// it writes.
+    #[inline]
+    unsafe fn defer(&self, ptr: NonNull<u8>) {
+        let mut block_ptr = ptr.cast::<BlockHeader>().sub(1);
+        #[cfg(feature = "alloc-debug")]
+        super::with_cs::<1, 7, _>(|_| {
+            let block = block_ptr.as_mut();
+            block.validate();
+            if block.magic != BlockHeader::MAGIC_USED {
+                panic!("heap: double free");
+            }
+            block.magic = BlockHeader::MAGIC_DEFERRED;
+        });
+
+        if block_ptr.as_ref().size() < size_of::<u16>() {
+            // Too small to hold a link, so free it right away; it's a single word write anyway.
+            super::with_cs::<1, 7, _>(|_| self.free_block(ptr));
+            return;
+        }
+
+        super::with_cs::<1, 7, _>(|_| {
+            // Data may be byte aligned, so the link is written a byte at a time.
+            ptr.cast::<[u8; 2]>().write(core::ptr::read_volatile(self.deferred.get()).to_be_bytes());
+            core::ptr::write_volatile(self.deferred.get(), ptr.addr().get() as u16);
+        });
+    }
+
+    /// Frees every block on the deferred list. Interrupts must be masked.
+    #[inline]
+    unsafe fn drain_deferred_locked(&self) {
+        let mut link = core::ptr::replace(self.deferred.get(), 0);
+        while link != 0 {
+            let ptr = self.link_to_ptr(link);
+            link = u16::from_be_bytes(ptr.cast::<[u8; 2]>().read());
+            self.free_block(ptr);
+        }
+    }
+
+    /// Frees every block on the deferred list.
+    ///
+    /// Allocating does this too, but calling it from the main loop keeps the list short and the
+    /// free block count accurate. The list is detached in one go, and each block is then freed with
+    /// interrupts masked only briefly.
+    pub fn drain_deferred(&self) {
+        let mut link = super::with_cs::<1, 7, _>(|_| unsafe { core::ptr::replace(self.deferred.get(), 0) });
+        while link != 0 {
+            let ptr = self.link_to_ptr(link);
+            unsafe {
+                link = u16::from_be_bytes(ptr.cast::<[u8; 2]>().read());
+                super::with_cs::<1, 7, _>(|_| self.free_block(ptr));
+            }
+        }
     }
 
     #[inline]
     pub unsafe fn init(&self) {
         // Initialize root block
-        *self.root_block().as_mut() = BlockHeader {
-            size: BlockHeader::FREE_BIT | ((heap_size() as u16) >> 1),
-        };
+        *self.root_block().as_mut() = BlockHeader::new(BlockHeader::FREE_BIT | (((self.capacity() - size_of::<BlockHeader>()) as u16) >> 1));
+
+        #[cfg(feature = "alloc-debug")]
+        {
+            let root = self.root_block().as_ref();
+            root.data_start().write_bytes(POISON, root.size());
+        }
+    }
+
+    /// Frees everything in the arena at once, e.g. when a level's data is no longer needed.
+    ///
+    /// # Safety
+    ///
+    /// Nothing allocated from the arena may be used afterwards.
+    pub unsafe fn reset(&self) {
+        super::with_cs::<1, 7, _>(|_| {
+            core::ptr::write_volatile(self.deferred.get(), 0);
+            self.init();
+        })
     }
 
     #[inline(never)]
     pub unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        self.drain_deferred_locked();
+
         let mut block_ptr = self.get_free_block(layout)?;
         let block = block_ptr.as_mut();
 
@@ -76,50 +206,150 @@ impl MDSpecializeAlloc {
         let data_ptr = block.data_with_layout(layout);
         let data_size = block.data_end().byte_offset_from_unsigned(data_ptr);
 
+        #[cfg(feature = "alloc-debug")]
+        if !core::slice::from_raw_parts(data_ptr.as_ptr(), data_size).iter().all(|&b| b == POISON) {
+            panic!("heap: freed memory was written to");
+        }
+
+        if data_ptr == block.data_start() {
+            // The data fills the whole block, so just take it
+            *block = BlockHeader::new(block.size & !BlockHeader::FREE_BIT);
+            return Some(data_ptr);
+        }
+
         // Initalize new block header
         let mut header_ptr = data_ptr.cast::<BlockHeader>().sub(1);
-        *header_ptr.as_mut() = BlockHeader {
-            size: (data_size as u16) >> 1, // No free bit
-        };
+        *header_ptr.as_mut() = BlockHeader::new((data_size as u16) >> 1); // No free bit
 
         // Change old block size to reflect new block
-        block.size -= block.data_start().byte_offset_from_unsigned(header_ptr) as u16;
+        block.size = BlockHeader::FREE_BIT | ((header_ptr.cast::<u8>().byte_offset_from_unsigned(block.data_start()) as u16) >> 1);
 
         Some(data_ptr)
     }
 
     #[inline(never)]
-    pub unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+    pub unsafe fn deallocate(&self, ptr: NonNull<u8>, _layout: Layout) {
+        self.free_block(ptr);
+    }
+
+    /// Marks a block as free, and merges it with any free blocks right after it. The block before it
+    /// can't be found from here, so that merge is left to `get_free_block`.
+    #[inline]
+    unsafe fn free_block(&self, ptr: NonNull<u8>) {
         let mut block_ptr = ptr.cast::<BlockHeader>().sub(1);
-        block_ptr.as_mut().size |= BlockHeader::FREE_BIT; // Mark block as free
+        let block = block_ptr.as_mut();
+
+        #[cfg(feature = "alloc-debug")]
+        {
+            block.validate();
+            if block.is_free() {
+                panic!("heap: double free");
+            }
+            block.data_start().write_bytes(POISON, block.size());
+            block.magic = BlockHeader::MAGIC_FREE;
+        }
+
+        block.size |= BlockHeader::FREE_BIT; // Mark block as free
+        self.absorb_free(block);
+    }
+
+    /// Merges every free block directly after `block` into it, headers included.
+    #[inline]
+    unsafe fn absorb_free(&self, block: &mut BlockHeader) {
+        while let Some(next_ptr) = block.next(self.region_end()) {
+            let next_block = next_ptr.as_ref();
+            next_block.validate();
+            if !next_block.is_free() {
+                break;
+            }
+            block.size += (next_block.size & !BlockHeader::FREE_BIT) + BlockHeader::WORDS;
+            #[cfg(feature = "alloc-debug")]
+            next_ptr.cast::<u8>().write_bytes(POISON, size_of::<BlockHeader>());
+        }
+    }
+
+    /// Resizes a used block to hold `new_size` bytes without moving it. Shrinking always works, and
+    /// growing works if there's enough free space right after the block. Whatever isn't needed is
+    /// split off into a free block. Interrupts must be masked.
+    unsafe fn resize_in_place(&self, ptr: NonNull<u8>, new_size: usize) -> bool {
+        self.drain_deferred_locked();
+
+        let mut block_ptr = ptr.cast::<BlockHeader>().sub(1);
+        let block = block_ptr.as_mut();
+        block.validate();
+
+        let needed = (new_size + 1) & !1;
+        let mut keep = needed;
+        if needed > block.size() {
+            let old_size = block.size();
+            self.absorb_free(block);
+            if needed > block.size() {
+                // Not enough room, so give back what was taken.
+                keep = old_size;
+            }
+        }
+
+        let spare = block.size() - keep;
+        if spare >= size_of::<BlockHeader>() {
+            block.size = (keep >> 1) as u16;
+            let mut rest_ptr = block.data_end().cast::<BlockHeader>();
+            let rest = rest_ptr.as_mut();
+            *rest = BlockHeader::new(BlockHeader::FREE_BIT | (((spare - size_of::<BlockHeader>()) as u16) >> 1));
+            #[cfg(feature = "alloc-debug")]
+            rest.data_start().write_bytes(POISON, rest.size());
+            self.absorb_free(rest);
+        }
+
+        keep == needed
+    }
+
+    /// Resizes an allocation, moving it only if it can't be resized in place. Interrupts must be
+    /// masked.
+    unsafe fn reallocate(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Option<NonNull<u8>> {
+        if self.resize_in_place(ptr, new_layout.size()) {
+            return Some(ptr);
+        }
+
+        let new_ptr = self.allocate(new_layout)?;
+        new_ptr.copy_from_nonoverlapping(ptr, old_layout.size().min(new_layout.size()));
+        self.free_block(ptr);
+        Some(new_ptr)
+    }
+
+    /// Resizes an allocation made through the `Allocator` impl.
+    unsafe fn resize(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if old_layout.size() == 0 || new_layout.size() == 0 || ptr.addr().get() & (new_layout.align() - 1) != 0 {
+            // Zero sized allocations aren't real blocks, and a stricter alignment needs a new one.
+            let new_ptr = Allocator::allocate(self, new_layout)?;
+            new_ptr.cast::<u8>().copy_from_nonoverlapping(ptr, old_layout.size().min(new_layout.size()));
+            Allocator::deallocate(self, ptr, old_layout);
+            return Ok(new_ptr);
+        }
+
+        let ptr = super::with_cs::<1, 7, _>(|_| self.reallocate(ptr, old_layout, new_layout)).ok_or(AllocError)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()))
     }
 }
 
 unsafe impl core::alloc::GlobalAlloc for MDSpecializeAlloc {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         let ptr = super::with_cs::<1, 7, _>(|_| self.allocate(layout));
+        if ptr.is_none() {
+            crate::warn!("{}: out of memory for {} bytes", self.name, super::fmt::Int(layout.size()));
+        }
 
         ptr.map_or(core::ptr::null_mut(), |ptr| ptr.as_ptr())
     }
     
-    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        super::with_cs::<1, 7, _>(|_| self.deallocate(NonNull::new_unchecked(ptr), layout));
+    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+        self.defer(NonNull::new_unchecked(ptr));
     }
 
     unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
         let old_ptr = NonNull::new_unchecked(ptr);
         let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
 
-        let new_ptr = super::with_cs::<1, 7, _>(|_| {
-            let new_ptr = self.allocate(new_layout);
-
-            if let Some(new_ptr) = new_ptr {
-                new_ptr.copy_from_nonoverlapping(old_ptr, layout.size().min(new_size));
-                self.deallocate(old_ptr, layout);
-            }
-
-            new_ptr
-        });
+        let new_ptr = super::with_cs::<1, 7, _>(|_| self.reallocate(old_ptr, layout, new_layout));
 
         new_ptr.map_or(core::ptr::null_mut(), |ptr| ptr.as_ptr())
     }
@@ -135,27 +365,281 @@ unsafe impl core::alloc::GlobalAlloc for MDSpecializeAlloc {
     }
 }
 
+/// The byte freed memory is filled with when the `alloc-debug` feature is enabled.
+#[cfg(feature = "alloc-debug")]
+pub const POISON: u8 = 0xDB;
+
+unsafe impl Allocator for MDSpecializeAlloc {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            let dangling = unsafe { NonNull::new_unchecked(layout.align() as *mut u8) };
+            return Ok(NonNull::slice_from_raw_parts(dangling, 0));
+        }
+
+        let ptr = super::with_cs::<1, 7, _>(|_| unsafe { MDSpecializeAlloc::allocate(self, layout) }).ok_or(AllocError)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            self.defer(ptr);
+        }
+    }
+
+    unsafe fn grow(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.resize(ptr, old_layout, new_layout)
+    }
+
+    unsafe fn grow_zeroed(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = self.resize(ptr, old_layout, new_layout)?;
+        new_ptr.cast::<u8>().add(old_layout.size()).write_bytes(0, new_layout.size() - old_layout.size());
+        Ok(new_ptr)
+    }
+
+    unsafe fn shrink(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.resize(ptr, old_layout, new_layout)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct BumpState {
+    /// The offset of the first unallocated byte.
+    top: u16,
+    /// The number of allocations that haven't been deallocated yet.
+    live: u16,
+}
+
+/// A bump allocator over a fixed region of RAM, for scratch data that only lives for a frame, like
+/// the sprite list being built for the next one.
+///
+/// Allocating just moves a pointer forward, and deallocating does nothing unless it was the most
+/// recent allocation. Everything is freed at once by `reset`, which refuses to run while any
+/// allocation is still live, so a collection that's accidentally kept for longer holds the arena
+/// rather than being freed from under.
+///
+/// With the `frame-arena` feature, `sys::frame_arena` is one of these, reset at the start of every
+/// vblank.
+pub struct BumpArena {
+    name: &'static str,
+    start: *mut u8,
+    end: *mut u8,
+    state: UnsafeCell<BumpState>,
+}
+
+// The region bounds never change, and the state is only ever touched with interrupts masked.
+unsafe impl Sync for BumpArena {}
+
+impl BumpArena {
+    /// An arena managing `region`, which is usually a static buffer.
+    ///
+    /// # Safety
+    ///
+    /// `region` must be no more than 0xFFFF bytes long, and not used for anything else for as long
+    /// as the arena is.
+    #[inline]
+    pub const unsafe fn new_in(name: &'static str, region: *mut [u8]) -> Self {
+        let start = region.cast::<u8>();
+        Self {
+            name,
+            start,
+            end: start.add(region.len()),
+            state: UnsafeCell::new(BumpState { top: 0, live: 0 }),
+        }
+    }
+
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The size of the region the arena manages, in bytes.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        unsafe { self.end.offset_from_unsigned(self.start) }
+    }
+
+    #[inline]
+    fn state(&self) -> BumpState {
+        super::with_cs::<1, 7, _>(|_| unsafe { core::ptr::read_volatile(self.state.get()) })
+    }
+
+    /// The number of bytes allocated since the last reset, including alignment padding.
+    #[inline]
+    pub fn used(&self) -> usize {
+        self.state().top as usize
+    }
+
+    /// The number of allocations that haven't been deallocated yet.
+    #[inline]
+    pub fn live(&self) -> u16 {
+        self.state().live
+    }
+
+    /// Frees everything in the arena. Returns false, and frees nothing, if any allocations are
+    /// still live.
+    #[inline]
+    pub fn reset(&self) -> bool {
+        super::with_cs::<1, 7, _>(|cs| self.reset_in(cs))
+    }
+
+    /// Same as `reset`, from code that already has interrupts masked, like the vblank handler.
+    pub(in crate::sys) fn reset_in(&self, _cs: cs::CriticalSection) -> bool {
+        let state = unsafe { &mut *self.state.get() };
+        if state.live != 0 {
+            return false;
+        }
+        state.top = 0;
+        true
+    }
+
+    /// Returns true if `ptr` to `ptr + size` is the most recent allocation, so it can be resized or
+    /// freed in place.
+    #[inline]
+    fn is_last(&self, state: &BumpState, ptr: NonNull<u8>, size: usize) -> bool {
+        ptr.as_ptr().wrapping_add(size) == self.start.wrapping_add(state.top as usize)
+    }
+}
+
+unsafe impl Allocator for BumpArena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            let dangling = unsafe { NonNull::new_unchecked(layout.align() as *mut u8) };
+            return Ok(NonNull::slice_from_raw_parts(dangling, 0));
+        }
+
+        super::with_cs::<1, 7, _>(|_| {
+            let state = unsafe { &mut *self.state.get() };
+            let top = self.start.wrapping_add(state.top as usize);
+            let start = state.top as usize + top.align_offset(layout.align());
+            let end = start.checked_add(layout.size()).filter(|&end| end <= self.capacity()).ok_or(AllocError)?;
+
+            state.top = end as u16;
+            state.live += 1;
+            let ptr = unsafe { NonNull::new_unchecked(self.start.add(start)) };
+            Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+        })
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() == 0 {
+            return;
+        }
+
+        super::with_cs::<1, 7, _>(|_| {
+            let state = &mut *self.state.get();
+            if self.is_last(state, ptr, layout.size()) {
+                state.top = ptr.as_ptr().offset_from_unsigned(self.start) as u16;
+            }
+            state.live -= 1;
+        })
+    }
+
+    unsafe fn grow(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let aligned = ptr.addr().get() & (new_layout.align() - 1) == 0;
+        if old_layout.size() != 0 && aligned {
+            // The most recent allocation can grow into the space after it.
+            let grown = super::with_cs::<1, 7, _>(|_| {
+                let state = &mut *self.state.get();
+                let start = ptr.as_ptr().offset_from_unsigned(self.start);
+                if !self.is_last(state, ptr, old_layout.size()) || start + new_layout.size() > self.capacity() {
+                    return false;
+                }
+                state.top = (start + new_layout.size()) as u16;
+                true
+            });
+            if grown {
+                return Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()));
+            }
+        }
+
+        let new_ptr = self.allocate(new_layout)?;
+        new_ptr.cast::<u8>().copy_from_nonoverlapping(ptr, old_layout.size());
+        self.deallocate(ptr, old_layout);
+        Ok(new_ptr)
+    }
+
+    unsafe fn shrink(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if new_layout.size() == 0 || ptr.addr().get() & (new_layout.align() - 1) != 0 {
+            let new_ptr = self.allocate(new_layout)?;
+            new_ptr.cast::<u8>().copy_from_nonoverlapping(ptr, new_layout.size());
+            self.deallocate(ptr, old_layout);
+            return Ok(new_ptr);
+        }
+
+        // The rest of the allocation is only given back if nothing comes after it.
+        super::with_cs::<1, 7, _>(|_| {
+            let state = &mut *self.state.get();
+            if self.is_last(state, ptr, old_layout.size()) {
+                state.top -= (old_layout.size() - new_layout.size()) as u16;
+            }
+        });
+        Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()))
+    }
+}
+
 #[repr(C)]
 struct BlockHeader {
+    /// Tells used, deferred and free blocks apart, and catches headers that have been overwritten.
+    #[cfg(feature = "alloc-debug")]
+    magic: u16,
     size: u16,
 }
 
 impl BlockHeader {
     pub const FREE_BIT: u16 = 0x8000;
 
+    /// The size of a header, in words.
+    pub const WORDS: u16 = (size_of::<Self>() >> 1) as u16;
+
+    #[cfg(feature = "alloc-debug")]
+    pub const MAGIC_USED: u16 = 0xA110;
+    #[cfg(feature = "alloc-debug")]
+    pub const MAGIC_DEFERRED: u16 = 0xDEFE;
+    #[cfg(feature = "alloc-debug")]
+    pub const MAGIC_FREE: u16 = 0xF4EE;
+
+    #[inline]
+    pub const fn new(size: u16) -> Self {
+        Self {
+            #[cfg(feature = "alloc-debug")]
+            magic: if size & Self::FREE_BIT != 0 { Self::MAGIC_FREE } else { Self::MAGIC_USED },
+            size,
+        }
+    }
+
+    /// Panics if the header has been corrupted. Does nothing unless `alloc-debug` is enabled.
+    #[inline]
+    pub fn validate(&self) {
+        #[cfg(feature = "alloc-debug")]
+        {
+            let valid = match self.magic {
+                Self::MAGIC_FREE => self.is_free(),
+                Self::MAGIC_USED | Self::MAGIC_DEFERRED => !self.is_free(),
+                _ => false,
+            };
+            if !valid {
+                panic!("heap: corrupted block header");
+            }
+        }
+    }
+
     #[inline]
     pub unsafe fn data_with_layout(&self, layout: Layout) -> NonNull<u8> {
+        // Data is always word aligned, since a header may need to go in front of it.
+        let align = layout.align().max(align_of::<Self>());
         let ptr = self.data_end().byte_sub(layout.size());
-        let align_diff = ptr.addr().get() & (layout.align() - 1);
+        let align_diff = ptr.addr().get() & (align - 1);
         if align_diff != 0 {
-            let align_offset = layout.align() - align_diff;
+            let align_offset = align - align_diff;
             ptr.byte_sub(align_offset)
         } else { ptr }
     }
 
     #[inline]
     pub unsafe fn satisfies_layout(&self, layout: Layout) -> bool {
-        unsafe { self.data_start() <= self.data_with_layout(layout) }
+        // Either the data takes the whole block, or there's room for a new header in front of it.
+        let data_ptr = unsafe { self.data_with_layout(layout) };
+        data_ptr == self.data_start() || unsafe { self.data_start().byte_add(size_of::<Self>()) } <= data_ptr
     }
 
     #[inline]
@@ -169,9 +653,9 @@ impl BlockHeader {
     }
 
     #[inline]
-    pub fn next(&self) -> Option<NonNull<BlockHeader>> {
+    pub fn next(&self, end: NonNull<u8>) -> Option<NonNull<BlockHeader>> {
         let next_ptr = self.data_end();
-        if core::ptr::addr_eq(next_ptr.as_ptr() as *const _, &raw const _heap_end) {
+        if next_ptr == end {
             None
         } else {
             Some(next_ptr.cast())
@@ -187,4 +671,35 @@ impl BlockHeader {
     pub fn data_end(&self) -> NonNull<u8> {
         unsafe { NonNull::new_unchecked((&raw const *self).add(1).byte_add(self.size()).cast::<u8>() as *mut u8) }
     }
-}
\ No newline at end of file
+}
+/// Places the heap at a fixed range of work RAM, instead of everything between `.bss` and the
+/// stack.
+///
+/// This leaves the rest of RAM free for other uses, like a framebuffer or a buffer shared with the
+/// Z80. The range is checked at compile time, and the link fails if it overlaps `.bss` or the stack.
+/// It should be used at most once, at the top level of the crate.
+///
+/// ```ignore
+/// heap_config!(start = 0xFF8000, end = 0xFFC000);
+/// ```
+#[macro_export]
+macro_rules! heap_config {
+    (start = $start:expr, end = $end:expr $(,)?) => {
+        const _: () = {
+            assert!($start >= 0xFF0000 && $end <= 0x1000000, "heap must be in work RAM");
+            assert!($start < $end, "heap must not be empty");
+            assert!($start & 1 == 0 && $end & 1 == 0, "heap bounds must be word aligned");
+            assert!($end - $start <= 0xFFFE, "heap is too big to describe with a single block");
+        };
+
+        // Symbols defined here take the place of the defaults provided by the link script.
+        core::arch::global_asm!(
+            ".global _heap_start",
+            ".global _heap_end",
+            ".set _heap_start, {start}",
+            ".set _heap_end, {end}",
+            start = const $start as u32,
+            end = const $end as u32,
+        );
+    };
+}
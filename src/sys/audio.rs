@@ -0,0 +1,35 @@
+use critical_section as cs;
+
+use crate::sys::{self, CsMask};
+
+pub mod z80;
+#[cfg(feature = "audio")]
+pub mod ext;
+#[cfg(feature = "audio")]
+pub mod fm;
+#[cfg(feature = "audio")]
+pub mod levels;
+#[cfg(feature = "audio")]
+pub mod music;
+#[cfg(feature = "audio")]
+pub mod psg;
+#[cfg(feature = "audio")]
+pub mod sfx;
+#[cfg(feature = "audio")]
+pub mod ym;
+#[cfg(feature = "pcm")]
+pub mod pcm;
+
+/// How the audio code's critical sections mask interrupts.
+///
+/// Audio state is only touched from the main loop and the vblank handler, so hblank is left
+/// running, and raster effects don't jitter while a song starts or the Z80 is busy. This means
+/// audio functions mustn't be called from an hblank handler.
+pub const CS_MASK: CsMask = CsMask::KeepHblank;
+
+/// Execute closure `f` in a critical section masked as `CS_MASK` says.
+#[inline(always)]
+pub(in crate::sys) fn with_cs<R>(f: impl FnOnce(cs::CriticalSection) -> R) -> R {
+    // SAFETY: The hblank handler never touches audio state.
+    unsafe { sys::with_cs_masked(CS_MASK, f) }
+}
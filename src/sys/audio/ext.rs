@@ -0,0 +1,81 @@
+use crate::sys::io;
+
+use super::{music, psg, ym, z80};
+
+/// Returns true if a Mega CD is attached, so its CD audio can play alongside the console's.
+///
+/// The Mega CD mixes its own audio with the console's in hardware, and its volume is only reachable
+/// from its sub CPU, so the crate doesn't control it. What's here keeps the console's side quiet
+/// while the other side plays.
+#[inline]
+pub fn mega_cd_present() -> bool {
+    crate::sys::scd::is_present()
+}
+
+/// Silences everything the console can play, in an order that doesn't click.
+///
+/// Music and samples are stopped first, so nothing plays another note. Then every FM channel is
+/// released, and the DAC is parked at its midpoint before it's turned off, since turning it off
+/// on any other value steps the output. The PSG goes last.
+pub fn hush() {
+    music::stop();
+    #[cfg(feature = "pcm")]
+    super::pcm::stop();
+
+    io::with_paused_z80(|bus| {
+        ym::key_off_all(bus);
+        ym::write(bus, 0, ym::REG_DAC, 0x80);
+        ym::write(bus, 0, ym::REG_DAC_ENABLE, 0x00);
+    });
+    psg::silence();
+}
+
+/// Replaces the Z80's program with `program`, silencing the console first so the swap doesn't
+/// cut a note off partway or leave the DAC stuck on a sample.
+///
+/// This is the same as `z80::load` after `hush`. Whatever the new driver plays is up to it, and
+/// `pcm::init` has to be called again before samples can be played with `pcm`.
+pub fn swap_driver(program: &[u8]) {
+    hush();
+    z80::load(program);
+}
+
+/// Quiets the console while some other source has the speakers, for example a CD track on a Mega CD
+/// during a cutscene. See `mute`.
+#[must_use]
+pub struct Muted {
+    resume: bool,
+}
+
+/// Pauses the music and silences the console until the returned guard is dropped, when the music
+/// picks up from where it was, if it was playing.
+///
+/// ```ignore
+/// let muted = ext::mute();
+/// // Start the CD track, and wait for it to finish.
+/// drop(muted);
+/// ```
+///
+/// Sound effects aren't stopped from starting again while muted, so the game should hold them off
+/// itself.
+pub fn mute() -> Muted {
+    let resume = music::state() == music::State::Playing;
+    music::pause();
+    #[cfg(feature = "pcm")]
+    super::pcm::stop();
+
+    io::with_paused_z80(|bus| {
+        ym::key_off_all(bus);
+        ym::write(bus, 0, ym::REG_DAC, 0x80);
+    });
+    psg::silence();
+    Muted { resume }
+}
+
+impl Drop for Muted {
+    fn drop(&mut self) {
+        if self.resume {
+            music::resume();
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use crate::sys::io;
+
+use super::levels::Channel;
+use super::ym;
+
+/// One operator's settings, as the values of its registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Operator {
+    /// Register 0x30: detune in bits 4-6, multiple in bits 0-3.
+    pub dt_mul: u8,
+    /// Register 0x40: total level, from 0 for loudest to 127.
+    pub tl: u8,
+    /// Register 0x50: rate scaling in bits 6-7, attack rate in bits 0-4.
+    pub rs_ar: u8,
+    /// Register 0x60: amplitude modulation in bit 7, first decay rate in bits 0-4.
+    pub am_dr: u8,
+    /// Register 0x70: second decay rate.
+    pub sr: u8,
+    /// Register 0x80: sustain level in bits 4-7, release rate in bits 0-3.
+    pub sl_rr: u8,
+    /// Register 0x90: SSG-EG mode, with bit 3 turning it on.
+    pub ssg_eg: u8,
+}
+
+impl Operator {
+    const ZERO: Self = Self { dt_mul: 0, tl: 0, rs_ar: 0, am_dr: 0, sr: 0, sl_rr: 0, ssg_eg: 0 };
+}
+
+/// An operator's settings as separate fields, the way trackers show them, to be packed into an
+/// `Operator`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OperatorFields {
+    pub mul: u8,
+    /// From -3 to 3.
+    pub dt: i8,
+    pub tl: u8,
+    pub rs: u8,
+    pub ar: u8,
+    pub am: bool,
+    pub dr: u8,
+    pub sr: u8,
+    pub sl: u8,
+    pub rr: u8,
+    pub ssg_eg: u8,
+}
+
+impl OperatorFields {
+    pub const fn pack(&self) -> Operator {
+        // The chip's detune is a sign and magnitude.
+        let dt = if self.dt < 0 { 4 | (-self.dt as u8 & 3) } else { self.dt as u8 & 3 };
+        Operator {
+            dt_mul: dt << 4 | (self.mul & 0x0F),
+            tl: self.tl & 0x7F,
+            rs_ar: (self.rs & 3) << 6 | (self.ar & 0x1F),
+            am_dr: (self.am as u8) << 7 | (self.dr & 0x1F),
+            sr: self.sr & 0x1F,
+            sl_rr: (self.sl & 0x0F) << 4 | (self.rr & 0x0F),
+            ssg_eg: self.ssg_eg & 0x0F,
+        }
+    }
+}
+
+/// An FM instrument: the settings of a channel's four operators, and how they're connected.
+///
+/// Patches made in DefleMask, Furnace and other trackers can be loaded from their exported files,
+/// in a constant so the parsing is done at build time:
+///
+/// ```ignore
+/// const BASS: Patch = match Patch::from_tfi(include_bytes!("../assets/bass.tfi")) {
+///     Some(patch) => patch,
+///     None => panic!("bad TFI file"),
+/// };
+///
+/// io::with_paused_z80(|bus| fm::load_patch(bus, Channel::Fm1, &BASS));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Patch {
+    /// The operators in register order, which is operators 1, 3, 2 and 4.
+    pub operators: [Operator; 4],
+    /// Register 0xB0: feedback in bits 3-5, algorithm in bits 0-2.
+    pub fb_alg: u8,
+    /// Register 0xB4: left and right outputs in bits 6-7, AM sensitivity in bits 4-5, and FM
+    /// sensitivity in bits 0-2.
+    pub lr_ams_fms: u8,
+}
+
+/// Where each of operators 1 to 4 goes in `Patch::operators`.
+const REGISTER_ORDER: [usize; 4] = [0, 2, 1, 3];
+
+/// Both speakers on, and no LFO sensitivity.
+const CENTERED: u8 = 0xC0;
+
+impl Patch {
+    /// Packs a patch from an algorithm, feedback, and operators 1 to 4 in their usual order.
+    pub const fn new(algorithm: u8, feedback: u8, operators: [Operator; 4]) -> Self {
+        Self {
+            operators: [operators[0], operators[2], operators[1], operators[3]],
+            fb_alg: (feedback & 7) << 3 | (algorithm & 7),
+            lr_ams_fms: CENTERED,
+        }
+    }
+
+    /// Sets the LFO sensitivities, with AM from 0 to 3 and FM from 0 to 7.
+    #[inline]
+    pub const fn with_lfo(mut self, ams: u8, fms: u8) -> Self {
+        self.lr_ams_fms = (self.lr_ams_fms & 0xC0) | (ams & 3) << 4 | (fms & 7);
+        self
+    }
+
+    /// Sets which speakers the channel plays on.
+    #[inline]
+    pub const fn with_pan(mut self, left: bool, right: bool) -> Self {
+        self.lr_ams_fms = (self.lr_ams_fms & 0x3F) | (left as u8) << 7 | (right as u8) << 6;
+        self
+    }
+
+    #[inline]
+    pub const fn algorithm(&self) -> u8 {
+        self.fb_alg & 7
+    }
+
+    #[inline]
+    pub const fn feedback(&self) -> u8 {
+        (self.fb_alg >> 3) & 7
+    }
+
+    /// Reads a TFI file, as exported by DefleMask and VGM Music Maker. Returns `None` if it's the
+    /// wrong size.
+    ///
+    /// TFI files are 42 bytes: the algorithm and feedback, then ten bytes for each operator in
+    /// register order.
+    pub const fn from_tfi(data: &[u8]) -> Option<Self> {
+        if data.len() != 42 {
+            return None;
+        }
+        let mut patch = Self::new(data[0], data[1], [Operator::ZERO; 4]);
+        let mut i = 0;
+        while i < 4 {
+            let op = 2 + i * 10;
+            patch.operators[i] = OperatorFields {
+                mul: data[op],
+                dt: data[op + 1] as i8 - 3,
+                tl: data[op + 2],
+                rs: data[op + 3],
+                ar: data[op + 4],
+                am: false,
+                dr: data[op + 5],
+                sr: data[op + 6],
+                rr: data[op + 7],
+                sl: data[op + 8],
+                ssg_eg: data[op + 9],
+            }
+            .pack();
+            i += 1;
+        }
+        Some(patch)
+    }
+
+    /// Reads an FM instrument from a version 11 DMP file, as saved by DefleMask and Furnace.
+    /// Returns `None` for other versions, or for instruments that aren't FM.
+    pub const fn from_dmp(data: &[u8]) -> Option<Self> {
+        const VERSION: u8 = 11;
+        const MODE_FM: u8 = 1;
+        const HEADER: usize = 7;
+        const OP_BYTES: usize = 11;
+
+        if data.len() < HEADER + 4 * OP_BYTES || data[0] != VERSION || data[2] != MODE_FM {
+            return None;
+        }
+        let (fms, feedback, algorithm, ams) = (data[3], data[4], data[5], data[6]);
+        let mut patch = Self::new(algorithm, feedback, [Operator::ZERO; 4]).with_lfo(ams, fms);
+        let mut i = 0;
+        while i < 4 {
+            let op = HEADER + i * OP_BYTES;
+            // Operators are in the order 1 to 4 here.
+            patch.operators[REGISTER_ORDER[i]] = OperatorFields {
+                mul: data[op],
+                tl: data[op + 1],
+                ar: data[op + 2],
+                dr: data[op + 3],
+                sl: data[op + 4],
+                rr: data[op + 5],
+                am: data[op + 6] != 0,
+                rs: data[op + 7],
+                dt: (data[op + 8] & 7) as i8 - 3,
+                sr: data[op + 9],
+                ssg_eg: data[op + 10],
+            }
+            .pack();
+            i += 1;
+        }
+        Some(patch)
+    }
+
+    /// Reads a Y12 file, the register dumps made by Gens KMod. Returns `None` if it's the wrong size.
+    ///
+    /// Y12 files are 128 bytes: sixteen for each operator in register order, holding the values of
+    /// registers 0x30 to 0x90, then the algorithm and feedback.
+    pub const fn from_y12(data: &[u8]) -> Option<Self> {
+        if data.len() != 128 {
+            return None;
+        }
+        let mut patch = Self::new(data[0x40], data[0x41], [Operator::ZERO; 4]);
+        let mut i = 0;
+        while i < 4 {
+            let op = i * 16;
+            patch.operators[i] = Operator {
+                dt_mul: data[op] & 0x7F,
+                tl: data[op + 1] & 0x7F,
+                rs_ar: data[op + 2] & 0xDF,
+                am_dr: data[op + 3] & 0x9F,
+                sr: data[op + 4] & 0x1F,
+                sl_rr: data[op + 5],
+                ssg_eg: data[op + 6] & 0x0F,
+            };
+            i += 1;
+        }
+        Some(patch)
+    }
+}
+
+/// Loads `patch` onto an FM channel, releasing whatever it was playing first.
+///
+/// If the music is playing, it puts its own instruments back on the channels it uses, so a channel
+/// should be `music::duck`ed before a patch is loaded onto it.
+///
+/// # Panics
+///
+/// Panics if `channel` isn't one of the FM channels.
+pub fn load_patch(bus: &io::Z80BusGuard<'_>, channel: Channel, patch: &Patch) {
+    let index = match channel {
+        Channel::Fm1 | Channel::Fm2 | Channel::Fm3 | Channel::Fm4 | Channel::Fm5 | Channel::Fm6 => channel as u8,
+        _ => panic!("patches can only be loaded onto FM channels"),
+    };
+    let port = index / 3;
+    let offset = index % 3;
+
+    ym::write(bus, 0, ym::REG_KEY_ON, port << 2 | offset);
+    for (slot, op) in patch.operators.iter().enumerate() {
+        let base = offset + slot as u8 * 4;
+        ym::write(bus, port, 0x30 + base, op.dt_mul);
+        ym::write(bus, port, 0x40 + base, op.tl);
+        ym::write(bus, port, 0x50 + base, op.rs_ar);
+        ym::write(bus, port, 0x60 + base, op.am_dr);
+        ym::write(bus, port, 0x70 + base, op.sr);
+        ym::write(bus, port, 0x80 + base, op.sl_rr);
+        ym::write(bus, port, 0x90 + base, op.ssg_eg);
+    }
+    ym::write(bus, port, 0xB0 + offset, patch.fb_alg);
+    ym::write(bus, port, 0xB4 + offset, patch.lr_ams_fms);
+    ym::select(bus, ym::REG_DAC);
+}
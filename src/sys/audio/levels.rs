@@ -0,0 +1,133 @@
+use core::cell;
+
+use critical_section as cs;
+
+use crate::sys::audio;
+
+/// The number of sound channels that levels are tracked for.
+pub const CHANNELS: usize = 11;
+
+/// The loudest level a channel can report.
+pub const MAX_LEVEL: u8 = 15;
+
+/// How much a released channel's level falls each frame.
+const DECAY: u8 = 1;
+
+/// A sound channel, on either the YM2612 or the PSG.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Channel {
+    Fm1 = 0,
+    Fm2 = 1,
+    Fm3 = 2,
+    Fm4 = 3,
+    Fm5 = 4,
+    Fm6 = 5,
+    Psg1 = 6,
+    Psg2 = 7,
+    Psg3 = 8,
+    /// The PSG's noise channel.
+    Noise = 9,
+    /// The YM2612's DAC, which replaces FM channel 6 while it's enabled.
+    Dac = 10,
+}
+
+impl Channel {
+    pub const ALL: [Self; CHANNELS] = [
+        Self::Fm1, Self::Fm2, Self::Fm3, Self::Fm4, Self::Fm5, Self::Fm6,
+        Self::Psg1, Self::Psg2, Self::Psg3, Self::Noise,
+        Self::Dac,
+    ];
+}
+
+/// An estimate of how active a channel is, for drawing visualizers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChannelLevel {
+    key_on: bool,
+    level: u8,
+}
+
+impl ChannelLevel {
+    pub const SILENT: Self = Self { key_on: false, level: 0 };
+
+    /// Returns true while a note is held on the channel.
+    #[inline]
+    pub const fn key_on(&self) -> bool {
+        self.key_on
+    }
+
+    /// The channel's level, from 0 to `MAX_LEVEL`. This holds while the key is on, and falls off
+    /// over a few frames once it's released.
+    #[inline]
+    pub const fn level(&self) -> u8 {
+        self.level
+    }
+}
+
+static LEVELS: cs::Mutex<cell::Cell<[ChannelLevel; CHANNELS]>> = cs::Mutex::new(cell::Cell::new([ChannelLevel::SILENT; CHANNELS]));
+
+/// Records a channel's state. Used by the sound engines, which may be running in the vblank handler.
+#[inline]
+pub(in crate::sys) fn report(cs: cs::CriticalSection, channel: Channel, key_on: bool, level: u8) {
+    let cell = LEVELS.borrow(cs);
+    let mut levels = cell.get();
+    levels[channel as usize] = ChannelLevel { key_on, level: level.min(MAX_LEVEL) };
+    cell.set(levels);
+}
+
+/// The level of one channel, for code that's already in a critical section.
+#[inline]
+pub(in crate::sys) fn level_in(cs: cs::CriticalSection, channel: Channel) -> ChannelLevel {
+    LEVELS.borrow(cs).get()[channel as usize]
+}
+
+/// Records a channel's note being released, keeping its level so it can fall from there.
+#[inline]
+pub(in crate::sys) fn release(cs: cs::CriticalSection, channel: Channel) {
+    let level = LEVELS.borrow(cs).get()[channel as usize].level;
+    report(cs, channel, false, level)
+}
+
+/// Records a note starting on `channel` at `level`, which holds until `key_off`.
+///
+/// Sound engines in this crate report their own notes, so this is only needed for sound written
+/// to the hardware directly.
+#[inline]
+pub fn key_on(channel: Channel, level: u8) {
+    audio::with_cs(|cs| report(cs, channel, true, level))
+}
+
+/// Records the note on `channel` being released, so its level starts to fall.
+#[inline]
+pub fn key_off(channel: Channel) {
+    audio::with_cs(|cs| release(cs, channel))
+}
+
+/// Records a sound on `channel` that isn't held, such as a one-shot sample, so its level starts to
+/// fall straight away.
+#[inline]
+pub fn trigger(channel: Channel, level: u8) {
+    audio::with_cs(|cs| report(cs, channel, false, level))
+}
+
+/// The current level of one channel.
+#[inline]
+pub fn level(channel: Channel) -> ChannelLevel {
+    audio::with_cs(|cs| level_in(cs, channel))
+}
+
+/// The current levels of every channel, indexed by `Channel`.
+#[inline]
+pub fn levels() -> [ChannelLevel; CHANNELS] {
+    audio::with_cs(|cs| LEVELS.borrow(cs).get())
+}
+
+/// Lets released channels' levels fall. Called by the vblank handler.
+pub(in crate::sys) fn tick(cs: cs::CriticalSection) {
+    let cell = LEVELS.borrow(cs);
+    let mut levels = cell.get();
+    for channel in levels.iter_mut().filter(|channel| !channel.key_on) {
+        channel.level = channel.level.saturating_sub(DECAY);
+    }
+    cell.set(levels);
+}
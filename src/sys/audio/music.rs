@@ -0,0 +1,566 @@
+use core::cell;
+
+use critical_section as cs;
+use fixed::types::U8F8;
+
+use crate::sys::{audio, io, timing};
+use crate::sys::collections::FixedVec;
+
+use super::levels::{self, Channel};
+use super::{psg, ym};
+
+/// VGM waits are counted in samples at 44.1 kHz, which is this many per frame.
+const NTSC_SAMPLES_PER_FRAME: u32 = 735;
+const PAL_SAMPLES_PER_FRAME: u32 = 882;
+
+/// The first and last YM2612 registers that belong to a single channel.
+const FIRST_CHANNEL_REG: u8 = 0x30;
+const LAST_CHANNEL_REG: u8 = 0xB7;
+const CHANNEL_REGS: usize = (LAST_CHANNEL_REG - FIRST_CHANNEL_REG) as usize + 1;
+
+/// The command that marks a point in a song, with the marker's id as its one operand. It's one of
+/// the VGM format's reserved commands, so other players skip over it.
+pub const MARKER_COMMAND: u8 = 0x32;
+
+/// The most markers reported to the marker hook in one frame. Any more are dropped.
+const MAX_MARKERS_PER_FRAME: usize = 8;
+
+/// A song in VGM format, stored in ROM.
+///
+/// Only YM2612 and PSG commands are played. DAC streams are skipped, so use `pcm` for samples.
+#[derive(Debug, Clone, Copy)]
+pub struct Song {
+    data: &'static [u8],
+    start: usize,
+    loop_start: Option<usize>,
+}
+
+impl Song {
+    /// Reads a VGM file's header. Returns `None` if it isn't a VGM file.
+    pub const fn from_vgm(data: &'static [u8]) -> Option<Self> {
+        if data.len() < 0x40 || data[0] != b'V' || data[1] != b'g' || data[2] != b'm' || data[3] != b' ' {
+            return None;
+        }
+
+        // Before version 1.50, the data always starts at 0x40.
+        let start = match read_u32(data, 0x34) {
+            Some(offset) if offset != 0 && read_u32(data, 0x08).unwrap_or(0) >= 0x150 => 0x34 + offset as usize,
+            _ => 0x40,
+        };
+        let loop_start = match read_u32(data, 0x1C) {
+            Some(offset) if offset != 0 => Some(0x1C + offset as usize),
+            _ => None,
+        };
+
+        if start >= data.len() {
+            return None;
+        }
+        Some(Self { data, start, loop_start })
+    }
+
+    /// Returns true if the song starts over when it ends.
+    #[inline]
+    pub const fn loops(&self) -> bool {
+        self.loop_start.is_some()
+    }
+
+    /// The song's markers, in the order they're played. If the song loops, so do the markers,
+    /// with their times carrying on counting up like `position` does.
+    #[inline]
+    pub const fn markers(&self) -> Markers {
+        Markers { song: *self, pos: self.start, time: 0, found: false }
+    }
+}
+
+/// A marker in a song. See `MARKER_COMMAND`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Marker {
+    pub id: u8,
+    /// When the marker is played, as a `position`.
+    pub time: u32,
+}
+
+/// Iterates over a song's markers. Made by `Song::markers`.
+#[derive(Debug, Clone)]
+pub struct Markers {
+    song: Song,
+    pos: usize,
+    time: u32,
+    /// Whether a marker has turned up since the song last started over, so a loop without any
+    /// isn't followed forever.
+    found: bool,
+}
+
+impl Iterator for Markers {
+    type Item = Marker;
+
+    fn next(&mut self) -> Option<Marker> {
+        let data = self.song.data;
+        loop {
+            let command = *data.get(self.pos)?;
+            if command == 0x66 {
+                match self.song.loop_start {
+                    Some(loop_start) if self.found => {
+                        self.pos = loop_start;
+                        self.found = false;
+                        continue;
+                    }
+                    _ => return None,
+                }
+            }
+
+            let (len, wait) = step(data, self.pos)?;
+            let marker = (command == MARKER_COMMAND).then(|| Marker { id: data[self.pos + 1], time: self.time });
+            self.pos += len;
+            self.time = self.time.wrapping_add(wait as u32);
+            if marker.is_some() {
+                self.found = true;
+                return marker;
+            }
+        }
+    }
+}
+
+/// The length of the command at `pos`, and how many samples it waits for. Returns `None` if it's
+/// cut short or isn't a command. The end of the song isn't handled here.
+fn step(data: &[u8], pos: usize) -> Option<(usize, i32)> {
+    let (len, wait) = match data[pos] {
+        0x61 => (3, read_u16(data, pos + 1)? as i32),
+        0x62 => (1, NTSC_SAMPLES_PER_FRAME as i32),
+        0x63 => (1, PAL_SAMPLES_PER_FRAME as i32),
+        // A data block, which only DAC streams use.
+        0x67 => (7 + (read_u32(data, pos + 3)? & 0x7FFF_FFFF) as usize, 0),
+        command @ 0x70..=0x7F => (1, (command & 0x0F) as i32 + 1),
+        // A DAC write from a data block, then a wait.
+        command @ 0x80..=0x8F => (1, (command & 0x0F) as i32),
+        // DAC stream control.
+        0x90 | 0x91 | 0x95 => (5, 0),
+        0x92 => (6, 0),
+        0x93 => (11, 0),
+        0x94 => (2, 0),
+        // PSG and YM2612 writes, markers, and commands for other chips.
+        0x30..=0x3F | 0x4F | 0x50 => (2, 0),
+        0x40..=0x4E | 0x51..=0x5F | 0xA0..=0xBF => (3, 0),
+        0xC0..=0xDF => (4, 0),
+        0xE0..=0xFF => (5, 0),
+        _ => return None,
+    };
+    if pos + len > data.len() {
+        return None;
+    }
+    Some((len, wait))
+}
+
+#[inline]
+const fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    if at + 2 > data.len() {
+        return None;
+    }
+    Some(u16::from_le_bytes([data[at], data[at + 1]]))
+}
+
+#[inline]
+const fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    if at + 4 > data.len() {
+        return None;
+    }
+    Some(u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]))
+}
+
+/// What the music player is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Stopped,
+    Playing,
+    Paused,
+}
+
+struct Player {
+    song: Option<Song>,
+    pos: usize,
+    /// Samples that can be played before the next wait, carried over between frames.
+    credit: i32,
+    /// Samples played before the current frame, counting from the start of the song.
+    position: u32,
+    /// The frame `position` was last updated on.
+    position_frame: u32,
+    /// Samples being played this frame.
+    rate: u32,
+    state: State,
+    tempo: U8F8,
+    /// Channels the music isn't allowed to touch, as bits indexed by `Channel`.
+    ducked: u16,
+    /// The last values the song wrote to each channel's registers, for restoring ducked channels.
+    ym_regs: [[u8; CHANNEL_REGS]; 2],
+    /// The PSG register the next data byte goes to, as the bits of the last latch byte.
+    psg_latch: u8,
+    psg_tone: [u16; 4],
+    psg_attenuation: [u8; 4],
+    marker_hook: Option<fn(cs::CriticalSection, Marker)>,
+}
+
+static PLAYER: cs::Mutex<cell::RefCell<Player>> = cs::Mutex::new(cell::RefCell::new(Player {
+    song: None,
+    pos: 0,
+    credit: 0,
+    position: 0,
+    position_frame: 0,
+    rate: 0,
+    state: State::Stopped,
+    tempo: U8F8::ONE,
+    ducked: 0,
+    ym_regs: [[0; CHANNEL_REGS]; 2],
+    psg_latch: 0,
+    psg_tone: [0; 4],
+    psg_attenuation: [0x0F; 4],
+    marker_hook: None,
+}));
+
+/// The FM channel for a key on/off register value.
+#[inline]
+const fn key_on_channel(value: u8) -> Option<Channel> {
+    match value & 0x07 {
+        0 => Some(Channel::Fm1),
+        1 => Some(Channel::Fm2),
+        2 => Some(Channel::Fm3),
+        4 => Some(Channel::Fm4),
+        5 => Some(Channel::Fm5),
+        6 => Some(Channel::Fm6),
+        _ => None,
+    }
+}
+
+/// The FM channel a per-channel register belongs to, or `None` for global registers.
+#[inline]
+const fn reg_channel(port: u8, reg: u8) -> Option<u8> {
+    if reg < FIRST_CHANNEL_REG || reg > LAST_CHANNEL_REG || reg & 0x03 == 0x03 {
+        return None;
+    }
+    Some((port & 1) * 3 + (reg & 0x03))
+}
+
+const PSG_CHANNELS: [Channel; 4] = [Channel::Psg1, Channel::Psg2, Channel::Psg3, Channel::Noise];
+
+impl Player {
+    #[inline]
+    fn is_ducked(&self, channel: Channel) -> bool {
+        self.ducked & (1 << channel as u16) != 0
+    }
+
+    fn write_ym(&mut self, cs: cs::CriticalSection, bus: &io::Z80BusGuard<'_>, port: u8, reg: u8, value: u8) {
+        let port = port & 1;
+        if let Some(channel) = reg_channel(port, reg) {
+            self.ym_regs[port as usize][(reg - FIRST_CHANNEL_REG) as usize] = value;
+            if self.is_ducked(Channel::ALL[channel as usize]) {
+                return;
+            }
+        } else if port == 0 && reg == ym::REG_KEY_ON {
+            let Some(channel) = key_on_channel(value) else { return };
+            if self.is_ducked(channel) {
+                return;
+            }
+            if value & 0xF0 != 0 {
+                levels::report(cs, channel, true, levels::MAX_LEVEL);
+            } else {
+                levels::release(cs, channel);
+            }
+        } else if port == 0 && (reg == ym::REG_DAC || reg == ym::REG_DAC_ENABLE) && self.is_ducked(Channel::Dac) {
+            return;
+        }
+        ym::write(bus, port, reg, value);
+    }
+
+    fn write_psg(&mut self, cs: cs::CriticalSection, value: u8) {
+        if value & 0x80 != 0 {
+            self.psg_latch = value & 0x70;
+        }
+        let channel = (self.psg_latch >> 5) as usize;
+        let is_volume = self.psg_latch & 0x10 != 0;
+
+        if is_volume {
+            let attenuation = value & 0x0F;
+            self.psg_attenuation[channel] = attenuation;
+            if !self.is_ducked(PSG_CHANNELS[channel]) {
+                levels::report(cs, PSG_CHANNELS[channel], attenuation < 0x0F, 0x0F - attenuation);
+            }
+        } else if value & 0x80 != 0 {
+            self.psg_tone[channel] = (self.psg_tone[channel] & !0x0F) | (value & 0x0F) as u16;
+        } else {
+            self.psg_tone[channel] = (self.psg_tone[channel] & 0x0F) | (((value & 0x3F) as u16) << 4);
+        }
+
+        if !self.is_ducked(PSG_CHANNELS[channel]) {
+            psg::write(value);
+        }
+    }
+
+    /// Rewrites everything the song last set on a channel that's no longer ducked.
+    fn restore(&self, bus: &io::Z80BusGuard<'_>, channel: Channel) {
+        let index = channel as usize;
+        match channel {
+            Channel::Fm1 | Channel::Fm2 | Channel::Fm3 | Channel::Fm4 | Channel::Fm5 | Channel::Fm6 => {
+                let port = (index / 3) as u8;
+                let offset = (index % 3) as u8;
+                let regs = &self.ym_regs[port as usize];
+                let mut reg = FIRST_CHANNEL_REG + offset;
+                while reg < 0xA0 {
+                    ym::write(bus, port, reg, regs[(reg - FIRST_CHANNEL_REG) as usize]);
+                    reg += 4;
+                }
+                // The frequency's high byte is latched until the low byte is written.
+                for reg in [0xA4 + offset, 0xA0 + offset, 0xB0 + offset, 0xB4 + offset] {
+                    ym::write(bus, port, reg, regs[(reg - FIRST_CHANNEL_REG) as usize]);
+                }
+            }
+            Channel::Psg1 | Channel::Psg2 | Channel::Psg3 | Channel::Noise => {
+                let psg_channel = index - Channel::Psg1 as usize;
+                let tone = self.psg_tone[psg_channel];
+                psg::write(0x80 | ((psg_channel as u8) << 5) | (tone & 0x0F) as u8);
+                if channel != Channel::Noise {
+                    psg::write(((tone >> 4) & 0x3F) as u8);
+                }
+                psg::set_attenuation(psg_channel as u8, self.psg_attenuation[psg_channel]);
+            }
+            Channel::Dac => {}
+        }
+    }
+
+    /// Silences everything the song might be playing.
+    fn silence(&self, bus: &io::Z80BusGuard<'_>) {
+        ym::key_off_all(bus);
+        for (channel, &psg_channel) in PSG_CHANNELS.iter().enumerate() {
+            if !self.is_ducked(psg_channel) {
+                psg::set_attenuation(channel as u8, 0x0F);
+            }
+        }
+    }
+
+    /// Plays commands until the song waits for longer than the credit left for this frame, keeping
+    /// the markers it passes in `markers`.
+    fn run(
+        &mut self,
+        cs: cs::CriticalSection,
+        bus: &io::Z80BusGuard<'_>,
+        song: Song,
+        markers: &mut FixedVec<Marker, MAX_MARKERS_PER_FRAME>,
+    ) {
+        let data = song.data;
+        while self.credit > 0 {
+            let pos = self.pos;
+            let Some(&command) = data.get(pos) else {
+                self.end(bus, song);
+                return;
+            };
+            if command == 0x66 {
+                self.end(bus, song);
+                if self.state != State::Playing {
+                    return;
+                }
+                continue;
+            }
+
+            let Some((len, wait)) = step(data, pos) else { break };
+            match command {
+                0x50 => self.write_psg(cs, data[pos + 1]),
+                0x52 | 0x53 => self.write_ym(cs, bus, command - 0x52, data[pos + 1], data[pos + 2]),
+                MARKER_COMMAND => {
+                    // The credit left is how far into the frame's samples the song has got.
+                    let time = self.position.wrapping_add(self.rate).wrapping_sub(self.credit as u32);
+                    let _ = markers.push(Marker { id: data[pos + 1], time });
+                }
+                _ => {}
+            }
+
+            self.pos = pos + len;
+            self.credit -= wait;
+        }
+
+        // Anything left in the credit here means the song couldn't be read, so stop instead of
+        // trying again every frame.
+        if self.credit > 0 {
+            self.stop(bus);
+        }
+    }
+
+    /// Loops the song, or stops it if it doesn't loop.
+    fn end(&mut self, bus: &io::Z80BusGuard<'_>, song: Song) {
+        match song.loop_start {
+            Some(loop_start) => self.pos = loop_start,
+            None => self.stop(bus),
+        }
+    }
+
+    fn stop(&mut self, bus: &io::Z80BusGuard<'_>) {
+        self.silence(bus);
+        self.song = None;
+        self.state = State::Stopped;
+        self.credit = 0;
+    }
+}
+
+/// Starts playing `song` from the beginning, replacing whatever was playing.
+pub fn play(song: Song) {
+    audio::with_cs(|cs| {
+        let mut player = PLAYER.borrow_ref_mut(cs);
+        io::with_paused_z80(|bus| {
+            player.silence(bus);
+            ym::select(bus, ym::REG_DAC);
+        });
+        player.song = Some(song);
+        player.pos = song.start;
+        player.credit = 0;
+        player.position = 0;
+        player.rate = 0;
+        player.psg_latch = 0;
+        player.state = State::Playing;
+    })
+}
+
+/// Stops the music and silences it.
+pub fn stop() {
+    audio::with_cs(|cs| {
+        let mut player = PLAYER.borrow_ref_mut(cs);
+        io::with_paused_z80(|bus| {
+            player.stop(bus);
+            ym::select(bus, ym::REG_DAC);
+        });
+    })
+}
+
+/// Pauses the music, silencing it until `resume` is called. Notes that were held stay silent until
+/// the song plays them again.
+pub fn pause() {
+    audio::with_cs(|cs| {
+        let mut player = PLAYER.borrow_ref_mut(cs);
+        if player.state == State::Playing {
+            io::with_paused_z80(|bus| {
+                player.silence(bus);
+                ym::select(bus, ym::REG_DAC);
+            });
+            player.state = State::Paused;
+        }
+    })
+}
+
+/// Resumes the music from where it was paused.
+pub fn resume() {
+    audio::with_cs(|cs| {
+        let mut player = PLAYER.borrow_ref_mut(cs);
+        if player.state == State::Paused {
+            for (channel, &psg_channel) in PSG_CHANNELS.iter().enumerate() {
+                if !player.is_ducked(psg_channel) {
+                    psg::set_attenuation(channel as u8, player.psg_attenuation[channel]);
+                }
+            }
+            player.state = State::Playing;
+        }
+    })
+}
+
+#[inline]
+pub fn state() -> State {
+    audio::with_cs(|cs| PLAYER.borrow_ref(cs).state)
+}
+
+/// Sets a function for the vblank handler to run with each marker the music plays, or clears it.
+#[inline]
+pub fn set_marker_hook(hook: Option<fn(cs::CriticalSection, Marker)>) {
+    audio::with_cs(|cs| PLAYER.borrow_ref_mut(cs).marker_hook = hook)
+}
+
+/// Sets how fast the music plays, where 1.0 is the song's own speed.
+#[inline]
+pub fn set_tempo(tempo: U8F8) {
+    audio::with_cs(|cs| PLAYER.borrow_ref_mut(cs).tempo = tempo)
+}
+
+#[inline]
+pub fn tempo() -> U8F8 {
+    audio::with_cs(|cs| PLAYER.borrow_ref(cs).tempo)
+}
+
+/// How far into the song the music is, in 44.1 kHz samples, as of the start of this frame. This
+/// keeps counting up when the song loops, and holds still while paused.
+#[inline]
+pub fn position() -> u32 {
+    audio::with_cs(|cs| PLAYER.borrow_ref(cs).position)
+}
+
+/// How far into the song the music was, or will be, `line` lines into `frame`'s vblank, which is
+/// what `io::manager::latched_at` reports. This is how to tell when a button was pressed relative
+/// to the music, more finely than once per frame. Frames other than this one are worked out from
+/// the current tempo.
+pub fn position_at(frame: u32, line: u16) -> u32 {
+    let lines = timing::lines_per_frame() as i32;
+    audio::with_cs(|cs| {
+        let player = PLAYER.borrow_ref(cs);
+        let rate = player.rate as i32;
+        let frames = (frame.wrapping_sub(player.position_frame) as i32).clamp(-0xFF, 0xFF);
+        (player.position as i32 + rate * frames + rate * line as i32 / lines).max(0) as u32
+    })
+}
+
+/// Stops the music from using `channel`, so a sound effect can have it. The music keeps track of
+/// what it would have played there, and picks up again when the channel is `unduck`ed.
+#[inline]
+pub fn duck(channel: Channel) {
+    audio::with_cs(|cs| duck_in(cs, channel))
+}
+
+/// Gives `channel` back to the music, restoring its instrument and volume.
+#[inline]
+pub fn unduck(channel: Channel) {
+    audio::with_cs(|cs| unduck_in(cs, channel))
+}
+
+/// `duck`, for code that's already in a critical section, such as the sound effect mixer.
+pub(in crate::sys) fn duck_in(cs: cs::CriticalSection, channel: Channel) {
+    let mut player = PLAYER.borrow_ref_mut(cs);
+    if !player.is_ducked(channel) {
+        player.ducked |= 1 << channel as u16;
+        levels::report(cs, channel, false, 0);
+    }
+}
+
+/// `unduck`, for code that's already in a critical section.
+pub(in crate::sys) fn unduck_in(cs: cs::CriticalSection, channel: Channel) {
+    let mut player = PLAYER.borrow_ref_mut(cs);
+    if player.is_ducked(channel) {
+        player.ducked &= !(1 << channel as u16);
+        if player.state != State::Stopped {
+            io::with_paused_z80(|bus| {
+                player.restore(bus, channel);
+                ym::select(bus, ym::REG_DAC);
+            });
+        }
+    }
+}
+
+/// Plays the music for one frame. Called by the vblank handler.
+pub(in crate::sys) fn tick(cs: cs::CriticalSection) {
+    let mut player = PLAYER.borrow_ref_mut(cs);
+    // Last frame's samples have been played by now.
+    player.position = player.position.wrapping_add(player.rate);
+    player.position_frame = timing::elapsed_frames();
+    player.rate = 0;
+    let (State::Playing, Some(song)) = (player.state, player.song) else { return };
+
+    let samples = if timing::is_pal() { PAL_SAMPLES_PER_FRAME } else { NTSC_SAMPLES_PER_FRAME };
+    player.rate = (samples * player.tempo.to_bits() as u32) >> 8;
+    player.credit += player.rate as i32;
+
+    let mut markers = FixedVec::new();
+    io::with_paused_z80(|bus| {
+        player.run(cs, bus, song, &mut markers);
+        ym::select(bus, ym::REG_DAC);
+    });
+
+    // The hook is run once the player is let go of, so it's free to call back into the music.
+    let hook = player.marker_hook;
+    drop(player);
+    if let Some(hook) = hook {
+        for &marker in &markers {
+            hook(cs, marker);
+        }
+    }
+}
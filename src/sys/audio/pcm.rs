@@ -0,0 +1,228 @@
+use core::cell;
+
+use critical_section as cs;
+
+use crate::sys::{audio, io, timing};
+
+use super::levels::{self, Channel};
+use super::z80;
+
+/// Suggested priorities. A sample only interrupts one that's still playing if its priority is at
+/// least as high.
+pub const PRIORITY_LOW: u8 = 0x00;
+pub const PRIORITY_NORMAL: u8 = 0x80;
+pub const PRIORITY_HIGH: u8 = 0xFF;
+
+/// The highest sample rate the driver can keep up with on NTSC consoles, in Hz. PAL is slightly
+/// lower.
+pub const MAX_RATE: u16 = 20_500;
+
+/// Where the driver's mailbox lives in Z80 RAM, just above its stack.
+const MAILBOX: u16 = 0x1FE0;
+
+// Mailbox layout. Everything the 68k writes is only written with the Z80 bus held, so the driver
+// always sees a complete request.
+/// Set to `CMD_PLAY` or `CMD_STOP` by the 68k, cleared by the driver once it has acted on it.
+const CMD: u16 = 0;
+/// The ROM bank holding the start of the sample, ie. its address divided by 0x8000.
+const BANK: u16 = 1;
+/// The start of the sample within the Z80's bank window at 0x8000.
+const OFFSET: u16 = 3;
+/// The length of the sample in bytes, as 24 bits.
+const LEN: u16 = 5;
+/// The number of `djnz` iterations to wait between samples.
+const DELAY: u16 = 8;
+/// Nonzero to restart the sample when it ends.
+const LOOPS: u16 = 9;
+/// Set by the driver to 1 while a sample is playing.
+const STATUS: u16 = 10;
+
+const CMD_PLAY: u8 = 1;
+const CMD_STOP: u8 = 2;
+
+/// The Z80's clock speed on NTSC and PAL consoles, in Hz.
+const NTSC_Z80_CLOCK: u32 = 3_579_545;
+const PAL_Z80_CLOCK: u32 = 3_546_894;
+
+/// Z80 cycles spent on each sample outside the delay loop, and per delay loop iteration.
+const LOOP_CYCLES: u32 = 161;
+const DELAY_CYCLES: u32 = 13;
+
+/// The Z80 can only see the first 4MB of the 68k's address space through its bank window.
+const BANK_WINDOW_END: usize = 0x400000;
+
+/// The sample streaming driver, hand assembled.
+///
+/// It enables the YM2612's DAC, then waits for a command in the mailbox. While playing, it writes one
+/// unsigned 8 bit sample to the DAC per loop, reading them from ROM through the bank window and
+/// moving to the next bank when it reaches the end of the window.
+#[rustfmt::skip]
+static DRIVER: [u8; 159] = [
+    0xF3,                                // 0000 start: di
+    0x31, 0xE0, 0x1F,                    // 0001 ld sp,MAILBOX
+    0xDD, 0x21, 0xE0, 0x1F,              // 0004 ld ix,MAILBOX
+    0xFD, 0x21, 0x00, 0x40,              // 0008 ld iy,0x4000
+    0xFD, 0x36, 0x00, 0x2B,              // 000C ld (iy+0),0x2B
+    0xFD, 0x36, 0x01, 0x80,              // 0010 ld (iy+1),0x80
+    0xDD, 0x36, 0x0A, 0x00,              // 0014 idle: ld (ix+STATUS),0
+    0xDD, 0x7E, 0x00,                    // 0018 wait: ld a,(ix+CMD)
+    0xFE, 0x01,                          // 001B cp 1
+    0x20, 0xF9,                          // 001D jr nz,wait
+    0xDD, 0x36, 0x00, 0x00,              // 001F play: ld (ix+CMD),0
+    0xDD, 0x36, 0x0A, 0x01,              // 0023 ld (ix+STATUS),1
+    0xDD, 0x7E, 0x01,                    // 0027 restart: ld a,(ix+BANK)
+    0xDD, 0x77, 0x0B,                    // 002A ld (ix+CUR_BANK),a
+    0xDD, 0x7E, 0x02,                    // 002D ld a,(ix+BANK+1)
+    0xDD, 0x77, 0x0C,                    // 0030 ld (ix+CUR_BANK+1),a
+    0xCD, 0x8C, 0x00,                    // 0033 call setbank
+    0xDD, 0x6E, 0x03,                    // 0036 ld l,(ix+OFFSET)
+    0xDD, 0x66, 0x04,                    // 0039 ld h,(ix+OFFSET+1)
+    0xDD, 0x5E, 0x05,                    // 003C ld e,(ix+LEN)
+    0xDD, 0x56, 0x06,                    // 003F ld d,(ix+LEN+1)
+    0xDD, 0x4E, 0x07,                    // 0042 ld c,(ix+LEN+2)
+    0xFD, 0x36, 0x00, 0x2A,              // 0045 ld (iy+0),0x2A
+    0x7E,                                // 0049 loop: ld a,(hl)
+    0xFD, 0x77, 0x01,                    // 004A ld (iy+1),a
+    0xDD, 0x46, 0x08,                    // 004D ld b,(ix+DELAY)
+    0x10, 0xFE,                          // 0050 delay: djnz delay
+    0xDD, 0x7E, 0x00,                    // 0052 ld a,(ix+CMD)
+    0xB7,                                // 0055 or a
+    0x20, 0x2A,                          // 0056 jr nz,command
+    0x23,                                // 0058 inc hl
+    0x7C,                                // 0059 ld a,h
+    0xB5,                                // 005A or l
+    0x20, 0x0D,                          // 005B jr nz,count
+    0x26, 0x80,                          // 005D ld h,0x80
+    0xDD, 0x34, 0x0B,                    // 005F inc (ix+CUR_BANK)
+    0x20, 0x03,                          // 0062 jr nz,nocarry
+    0xDD, 0x34, 0x0C,                    // 0064 inc (ix+CUR_BANK+1)
+    0xCD, 0x8C, 0x00,                    // 0067 nocarry: call setbank
+    0x7B,                                // 006A count: ld a,e
+    0xD6, 0x01,                          // 006B sub 1
+    0x5F,                                // 006D ld e,a
+    0x7A,                                // 006E ld a,d
+    0xDE, 0x00,                          // 006F sbc a,0
+    0x57,                                // 0071 ld d,a
+    0x79,                                // 0072 ld a,c
+    0xDE, 0x00,                          // 0073 sbc a,0
+    0x4F,                                // 0075 ld c,a
+    0xB2,                                // 0076 or d
+    0xB3,                                // 0077 or e
+    0x20, 0xCF,                          // 0078 jr nz,loop
+    0xDD, 0x7E, 0x09,                    // 007A ld a,(ix+LOOPS)
+    0xB7,                                // 007D or a
+    0x20, 0xA7,                          // 007E jr nz,restart
+    0x18, 0x92,                          // 0080 jr idle
+    0xFE, 0x01,                          // 0082 command: cp 1
+    0x28, 0x99,                          // 0084 jr z,play
+    0xDD, 0x36, 0x00, 0x00,              // 0086 ld (ix+CMD),0
+    0x18, 0x88,                          // 008A jr idle
+    0xE5,                                // 008C setbank: push hl
+    0x21, 0x00, 0x60,                    // 008D ld hl,0x6000
+    0xDD, 0x7E, 0x0B,                    // 0090 ld a,(ix+CUR_BANK)
+    0x06, 0x08,                          // 0093 ld b,8
+    0x77,                                // 0095 bits: ld (hl),a
+    0x0F,                                // 0096 rrca
+    0x10, 0xFC,                          // 0097 djnz bits
+    0xDD, 0x7E, 0x0C,                    // 0099 ld a,(ix+CUR_BANK+1)
+    0x77,                                // 009C ld (hl),a
+    0xE1,                                // 009D pop hl
+    0xC9,                                // 009E ret
+];
+
+/// The priority of the sample that's playing, or was last played.
+static CURRENT_PRIORITY: cs::Mutex<cell::Cell<u8>> = cs::Mutex::new(cell::Cell::new(PRIORITY_LOW));
+
+/// Loads the driver onto the Z80. This must be called before any samples are played, and replaces
+/// whatever the Z80 was running before.
+pub fn init() {
+    z80::load(&DRIVER);
+}
+
+/// Plays an unsigned 8 bit PCM sample on the DAC at `rate` Hz, with normal priority.
+///
+/// See `play_sample_with_priority`.
+#[inline]
+pub fn play_sample(sample: &'static [u8], rate: u16, loops: bool) -> bool {
+    play_sample_with_priority(sample, rate, loops, PRIORITY_NORMAL)
+}
+
+/// Plays an unsigned 8 bit PCM sample on the DAC at `rate` Hz, up to `MAX_RATE`. If `loops` is set,
+/// the sample repeats until it's stopped or replaced.
+///
+/// There's only one DAC, so if another sample is still playing it's cut off, as long as its priority
+/// isn't higher than `priority`. Returns false if the sample wasn't played for that reason.
+///
+/// # Panics
+///
+/// Panics if the sample isn't in the first 4MB of ROM, where the Z80 can read it.
+pub fn play_sample_with_priority(sample: &'static [u8], rate: u16, loops: bool, priority: u8) -> bool {
+    let addr = sample.as_ptr() as usize;
+    if addr + sample.len() > BANK_WINDOW_END {
+        panic!("PCM samples must be in the first 4MB of ROM");
+    }
+    if sample.is_empty() {
+        return true;
+    }
+
+    let bank = (addr >> 15) as u16;
+    let offset = 0x8000 | (addr & 0x7FFF) as u16;
+    let len = sample.len() as u32;
+    // Everything from `BANK` up to `STATUS`, which the driver owns.
+    let mut params = [0u8; (STATUS - BANK) as usize];
+    let mut set = |field: u16, bytes: &[u8]| {
+        let start = (field - BANK) as usize;
+        params[start..start + bytes.len()].copy_from_slice(bytes);
+    };
+    set(BANK, &bank.to_le_bytes());
+    set(OFFSET, &offset.to_le_bytes());
+    set(LEN, &len.to_le_bytes()[..3]);
+    set(DELAY, &[delay_for(rate)]);
+    set(LOOPS, &[loops as u8]);
+
+    audio::with_cs(|cs| {
+        let current = CURRENT_PRIORITY.borrow(cs);
+        io::with_paused_z80(|bus| {
+            if is_playing_on(bus) && priority < current.get() {
+                return false;
+            }
+            z80::write(bus, MAILBOX + BANK, &params);
+            z80::write(bus, MAILBOX + CMD, &[CMD_PLAY]);
+            current.set(priority);
+            // The driver doesn't say when a sample ends, so only looping samples are held.
+            levels::report(cs, Channel::Dac, loops, levels::MAX_LEVEL);
+            true
+        })
+    })
+}
+
+/// Stops the sample that's playing, if there is one.
+pub fn stop() {
+    audio::with_cs(|cs| {
+        io::with_paused_z80(|bus| {
+            if is_playing_on(bus) {
+                z80::write(bus, MAILBOX + CMD, &[CMD_STOP]);
+            }
+        });
+        levels::report(cs, Channel::Dac, false, 0);
+    })
+}
+
+/// Returns true if a sample is playing, or about to start.
+#[inline]
+pub fn is_playing() -> bool {
+    audio::with_cs(|_| io::with_paused_z80(is_playing_on))
+}
+
+fn is_playing_on(bus: &io::Z80BusGuard<'_>) -> bool {
+    let mut mailbox = [0u8; STATUS as usize + 1];
+    z80::read(bus, MAILBOX, &mut mailbox);
+    mailbox[CMD as usize] == CMD_PLAY || mailbox[STATUS as usize] != 0
+}
+
+/// The driver's delay count for a sample rate.
+fn delay_for(rate: u16) -> u8 {
+    let clock = if timing::is_pal() { PAL_Z80_CLOCK } else { NTSC_Z80_CLOCK };
+    let cycles = clock / rate.max(1) as u32;
+    (cycles.saturating_sub(LOOP_CYCLES) / DELAY_CYCLES).clamp(1, 255) as u8
+}
@@ -0,0 +1,21 @@
+/// The PSG's write-only port.
+const PSG_PORT: *mut u8 = 0xC00011 as _;
+
+/// Writes a raw byte to the PSG.
+#[inline]
+pub fn write(value: u8) {
+    unsafe { core::ptr::write_volatile(PSG_PORT, value) }
+}
+
+/// Sets the attenuation of `channel` (0-3, where 3 is noise), from 0 for loudest to 15 for silent.
+#[inline]
+pub fn set_attenuation(channel: u8, attenuation: u8) {
+    write(0x90 | ((channel & 3) << 5) | (attenuation & 0x0F));
+}
+
+/// Silences all four channels.
+pub fn silence() {
+    for channel in 0..4 {
+        set_attenuation(channel, 0x0F);
+    }
+}
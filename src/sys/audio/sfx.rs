@@ -0,0 +1,323 @@
+use core::cell;
+
+use critical_section as cs;
+
+use crate::sys::audio;
+#[cfg(feature = "pcm")]
+use crate::sys::timing;
+
+use super::levels::{self, Channel};
+use super::{music, psg};
+
+/// Suggested priorities. A sound only cuts off another that doesn't outrank it.
+pub const PRIORITY_LOW: u8 = 0x00;
+pub const PRIORITY_NORMAL: u8 = 0x80;
+pub const PRIORITY_HIGH: u8 = 0xFF;
+
+/// The PSG's clock on NTSC consoles, in Hz, divided down by the 32 the tone counters count in.
+const PSG_TONE_CLOCK: u32 = 3_579_545 / 32;
+
+/// The channels the mixer plays on: the three square wave channels, the noise channel, and the DAC.
+const VOICE_CHANNELS: [Channel; 5] = [Channel::Psg1, Channel::Psg2, Channel::Psg3, Channel::Noise, Channel::Dac];
+const NOISE_VOICE: usize = 3;
+#[cfg(feature = "pcm")]
+const DAC_VOICE: usize = 4;
+
+/// The PSG tone period for a note of `hz` Hz, for `Step::new`.
+#[inline]
+pub const fn tone_period(hz: u16) -> u16 {
+    let period = PSG_TONE_CLOCK / if hz == 0 { 1 } else { hz as u32 };
+    if period > 0x3FF { 0x3FF } else { period as u16 }
+}
+
+/// One step of a PSG sound's envelope, held for some frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    /// The tone period, from `tone_period`. Noise sounds ignore this.
+    pub tone: u16,
+    /// From 0 for loudest to 15 for silent.
+    pub attenuation: u8,
+    pub frames: u8,
+}
+
+impl Step {
+    #[inline]
+    pub const fn new(tone: u16, attenuation: u8, frames: u8) -> Self {
+        Self { tone: tone & 0x3FF, attenuation: attenuation & 0x0F, frames }
+    }
+}
+
+/// How fast the noise channel's shift register runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseRate {
+    High = 0,
+    Medium = 1,
+    Low = 2,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Sound {
+    Tone(&'static [Step]),
+    Noise { white: bool, rate: NoiseRate, steps: &'static [Step] },
+    #[cfg(feature = "pcm")]
+    Pcm { sample: &'static [u8], rate: u16 },
+}
+
+/// A sound effect, kept in ROM.
+///
+/// ```ignore
+/// const JUMP: Effect = Effect::tone(&[
+///     Step::new(tone_period(440), 2, 3),
+///     Step::new(tone_period(660), 4, 3),
+///     Step::new(tone_period(880), 8, 4),
+/// ], PRIORITY_NORMAL);
+/// const HIT: Effect = Effect::noise(true, NoiseRate::Medium, &[Step::new(0, 0, 2), Step::new(0, 6, 6)], PRIORITY_HIGH);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Effect {
+    sound: Sound,
+    priority: u8,
+}
+
+impl Effect {
+    /// A sound on one of the PSG's square wave channels, following `steps`.
+    #[inline]
+    pub const fn tone(steps: &'static [Step], priority: u8) -> Self {
+        Self { sound: Sound::Tone(steps), priority }
+    }
+
+    /// A burst on the PSG's noise channel, with white noise or periodic noise.
+    #[inline]
+    pub const fn noise(white: bool, rate: NoiseRate, steps: &'static [Step], priority: u8) -> Self {
+        Self { sound: Sound::Noise { white, rate, steps }, priority }
+    }
+
+    /// A sample played on the DAC through `pcm`, which must have been set up with `pcm::init`.
+    #[cfg(feature = "pcm")]
+    #[inline]
+    pub const fn pcm(sample: &'static [u8], rate: u16, priority: u8) -> Self {
+        Self { sound: Sound::Pcm { sample, rate }, priority }
+    }
+
+    #[inline]
+    pub const fn priority(&self) -> u8 {
+        self.priority
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Voice {
+    effect: Option<&'static Effect>,
+    /// The next step to play.
+    step: u8,
+    /// Frames left until the next step.
+    frames: u16,
+}
+
+impl Voice {
+    const IDLE: Self = Self { effect: None, step: 0, frames: 0 };
+}
+
+struct Mixer {
+    voices: [Voice; VOICE_CHANNELS.len()],
+    bank: &'static [Effect],
+    music_priority: u8,
+}
+
+static MIXER: cs::Mutex<cell::RefCell<Mixer>> = cs::Mutex::new(cell::RefCell::new(Mixer {
+    voices: [Voice::IDLE; VOICE_CHANNELS.len()],
+    bank: &[],
+    music_priority: PRIORITY_LOW,
+}));
+
+impl Mixer {
+    /// Picks the voice for a sound out of `candidates`, or `None` if they're all taken by sounds
+    /// that outrank it, or by music it doesn't outrank.
+    ///
+    /// Idle channels the music isn't using come first, then channels taken from the music, then
+    /// the channel with the lowest priority sound.
+    fn pick(&self, cs: cs::CriticalSection, candidates: &[usize], priority: u8) -> Option<usize> {
+        candidates
+            .iter()
+            .filter_map(|&index| {
+                let rank = match self.voices[index].effect {
+                    Some(effect) if effect.priority <= priority => (2, effect.priority),
+                    Some(_) => return None,
+                    None if levels::level_in(cs, VOICE_CHANNELS[index]).key_on() => {
+                        if priority <= self.music_priority {
+                            return None;
+                        }
+                        (1, 0)
+                    }
+                    None => (0, 0),
+                };
+                Some((rank, index))
+            })
+            .min_by_key(|&(rank, _)| rank)
+            .map(|(_, index)| index)
+    }
+
+    fn start(&mut self, cs: cs::CriticalSection, index: usize, effect: &'static Effect) {
+        let channel = VOICE_CHANNELS[index];
+        music::duck_in(cs, channel);
+        if let Sound::Noise { white, rate, .. } = effect.sound {
+            psg::write(0xE0 | ((white as u8) << 2) | rate as u8);
+        }
+        self.voices[index] = Voice { effect: Some(effect), step: 0, frames: 0 };
+        self.advance(cs, index);
+    }
+
+    /// Plays the voice's next step once the current one is over, or ends it.
+    fn advance(&mut self, cs: cs::CriticalSection, index: usize) {
+        let voice = &mut self.voices[index];
+        let Some(effect) = voice.effect else { return };
+        if voice.frames > 1 {
+            voice.frames -= 1;
+            return;
+        }
+
+        let steps = match effect.sound {
+            Sound::Tone(steps) | Sound::Noise { steps, .. } => steps,
+            #[cfg(feature = "pcm")]
+            Sound::Pcm { .. } => &[],
+        };
+        let Some(step) = steps.get(voice.step as usize) else {
+            self.stop(cs, index);
+            return;
+        };
+        let psg_channel = index as u8;
+        if index != NOISE_VOICE {
+            psg::write(0x80 | (psg_channel << 5) | (step.tone & 0x0F) as u8);
+            psg::write((step.tone >> 4) as u8 & 0x3F);
+        }
+        psg::set_attenuation(psg_channel, step.attenuation);
+        levels::report(cs, VOICE_CHANNELS[index], true, 0x0F - step.attenuation);
+        voice.step += 1;
+        voice.frames = step.frames as u16;
+    }
+
+    fn stop(&mut self, cs: cs::CriticalSection, index: usize) {
+        if self.voices[index].effect.take().is_none() {
+            return;
+        }
+        let channel = VOICE_CHANNELS[index];
+        if index <= NOISE_VOICE {
+            psg::set_attenuation(index as u8, 0x0F);
+        }
+        levels::release(cs, channel);
+        music::unduck_in(cs, channel);
+    }
+}
+
+/// Sets the effects that `play_id` plays, such as a table built alongside the game's sound IDs.
+pub fn register(bank: &'static [Effect]) {
+    audio::with_cs(|cs| MIXER.borrow_ref_mut(cs).bank = bank)
+}
+
+/// Sets how important the music is. Sounds that don't outrank it only play on channels the music
+/// isn't using, and sounds that do take channels from the music until they end. The music has the
+/// lowest priority to begin with, so any sound can interrupt it.
+#[inline]
+pub fn set_music_priority(priority: u8) {
+    audio::with_cs(|cs| MIXER.borrow_ref_mut(cs).music_priority = priority)
+}
+
+/// Plays effect `id` from the table given to `register`. See `play`.
+#[inline]
+pub fn play_id(id: usize) -> Option<Channel> {
+    let effect = audio::with_cs(|cs| MIXER.borrow_ref(cs).bank.get(id))?;
+    play(effect)
+}
+
+/// Plays `effect` on a free channel, or on one taken from a sound or music that it outranks.
+/// Channels taken from the music are ducked until the sound ends.
+///
+/// Returns the channel the sound is playing on, or `None` if there wasn't one to be had.
+pub fn play(effect: &'static Effect) -> Option<Channel> {
+    let candidates: &[usize] = match effect.sound {
+        // The last channel first, since music more often leads on the first.
+        Sound::Tone(_) => &[2, 1, 0],
+        Sound::Noise { .. } => &[NOISE_VOICE],
+        #[cfg(feature = "pcm")]
+        Sound::Pcm { sample, rate } => return play_pcm(effect, sample, rate),
+    };
+    audio::with_cs(|cs| {
+        let mut mixer = MIXER.borrow_ref_mut(cs);
+        let index = mixer.pick(cs, candidates, effect.priority)?;
+        mixer.start(cs, index, effect);
+        Some(VOICE_CHANNELS[index])
+    })
+}
+
+#[cfg(feature = "pcm")]
+fn play_pcm(effect: &'static Effect, sample: &'static [u8], rate: u16) -> Option<Channel> {
+    if !super::pcm::play_sample_with_priority(sample, rate, false, effect.priority) {
+        return None;
+    }
+    // The driver doesn't say when the sample ends, so the DAC is held for as long as it should
+    // take, in whole frames.
+    let fps = timing::frame_rate().round().to_num::<u32>();
+    let frames = (sample.len() as u32 * fps / rate.max(1) as u32 + 1).min(u16::MAX as u32) as u16;
+    audio::with_cs(|cs| {
+        music::duck_in(cs, Channel::Fm6);
+        MIXER.borrow_ref_mut(cs).voices[DAC_VOICE] = Voice { effect: Some(effect), step: 0, frames };
+    });
+    Some(Channel::Dac)
+}
+
+/// Stops the sound on `channel`, if the mixer is playing one there, and gives the channel back to
+/// the music.
+pub fn stop(channel: Channel) {
+    let Some(index) = VOICE_CHANNELS.iter().position(|&voice| voice == channel) else { return };
+    #[cfg(feature = "pcm")]
+    if index == DAC_VOICE && is_playing(channel) {
+        super::pcm::stop();
+    }
+    audio::with_cs(|cs| {
+        let mut mixer = MIXER.borrow_ref_mut(cs);
+        stop_voice(cs, &mut mixer, index);
+    })
+}
+
+/// Stops every sound the mixer is playing.
+pub fn stop_all() {
+    for channel in VOICE_CHANNELS {
+        stop(channel);
+    }
+}
+
+/// Returns true if the mixer is playing a sound on `channel`.
+#[inline]
+pub fn is_playing(channel: Channel) -> bool {
+    let Some(index) = VOICE_CHANNELS.iter().position(|&voice| voice == channel) else { return false };
+    audio::with_cs(|cs| MIXER.borrow_ref(cs).voices[index].effect.is_some())
+}
+
+fn stop_voice(cs: cs::CriticalSection, mixer: &mut Mixer, index: usize) {
+    #[cfg(feature = "pcm")]
+    if index == DAC_VOICE {
+        if mixer.voices[index].effect.take().is_some() {
+            music::unduck_in(cs, Channel::Fm6);
+        }
+        return;
+    }
+    mixer.stop(cs, index);
+}
+
+/// Steps every sound along by a frame. Called by the vblank handler, after the music.
+pub(in crate::sys) fn tick(cs: cs::CriticalSection) {
+    let mut mixer = MIXER.borrow_ref_mut(cs);
+    for index in 0..=NOISE_VOICE {
+        mixer.advance(cs, index);
+    }
+    #[cfg(feature = "pcm")]
+    {
+        let voice = &mut mixer.voices[DAC_VOICE];
+        if voice.effect.is_some() {
+            voice.frames = voice.frames.saturating_sub(1);
+            if voice.frames == 0 {
+                stop_voice(cs, &mut mixer, DAC_VOICE);
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::sys::io;
+
+/// The YM2612's address and data registers for each of its two ports, as seen by the 68k.
+const YM_ADDR: [*mut u8; 2] = [0xA04000 as _, 0xA04002 as _];
+const YM_DATA: [*mut u8; 2] = [0xA04001 as _, 0xA04003 as _];
+
+/// The key on/off register. Its value selects the channel in bits 0-2 and the operators in bits 4-7.
+pub const REG_KEY_ON: u8 = 0x28;
+/// The DAC sample register.
+pub const REG_DAC: u8 = 0x2A;
+/// The DAC enable register. Bit 7 replaces FM channel 6 with the DAC.
+pub const REG_DAC_ENABLE: u8 = 0x2B;
+
+/// The timer control register, which also holds channel 3's special mode in bits 6-7.
+pub const REG_TIMER_CTRL: u8 = 0x27;
+
+/// Reads the status register: busy in bit 7, timer B overflow in bit 1 and timer A in bit 0.
+#[inline]
+pub fn status(_bus: &io::Z80BusGuard<'_>) -> u8 {
+    unsafe { core::ptr::read_volatile(YM_ADDR[0]) }
+}
+
+/// Returns true while the YM2612 is still processing the last write.
+#[inline]
+pub fn busy(_bus: &io::Z80BusGuard<'_>) -> bool {
+    unsafe { core::ptr::read_volatile(YM_ADDR[0]) & 0x80 != 0 }
+}
+
+/// Writes `value` to register `reg` on `port` (0 or 1), waiting for the chip to be ready first.
+///
+/// The 68k can only reach the YM2612 while it holds the Z80's bus.
+#[inline]
+pub fn write(bus: &io::Z80BusGuard<'_>, port: u8, reg: u8, value: u8) {
+    let port = (port & 1) as usize;
+    while busy(bus) {}
+    unsafe { core::ptr::write_volatile(YM_ADDR[port], reg) }
+    while busy(bus) {}
+    unsafe { core::ptr::write_volatile(YM_DATA[port], value) }
+}
+
+/// Selects register `reg` on port 0 without writing to it.
+///
+/// The PCM driver only selects the DAC register once per sample, so anything else that writes to the
+/// YM2612 while a sample might be playing should select `REG_DAC` again afterwards.
+#[inline]
+pub fn select(bus: &io::Z80BusGuard<'_>, reg: u8) {
+    while busy(bus) {}
+    unsafe { core::ptr::write_volatile(YM_ADDR[0], reg) }
+}
+
+/// Releases every operator on every FM channel.
+pub fn key_off_all(bus: &io::Z80BusGuard<'_>) {
+    for channel in [0, 1, 2, 4, 5, 6] {
+        write(bus, 0, REG_KEY_ON, channel);
+    }
+}
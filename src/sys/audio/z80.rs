@@ -0,0 +1,56 @@
+use crate::sys::{self, io};
+
+/// The size of the Z80's RAM, in bytes.
+pub const RAM_SIZE: usize = 0x2000;
+
+/// A program that does nothing forever, so the Z80 is in a known state and can be paused.
+pub const IDLE_PROGRAM: &[u8] = &[
+    0xF3,       // di
+    0x18, 0xFE, // jr $
+];
+
+/// Copies `program` to the start of Z80 RAM and resets the Z80 so it starts running it.
+///
+/// The YM2612 is reset along with the Z80.
+///
+/// # Panics
+///
+/// Panics if `program` doesn't fit in Z80 RAM.
+pub fn load(program: &[u8]) {
+    if program.len() > RAM_SIZE {
+        panic!("Z80 program is larger than Z80 RAM");
+    }
+
+    unsafe { io::release_z80_reset(); }
+    io::with_paused_z80(|bus| {
+        write(bus, 0, program);
+        unsafe { io::assert_z80_reset(); }
+    });
+    // The YM2612 needs its reset held for a little while after the bus is released.
+    sys::delay_us(30);
+    unsafe { io::release_z80_reset(); }
+}
+
+/// Copies `data` into Z80 RAM at `addr`.
+#[inline]
+pub fn write(_bus: &io::Z80BusGuard<'_>, addr: u16, data: &[u8]) {
+    let addr = addr as usize;
+    if addr + data.len() > RAM_SIZE {
+        panic!("Z80 RAM write out of range");
+    }
+    for (i, &byte) in data.iter().enumerate() {
+        unsafe { core::ptr::write_volatile(io::Z80_BUS.add(addr + i), byte) }
+    }
+}
+
+/// Copies Z80 RAM at `addr` into `buf`.
+#[inline]
+pub fn read(_bus: &io::Z80BusGuard<'_>, addr: u16, buf: &mut [u8]) {
+    let addr = addr as usize;
+    if addr + buf.len() > RAM_SIZE {
+        panic!("Z80 RAM read out of range");
+    }
+    for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = unsafe { core::ptr::read_volatile(io::Z80_BUS.add(addr + i)) }
+    }
+}
@@ -0,0 +1,580 @@
+use core::{fmt, mem, ops, ptr, slice};
+
+/// A vector with room for `N` items, kept inline, so it can live in a static or on the stack
+/// without the heap. Lengths are kept in 16 bits, so `N` can be at most 65535.
+///
+/// ```ignore
+/// let mut hits: FixedVec<Handle, 16> = FixedVec::new();
+/// hits.push(handle).ok();
+/// for hit in &hits {
+///     // ...
+/// }
+/// ```
+pub struct FixedVec<T, const N: usize> {
+    len: u16,
+    data: [mem::MaybeUninit<T>; N],
+}
+
+impl<T, const N: usize> FixedVec<T, N> {
+    /// # Panics
+    ///
+    /// Panics if `N` is more than 65535, which fails the build when used in a const.
+    pub const fn new() -> Self {
+        if N > u16::MAX as usize {
+            panic!("fixed vectors hold at most 65535 items");
+        }
+        Self { len: 0, data: [const { mem::MaybeUninit::uninit() }; N] }
+    }
+
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub const fn is_full(&self) -> bool {
+        self.len as usize == N
+    }
+
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.data.as_ptr().cast(), self.len as usize) }
+    }
+
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.data.as_mut_ptr().cast(), self.len as usize) }
+    }
+
+    /// Adds `value` to the end, or returns it if the vector is full.
+    #[inline]
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(value);
+        }
+        self.data[self.len as usize].write(value);
+        self.len += 1;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(unsafe { self.data[self.len as usize].assume_init_read() })
+    }
+
+    /// Puts `value` at `index`, moving everything after it along, or returns it if the vector is
+    /// full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is past the end.
+    pub fn insert(&mut self, index: usize, value: T) -> Result<(), T> {
+        if index > self.len as usize {
+            panic!("insert index out of bounds");
+        }
+        if self.is_full() {
+            return Err(value);
+        }
+        unsafe {
+            let at = self.data.as_mut_ptr().add(index).cast::<T>();
+            ptr::copy(at, at.add(1), self.len as usize - index);
+            at.write(value);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Takes out the item at `index`, moving everything after it back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> T {
+        if index >= self.len as usize {
+            panic!("remove index out of bounds");
+        }
+        self.len -= 1;
+        unsafe {
+            let at = self.data.as_mut_ptr().add(index).cast::<T>();
+            let value = at.read();
+            ptr::copy(at.add(1), at, self.len as usize - index);
+            value
+        }
+    }
+
+    /// Takes out the item at `index`, putting the last item in its place. Faster than `remove`,
+    /// but doesn't keep the order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        if index >= self.len as usize {
+            panic!("swap_remove index out of bounds");
+        }
+        let last = self.len as usize - 1;
+        self.as_mut_slice().swap(index, last);
+        self.pop().unwrap()
+    }
+
+    /// Drops every item from `len` on.
+    pub fn truncate(&mut self, len: usize) {
+        while self.len as usize > len {
+            self.pop();
+        }
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Keeps only the items `keep` returns true for, in order.
+    pub fn retain(&mut self, mut keep: impl FnMut(&mut T) -> bool) {
+        let mut i = 0;
+        while i < self.len as usize {
+            if keep(&mut self.as_mut_slice()[i]) {
+                i += 1;
+            } else {
+                self.remove(i);
+            }
+        }
+    }
+}
+
+impl<T, const N: usize> Default for FixedVec<T, N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for FixedVec<T, N> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.as_mut_slice()) }
+    }
+}
+
+impl<T, const N: usize> ops::Deref for FixedVec<T, N> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T, const N: usize> ops::DerefMut for FixedVec<T, N> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a FixedVec<T, N> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a mut FixedVec<T, N> {
+    type Item = &'a mut T;
+    type IntoIter = slice::IterMut<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for FixedVec<T, N> {
+    fn clone(&self) -> Self {
+        let mut out = Self::new();
+        for item in self {
+            let _ = out.push(item.clone());
+        }
+        out
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for FixedVec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// A ring buffer with room for `N` items, which can be pushed and popped at either end, for queues
+/// such as the DMA queue or bytes coming in over a serial port. Indices are kept in 16 bits, so `N`
+/// can be at most 65535.
+///
+/// ```ignore
+/// static RX: cs::Mutex<cell::RefCell<RingBuffer<u8, 64>>> = cs::Mutex::new(cell::RefCell::new(RingBuffer::new()));
+/// ```
+pub struct RingBuffer<T, const N: usize> {
+    head: u16,
+    tail: u16,
+    full: bool,
+    data: [mem::MaybeUninit<T>; N],
+}
+
+/// Another name for `RingBuffer`, for code that uses it as a double-ended queue.
+pub type FixedDeque<T, const N: usize> = RingBuffer<T, N>;
+
+impl<T, const N: usize> RingBuffer<T, N> {
+    /// # Panics
+    ///
+    /// Panics if `N` is 0 or more than 65535, which fails the build when used in a const.
+    pub const fn new() -> Self {
+        if N == 0 || N > u16::MAX as usize {
+            panic!("ring buffers hold between 1 and 65535 items");
+        }
+        Self { head: 0, tail: 0, full: false, data: [const { mem::MaybeUninit::uninit() }; N] }
+    }
+
+    #[inline]
+    fn increment(i: u16) -> u16 {
+        unsafe {
+            let out: u16;
+            core::arch::asm!(
+                "add.w  #1,{i}",
+                "cmpi.w #{N},{i}",
+                "bne    2f",
+                "move.w #0,{i}",
+                "2:",
+                i = inout(reg_data) i => out,
+                N = const N,
+            );
+            out
+        }
+    }
+
+    #[inline]
+    fn decrement(i: u16) -> u16 {
+        unsafe {
+            let out: u16;
+            core::arch::asm!(
+                "sub.w  #1,{i}",
+                "bcc    2f",
+                "move.w #{Nm1},{i}",
+                "2:",
+                i = inout(reg_data) i => out,
+                Nm1 = const N - 1,
+            );
+            out
+        }
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.head == self.tail && !self.full
+    }
+
+    #[inline]
+    pub const fn is_full(&self) -> bool {
+        self.full
+    }
+
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    #[inline]
+    pub const fn len(&self) -> usize {
+        if self.full {
+            N
+        } else {
+            (self.tail as usize + N - self.head as usize) % N
+        }
+    }
+
+    /// The item `index` places from the front.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len() {
+            return None;
+        }
+        Some(unsafe { self.data.get_unchecked((self.head as usize + index) % N).assume_init_ref() })
+    }
+
+    #[inline]
+    pub fn front(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    #[inline]
+    pub fn back(&self) -> Option<&T> {
+        self.get(self.len().wrapping_sub(1))
+    }
+
+    /// The items, from front to back.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.len()).map(move |i| unsafe { self.data.get_unchecked((self.head as usize + i) % N).assume_init_ref() })
+    }
+
+    #[inline]
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(unsafe { self.pop_front_unchecked() })
+        }
+    }
+
+    #[inline]
+    pub fn pop_back(&mut self) -> Option<T> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(unsafe { self.pop_back_unchecked() })
+        }
+    }
+
+    /// Adds `value` at the front, or returns it if the buffer is full.
+    #[inline]
+    pub fn push_front(&mut self, value: T) -> Result<(), T> {
+        if self.is_full() {
+            Err(value)
+        } else {
+            unsafe { self.push_front_unchecked(value) }
+            Ok(())
+        }
+    }
+
+    /// Adds `value` at the back, or returns it if the buffer is full.
+    #[inline]
+    pub fn push_back(&mut self, value: T) -> Result<(), T> {
+        if self.is_full() {
+            Err(value)
+        } else {
+            unsafe { self.push_back_unchecked(value) }
+            Ok(())
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The buffer can't be empty.
+    #[inline]
+    pub unsafe fn pop_front_unchecked(&mut self) -> T {
+        let index = self.head as usize;
+        self.full = false;
+        self.head = Self::increment(self.head);
+        self.data.get_unchecked_mut(index).assume_init_read()
+    }
+
+    /// # Safety
+    ///
+    /// The buffer can't be empty.
+    #[inline]
+    pub unsafe fn pop_back_unchecked(&mut self) -> T {
+        self.full = false;
+        self.tail = Self::decrement(self.tail);
+        self.data.get_unchecked_mut(self.tail as usize).assume_init_read()
+    }
+
+    /// # Safety
+    ///
+    /// The buffer can't be full.
+    #[inline]
+    pub unsafe fn push_front_unchecked(&mut self, value: T) {
+        self.head = Self::decrement(self.head);
+        self.data.get_unchecked_mut(self.head as usize).write(value);
+        self.full = self.head == self.tail;
+    }
+
+    /// # Safety
+    ///
+    /// The buffer can't be full.
+    #[inline]
+    pub unsafe fn push_back_unchecked(&mut self, value: T) {
+        self.data.get_unchecked_mut(self.tail as usize).write(value);
+        self.tail = Self::increment(self.tail);
+        self.full = self.head == self.tail;
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
+
+impl<T, const N: usize> Default for RingBuffer<T, N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for RingBuffer<T, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for RingBuffer<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Refers to a value in a `SlotMap`. Once the value is removed, its key doesn't find anything,
+/// even if the slot has been reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    index: u8,
+    generation: u8,
+}
+
+impl Key {
+    /// The value's slot, which stays the same for as long as it's in the map.
+    #[inline]
+    pub const fn index(&self) -> u8 {
+        self.index
+    }
+}
+
+struct Slot<T> {
+    value: Option<T>,
+    /// Counts up every time the slot empties, so old keys miss.
+    generation: u8,
+}
+
+impl<T> Slot<T> {
+    const EMPTY: Self = Self { value: None, generation: 0 };
+}
+
+/// Up to `N` values, each found by the `Key` it was given when inserted, for things that refer to
+/// each other without references, such as a target an enemy is chasing. Keys are two bytes.
+///
+/// ```ignore
+/// let mut targets: SlotMap<Target, 32> = SlotMap::new();
+/// let key = targets.insert(Target::new(x, y)).ok().unwrap();
+/// if let Some(target) = targets.get(key) {
+///     // ...
+/// }
+/// ```
+pub struct SlotMap<T, const N: usize> {
+    slots: [Slot<T>; N],
+    len: u16,
+}
+
+impl<T, const N: usize> SlotMap<T, N> {
+    /// # Panics
+    ///
+    /// Panics if `N` is more than 256, which fails the build when used in a const.
+    pub const fn new() -> Self {
+        if N > 256 {
+            panic!("slot maps hold at most 256 values");
+        }
+        Self { slots: [const { Slot::EMPTY }; N], len: 0 }
+    }
+
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub const fn is_full(&self) -> bool {
+        self.len as usize == N
+    }
+
+    /// Puts `value` in the first free slot, or returns it if every slot is taken.
+    pub fn insert(&mut self, value: T) -> Result<Key, T> {
+        let Some(index) = self.slots.iter().position(|slot| slot.value.is_none()) else { return Err(value) };
+        let slot = &mut self.slots[index];
+        slot.value = Some(value);
+        self.len += 1;
+        Ok(Key { index: index as u8, generation: slot.generation })
+    }
+
+    /// Takes out a value, if it's still there.
+    pub fn remove(&mut self, key: Key) -> Option<T> {
+        let slot = self.slots.get_mut(key.index as usize).filter(|slot| slot.generation == key.generation)?;
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.len -= 1;
+        Some(value)
+    }
+
+    #[inline]
+    pub fn get(&self, key: Key) -> Option<&T> {
+        self.slots.get(key.index as usize).filter(|slot| slot.generation == key.generation)?.value.as_ref()
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut T> {
+        self.slots.get_mut(key.index as usize).filter(|slot| slot.generation == key.generation)?.value.as_mut()
+    }
+
+    #[inline]
+    pub fn contains(&self, key: Key) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            if slot.value.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+            }
+        }
+        self.len = 0;
+    }
+
+    /// The values, in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (Key, &T)> {
+        self.slots.iter().enumerate().filter_map(|(i, slot)| {
+            let key = Key { index: i as u8, generation: slot.generation };
+            slot.value.as_ref().map(|value| (key, value))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Key, &mut T)> {
+        self.slots.iter_mut().enumerate().filter_map(|(i, slot)| {
+            let key = Key { index: i as u8, generation: slot.generation };
+            slot.value.as_mut().map(|value| (key, value))
+        })
+    }
+
+    /// Removes every value `keep` returns false for.
+    pub fn retain(&mut self, mut keep: impl FnMut(Key, &mut T) -> bool) {
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            let key = Key { index: i as u8, generation: slot.generation };
+            if slot.value.as_mut().is_some_and(|value| !keep(key, value)) {
+                slot.value = None;
+                slot.generation = slot.generation.wrapping_add(1);
+                self.len -= 1;
+            }
+        }
+    }
+}
+
+impl<T, const N: usize> Default for SlotMap<T, N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
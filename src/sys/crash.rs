@@ -0,0 +1,192 @@
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use core::ptr;
+
+use super::exceptions::{Access, Frame, Registers};
+use super::layout;
+use super::vdp::{Address, Color, TileFlags, VRAMAddress, WordCmd, Writer, VDP};
+
+/// ASCII, one tile per character code.
+const FONT: &[super::vdp::Tile] = crate::include_tiles!("../assets/font4bpp.bin");
+
+// The crash screen's own VDP layout, which doesn't depend on what the game had set up.
+const PLANE_A: u32 = 0xC000;
+const COLUMNS: u8 = 40;
+const ROWS: u8 = 28;
+/// Text is kept a column in from each edge, where some TVs cut the picture off.
+const MARGIN: u8 = 1;
+
+const BACKGROUND: Color = Color::new(0, 0, 2);
+
+const STACK_ROWS: u8 = 6;
+const STACK_TOP: u32 = layout::STACK_TOP as u32;
+const RAM_START: u32 = layout::RAM_START as u32;
+
+/// The panic message, saved for the crash screen.
+struct Message {
+    buf: [u8; Message::LEN],
+    len: usize,
+}
+
+impl Message {
+    /// Four lines of text.
+    const LEN: usize = (COLUMNS - 2 * MARGIN) as usize * 4;
+}
+
+impl fmt::Write for Message {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(Self::LEN - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+static mut MESSAGE: Message = Message { buf: [0; Message::LEN], len: 0 };
+
+/// Saves the panic message, then raises `trap #0` so the crash screen gets the registers and an
+/// exception frame to show.
+pub(super) fn panic(info: &PanicInfo) -> ! {
+    let message = unsafe { &mut *(&raw mut MESSAGE) };
+    message.len = 0;
+    let _ = write!(message, "{}", info.message());
+    if let Some(location) = info.location() {
+        let _ = write!(message, "\nat {}:{}", location.file(), super::fmt::Int(location.line()));
+    }
+
+    unsafe { core::arch::asm!("trap #0", options(noreturn)) }
+}
+
+/// Where the next character is drawn.
+struct Screen {
+    x: u8,
+    y: u8,
+}
+
+impl Screen {
+    #[inline]
+    fn at(x: u8, y: u8) -> Self {
+        Self { x: MARGIN + x, y }
+    }
+
+    fn put(&mut self, c: u8) {
+        if c == b'\n' || self.x >= COLUMNS - MARGIN {
+            self.x = MARGIN;
+            self.y += 1;
+            if c == b'\n' {
+                return;
+            }
+        }
+        if self.y >= ROWS {
+            return;
+        }
+        let c = if c < 0x80 { c } else { b'?' };
+        let addr = VRAMAddress::from_byte_addr(PLANE_A + ((self.y as u32 * 64 + self.x as u32) << 1));
+        VDP::write_tile_flags(&[TileFlags::for_tile(c as u16, 0)], addr, None);
+        self.x += 1;
+    }
+
+    fn text(&mut self, text: &[u8]) -> &mut Self {
+        for &c in text {
+            self.put(c);
+        }
+        self
+    }
+
+    fn hex(&mut self, value: u32, digits: u8) -> &mut Self {
+        for i in (0..digits).rev() {
+            let digit = ((value >> (i * 4)) & 0xF) as u8;
+            self.put(if digit < 10 { b'0' + digit } else { b'A' + digit - 10 });
+        }
+        self
+    }
+}
+
+/// Puts the VDP into a known state with the font loaded, and the display off while it's drawn on.
+fn reset_vdp() {
+    // Reading the status also cancels a half written command, if the crash came in the middle of one.
+    while VDP::status().dma_in_progress() {
+        core::hint::spin_loop();
+    }
+
+    for (reg, val) in [
+        (0, 0x04), (1, 0x04), (2, 0x30), (3, 0x34), (4, 0x07), (5, 0x78), (7, 0x00), (10, 0xFF),
+        (11, 0x00), (12, 0x81), (13, 0x3D), (15, 0x02), (16, 0x01), (17, 0x00), (18, 0x00),
+    ] {
+        WordCmd::set_reg(reg, val).execute();
+    }
+
+    // Clears the planes, sprites and scroll table, which all live above the font.
+    let zeros = [0u16; 64];
+    for addr in (PLANE_A..0x10000).step_by(128) {
+        Writer::new(Address::VRAM(VRAMAddress::from_byte_addr(addr))).write::<[u16]>(&zeros);
+    }
+    Writer::new(Address::VSRAM(0)).write::<[u16]>(&zeros[..40]);
+
+    let mut palette = [Color::WHITE; 16];
+    palette[0] = BACKGROUND;
+    Writer::new(Address::CRAM(0)).write::<[Color]>(&palette);
+    Writer::new(Address::VRAM(VRAMAddress::from_tile_index(0))).write::<[super::vdp::Tile]>(FONT);
+}
+
+/// Draws the crash screen for an exception, then stops for good. `sp` is the stack pointer from
+/// before the exception.
+pub(super) fn show(title: &[u8], registers: &Registers, frame: &Frame, sp: u32, access: Option<&Access>) -> ! {
+    unsafe { super::set_int_level::<7>() };
+    reset_vdp();
+
+    Screen::at(0, 1).text(title);
+
+    if let Some(access) = access {
+        let (address, instruction) = (access.address, access.instruction);
+        Screen::at(0, 3)
+            .text(if access.is_read() { b"READ " } else { b"WRITE " })
+            .hex(address, 8)
+            .text(b"  FC ").hex(access.function_code() as u32, 1)
+            .text(b"  IR ").hex(instruction as u32, 4);
+    } else {
+        let message = unsafe { &*(&raw const MESSAGE) };
+        Screen::at(0, 3).text(&message.buf[..message.len]);
+    }
+
+    let (pc, sr) = (frame.pc, frame.sr);
+    Screen::at(0, 8).text(b"PC ").hex(pc, 8).text(b"  SR ").hex(sr as u32, 4).text(b"  SP ").hex(sp, 8);
+
+    let (d, a) = (registers.d, registers.a);
+    for i in 0..8 {
+        let mut line = Screen::at(0, 10 + i);
+        line.text(b"D").hex(i as u32, 1).text(b" ").hex(d[i as usize], 8);
+        line.text(b"   A").hex(i as u32, 1).text(b" ").hex(a.get(i as usize).copied().unwrap_or(sp), 8);
+    }
+
+    // Only the stack in work RAM is dumped, in case the stack pointer itself is what went wrong.
+    Screen::at(0, 19).text(b"STACK");
+    if sp & 1 == 0 && (RAM_START..STACK_TOP).contains(&sp) {
+        let mut addr = sp;
+        for row in 0..STACK_ROWS {
+            let mut line = Screen::at(0, 20 + row);
+            for _ in 0..4 {
+                if addr >= STACK_TOP {
+                    break;
+                }
+                line.hex(unsafe { ptr::read_volatile(addr as *const u32) }, 8).text(b" ");
+                addr += 4;
+            }
+        }
+    }
+
+    WordCmd::set_reg(1, 0x44).execute();
+    VDP::debug_halt();
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Called by the `trap #0` handler in the vector table, which `panic` raises, with the saved
+/// registers, which the exception's stack frame follows.
+#[no_mangle]
+unsafe extern "C" fn _crash(registers: &Registers) -> ! {
+    let frame = &*(registers as *const Registers).add(1).cast::<Frame>();
+    let sp = (frame as *const Frame).addr() as u32 + core::mem::size_of::<Frame>() as u32;
+    show(b"PANIC", registers, frame, sp, None)
+}
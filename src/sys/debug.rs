@@ -0,0 +1,336 @@
+use core::{fmt, ptr};
+
+use super::vdp::VDP;
+
+mod profiler;
+
+pub use profiler::{ProfileMode, Profiler, Scope, MAX_BUCKETS, MAX_DEPTH};
+
+/// How important a log message is. Messages are only logged when their level is at or below the
+/// max level.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Only used as a max level, to turn logging off.
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl Level {
+    #[inline]
+    const fn tag(self) -> &'static [u8] {
+        match self {
+            Level::Off => b"",
+            Level::Error => b"[E] ",
+            Level::Warn => b"[W] ",
+            Level::Info => b"[I] ",
+            Level::Debug => b"[D] ",
+            Level::Trace => b"[T] ",
+        }
+    }
+
+    #[inline]
+    const fn from_u8(level: u8) -> Self {
+        match level {
+            1 => Level::Error,
+            2 => Level::Warn,
+            3 => Level::Info,
+            4 => Level::Debug,
+            5 => Level::Trace,
+            _ => Level::Off,
+        }
+    }
+}
+
+/// The most verbose level that's compiled in. Messages above it are removed from the ROM entirely.
+///
+/// This is `Trace` in debug builds and `Info` in release builds, unless one of the `log-off` or
+/// `log-max-*` features lowers it.
+pub const STATIC_MAX_LEVEL: Level = if cfg!(feature = "log-off") {
+    Level::Off
+} else if cfg!(feature = "log-max-error") {
+    Level::Error
+} else if cfg!(feature = "log-max-warn") {
+    Level::Warn
+} else if cfg!(feature = "log-max-info") {
+    Level::Info
+} else if cfg!(feature = "log-max-debug") {
+    Level::Debug
+} else if cfg!(debug_assertions) {
+    Level::Trace
+} else {
+    Level::Info
+};
+
+/// How many of the `log-off` and `log-max-*` features are enabled. Each sets a different maximum,
+/// so two at once would quietly pick the lowest.
+const MAX_LEVEL_FEATURES: usize = cfg!(feature = "log-off") as usize
+    + cfg!(feature = "log-max-error") as usize
+    + cfg!(feature = "log-max-warn") as usize
+    + cfg!(feature = "log-max-info") as usize
+    + cfg!(feature = "log-max-debug") as usize;
+const _: () = assert!(MAX_LEVEL_FEATURES <= 1, "only one of the `log-off` and `log-max-*` features can be enabled");
+
+/// Max levels for the crate's own subsystems, by module path. Each is raised to `Trace` by its
+/// `log-trace-*` feature, and left at `Info` otherwise, so tracing one subsystem doesn't drown out
+/// the debug channel with the rest.
+const MODULE_LEVELS: &[(&str, Level)] = &[
+    ("mdrs::sys::vdp", if cfg!(feature = "log-trace-vdp") { Level::Trace } else { Level::Info }),
+    ("mdrs::sys::audio", if cfg!(feature = "log-trace-audio") { Level::Trace } else { Level::Info }),
+    ("mdrs::sys::io", if cfg!(feature = "log-trace-io") { Level::Trace } else { Level::Info }),
+    ("mdrs::sys::alloc", if cfg!(feature = "log-trace-alloc") { Level::Trace } else { Level::Info }),
+];
+
+/// Returns true if `module` is `prefix`, or a module inside it.
+const fn in_module(module: &str, prefix: &str) -> bool {
+    let (module, prefix) = (module.as_bytes(), prefix.as_bytes());
+    if module.len() < prefix.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < prefix.len() {
+        if module[i] != prefix[i] {
+            return false;
+        }
+        i += 1;
+    }
+    module.len() == prefix.len() || (module[i] == b':' && module.len() > i + 1 && module[i + 1] == b':')
+}
+
+/// The most verbose level compiled in for messages from `module`, a `module_path!()`. This is the
+/// lower of `STATIC_MAX_LEVEL` and the module's entry in the subsystem table, if it has one.
+pub const fn module_max_level(module: &str) -> Level {
+    let mut i = 0;
+    while i < MODULE_LEVELS.len() {
+        let (prefix, level) = MODULE_LEVELS[i];
+        if in_module(module, prefix) {
+            return if (level as u8) < (STATIC_MAX_LEVEL as u8) { level } else { STATIC_MAX_LEVEL };
+        }
+        i += 1;
+    }
+    STATIC_MAX_LEVEL
+}
+
+/// Where log messages go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outputs(u8);
+
+impl Outputs {
+    pub const NONE: Self = Self(0);
+    /// The Gens KMod debug register, which emulators with KMod support show in their message log.
+    /// Real hardware ignores it.
+    pub const KMOD: Self = Self(0x1);
+    /// A ring buffer in RAM, with the `log-ring` feature, for reading back on a crash screen or in a
+    /// memory viewer.
+    pub const RING: Self = Self(0x2);
+
+    #[inline]
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    #[inline]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// The longest a log line can be. Longer messages are cut short.
+pub const LINE_LEN: usize = 80;
+
+// Single bytes, so they can be read and written from anywhere without masking interrupts.
+static mut MAX_LEVEL: u8 = STATIC_MAX_LEVEL as u8;
+static mut OUTPUTS: u8 = if cfg!(feature = "log-ring") { 0x3 } else { 0x1 };
+
+/// Sets the most verbose level that's logged. Levels above `STATIC_MAX_LEVEL` are never logged.
+#[inline]
+pub fn set_max_level(level: Level) {
+    unsafe { ptr::write_volatile(&raw mut MAX_LEVEL, level as u8) }
+}
+
+#[inline]
+pub fn max_level() -> Level {
+    Level::from_u8(unsafe { ptr::read_volatile(&raw const MAX_LEVEL) })
+}
+
+/// Sets where log messages go. Logging goes to the KMod register, and the ring buffer if it's
+/// enabled, to begin with.
+#[inline]
+pub fn set_outputs(outputs: Outputs) {
+    unsafe { ptr::write_volatile(&raw mut OUTPUTS, outputs.0) }
+}
+
+#[inline]
+pub fn outputs() -> Outputs {
+    Outputs(unsafe { ptr::read_volatile(&raw const OUTPUTS) })
+}
+
+/// Returns true if a message at `level` would be logged from `module`, a `module_path!()`.
+#[inline(always)]
+pub fn enabled(module: &str, level: Level) -> bool {
+    level <= module_max_level(module) && level <= max_level()
+}
+
+/// A log line being formatted, which cuts off anything that doesn't fit.
+struct Line {
+    buf: [u8; LINE_LEN],
+    len: usize,
+}
+
+impl fmt::Write for Line {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(LINE_LEN - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+/// Masks interrupts while `f` runs, putting the mask back to how it was afterwards, so it's safe to
+/// log from interrupt handlers and critical sections too.
+#[inline(always)]
+fn masked<R>(f: impl FnOnce() -> R) -> R {
+    let sr: u16;
+    unsafe {
+        core::arch::asm!(
+            "move.w %sr,{sr}",
+            "ori.w #0x0700,%sr",
+            sr = out(reg_data) sr,
+        );
+    }
+    let result = f();
+    unsafe {
+        core::arch::asm!(
+            "move.w {sr},%sr",
+            sr = in(reg_data) sr,
+        );
+    }
+    result
+}
+
+/// Logs a message. Use the `log!` macros instead, which skip formatting messages that won't be
+/// logged.
+#[inline(never)]
+pub fn write(level: Level, args: fmt::Arguments<'_>) {
+    let mut line = Line { buf: [0; LINE_LEN], len: 0 };
+    let tag = level.tag();
+    line.buf[..tag.len()].copy_from_slice(tag);
+    line.len = tag.len();
+    let _ = fmt::write(&mut line, args);
+    let message = &line.buf[..line.len];
+
+    let outputs = outputs();
+    masked(|| {
+        if outputs.contains(Outputs::KMOD) {
+            VDP::debug_alert(message);
+        }
+        #[cfg(feature = "log-ring")]
+        if outputs.contains(Outputs::RING) {
+            ring::push(message);
+        }
+    });
+}
+
+#[cfg(feature = "log-ring")]
+pub use ring::{clear_ring, copy_ring, RING_SIZE};
+
+#[cfg(feature = "log-ring")]
+mod ring {
+    use core::ptr;
+
+    /// The size of the log ring buffer, in bytes. Once it's full, the oldest messages are
+    /// overwritten.
+    pub const RING_SIZE: usize = 0x400;
+
+    static mut RING: [u8; RING_SIZE] = [0; RING_SIZE];
+    /// Where the next byte goes.
+    static mut HEAD: u16 = 0;
+    /// Set once the buffer has wrapped around.
+    static mut WRAPPED: bool = false;
+
+    /// Appends a message and a newline. Interrupts must be masked.
+    pub(super) fn push(message: &[u8]) {
+        unsafe {
+            let ring = &mut *(&raw mut RING);
+            let mut head = ptr::read_volatile(&raw const HEAD) as usize;
+            for &byte in message.iter().chain(b"\n") {
+                ring[head] = byte;
+                head += 1;
+                if head == RING_SIZE {
+                    head = 0;
+                    ptr::write_volatile(&raw mut WRAPPED, true);
+                }
+            }
+            ptr::write_volatile(&raw mut HEAD, head as u16);
+        }
+    }
+
+    /// Copies the logged lines into `buf`, oldest first, and returns how many bytes there were.
+    pub fn copy_ring(buf: &mut [u8; RING_SIZE]) -> usize {
+        super::masked(|| unsafe {
+            let ring = &*(&raw const RING);
+            let head = ptr::read_volatile(&raw const HEAD) as usize;
+            if ptr::read_volatile(&raw const WRAPPED) {
+                buf[..RING_SIZE - head].copy_from_slice(&ring[head..]);
+                buf[RING_SIZE - head..].copy_from_slice(&ring[..head]);
+                RING_SIZE
+            } else {
+                buf[..head].copy_from_slice(&ring[..head]);
+                head
+            }
+        })
+    }
+
+    pub fn clear_ring() {
+        super::masked(|| unsafe {
+            ptr::write_volatile(&raw mut HEAD, 0);
+            ptr::write_volatile(&raw mut WRAPPED, false);
+        })
+    }
+}
+
+/// Logs a formatted message at a level, if that level is enabled for the calling module.
+///
+/// ```ignore
+/// mdrs::log!(Level::Info, "loaded level {}", Int(level));
+/// ```
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => {{
+        let level: $crate::sys::debug::Level = $level;
+        if level <= const { $crate::sys::debug::module_max_level(module_path!()) } && level <= $crate::sys::debug::max_level() {
+            $crate::sys::debug::write(level, format_args!($($arg)+));
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => { $crate::log!($crate::sys::debug::Level::Error, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => { $crate::log!($crate::sys::debug::Level::Warn, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => { $crate::log!($crate::sys::debug::Level::Info, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::log!($crate::sys::debug::Level::Debug, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => { $crate::log!($crate::sys::debug::Level::Trace, $($arg)+) };
+}
+
+pub use crate::{debug, error, info, log, profile_scope, trace, warn};
@@ -0,0 +1,268 @@
+use crate::sys::fmt::Int;
+use crate::sys::timing;
+use crate::sys::vdp::{Address, Color, Settings, Writer};
+
+/// The number of named scopes the profiler keeps timings for. Scopes past that are timed into the
+/// last one.
+pub const MAX_BUCKETS: usize = 8;
+/// How deeply scopes can be nested. Deeper scopes still time, but leave the backdrop alone.
+pub const MAX_DEPTH: usize = 8;
+
+/// The colors the backdrop is set to for each bucket, in the order scopes are first entered.
+const BAR_COLORS: [Color; MAX_BUCKETS] = [
+    Color::RED,
+    Color::GREEN,
+    Color::BLUE,
+    Color::new(7, 7, 0),
+    Color::new(7, 0, 7),
+    Color::new(0, 7, 7),
+    Color::new(7, 4, 0),
+    Color::WHITE,
+];
+
+/// What the profiler does with scopes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileMode {
+    /// Scopes do nothing.
+    Off,
+    /// Each scope sets the backdrop to its own color while it runs, so the border shows how far
+    /// down the screen the beam got while it ran. This costs next to nothing, but only shows what
+    /// runs during the active display.
+    Bars,
+    /// Each scope adds the scanlines it ran for to its bucket, and `end_frame` logs the buckets at
+    /// `Info` every `report_interval` frames.
+    Timings,
+}
+
+#[derive(Clone, Copy)]
+struct Bucket {
+    name: &'static str,
+    lines: u32,
+    max: u16,
+    calls: u16,
+}
+
+struct State {
+    mode: ProfileMode,
+    /// The byte in CRAM the backdrop color is read from.
+    backdrop: u8,
+    /// The backdrop color outside of every scope.
+    base: Color,
+    depth: u8,
+    stack: [Color; MAX_DEPTH],
+    buckets: [Bucket; MAX_BUCKETS],
+    used: u8,
+    frames: u16,
+    report_interval: u16,
+}
+
+static mut STATE: State = State {
+    mode: ProfileMode::Off,
+    backdrop: 0,
+    base: Color::BLACK,
+    depth: 0,
+    stack: [Color::BLACK; MAX_DEPTH],
+    buckets: [Bucket { name: "", lines: 0, max: 0, calls: 0 }; MAX_BUCKETS],
+    used: 0,
+    frames: 0,
+    report_interval: 60,
+};
+
+/// Runs `f` on the profiler's state with interrupts masked, so scopes can be used anywhere,
+/// including interrupt handlers and critical sections.
+#[inline(always)]
+fn with_state<R>(f: impl FnOnce(&mut State) -> R) -> R {
+    super::masked(|| f(unsafe { &mut *(&raw mut STATE) }))
+}
+
+#[inline]
+fn set_backdrop(byte: u8, color: Color) {
+    Writer::new(Address::CRAM(byte)).write::<[Color]>([color]);
+}
+
+/// Shows how much of a frame parts of the game take, either as colored bars in the border or as
+/// scanline counts sent to the log.
+///
+/// Parts of the game are marked with `profile_scope!`, which does nothing until the profiler is
+/// turned on with `set_mode`.
+///
+/// ```ignore
+/// Profiler::set_mode(ProfileMode::Timings);
+/// loop {
+///     {
+///         profile_scope!("physics");
+///         // ...
+///     }
+///     {
+///         profile_scope!("sprites");
+///         // ...
+///     }
+///     Profiler::end_frame();
+///     VDP::wait_for_vblank(None);
+/// }
+/// ```
+pub struct Profiler;
+
+impl Profiler {
+    /// Turns the profiler on or off, and clears the timings.
+    ///
+    /// Bars are drawn in whichever CRAM entry `Settings` has as the backdrop when this is called,
+    /// and turning them off sets it to the base color, which is black unless `set_base_color` says
+    /// otherwise.
+    pub fn set_mode(mode: ProfileMode) {
+        let (line, index) = Settings::current().background_color();
+        with_state(|state| {
+            if state.mode == ProfileMode::Bars && mode != ProfileMode::Bars {
+                set_backdrop(state.backdrop, state.base);
+            }
+            state.mode = mode;
+            state.depth = 0;
+            state.backdrop = (line * 16 + index) * 2;
+            Self::clear(state);
+        });
+    }
+
+    #[inline]
+    pub fn mode() -> ProfileMode {
+        with_state(|state| state.mode)
+    }
+
+    /// Sets the backdrop color that's shown outside of every scope while bars are on.
+    #[inline]
+    pub fn set_base_color(color: Color) {
+        with_state(|state| state.base = color);
+    }
+
+    /// Sets how many frames `end_frame` adds timings up over before logging them.
+    #[inline]
+    pub fn set_report_interval(frames: u16) {
+        with_state(|state| state.report_interval = frames.max(1));
+    }
+
+    fn clear(state: &mut State) {
+        state.buckets = [Bucket { name: "", lines: 0, max: 0, calls: 0 }; MAX_BUCKETS];
+        state.used = 0;
+        state.frames = 0;
+    }
+
+    /// Counts a frame, and every `report_interval` frames, logs and clears the timings. Call this
+    /// once a frame when timings are on.
+    pub fn end_frame() {
+        let mut report = None;
+        with_state(|state| {
+            if state.mode != ProfileMode::Timings {
+                return;
+            }
+            state.frames += 1;
+            if state.frames >= state.report_interval {
+                report = Some((state.buckets, state.used, state.frames));
+                Self::clear(state);
+            }
+        });
+
+        if let Some((buckets, used, frames)) = report {
+            let total = timing::lines_per_frame() as u32;
+            for bucket in &buckets[..used as usize] {
+                let average = bucket.lines / frames as u32;
+                crate::info!(
+                    "{}: {} lines ({}%), max {}, {} calls",
+                    bucket.name,
+                    Int(average),
+                    Int(average * 100 / total),
+                    Int(bucket.max),
+                    Int(bucket.calls),
+                );
+            }
+        }
+    }
+
+    /// The bucket for `name`, adding it if it's new.
+    fn bucket(state: &mut State, name: &'static str) -> usize {
+        let used = state.used as usize;
+        if let Some(index) = state.buckets[..used].iter().position(|bucket| bucket.name == name) {
+            return index;
+        }
+        if used == MAX_BUCKETS {
+            return MAX_BUCKETS - 1;
+        }
+        state.buckets[used].name = name;
+        state.used += 1;
+        used
+    }
+}
+
+/// A part of the frame being profiled, which ends when it's dropped. Use `profile_scope!`.
+#[must_use]
+pub struct Scope {
+    /// The bucket, or `None` if the profiler was off when the scope started.
+    bucket: Option<u8>,
+    start: u16,
+}
+
+impl Scope {
+    #[inline]
+    pub fn enter(name: &'static str) -> Self {
+        let start = timing::lines_since_vblank();
+        let bucket = with_state(|state| {
+            if state.mode == ProfileMode::Off {
+                return None;
+            }
+            let bucket = Profiler::bucket(state, name);
+            if state.mode == ProfileMode::Bars && (state.depth as usize) < MAX_DEPTH {
+                let color = BAR_COLORS[bucket];
+                state.stack[state.depth as usize] = color;
+                set_backdrop(state.backdrop, color);
+            }
+            state.depth += 1;
+            Some(bucket as u8)
+        });
+        Self { bucket, start }
+    }
+}
+
+impl Drop for Scope {
+    #[inline]
+    fn drop(&mut self) {
+        let Some(bucket) = self.bucket else { return };
+        let end = timing::lines_since_vblank();
+        let lines = if end >= self.start { end - self.start } else { end + timing::lines_per_frame() - self.start };
+        with_state(|state| {
+            if state.depth == 0 {
+                // The profiler was turned off and on again while the scope ran.
+                return;
+            }
+            state.depth -= 1;
+            match state.mode {
+                ProfileMode::Bars => {
+                    let depth = state.depth as usize;
+                    if depth < MAX_DEPTH {
+                        let color = if depth == 0 { state.base } else { state.stack[depth - 1] };
+                        set_backdrop(state.backdrop, color);
+                    }
+                }
+                ProfileMode::Timings => {
+                    let bucket = &mut state.buckets[bucket as usize];
+                    bucket.lines += lines as u32;
+                    bucket.max = bucket.max.max(lines);
+                    bucket.calls = bucket.calls.saturating_add(1);
+                }
+                ProfileMode::Off => {}
+            }
+        });
+    }
+}
+
+/// Profiles the rest of the enclosing block under a name, with `Profiler`.
+///
+/// ```ignore
+/// fn update_enemies() {
+///     profile_scope!("enemies");
+///     // ...
+/// }
+/// ```
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::sys::debug::Scope::enter($name);
+    };
+}
@@ -0,0 +1,46 @@
+use super::vdp::VDP;
+
+/// Busy-waits for `count + 1` iterations of a `dbra` loop, 10 cycles each.
+#[inline(always)]
+fn spin(count: u16) {
+    unsafe {
+        core::arch::asm!(
+            "1:",
+            "dbra {n},1b",
+            n = inout(reg_data) count => _,
+            options(nomem, nostack),
+        );
+    }
+}
+
+/// Waits for at least `us` microseconds.
+///
+/// The delay is counted in CPU cycles, calibrated to the 68000's clock on NTSC (7.67 MHz) or PAL
+/// (7.60 MHz) consoles, so it's accurate to within a couple of microseconds. Interrupts that fire
+/// during the wait only make it longer. For anything over a scanline, `delay_lines` is cheaper.
+#[inline(always)]
+pub fn delay_us(us: u16) {
+    // Loop iterations per microsecond, in 0.10 fixed point: clock / 10 cycles per iteration.
+    const NTSC_RATE: u32 = 786;
+    const PAL_RATE: u32 = 778;
+
+    let rate = if VDP::status().is_pal() { PAL_RATE } else { NTSC_RATE };
+    let count = ((us as u32 * rate) >> 10) as u16;
+    spin(count.saturating_sub(1));
+}
+
+/// Waits for `lines` scanlines to pass, about 64 microseconds each, using the V counter.
+///
+/// Lines are counted as changes of the counter, so this copes with the jumps it makes during vblank.
+#[inline(never)]
+pub fn delay_lines(lines: u16) {
+    let mut last = (VDP::hv_counter() >> 8) as u8;
+    let mut remaining = lines;
+    while remaining > 0 {
+        let line = (VDP::hv_counter() >> 8) as u8;
+        if line != last {
+            last = line;
+            remaining -= 1;
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use core::ptr;
+
+use super::delay_us;
+
+/// A serial EEPROM chip from the 24Cxx family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip {
+    /// 128 bytes. This is the standard 24C01 protocol, not the X24C01's, which some older carts use.
+    C01,
+    /// 256 bytes.
+    C02,
+    /// 512 bytes.
+    C04,
+    /// 1KB.
+    C08,
+    /// 2KB.
+    C16,
+    /// 4KB.
+    C32,
+    /// 8KB.
+    C64,
+}
+
+impl Chip {
+    /// The size of the chip, in bytes.
+    #[inline]
+    pub const fn size(&self) -> u16 {
+        match self {
+            Chip::C01 => 0x80,
+            Chip::C02 => 0x100,
+            Chip::C04 => 0x200,
+            Chip::C08 => 0x400,
+            Chip::C16 => 0x800,
+            Chip::C32 => 0x1000,
+            Chip::C64 => 0x2000,
+        }
+    }
+
+    /// The most bytes that can be written in one go. Writes can't cross a page boundary.
+    #[inline]
+    pub const fn page_size(&self) -> u16 {
+        match self {
+            Chip::C01 | Chip::C02 => 8,
+            Chip::C04 | Chip::C08 | Chip::C16 => 16,
+            Chip::C32 | Chip::C64 => 32,
+        }
+    }
+
+    /// Returns true if the chip takes a two byte word address. Smaller chips put the high address
+    /// bits in the device address instead.
+    #[inline]
+    const fn wide_address(&self) -> bool {
+        matches!(self, Chip::C32 | Chip::C64)
+    }
+}
+
+/// Where a cartridge wires up the EEPROM's data (SDA) and clock (SCL) lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lines {
+    pub sda_in: *const u8,
+    pub sda_in_bit: u8,
+    pub sda_out: *mut u8,
+    pub sda_out_bit: u8,
+    pub scl: *mut u8,
+    pub scl_bit: u8,
+}
+
+impl Lines {
+    /// Sega's own mapping: SDA on bit 0 and SCL on bit 1 of 0x200001.
+    pub const SEGA: Self = Self {
+        sda_in: 0x200001 as _,
+        sda_in_bit: 0,
+        sda_out: 0x200001 as _,
+        sda_out_bit: 0,
+        scl: 0x200001 as _,
+        scl_bit: 1,
+    };
+
+    /// Acclaim's mapping for its later carts: SDA on bit 0 of 0x200001, SCL on bit 0 of 0x200000.
+    pub const ACCLAIM: Self = Self {
+        sda_in: 0x200001 as _,
+        sda_in_bit: 0,
+        sda_out: 0x200001 as _,
+        sda_out_bit: 0,
+        scl: 0x200000 as _,
+        scl_bit: 0,
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The access runs past the end of the chip.
+    OutOfRange,
+    /// The chip didn't acknowledge a byte, which usually means there isn't one.
+    NoAck,
+}
+
+/// The device address of every 24Cxx chip, before the chip select and high address bits.
+const DEVICE_ADDR: u8 = 0xA0;
+const READ: u8 = 0x01;
+
+/// Half of an I2C clock period at 100 kHz.
+const HALF_PERIOD_US: u16 = 5;
+
+/// How many times to poll for the end of a write cycle before giving up. Writes take up to 10ms.
+const WRITE_POLLS: u16 = 200;
+
+/// A serial EEPROM, driven by bit-banged I2C.
+///
+/// Every access blocks until it's finished, and a write can take 10ms per page, so save outside of
+/// gameplay. Writes only touch the bytes that actually changed, and skip pages that didn't change
+/// at all, since each page survives a limited number of writes.
+pub struct Eeprom {
+    chip: Chip,
+    lines: Lines,
+    /// The last values written to the output registers, which can't be read back.
+    sda: bool,
+    scl: bool,
+}
+
+impl Eeprom {
+    pub const fn new(chip: Chip, lines: Lines) -> Self {
+        Self {
+            chip,
+            lines,
+            sda: true,
+            scl: true,
+        }
+    }
+
+    #[inline]
+    pub const fn chip(&self) -> Chip {
+        self.chip
+    }
+
+    /// Reads `buf.len()` bytes starting at `addr`.
+    pub fn read(&mut self, addr: u16, buf: &mut [u8]) -> Result<(), Error> {
+        self.check_range(addr, buf.len())?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        let result = self.read_inner(addr, buf);
+        self.stop();
+        result
+    }
+
+    /// Writes `data` starting at `addr`, one page at a time.
+    pub fn write(&mut self, addr: u16, data: &[u8]) -> Result<(), Error> {
+        self.check_range(addr, data.len())?;
+
+        let page_size = self.chip.page_size();
+        let mut addr = addr;
+        let mut data = data;
+        while !data.is_empty() {
+            let len = ((page_size - addr % page_size) as usize).min(data.len());
+            let (page, rest) = data.split_at(len);
+            self.write_page(addr, page)?;
+            addr += len as u16;
+            data = rest;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn check_range(&self, addr: u16, len: usize) -> Result<(), Error> {
+        if addr as usize + len > self.chip.size() as usize {
+            return Err(Error::OutOfRange);
+        }
+        Ok(())
+    }
+
+    fn read_inner(&mut self, addr: u16, buf: &mut [u8]) -> Result<(), Error> {
+        // A dummy write sets the address, then a repeated start switches to reading.
+        self.address(addr)?;
+        self.start();
+        self.write_byte(self.device_addr(addr) | READ)?;
+
+        let last = buf.len() - 1;
+        for (i, byte) in buf.iter_mut().enumerate() {
+            *byte = self.read_byte(i != last);
+        }
+        Ok(())
+    }
+
+    /// Writes the bytes of one page that differ from what's already stored.
+    fn write_page(&mut self, addr: u16, data: &[u8]) -> Result<(), Error> {
+        let mut current = [0u8; 32];
+        let current = &mut current[..data.len()];
+        self.read(addr, current)?;
+
+        let Some(first) = data.iter().zip(current.iter()).position(|(new, old)| new != old) else {
+            return Ok(());
+        };
+        let last = data.iter().zip(current.iter()).rposition(|(new, old)| new != old).unwrap_or(first);
+        let addr = addr + first as u16;
+        let data = &data[first..=last];
+
+        let result = self.address(addr).and_then(|_| data.iter().try_for_each(|&byte| self.write_byte(byte)));
+        self.stop();
+        result?;
+        self.wait_for_write(addr)
+    }
+
+    /// Starts a write, sending the device and word address.
+    fn address(&mut self, addr: u16) -> Result<(), Error> {
+        self.start();
+        self.write_byte(self.device_addr(addr))?;
+        if self.chip.wide_address() {
+            self.write_byte((addr >> 8) as u8)?;
+        }
+        self.write_byte(addr as u8)
+    }
+
+    #[inline]
+    fn device_addr(&self, addr: u16) -> u8 {
+        if self.chip.wide_address() {
+            DEVICE_ADDR
+        } else {
+            // 24C04 to 24C16 take the bits above the first 256 bytes here.
+            DEVICE_ADDR | (((addr >> 8) as u8 & 0x07) << 1)
+        }
+    }
+
+    /// Polls the chip until it acknowledges again, which it doesn't do during a write cycle.
+    fn wait_for_write(&mut self, addr: u16) -> Result<(), Error> {
+        for _ in 0..WRITE_POLLS {
+            self.start();
+            let acked = self.write_byte(self.device_addr(addr)).is_ok();
+            self.stop();
+            if acked {
+                return Ok(());
+            }
+            delay_us(50);
+        }
+        Err(Error::NoAck)
+    }
+
+    fn set_lines(&mut self, sda: bool, scl: bool) {
+        self.sda = sda;
+        self.scl = scl;
+        let lines = self.lines;
+        let sda_bit = (sda as u8) << lines.sda_out_bit;
+        let scl_bit = (scl as u8) << lines.scl_bit;
+        unsafe {
+            if ptr::eq(lines.sda_out, lines.scl) {
+                ptr::write_volatile(lines.sda_out, sda_bit | scl_bit);
+            } else {
+                ptr::write_volatile(lines.sda_out, sda_bit);
+                ptr::write_volatile(lines.scl, scl_bit);
+            }
+        }
+        delay_us(HALF_PERIOD_US);
+    }
+
+    #[inline]
+    fn read_sda(&self) -> bool {
+        unsafe { ptr::read_volatile(self.lines.sda_in) & (1 << self.lines.sda_in_bit) != 0 }
+    }
+
+    fn start(&mut self) {
+        self.set_lines(true, self.scl);
+        self.set_lines(true, true);
+        self.set_lines(false, true);
+        self.set_lines(false, false);
+    }
+
+    fn stop(&mut self) {
+        self.set_lines(false, false);
+        self.set_lines(false, true);
+        self.set_lines(true, true);
+    }
+
+    /// Sends a byte, most significant bit first, and reads the acknowledge bit.
+    fn write_byte(&mut self, byte: u8) -> Result<(), Error> {
+        for bit in (0..8).rev() {
+            let sda = byte & (1 << bit) != 0;
+            self.set_lines(sda, false);
+            self.set_lines(sda, true);
+            self.set_lines(sda, false);
+        }
+
+        // Let go of SDA so the chip can pull it low.
+        self.set_lines(true, false);
+        self.set_lines(true, true);
+        let acked = !self.read_sda();
+        self.set_lines(true, false);
+        if acked { Ok(()) } else { Err(Error::NoAck) }
+    }
+
+    /// Reads a byte, then acknowledges it if more are wanted.
+    fn read_byte(&mut self, ack: bool) -> u8 {
+        let mut byte = 0;
+        for _ in 0..8 {
+            self.set_lines(true, true);
+            byte = (byte << 1) | self.read_sda() as u8;
+            self.set_lines(true, false);
+        }
+
+        self.set_lines(!ack, false);
+        self.set_lines(!ack, true);
+        self.set_lines(!ack, false);
+        byte
+    }
+}
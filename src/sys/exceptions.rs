@@ -0,0 +1,161 @@
+use core::ptr;
+
+use super::crash;
+
+/// The CPU exceptions that can have handlers set. Others go straight to a halt.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exception {
+    BusError = 0,
+    /// A word or long access at an odd address, other than the long branches the vector table
+    /// fixes up itself.
+    AddressError = 1,
+    IllegalInstruction = 2,
+    ZeroDivide = 3,
+}
+
+impl Exception {
+    const COUNT: usize = 4;
+
+    #[inline]
+    const fn from_u32(kind: u32) -> Self {
+        match kind {
+            0 => Exception::BusError,
+            1 => Exception::AddressError,
+            2 => Exception::IllegalInstruction,
+            _ => Exception::ZeroDivide,
+        }
+    }
+
+    /// Bus and address errors push an `Access` as well, and can't be resumed from.
+    #[inline]
+    pub const fn is_access_fault(self) -> bool {
+        matches!(self, Exception::BusError | Exception::AddressError)
+    }
+
+    #[inline]
+    pub(in crate::sys) const fn name(self) -> &'static [u8] {
+        match self {
+            Exception::BusError => b"BUS ERROR",
+            Exception::AddressError => b"ADDRESS ERROR",
+            Exception::IllegalInstruction => b"ILLEGAL INSTRUCTION",
+            Exception::ZeroDivide => b"DIVIDE BY ZERO",
+        }
+    }
+}
+
+/// D0 to D7 and A0 to A6, as they were when the exception was taken. Changes are put back if the
+/// handler returns.
+#[repr(C, packed(2))]
+#[derive(Debug, Clone, Copy)]
+pub struct Registers {
+    pub d: [u32; 8],
+    pub a: [u32; 7],
+}
+
+/// What the CPU saves about the access that caused a bus or address error.
+#[repr(C, packed(2))]
+#[derive(Debug, Clone, Copy)]
+pub struct Access {
+    /// The access's function code and direction.
+    pub status: u16,
+    pub address: u32,
+    /// The first word of the instruction that was running.
+    pub instruction: u16,
+}
+
+impl Access {
+    #[inline]
+    pub fn is_read(&self) -> bool {
+        self.status & 0x10 != 0
+    }
+
+    /// The function code, which is 1 or 5 for data and 2 or 6 for program accesses.
+    #[inline]
+    pub fn function_code(&self) -> u8 {
+        (self.status & 0x7) as u8
+    }
+}
+
+/// The status register and program counter the CPU returns to.
+#[repr(C, packed(2))]
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    pub sr: u16,
+    pub pc: u32,
+}
+
+/// An exception being handled.
+pub struct Fault<'a> {
+    pub exception: Exception,
+    pub registers: &'a mut Registers,
+    /// Only there for bus and address errors.
+    pub access: Option<&'a Access>,
+    pub frame: &'a mut Frame,
+}
+
+impl Fault<'_> {
+    /// The stack pointer from before the exception.
+    #[inline]
+    pub fn sp(&self) -> u32 {
+        (&raw const *self.frame).addr() as u32 + core::mem::size_of::<Frame>() as u32
+    }
+}
+
+/// A function run when an exception happens. Returning goes back to `fault.frame.pc`, which for an
+/// illegal instruction is the instruction itself, so the handler should move it on. Divides by
+/// zero go back to the instruction after the divide.
+pub type Handler = fn(&mut Fault<'_>);
+
+static mut HANDLERS: [Option<Handler>; Exception::COUNT] = [None; Exception::COUNT];
+
+/// Sets the function run when `exception` happens, or goes back to showing the crash screen.
+///
+/// The 68000 can't carry on after a bus or address error, so the crash screen is shown if their
+/// handlers return. Those handlers are for logging, or for resetting into a recovery screen.
+#[inline]
+pub fn set_handler(exception: Exception, handler: Option<Handler>) {
+    // Function pointers are written in one move, so there's no need to mask interrupts.
+    unsafe { ptr::write_volatile(&raw mut HANDLERS[exception as usize], handler) }
+}
+
+#[inline]
+pub fn handler(exception: Exception) -> Option<Handler> {
+    unsafe { ptr::read_volatile(&raw const HANDLERS[exception as usize]) }
+}
+
+/// Shows the crash screen for a fault, which is what happens when no handler is set. Handlers can
+/// call this for the faults they don't deal with themselves.
+pub fn show_crash_screen(fault: &Fault<'_>) -> ! {
+    crash::show(fault.exception.name(), fault.registers, fault.frame, fault.sp(), fault.access)
+}
+
+/// Called by the exception vectors, with the saved registers, which the exception's stack frame
+/// follows.
+#[no_mangle]
+unsafe extern "C" fn _exception(registers: *mut Registers, kind: u32) {
+    let exception = Exception::from_u32(kind);
+    let mut rest = registers.add(1).cast::<u8>();
+    let access = if exception.is_access_fault() {
+        let access = &*rest.cast::<Access>();
+        rest = rest.add(core::mem::size_of::<Access>());
+        Some(access)
+    } else {
+        None
+    };
+
+    let mut fault = Fault {
+        exception,
+        registers: &mut *registers,
+        access,
+        frame: &mut *rest.cast::<Frame>(),
+    };
+
+    match handler(exception) {
+        Some(handler) => handler(&mut fault),
+        None => show_crash_screen(&fault),
+    }
+    if exception.is_access_fault() {
+        show_crash_screen(&fault);
+    }
+}
@@ -127,10 +127,12 @@ where
 
 const ATAN_TABLE: &'static [u32] = include_bytes_aligned_as!(u32, "atan_u0f32.bin");
 const ATANH_TABLE: &'static [u32] = include_bytes_aligned_as!(u32, "atanh_u0f32.bin");
-// const EXPM1_TABLE: &'static [u32] = include_bytes_aligned_as!(u32, "expm1_u0f32.bin");
+/// e^(2^-i) - 1, from i = 1.
+const EXPM1_TABLE: &'static [u32] = include_bytes_aligned_as!(u32, "expm1_u0f32.bin");
 
 const INV_GAIN: U0F32 = U0F32::from_bits(0x9B74EDA8); // 0.607252935009
 const HYP_GAIN_M1: U0F32 = U0F32::from_bits(0x351E777E); // 0.20749613601
+const LN_2: U0F32 = U0F32::from_bits(0xB17217F8); // 0.69314718056
 
 #[inline]
 fn cordic_circular<T: FixedCordic>(mut x: T, mut y: T, mut z: T, vecmode: T) -> (T, T, T) {
@@ -157,10 +159,16 @@ fn cordic_circular<T: FixedCordic>(mut x: T, mut y: T, mut z: T, vecmode: T) ->
 #[inline]
 fn cordic_hyperbolic<T: FixedCordic>(mut x: T, mut y: T, mut z: T, vecmode: T) -> (T, T, T) {
     let mut i = 1u8;
-    let mut k = 3u8;
+    // Iterations 4, 13, 40 and so on are done twice, or the steps don't add up to every angle.
+    let mut repeat = 4u8;
 
     while i < T::FRAC_BITS {
-        let mut j = 0u8;
+        let mut j = if i == repeat {
+            repeat = repeat * 3 + 1;
+            0u8
+        } else {
+            1u8
+        };
 
         while j < 2 {
             if vecmode >= T::ZERO && y < vecmode || vecmode < T::ZERO && z >= T::ZERO {
@@ -175,11 +183,6 @@ fn cordic_hyperbolic<T: FixedCordic>(mut x: T, mut y: T, mut z: T, vecmode: T) -
                 z = z + T::from_u0f32(U0F32::from_bits(ATANH_TABLE[(i-1) as usize]));
             }
 
-            if k > 0 {
-                k -= 1;
-                break;
-            } else { k = 3; }
-
             j += 1;
         }
 
@@ -251,6 +254,118 @@ fn asin<T: FixedCordic>(mut val: T) -> T {
     theta
 }
 
+/// The length and angle of `(x, y)`, by rotating it onto the x axis.
+#[inline]
+fn to_polar<T: FixedCordic>(x: T, y: T) -> (T, T) {
+    // Vectoring only converges for x >= 0, so the left half is mirrored across the y axis.
+    let mirrored = x < T::ZERO;
+    let (len, _, angle) = cordic_circular(if mirrored { -x } else { x }, y, T::ZERO, T::ZERO);
+    let len = len * T::from_u0f32(INV_GAIN);
+
+    let angle = match (mirrored, y >= T::ZERO) {
+        (false, _) => angle,
+        (true, true) => T::PI - angle,
+        (true, false) => -T::PI - angle,
+    };
+    (len, angle)
+}
+
+/// Multiplies by 2^`n`.
+#[inline]
+fn scale_pow2<T: FixedCordic>(val: T, n: i8) -> T {
+    if n >= 0 { val << n as u8 } else { val >> (-n) as u8 }
+}
+
+#[inline]
+fn sqrt<T: FixedCordic>(val: T) -> T {
+    if val <= T::ZERO {
+        return T::ZERO;
+    }
+
+    // val = m * 4^n, with m in [0.25, 1), where vectoring converges.
+    let (mut m, mut n) = (val, 0i8);
+    while m >= T::ONE {
+        m = m >> 2;
+        n += 1;
+    }
+    while m < T::ONE >> 2 {
+        m = m << 2;
+        n -= 1;
+    }
+
+    // Vectoring leaves x at sqrt(x^2 - y^2), which is sqrt(m), times the gain.
+    let quarter = T::ONE >> 2;
+    let (x, _, _) = cordic_hyperbolic(m + quarter, m - quarter, T::ZERO, T::ZERO);
+    scale_pow2(x + x * T::from_u0f32(HYP_GAIN_M1), n)
+}
+
+#[inline]
+fn exp<T: FixedCordic>(val: T) -> T {
+    // val = n * ln 2 + r, with r in [0, ln 2).
+    let ln2 = T::from_u0f32(LN_2);
+    let int_bits = (T::BITS - T::FRAC_BITS) as i8;
+    let (mut r, mut n) = (val, 0i8);
+    while r < T::ZERO {
+        r += ln2;
+        n -= 1;
+        if n < -(T::FRAC_BITS as i8) {
+            return T::ZERO;
+        }
+    }
+    while r >= ln2 && n < int_bits {
+        r -= ln2;
+        n += 1;
+    }
+
+    // e^r is the product of e^(2^-i) over the bits of r.
+    let mut y = T::ONE;
+    let mut i = 1u8;
+    while i < T::FRAC_BITS {
+        let step = T::ONE >> i;
+        if r >= step {
+            r -= step;
+            y += y * T::from_u0f32(U0F32::from_bits(EXPM1_TABLE[(i - 1) as usize]));
+        }
+        i += 1;
+    }
+    // What's left is small enough that e^r is 1 + r.
+    y += y * r;
+
+    scale_pow2(y, n)
+}
+
+#[inline]
+fn ln<T: FixedCordic>(val: T) -> T {
+    if val <= T::ZERO {
+        return T::ZERO;
+    }
+
+    // val = m * 2^n, with m in [0.5, 1).
+    let (mut m, mut n) = (val, 0i8);
+    while m >= T::ONE {
+        m = m >> 1;
+        n += 1;
+    }
+    while m < T::ONE >> 1 {
+        m = m << 1;
+        n -= 1;
+    }
+
+    // ln m = 2 atanh((m - 1) / (m + 1)), which vectoring leaves in z.
+    let (_, _, z) = cordic_hyperbolic(m + T::ONE, m - T::ONE, T::ZERO, T::ZERO);
+    let ln2 = T::from_u0f32(LN_2);
+    let mut result = z << 1;
+    while n > 0 {
+        result += ln2;
+        n -= 1;
+    }
+    while n < 0 {
+        result -= ln2;
+        n += 1;
+    }
+    result
+}
+
 pub trait FixedCordicMath: FixedCordic {
     fn cordic_circular(x: Self, y: Self, z: Self, vecmode: Self) -> (Self, Self, Self) {
         cordic_circular(x, y, z, vecmode)
@@ -288,6 +403,37 @@ pub trait FixedCordicMath: FixedCordic {
     fn acos(self) -> Self {
         Self::FRAC_PI_2 - asin(self)
     }
+
+    /// The square root, or zero for anything that isn't positive.
+    fn sqrt(self) -> Self {
+        sqrt(self)
+    }
+
+    /// e to the power of `self`. Results too big for the type overflow.
+    fn exp(self) -> Self {
+        exp(self)
+    }
+
+    /// The natural logarithm, or zero for anything that isn't positive.
+    fn ln(self) -> Self {
+        ln(self)
+    }
+
+    /// The angle of the point `(x, self)`, from -pi to pi, in whichever quadrant it's in.
+    fn atan2(self, x: Self) -> Self {
+        to_polar(x, self).1
+    }
+
+    /// The length of `(self, y)`. Lengths past about 60% of the type's range overflow partway
+    /// through.
+    fn hypot(self, y: Self) -> Self {
+        to_polar(self, y).0
+    }
+
+    /// The length and angle of `(x, y)` together, for the cost of one of them.
+    fn to_polar(x: Self, y: Self) -> (Self, Self) {
+        to_polar(x, y)
+    }
 }
 
 impl<T: FixedCordic> FixedCordicMath for T {}
@@ -0,0 +1,185 @@
+use super::sram::SramGuard;
+
+/// A named bit in a `FlagSet`, such as an unlock or a story flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flag {
+    index: u16,
+    name: &'static str,
+}
+
+impl Flag {
+    pub const fn new(index: u16, name: &'static str) -> Self {
+        Self { index, name }
+    }
+
+    #[inline]
+    pub const fn index(&self) -> u16 {
+        self.index
+    }
+
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Find a flag in a registration table by its name.
+    pub fn lookup(table: &[Flag], name: &str) -> Option<Flag> {
+        table.iter().copied().find(|flag| flag.name == name)
+    }
+}
+
+/// Defines a module of named flags, along with an `ALL` table listing every one of them.
+///
+/// ```ignore
+/// define_flags! {
+///     pub mod story {
+///         MET_WIZARD = 0,
+///         GOT_SWORD = 1,
+///     }
+/// }
+///
+/// FLAGS.set(story::GOT_SWORD, true);
+/// ```
+#[macro_export]
+macro_rules! define_flags {
+    ($vis:vis mod $module:ident { $($flag:ident = $index:expr),* $(,)? }) => {
+        $vis mod $module {
+            $(pub const $flag: $crate::sys::flags::Flag = $crate::sys::flags::Flag::new($index, stringify!($flag));)*
+
+            pub const ALL: &[$crate::sys::flags::Flag] = &[$($flag),*];
+        }
+    };
+}
+
+/// A compact set of `BYTES * 8` flags, packed one bit per flag.
+#[derive(Clone, Copy)]
+pub struct FlagSet<const BYTES: usize> {
+    bits: [u8; BYTES],
+    on_change: Option<fn(Flag, bool)>,
+}
+
+impl<const BYTES: usize> FlagSet<BYTES> {
+    /// Marks the start of a flag set stored in SRAM.
+    const MAGIC: [u8; 2] = *b"FS";
+
+    /// The number of SRAM bytes taken up by a saved flag set.
+    pub const SAVE_SIZE: usize = Self::MAGIC.len() + BYTES + 1;
+
+    pub const fn new() -> Self {
+        Self {
+            bits: [0; BYTES],
+            on_change: None,
+        }
+    }
+
+    /// Registers a function to be called whenever a flag changes value.
+    pub const fn with_listener(mut self, on_change: fn(Flag, bool)) -> Self {
+        self.on_change = Some(on_change);
+        self
+    }
+
+    #[inline]
+    pub fn set_listener(&mut self, on_change: Option<fn(Flag, bool)>) {
+        self.on_change = on_change;
+    }
+
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        BYTES << 3
+    }
+
+    #[inline]
+    pub fn get(&self, flag: Flag) -> bool {
+        self.bits.get((flag.index >> 3) as usize).is_some_and(|byte| byte & (1 << (flag.index & 7)) != 0)
+    }
+
+    /// Sets the value of a flag, returning true if it changed.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the flag doesn't fit in this set.
+    #[inline]
+    pub fn set(&mut self, flag: Flag, value: bool) -> bool {
+        let byte = &mut self.bits[(flag.index >> 3) as usize];
+        let mask = 1u8 << (flag.index & 7);
+        let old = *byte & mask != 0;
+        if old == value {
+            return false;
+        }
+
+        *byte ^= mask;
+        if let Some(on_change) = self.on_change {
+            on_change(flag, value);
+        }
+        true
+    }
+
+    #[inline]
+    pub fn toggle(&mut self, flag: Flag) {
+        self.set(flag, !self.get(flag));
+    }
+
+    /// Clears every flag without raising change events.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.bits = [0; BYTES];
+    }
+
+    /// Counts how many flags are set, e.g. for a completion percentage.
+    pub fn count(&self) -> u16 {
+        self.bits.iter().map(|byte| byte.count_ones() as u16).sum()
+    }
+
+    /// Calls `f` for every flag in `table` that is set.
+    pub fn for_each_set(&self, table: &[Flag], mut f: impl FnMut(Flag)) {
+        for &flag in table {
+            if self.get(flag) {
+                f(flag);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8; BYTES] {
+        &self.bits
+    }
+
+    #[inline]
+    fn checksum(bits: &[u8; BYTES]) -> u8 {
+        bits.iter().fold(0xA5u8, |sum, &byte| sum.rotate_left(1) ^ byte)
+    }
+
+    /// Writes this set to SRAM at the byte offset `offset`, taking up `SAVE_SIZE` bytes.
+    pub fn save(&self, sram: &SramGuard, offset: u16) {
+        sram.write(offset, &Self::MAGIC);
+        sram.write(offset + Self::MAGIC.len() as u16, &self.bits);
+        sram.write_byte(offset + (Self::MAGIC.len() + BYTES) as u16, Self::checksum(&self.bits));
+    }
+
+    /// Reads a set back from SRAM at the byte offset `offset`.
+    ///
+    /// Returns false and leaves the set untouched if SRAM doesn't hold a valid save, which is
+    /// always the case on first boot. Change events aren't raised for loaded flags.
+    pub fn load(&mut self, sram: &SramGuard, offset: u16) -> bool {
+        let mut magic = [0u8; 2];
+        sram.read(offset, &mut magic);
+        if magic != Self::MAGIC {
+            return false;
+        }
+
+        let mut bits = [0u8; BYTES];
+        sram.read(offset + Self::MAGIC.len() as u16, &mut bits);
+        if sram.read_byte(offset + (Self::MAGIC.len() + BYTES) as u16) != Self::checksum(&bits) {
+            return false;
+        }
+
+        self.bits = bits;
+        true
+    }
+}
+
+impl<const BYTES: usize> Default for FlagSet<BYTES> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
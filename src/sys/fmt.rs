@@ -0,0 +1,267 @@
+use core::fmt;
+
+use fixed::types::{I16F16, I8F8, U16F16, U8F8};
+
+/// The longest an integer can get in decimal, which is `i32::MIN`.
+pub const INT_BUFFER_LEN: usize = 11;
+
+/// Divides a long by a word with a single `divu`, returning the quotient and remainder.
+///
+/// The quotient must fit in a word, otherwise the result is garbage.
+#[inline(always)]
+fn divu(n: u32, d: u16) -> (u16, u16) {
+    let out: u32;
+    unsafe {
+        core::arch::asm!(
+            "divu.w {d},{n}",
+            n = inout(reg_data) n => out,
+            d = in(reg_data) d,
+        );
+    }
+    (out as u16, (out >> 16) as u16)
+}
+
+/// Divides a word by ten with a multiply and a shift, which is exact for every `u16`.
+#[inline(always)]
+const fn div10(n: u16) -> u16 {
+    ((n as u32 * 0xCCCD) >> 19) as u16
+}
+
+/// Writes the digits of `n` so they end at `end`, returning where they start.
+#[inline]
+fn write_u16(buf: &mut [u8; INT_BUFFER_LEN], mut end: usize, mut n: u16) -> usize {
+    loop {
+        let q = div10(n);
+        end -= 1;
+        buf[end] = b'0' + (n - q * 10) as u8;
+        n = q;
+        if n == 0 {
+            return end;
+        }
+    }
+}
+
+/// Writes the digits of `n` so they end at `end`, returning where they start.
+///
+/// Longs are cut into groups of four digits, each needing two `divu`s, until what's left fits in a
+/// word.
+#[inline]
+fn write_u32(buf: &mut [u8; INT_BUFFER_LEN], mut end: usize, mut n: u32) -> usize {
+    while n > u16::MAX as u32 {
+        let (q_hi, r_hi) = divu(n >> 16, 10000);
+        let (q_lo, mut group) = divu(((r_hi as u32) << 16) | (n & 0xFFFF), 10000);
+        n = ((q_hi as u32) << 16) | q_lo as u32;
+
+        for _ in 0..4 {
+            let q = div10(group);
+            end -= 1;
+            buf[end] = b'0' + (group - q * 10) as u8;
+            group = q;
+        }
+    }
+    write_u16(buf, end, n as u16)
+}
+
+/// An integer that can be printed with `IntBuffer` or `Int`.
+pub trait Integer: Copy {
+    /// Writes the digits of the value's magnitude to the end of `buf`, returning where they start
+    /// and whether the value is negative.
+    fn write_digits(self, buf: &mut [u8; INT_BUFFER_LEN]) -> (usize, bool);
+}
+
+macro_rules! impl_integer {
+    ($($t:ty => $write:ident as $u:ty),* $(,)?) => {
+        $(
+            impl Integer for $t {
+                #[inline]
+                fn write_digits(self, buf: &mut [u8; INT_BUFFER_LEN]) -> (usize, bool) {
+                    (($write)(buf, INT_BUFFER_LEN, self.unsigned_abs() as $u), self < 0)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_unsigned_integer {
+    ($($t:ty => $write:ident as $u:ty),* $(,)?) => {
+        $(
+            impl Integer for $t {
+                #[inline]
+                fn write_digits(self, buf: &mut [u8; INT_BUFFER_LEN]) -> (usize, bool) {
+                    (($write)(buf, INT_BUFFER_LEN, self as $u), false)
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(i8 => write_u16 as u16, i16 => write_u16 as u16, i32 => write_u32 as u32);
+impl_unsigned_integer!(u8 => write_u16 as u16, u16 => write_u16 as u16, u32 => write_u32 as u32, usize => write_u32 as u32);
+
+/// A buffer for formatting integers as decimal text, without going through `core::fmt`.
+///
+/// ```ignore
+/// let mut buf = IntBuffer::new();
+/// draw_text(buf.format(score));
+/// ```
+pub struct IntBuffer {
+    bytes: [u8; INT_BUFFER_LEN],
+}
+
+impl IntBuffer {
+    #[inline]
+    pub const fn new() -> Self {
+        Self { bytes: [0; INT_BUFFER_LEN] }
+    }
+
+    /// Formats `n`, returning the text, which lives in the buffer.
+    #[inline]
+    pub fn format<I: Integer>(&mut self, n: I) -> &str {
+        let (mut start, negative) = n.write_digits(&mut self.bytes);
+        if negative {
+            start -= 1;
+            self.bytes[start] = b'-';
+        }
+        unsafe { core::str::from_utf8_unchecked(&self.bytes[start..]) }
+    }
+}
+
+impl Default for IntBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Prints an integer using the fast path, while still honouring widths, fill and `+` flags.
+#[derive(Clone, Copy)]
+pub struct Int<I: Integer>(pub I);
+
+impl<I: Integer> fmt::Display for Int<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut buf = [0u8; INT_BUFFER_LEN];
+        let (start, negative) = self.0.write_digits(&mut buf);
+        f.pad_integral(!negative, "", unsafe { core::str::from_utf8_unchecked(&buf[start..]) })
+    }
+}
+
+/// The most decimal places `Decimal` will print.
+pub const MAX_PLACES: u8 = 16;
+
+/// A fixed-point type that can be printed with `Decimal`.
+pub trait FixedParts: Copy {
+    const FRAC_BITS: u8;
+
+    /// Splits the value into its sign, the magnitude of its integer part, and the magnitude of its
+    /// fractional bits.
+    fn parts(self) -> (bool, u16, u32);
+}
+
+impl FixedParts for I8F8 {
+    const FRAC_BITS: u8 = 8;
+
+    #[inline]
+    fn parts(self) -> (bool, u16, u32) {
+        let bits = self.to_bits();
+        let mag = bits.unsigned_abs();
+        (bits < 0, mag >> 8, (mag & 0xFF) as u32)
+    }
+}
+
+impl FixedParts for U8F8 {
+    const FRAC_BITS: u8 = 8;
+
+    #[inline]
+    fn parts(self) -> (bool, u16, u32) {
+        let bits = self.to_bits();
+        (false, bits >> 8, (bits & 0xFF) as u32)
+    }
+}
+
+impl FixedParts for I16F16 {
+    const FRAC_BITS: u8 = 16;
+
+    #[inline]
+    fn parts(self) -> (bool, u16, u32) {
+        let bits = self.to_bits();
+        let mag = bits.unsigned_abs();
+        (bits < 0, (mag >> 16) as u16, mag & 0xFFFF)
+    }
+}
+
+impl FixedParts for U16F16 {
+    const FRAC_BITS: u8 = 16;
+
+    #[inline]
+    fn parts(self) -> (bool, u16, u32) {
+        let bits = self.to_bits();
+        (false, (bits >> 16) as u16, bits & 0xFFFF)
+    }
+}
+
+/// Prints a fixed-point value in decimal without allocating, and without any 32 bit divisions.
+///
+/// The number of decimal places is taken from the formatter's precision if there is one, so
+/// `{:.3}` prints three places. Extra places are truncated, not rounded. Width, fill, alignment
+/// and `+` work as they do for integers.
+#[derive(Clone, Copy)]
+pub struct Decimal<F: FixedParts> {
+    value: F,
+    places: u8,
+}
+
+impl<F: FixedParts> Decimal<F> {
+    #[inline]
+    pub const fn new(value: F, places: u8) -> Self {
+        Self { value, places }
+    }
+}
+
+/// Shorthand for `Decimal::new`.
+#[inline]
+pub const fn decimal<F: FixedParts>(value: F, places: u8) -> Decimal<F> {
+    Decimal::new(value, places)
+}
+
+impl<F: FixedParts> fmt::Display for Decimal<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let places = f.precision().map_or(self.places, |p| p.min(MAX_PLACES as usize) as u8).min(MAX_PLACES);
+        let (negative, int, mut frac) = self.value.parts();
+
+        // 5 integer digits, point, and the fraction. The sign is left to `pad_integral`.
+        let mut buf = [0u8; 6 + MAX_PLACES as usize];
+        let mut len = 0;
+
+        let mut digits = [0u8; INT_BUFFER_LEN];
+        let start = write_u16(&mut digits, INT_BUFFER_LEN, int);
+        buf[..INT_BUFFER_LEN - start].copy_from_slice(&digits[start..]);
+        len += INT_BUFFER_LEN - start;
+
+        // Only a number with a digit that isn't 0 shows its sign, so -0.3 to no places is "0".
+        let mut nonzero = int != 0;
+        if places > 0 {
+            buf[len] = b'.';
+            len += 1;
+
+            // Each digit is the integer part of the fraction times ten.
+            let mask = (1u32 << F::FRAC_BITS) - 1;
+            for _ in 0..places {
+                frac = (frac << 3) + (frac << 1);
+                let digit = (frac >> F::FRAC_BITS) as u8;
+                nonzero |= digit != 0;
+                buf[len] = b'0' + digit;
+                len += 1;
+                frac &= mask;
+            }
+        }
+
+        let digits = unsafe { core::str::from_utf8_unchecked(&buf[..len]) };
+        f.pad_integral(!(negative && nonzero), "", digits)
+    }
+}
+
+impl<F: FixedParts> fmt::Debug for Decimal<F> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
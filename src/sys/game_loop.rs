@@ -0,0 +1,108 @@
+use fixed::types::U16F16;
+
+use super::io::manager::PadInput;
+use super::io::{P1_CONTROLLER, P2_CONTROLLER};
+use super::timing;
+use super::vdp::{DMACommand, PlaneBuffer, Scroller, VDP};
+
+/// Something kept in RAM and sent to the VDP once a frame, such as a sprite table or scroll values.
+pub trait Commit {
+    /// Queues whatever has changed to be sent during the next vblank. Anything that doesn't fit in
+    /// the DMA queue stays marked, to be sent by a later commit.
+    fn commit(&mut self) -> Result<(), DMACommand>;
+}
+
+impl Commit for Scroller {
+    #[inline]
+    fn commit(&mut self) -> Result<(), DMACommand> {
+        self.flush()
+    }
+}
+
+impl Commit for PlaneBuffer {
+    #[inline]
+    fn commit(&mut self) -> Result<(), DMACommand> {
+        self.flush();
+        Ok(())
+    }
+}
+
+/// What `run_game_loop` passes to each update.
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    /// The number of updates before this one.
+    pub count: u32,
+    /// The number of vblanks since the last update. This is 1 unless the last update ran long.
+    pub elapsed: u32,
+    /// The time since the last update, in seconds.
+    pub delta: U16F16,
+    /// Player 1's pad, as polled at the last vblank.
+    pub p1: PadInput,
+    pub p2: PadInput,
+}
+
+impl Frame {
+    /// Returns true if the last update took longer than a frame, and vblanks were missed.
+    #[inline]
+    pub fn lagged(&self) -> bool {
+        self.elapsed > 1
+    }
+
+    /// Queues `shadow` to be sent in the vblank at the end of this frame. Call this for each
+    /// sprite table, scroller or plane buffer once it's been updated.
+    ///
+    /// The shadow is sent straight from where it is at vblank, so it has to live outside `update`,
+    /// in a static or in the function that calls `run_game_loop`.
+    #[inline]
+    pub fn commit<C: Commit + ?Sized>(&self, shadow: &mut C) -> Result<(), DMACommand> {
+        shadow.commit()
+    }
+}
+
+/// Runs `update` once a frame, forever.
+///
+/// Each frame, the pads polled at the last vblank are passed to `update` along with the frame
+/// count and time. Once it returns, deferred frees are drained and the loop waits for vblank,
+/// where everything `update` committed is sent, along with the palette and VSRAM shadows in
+/// `vdp::shadow`. The screen is only ever changed between frames, so nothing half-drawn is shown.
+///
+/// ```ignore
+/// static mut SPRITES: SpriteTable = SpriteTable::new(VRAMAddress::from_word_addr(0x7C00));
+///
+/// fn run() -> ! {
+///     let sprites = unsafe { &mut *(&raw mut SPRITES) };
+///     let mut player = Player::new();
+///     sys::run_game_loop(|frame| {
+///         player.update(frame.p1, frame.delta);
+///         sprites.clear();
+///         player.draw(sprites);
+///         let _ = frame.commit(sprites);
+///     })
+/// }
+/// ```
+pub fn run_game_loop(mut update: impl FnMut(Frame)) -> ! {
+    let mut count = 0u32;
+    let mut last = timing::elapsed_frames();
+    loop {
+        let now = timing::elapsed_frames();
+        let elapsed = now.wrapping_sub(last).max(1);
+        last = now;
+
+        let (p1, p2) = super::with_cs::<1, 7, _>(|cs| {
+            let p1 = P1_CONTROLLER.borrow(cs).get();
+            let p2 = P2_CONTROLLER.borrow(cs).get();
+            (PadInput::new(p1.buttons(), p1.previous()), PadInput::new(p2.buttons(), p2.previous()))
+        });
+        update(Frame {
+            count,
+            elapsed,
+            delta: timing::frames_to_seconds(elapsed),
+            p1,
+            p2,
+        });
+        count = count.wrapping_add(1);
+
+        super::drain_deferred_frees();
+        VDP::wait_for_vblank(None);
+    }
+}
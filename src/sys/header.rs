@@ -0,0 +1,288 @@
+use core::ptr;
+
+extern "C" {
+    /// The last byte of the ROM image, from the linker script.
+    static _rom_end: u8;
+}
+
+/// Where the header sits in ROM, right after the vector table.
+const HEADER_ADDR: usize = 0x100;
+/// Where the checksummed part of the ROM starts.
+const CHECKSUM_START: usize = 0x200;
+
+/// Which regions a ROM runs in, written to the header as "J", "U" and "E".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region(u8);
+
+impl Region {
+    pub const JAPAN: Self = Self(0x1);
+    pub const AMERICAS: Self = Self(0x2);
+    pub const EUROPE: Self = Self(0x4);
+    pub const ALL: Self = Self(0x7);
+
+    #[inline]
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    #[inline]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// How the cartridge's SRAM is wired, as declared in the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SramKind {
+    /// Battery backed, on the odd bytes only. This is how nearly every cartridge does it.
+    OddBytes,
+    /// Battery backed, on the even bytes only.
+    EvenBytes,
+    /// Battery backed, on both bytes of each word.
+    Words,
+}
+
+impl SramKind {
+    #[inline]
+    const fn type_byte(self) -> u8 {
+        match self {
+            SramKind::OddBytes => 0xF8,
+            SramKind::EvenBytes => 0xF0,
+            SramKind::Words => 0xE0,
+        }
+    }
+}
+
+/// Everything that goes into the ROM header, built up with the `with_*` methods and placed in ROM
+/// by `entry!`. Text fields are truncated or padded with spaces to fit.
+#[derive(Debug, Clone, Copy)]
+pub struct RomConfig {
+    pub system: &'static str,
+    pub copyright: &'static str,
+    pub domestic_title: &'static str,
+    pub overseas_title: &'static str,
+    pub serial: &'static str,
+    /// The supported devices, one letter each: "J" for a 3 button pad, "6" for a 6 button pad, "M"
+    /// for a mouse, "4" for a multitap and so on.
+    pub devices: &'static str,
+    pub region: Region,
+    /// SRAM's wiring, first byte and last byte.
+    pub sram: Option<(SramKind, u32, u32)>,
+}
+
+impl RomConfig {
+    pub const DEFAULT: Self = Self {
+        system: "SEGA GENESIS/MD",
+        copyright: "(C)SPPY 2024.APR",
+        domestic_title: "GENESIS TESTER",
+        overseas_title: "GENESIS TESTER",
+        serial: "GM 11111110-00",
+        devices: "J6",
+        region: Region::ALL,
+        sram: None,
+    };
+
+    /// Uses the same title in every region.
+    pub const fn with_title(mut self, title: &'static str) -> Self {
+        self.domestic_title = title;
+        self.overseas_title = title;
+        self
+    }
+
+    pub const fn with_domestic_title(mut self, title: &'static str) -> Self {
+        self.domestic_title = title;
+        self
+    }
+
+    pub const fn with_overseas_title(mut self, title: &'static str) -> Self {
+        self.overseas_title = title;
+        self
+    }
+
+    /// Sets the copyright line, conventionally "(C)XXXX YYYY.MMM" with a 4 character company code.
+    pub const fn with_copyright(mut self, copyright: &'static str) -> Self {
+        self.copyright = copyright;
+        self
+    }
+
+    /// Sets the product type and serial number, conventionally "GM XXXXXXXX-YY".
+    pub const fn with_serial(mut self, serial: &'static str) -> Self {
+        self.serial = serial;
+        self
+    }
+
+    pub const fn with_devices(mut self, devices: &'static str) -> Self {
+        self.devices = devices;
+        self
+    }
+
+    pub const fn with_region(mut self, region: Region) -> Self {
+        self.region = region;
+        self
+    }
+
+    /// Declares SRAM covering `start..=end`, which emulators use to decide whether to save it.
+    pub const fn with_sram(mut self, kind: SramKind, start: u32, end: u32) -> Self {
+        self.sram = Some((kind, start, end));
+        self
+    }
+}
+
+/// The ROM header, exactly as it's laid out at 0x100.
+#[repr(C)]
+pub struct RomHeader {
+    system: [u8; 16],
+    copyright: [u8; 16],
+    domestic_title: [u8; 48],
+    overseas_title: [u8; 48],
+    serial: [u8; 14],
+    checksum: u16,
+    devices: [u8; 16],
+    rom_start: u32,
+    rom_end: *const u8,
+    ram_start: u32,
+    ram_end: u32,
+    sram: [u8; 12],
+    modem: [u8; 12],
+    notes: [u8; 40],
+    region: [u8; 16],
+}
+
+// The only pointer is to the end of ROM.
+unsafe impl Sync for RomHeader {}
+
+#[cfg(target_pointer_width = "32")]
+const _: () = assert!(core::mem::size_of::<RomHeader>() == 0x100);
+
+/// Copies `text` into a space padded field.
+const fn field<const N: usize>(text: &str) -> [u8; N] {
+    let text = text.as_bytes();
+    let mut out = [b' '; N];
+    let mut i = 0;
+    while i < N && i < text.len() {
+        out[i] = text[i];
+        i += 1;
+    }
+    out
+}
+
+impl RomHeader {
+    /// Lays out a header. The checksum is left as 0 for the build to fill in once the ROM is linked.
+    pub const fn new(config: &RomConfig) -> Self {
+        let mut sram = [b' '; 12];
+        if let Some((kind, start, end)) = config.sram {
+            let start = start.to_be_bytes();
+            let end = end.to_be_bytes();
+            sram = [b'R', b'A', kind.type_byte(), 0x20, start[0], start[1], start[2], start[3], end[0], end[1], end[2], end[3]];
+        }
+
+        let mut region = [b' '; 16];
+        let mut len = 0;
+        let codes = [(Region::JAPAN, b'J'), (Region::AMERICAS, b'U'), (Region::EUROPE, b'E')];
+        let mut i = 0;
+        while i < codes.len() {
+            if config.region.contains(codes[i].0) {
+                region[len] = codes[i].1;
+                len += 1;
+            }
+            i += 1;
+        }
+
+        Self {
+            system: field(config.system),
+            copyright: field(config.copyright),
+            domestic_title: field(config.domestic_title),
+            overseas_title: field(config.overseas_title),
+            serial: field(config.serial),
+            checksum: 0,
+            devices: field(config.devices),
+            rom_start: 0,
+            rom_end: &raw const _rom_end,
+            ram_start: 0xFF0000,
+            ram_end: 0xFFFFFF,
+            sram,
+            modem: [b' '; 12],
+            notes: [b' '; 40],
+            region,
+        }
+    }
+
+    #[inline]
+    pub fn domestic_title(&self) -> &[u8] {
+        &self.domestic_title
+    }
+
+    #[inline]
+    pub fn overseas_title(&self) -> &[u8] {
+        &self.overseas_title
+    }
+
+    #[inline]
+    pub fn serial(&self) -> &[u8] {
+        &self.serial
+    }
+
+    /// The checksum recorded in the header.
+    #[inline]
+    pub fn checksum(&self) -> u16 {
+        self.checksum
+    }
+
+    /// The regions the header says the ROM runs in. Letters other than "J", "U" and "E" are
+    /// ignored.
+    pub fn region(&self) -> Region {
+        let mut region = Region(0);
+        for &code in &self.region {
+            region = match code {
+                b'J' => region.union(Region::JAPAN),
+                b'U' => region.union(Region::AMERICAS),
+                b'E' => region.union(Region::EUROPE),
+                _ => region,
+            };
+        }
+        region
+    }
+
+    /// The size of the ROM, in bytes.
+    #[inline]
+    pub fn rom_size(&self) -> usize {
+        self.rom_end as usize + 1
+    }
+}
+
+/// The header of the running ROM.
+#[inline]
+pub fn header() -> &'static RomHeader {
+    unsafe { &*(HEADER_ADDR as *const RomHeader) }
+}
+
+/// Sums every word of the ROM after the header, which is what the header's checksum should hold.
+///
+/// This reads the whole ROM, so it takes a few frames for larger ones.
+pub fn rom_checksum() -> u16 {
+    let end = header().rom_size() & !1;
+    let mut sum = 0u16;
+    let mut addr = CHECKSUM_START;
+    while addr < end {
+        sum = sum.wrapping_add(unsafe { ptr::read_volatile(addr as *const u16) });
+        addr += 2;
+    }
+    sum
+}
+
+/// Returns true if the ROM's checksum matches its header, which is a cheap check for a bad dump or
+/// a dirty cartridge connector.
+#[inline]
+pub fn rom_checksum_valid() -> bool {
+    rom_checksum() == header().checksum()
+}
+
+/// Places a ROM header built from a `RomConfig` in ROM. `entry!` does this for you.
+#[macro_export]
+macro_rules! rom_header {
+    ($config:expr) => {
+        #[used]
+        #[link_section = ".text.hdr"]
+        static _ROM_HEADER: $crate::sys::header::RomHeader = $crate::sys::header::RomHeader::new(&$config);
+    };
+}
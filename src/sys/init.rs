@@ -0,0 +1,189 @@
+use core::ptr;
+
+/// Written to RAM the linker leaves alone once the console has started, so a reset can tell it's
+/// not a power on. "MDRS".
+const BOOT_MAGIC: u32 = 0x4D445253;
+
+extern "C" {
+    static mut _noinit_start: u8;
+    static mut _noinit_end: u8;
+}
+
+/// Kept across resets, in the `.noinit` section, which `_init` doesn't clear.
+#[link_section = ".noinit"]
+static mut MAGIC: [u32; 2] = [0; 2];
+#[link_section = ".noinit"]
+static mut RESETS: u16 = 0;
+
+static mut REPORT: BootReport = BootReport {
+    kind: BootKind::Cold,
+    resets: 0,
+    #[cfg(feature = "boot-memtest")]
+    memtest: MemTest::PASSED,
+};
+
+/// How the console came to be running the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootKind {
+    /// The console was turned on. RAM held whatever it powered up with.
+    Cold,
+    /// The reset button was pressed, and RAM kept what the program left in it.
+    Warm,
+}
+
+/// What `_init` found when the program started.
+#[derive(Debug, Clone, Copy)]
+pub struct BootReport {
+    kind: BootKind,
+    resets: u16,
+    #[cfg(feature = "boot-memtest")]
+    memtest: MemTest,
+}
+
+impl BootReport {
+    #[inline]
+    pub const fn kind(&self) -> BootKind {
+        self.kind
+    }
+
+    /// Returns true after a reset, which a game can use to skip its intro.
+    #[inline]
+    pub const fn is_warm(&self) -> bool {
+        matches!(self.kind, BootKind::Warm)
+    }
+
+    /// The number of resets since the console was turned on.
+    #[inline]
+    pub const fn resets(&self) -> u16 {
+        self.resets
+    }
+
+    #[cfg(feature = "boot-memtest")]
+    #[inline]
+    pub const fn memtest(&self) -> &MemTest {
+        &self.memtest
+    }
+}
+
+/// The results of the `boot-memtest` feature's checks of work RAM and VRAM.
+#[cfg(feature = "boot-memtest")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemTest {
+    /// The number of longs of work RAM that didn't read back what was written.
+    pub ram_errors: u16,
+    pub first_bad_ram: Option<u32>,
+    /// The number of words of VRAM that didn't read back what was written.
+    pub vram_errors: u16,
+    pub first_bad_vram: Option<u16>,
+}
+
+#[cfg(feature = "boot-memtest")]
+impl MemTest {
+    const PASSED: Self = Self { ram_errors: 0, first_bad_ram: None, vram_errors: 0, first_bad_vram: None };
+
+    #[inline]
+    pub const fn passed(&self) -> bool {
+        self.ram_errors == 0 && self.vram_errors == 0
+    }
+}
+
+/// What `_init` found when the program started.
+#[inline]
+pub fn report() -> BootReport {
+    unsafe { ptr::read_volatile(&raw const REPORT) }
+}
+
+/// The part of RAM that's kept across resets.
+#[cfg(any(feature = "boot-memtest", all(feature = "ram-fill", debug_assertions)))]
+#[inline]
+pub(super) fn noinit_range() -> core::ops::Range<usize> {
+    (&raw const _noinit_start).addr()..(&raw const _noinit_end).addr()
+}
+
+/// Reads the reset count from RAM kept across resets, before anything's been set up, and returns
+/// it if this is a reset.
+#[inline(always)]
+pub(super) unsafe fn check_warm() -> Option<u16> {
+    let magic = ptr::read_volatile(&raw const MAGIC);
+    (magic == [BOOT_MAGIC, !BOOT_MAGIC]).then(|| ptr::read_volatile(&raw const RESETS))
+}
+
+/// Marks RAM so the next reset is seen as one, and saves the report. Runs once .bss is set up.
+#[inline(always)]
+pub(super) unsafe fn finish(warm: Option<u16>, #[cfg(feature = "boot-memtest")] memtest: MemTest) {
+    let resets = warm.map_or(0, |resets| resets.wrapping_add(1));
+    ptr::write_volatile(&raw mut MAGIC, [BOOT_MAGIC, !BOOT_MAGIC]);
+    ptr::write_volatile(&raw mut RESETS, resets);
+    ptr::write_volatile(&raw mut REPORT, BootReport {
+        kind: if warm.is_some() { BootKind::Warm } else { BootKind::Cold },
+        resets,
+        #[cfg(feature = "boot-memtest")]
+        memtest,
+    });
+}
+
+/// Tests work RAM from its start up to just below the stack, leaving out the RAM kept across
+/// resets, and leaves it cleared. This has to run before .data is set up.
+///
+/// Each long is written with a pattern made from its own address, which catches address lines
+/// that are stuck or shorted as well as bad bits, then checked and written with the inverse, then
+/// checked again.
+#[cfg(feature = "boot-memtest")]
+#[inline(always)]
+pub(super) unsafe fn test_ram(test: &mut MemTest) {
+    use super::layout::RAM_START;
+
+    /// Room left for `_init`'s own stack frame.
+    const STACK_MARGIN: usize = 0x100;
+    const PATTERN: u32 = 0x55AA55AA;
+
+    let sp: usize;
+    core::arch::asm!("move.l %sp,{sp}", sp = out(reg_addr) sp);
+    let end = (sp - STACK_MARGIN) & !3;
+    let skip = noinit_range();
+    let longs = || (RAM_START..end).step_by(4).filter(|addr| !skip.contains(addr));
+
+    for addr in longs() {
+        ptr::write_volatile(addr as *mut u32, addr as u32 ^ PATTERN);
+    }
+    for pass in [PATTERN, !PATTERN] {
+        for addr in longs() {
+            let expected = addr as u32 ^ pass;
+            if ptr::read_volatile(addr as *const u32) != expected {
+                test.ram_errors = test.ram_errors.saturating_add(1);
+                test.first_bad_ram.get_or_insert(addr as u32);
+            }
+            ptr::write_volatile(addr as *mut u32, if pass == PATTERN { !expected } else { 0 });
+        }
+    }
+}
+
+/// Tests the whole of VRAM the same way as `test_ram`, and clears it, CRAM and VSRAM.
+#[cfg(feature = "boot-memtest")]
+pub(super) fn test_vdp(test: &mut MemTest) {
+    use super::vdp::{Address, Reader, VRAMAddress, WordCmd, Writer};
+
+    const VRAM_WORDS: u32 = 0x8000;
+    const PATTERN: u16 = 0x55AA;
+
+    // Mode 5 with the display off, so VRAM can be written at full speed.
+    WordCmd::set_reg(1, 0x04).execute();
+    let start = Address::VRAM(VRAMAddress::from_word_addr(0));
+    let words = || (0..VRAM_WORDS).map(|word| word as u16);
+
+    Writer::new(start).with_autoinc(2).write_iter::<[u16]>(words().map(|word| [word ^ PATTERN]));
+    for pass in [PATTERN, !PATTERN] {
+        for word in words() {
+            let addr = Address::VRAM(VRAMAddress::from_word_addr(word));
+            let expected = word ^ pass;
+            if Reader::new(addr).read_word() != expected {
+                test.vram_errors = test.vram_errors.saturating_add(1);
+                test.first_bad_vram.get_or_insert(word);
+            }
+            Writer::new(addr).write::<[u16]>([if pass == PATTERN { !expected } else { 0 }]);
+        }
+    }
+
+    Writer::new(Address::CRAM(0)).write::<[u16]>([0; 64]);
+    Writer::new(Address::VSRAM(0)).write::<[u16]>([0; 40]);
+}
@@ -0,0 +1,197 @@
+use core::cell;
+
+use critical_section as cs;
+
+/// The CRC-16/CCITT lookup table, generated at compile time.
+const CRC16_TABLE: [u16; 256] = const {
+    let mut table = [0u16; 256];
+    let mut i = 0usize;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// The initial value for `crc16_update`.
+pub const CRC16_INIT: u16 = 0xFFFF;
+
+/// Feeds `data` into a running CRC-16/CCITT.
+#[inline]
+pub fn crc16_update(mut crc: u16, data: &[u8]) -> u16 {
+    for &byte in data {
+        crc = (crc << 8) ^ CRC16_TABLE[(((crc >> 8) as u8) ^ byte) as usize];
+    }
+    crc
+}
+
+/// Computes the CRC-16/CCITT of `data`.
+#[inline]
+pub fn crc16(data: &[u8]) -> u16 {
+    crc16_update(CRC16_INIT, data)
+}
+
+/// The maximum number of regions that can be watched at once.
+pub const MAX_REGIONS: usize = 8;
+
+#[derive(Clone, Copy)]
+struct Region {
+    data: &'static [u8],
+    expected: u16,
+}
+
+struct Checker {
+    regions: [Option<Region>; MAX_REGIONS],
+    hook: Option<fn(usize)>,
+    chunk: u16,
+    interval: u8,
+    countdown: u8,
+    cursor: usize,
+    offset: usize,
+    running: u16,
+}
+
+static CHECKER: cs::Mutex<cell::RefCell<Checker>> = cs::Mutex::new(cell::RefCell::new(Checker {
+    regions: [None; MAX_REGIONS],
+    hook: None,
+    chunk: 256,
+    interval: 1,
+    countdown: 1,
+    cursor: 0,
+    offset: 0,
+    running: CRC16_INIT,
+}));
+
+/// Starts watching a region of ROM or RAM, using its current contents as the reference.
+///
+/// Returns the region's index, which is what gets passed to the mismatch hook, or `None` if
+/// `MAX_REGIONS` regions are already being watched.
+pub fn watch(data: &'static [u8]) -> Option<usize> {
+    let expected = crc16(data);
+    super::with_cs::<1, 7, _>(|cs| {
+        let mut checker = CHECKER.borrow_ref_mut(cs);
+        let index = checker.regions.iter().position(Option::is_none)?;
+        checker.regions[index] = Some(Region { data, expected });
+        Some(index)
+    })
+}
+
+/// Stops watching a region.
+pub fn unwatch(index: usize) {
+    super::with_cs::<1, 7, _>(|cs| {
+        let mut checker = CHECKER.borrow_ref_mut(cs);
+        if let Some(region) = checker.regions.get_mut(index) {
+            *region = None;
+        }
+        if checker.cursor == index {
+            checker.offset = 0;
+            checker.running = CRC16_INIT;
+        }
+    })
+}
+
+/// Takes the current contents of a watched region as its new reference, after a legitimate change
+/// to watched RAM.
+pub fn rebaseline(index: usize) {
+    super::with_cs::<1, 7, _>(|cs| {
+        let mut checker = CHECKER.borrow_ref_mut(cs);
+        if let Some(Some(region)) = checker.regions.get_mut(index) {
+            region.expected = crc16(region.data);
+        }
+        if checker.cursor == index {
+            checker.offset = 0;
+            checker.running = CRC16_INIT;
+        }
+    })
+}
+
+/// Sets the function called with a region's index whenever its contents don't match the reference.
+#[inline]
+pub fn set_hook(hook: fn(usize)) {
+    super::with_cs::<1, 7, _>(|cs| CHECKER.borrow_ref_mut(cs).hook = Some(hook))
+}
+
+/// Configures how much checking `tick` does: `chunk` bytes every `interval` ticks.
+///
+/// Checks are spread out over several frames so they never take a noticeable amount of time.
+pub fn configure(chunk: u16, interval: u8) {
+    super::with_cs::<1, 7, _>(|cs| {
+        let mut checker = CHECKER.borrow_ref_mut(cs);
+        checker.chunk = chunk.max(1);
+        checker.interval = interval.max(1);
+        checker.countdown = checker.interval;
+    })
+}
+
+/// Advances the integrity check. This should be called once per frame from the main loop.
+pub fn tick() {
+    // Pick the next chunk to check, and where the check has got to.
+    let next = super::with_cs::<1, 7, _>(|cs| {
+        let mut checker = CHECKER.borrow_ref_mut(cs);
+
+        checker.countdown -= 1;
+        if checker.countdown != 0 {
+            return None;
+        }
+        checker.countdown = checker.interval;
+
+        // Find the next region still being watched.
+        let mut tries = 0;
+        let region = loop {
+            if let Some(region) = checker.regions[checker.cursor] {
+                break region;
+            }
+            checker.cursor = (checker.cursor + 1) % MAX_REGIONS;
+            checker.offset = 0;
+            checker.running = CRC16_INIT;
+            tries += 1;
+            if tries == MAX_REGIONS {
+                return None;
+            }
+        };
+
+        Some((checker.cursor, region, checker.offset, checker.running, checker.chunk))
+    });
+    let Some((index, region, offset, running, chunk)) = next else { return };
+
+    // The CRC itself is worked out with interrupts on, so it never holds up the vblank handler.
+    let end = (offset + chunk as usize).min(region.data.len());
+    let running = crc16_update(running, &region.data[offset..end]);
+
+    let mismatch = super::with_cs::<1, 7, _>(|cs| {
+        let mut checker = CHECKER.borrow_ref_mut(cs);
+
+        // If the region was unwatched or rebaselined meanwhile, this chunk is out of date.
+        let current = match checker.regions[index] {
+            Some(current) if checker.cursor == index
+                && checker.offset == offset
+                && core::ptr::eq(current.data, region.data) => current,
+            _ => return None,
+        };
+
+        checker.running = running;
+        checker.offset = end;
+
+        if end < region.data.len() {
+            return None;
+        }
+
+        let valid = running == current.expected;
+        checker.cursor = (checker.cursor + 1) % MAX_REGIONS;
+        checker.offset = 0;
+        checker.running = CRC16_INIT;
+
+        if valid { None } else { checker.hook.map(|hook| (hook, index)) }
+    });
+
+    // The hook is run outside of the critical section, since it may well want to draw something.
+    if let Some((hook, index)) = mismatch {
+        hook(index);
+    }
+}
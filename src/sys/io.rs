@@ -1,4 +1,13 @@
-use core::{cell, ptr};
+pub mod demo;
+pub mod lightgun;
+pub mod mouse;
+pub mod multitap;
+pub mod port;
+pub mod manager;
+pub mod serial;
+
+use core::marker::PhantomData;
+use core::{cell, mem, ptr};
 
 use critical_section as cs;
 
@@ -41,7 +50,7 @@ pub fn version() -> SystemVersion {
 }
 
 
-const Z80_BUS: *mut u8 = 0xA00000 as *mut _;
+pub(in crate::sys) const Z80_BUS: *mut u8 = 0xA00000 as *mut _;
 const Z80_BUSREQ: *mut u16 = 0xA11100 as *mut _;
 const Z80_RESET: *mut u16 = 0xA11200 as *mut _;
 
@@ -62,7 +71,13 @@ pub unsafe fn pause_z80() {
 
 #[inline]
 pub unsafe fn unpause_z80() {
-    core::ptr::write_volatile(Z80_BUSREQ, 0x0100);
+    core::ptr::write_volatile(Z80_BUSREQ, 0x0000);
+}
+
+/// Returns true once a bus request made by `pause_z80` has been granted.
+#[inline]
+pub fn z80_bus_granted() -> bool {
+    unsafe { core::ptr::read_volatile(Z80_BUSREQ as *const u8) & 0x01 == 0 }
 }
 
 #[inline]
@@ -79,6 +94,7 @@ impl<'a> Z80BusGuard<'a> {
     #[inline(always)]
     pub unsafe fn new() -> Self {
         unsafe { pause_z80(); }
+        while !z80_bus_granted() {}
         Self(core::marker::PhantomData)
     }
 }
@@ -97,6 +113,9 @@ pub fn with_paused_z80<T, F: FnOnce(&Z80BusGuard<'_>) -> T>(f: F) -> T {
 }
 
 pub trait IOPort {
+    /// The port's index in the port registry.
+    const INDEX: u8;
+
     const CTRL: *mut u8;
     const DATA: *mut u8;
     
@@ -121,6 +140,8 @@ pub trait IOPort {
 pub struct Player1;
 
 impl IOPort for Player1 {
+    const INDEX: u8 = 0;
+
     const CTRL: *mut u8 = 0xA10009 as *mut _;
     const DATA: *mut u8 = 0xA10003 as *mut _;
 
@@ -133,6 +154,8 @@ impl IOPort for Player1 {
 pub struct Player2;
 
 impl IOPort for Player2 {
+    const INDEX: u8 = 1;
+
     const CTRL: *mut u8 = 0xA1000B as *mut _;
     const DATA: *mut u8 = 0xA10005 as *mut _;
 
@@ -145,6 +168,8 @@ impl IOPort for Player2 {
 pub struct Modem;
 
 impl IOPort for Modem {
+    const INDEX: u8 = 2;
+
     const CTRL: *mut u8 = 0xA1000D as *mut _;
     const DATA: *mut u8 = 0xA10007 as *mut _;
 
@@ -153,15 +178,212 @@ impl IOPort for Modem {
     const TXDATA: *mut u8 = 0xA1001B as *mut _;
 }
 
+/// The number of polls to wait for a peripheral to acknowledge a nibble before giving up.
+const HANDSHAKE_TIMEOUT: u16 = 256;
+
+/// Reads one nibble of a TH/TR handshake, as used by the mouse and the multitap.
+///
+/// Nibble `index` is requested by setting TR to the inverse of its parity, and is ready once the
+/// peripheral mirrors TR on TL. The first nibble also pulls TH low, which starts the transfer.
+/// On timeout the port is returned to its idle state.
+fn handshake_nibble<P: IOPort>(guard: &Z80BusGuard, index: usize) -> Option<u8> {
+    let tr = index & 1 == 0;
+    P::write(guard, if tr { 0x20 } else { 0x00 });
+
+    let mut timeout = HANDSHAKE_TIMEOUT;
+    loop {
+        let data = P::read(guard);
+        if (data & 0x10 != 0) == tr {
+            return Some(data & 0xF);
+        }
+        timeout -= 1;
+        if timeout == 0 {
+            P::write(guard, 0x60);
+            return None;
+        }
+    }
+}
+
+/// Polls the controllers, unless `manager::set_poll_mode` has moved polling to a scanline.
+///
+/// This is called by the vertical interrupt handler.
+pub(super) fn poll(cs: cs::CriticalSection) {
+    if manager::on_vblank(cs) {
+        poll_ports(cs);
+    }
+}
+
+/// Polls the controllers if this is the scanline they're latched on.
+///
+/// This is called by the horizontal interrupt handler.
+pub(super) fn poll_hblank(cs: cs::CriticalSection) {
+    if manager::on_hblank(cs) {
+        poll_ports(cs);
+    }
+}
+
+/// The number of drivers using the external interrupt, which is on while there are any.
+static XINT_USERS: cs::Mutex<cell::Cell<u8>> = cs::Mutex::new(cell::Cell::new(0));
+
+/// Turns the external interrupt on for a driver, or off once the last one is done with it.
+fn use_xint(enable: bool) {
+    let users = super::with_cs::<1, 7, _>(|cs| {
+        let cell = XINT_USERS.borrow(cs);
+        let users = if enable { cell.get() + 1 } else { cell.get().saturating_sub(1) };
+        cell.set(users);
+        users
+    });
+    let mut settings = super::vdp::Settings::current();
+    settings.modify_mode(if users != 0 { 0x80000 } else { 0 }, 0x80000);
+    settings.apply::<false>();
+}
+
+/// Passes the external interrupt on to the drivers that use it.
+///
+/// This is called by the external interrupt handler.
+pub(super) fn on_extint(cs: cs::CriticalSection) {
+    lightgun::on_extint(cs);
+    serial::on_extint(cs);
+}
+
+/// Polls every controller port with the driver bound to it by `manager::bind`. Ports claimed
+/// through `port::claim` for anything else are left alone.
+fn poll_ports(cs: cs::CriticalSection) {
+    manager::poll(cs);
+
+    let p1 = P1_CONTROLLER.borrow(cs);
+    // The frames players press buttons on are unpredictable, so they make for good entropy.
+    let changed = p1.get().buttons().bits() ^ p1.get().previous().bits();
+    if changed != 0 {
+        super::rand::stir(cs, (super::timing::elapsed_frames() << 16) | changed as u32);
+    }
+}
+
+/// A set of controller buttons, using the same bit layout as `ControllerState`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Buttons(u16);
+
+impl Buttons {
+    pub const NONE: Self = Self(0x000);
+    pub const UP: Self = Self(0x001);
+    pub const DOWN: Self = Self(0x002);
+    pub const LEFT: Self = Self(0x004);
+    pub const RIGHT: Self = Self(0x008);
+    pub const B: Self = Self(0x010);
+    pub const C: Self = Self(0x020);
+    pub const A: Self = Self(0x040);
+    pub const START: Self = Self(0x080);
+    pub const Z: Self = Self(0x100);
+    pub const Y: Self = Self(0x200);
+    pub const X: Self = Self(0x400);
+    pub const MODE: Self = Self(0x800);
+    pub const DPAD: Self = Self(0x00F);
+    pub const ALL: Self = Self(0xFFF);
+
+    #[inline]
+    pub const fn from_bits(bits: u16) -> Self {
+        Self(bits & Self::ALL.0)
+    }
+
+    #[inline]
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    #[inline]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns true if every button in `other` is in this set.
+    #[inline]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns true if any button in `other` is in this set.
+    #[inline]
+    pub const fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    #[inline]
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    #[inline]
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    #[inline]
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+}
+
+impl core::ops::BitOr for Buttons {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.union(rhs)
+    }
+}
+
+impl core::ops::BitOrAssign for Buttons {
+    #[inline]
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = self.union(rhs);
+    }
+}
+
+impl core::ops::BitAnd for Buttons {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.intersection(rhs)
+    }
+}
+
+impl core::ops::BitAndAssign for Buttons {
+    #[inline]
+    fn bitand_assign(&mut self, rhs: Self) {
+        *self = self.intersection(rhs);
+    }
+}
+
+impl core::ops::Sub for Buttons {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.difference(rhs)
+    }
+}
+
+impl core::ops::Not for Buttons {
+    type Output = Self;
+
+    #[inline]
+    fn not(self) -> Self::Output {
+        Self(!self.0 & Self::ALL.0)
+    }
+}
+
 pub static P1_CONTROLLER: cs::Mutex<cell::Cell<ControllerState<Player1>>> = cs::Mutex::new(cell::Cell::new(ControllerState::new(Player1)));
 pub static P2_CONTROLLER: cs::Mutex<cell::Cell<ControllerState<Player2>>> = cs::Mutex::new(cell::Cell::new(ControllerState::new(Player2)));
 
 #[derive(Clone, Copy)]
-pub struct ControllerState<P: IOPort>(u16, u16, P);
+pub struct ControllerState<P: IOPort>(u16, u16, PhantomData<P>);
 
 impl<P: IOPort> ControllerState<P> {
     pub const fn new(port: P) -> Self {
-        Self(0, 0, port)
+        // The port is only needed for its type.
+        mem::forget(port);
+        Self(0, 0, PhantomData)
     }
 
     pub fn init(self) -> Self {
@@ -178,33 +400,33 @@ impl<P: IOPort> ControllerState<P> {
         self.0 = with_paused_z80(|guard| {
             // 1st step
             P::write(guard, 0x40);
-            unsafe { core::arch::asm!("nop","nop","nop","nop") }
+            super::delay_us(2);
             let first = P::read(guard) as u16;
 
             // 2nd step
             P::write(guard, 0x00);
-            unsafe { core::arch::asm!("nop","nop","nop","nop") }
+            super::delay_us(2);
             let second = P::read(guard) as u16;
 
             // 3rd step
             P::write(guard, 0x40);
-            unsafe { core::arch::asm!("nop","nop","nop","nop") }
+            super::delay_us(2);
 
             // 4th step
             P::write(guard, 0x00);
-            unsafe { core::arch::asm!("nop","nop","nop","nop") }
+            super::delay_us(2);
 
             // 5th step
             P::write(guard, 0x40);
-            unsafe { core::arch::asm!("nop","nop","nop","nop") }
+            super::delay_us(2);
 
             // 6th step
             P::write(guard, 0x00);
-            unsafe { core::arch::asm!("nop","nop","nop","nop") }
+            super::delay_us(2);
             let third = if P::read(guard) & 0xF == 0 {
                 // 7th step
                 P::write(guard, 0x40);
-                unsafe { core::arch::asm!("nop","nop","nop","nop") }
+                super::delay_us(2);
                 P::read(guard) as u16
             } else { 0 };
 
@@ -213,6 +435,36 @@ impl<P: IOPort> ControllerState<P> {
         self
     }
 
+    /// The buttons held down this frame.
+    #[inline]
+    pub fn buttons(&self) -> Buttons {
+        Buttons(self.0)
+    }
+
+    /// The buttons held down last frame.
+    #[inline]
+    pub fn previous(&self) -> Buttons {
+        Buttons(self.1)
+    }
+
+    /// The buttons that went down this frame.
+    #[inline]
+    pub fn just_pressed(&self) -> Buttons {
+        Buttons(self.0 & !self.1)
+    }
+
+    /// The buttons that came up this frame.
+    #[inline]
+    pub fn just_released(&self) -> Buttons {
+        Buttons(self.1 & !self.0)
+    }
+
+    /// The buttons held down both this frame and last frame.
+    #[inline]
+    pub fn held(&self) -> Buttons {
+        Buttons(self.0 & self.1)
+    }
+
     pub fn start(&self) -> bool {
         self.0 & 0x080 != 0
     }
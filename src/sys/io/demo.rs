@@ -0,0 +1,168 @@
+use super::Buttons;
+use super::manager::PadInput;
+
+/// The words at the start of a recording before the frames, which hold the seed.
+const HEADER_WORDS: usize = 2;
+/// The most frames one word of a recording covers.
+const MAX_RUN: u8 = 16;
+
+/// Records a pad's buttons every frame, for demos and for replaying a bug.
+///
+/// Recordings are words, with the buttons held in the low 12 bits, in `Buttons`'s layout, and the
+/// number of frames they were held for, minus one, in the top 4. Held buttons take a word every 16
+/// frames, so a 30 second demo with a fair bit of play in it is usually a few hundred bytes. The
+/// words can be dumped from RAM with an emulator's memory viewer, and put in ROM to play back.
+///
+/// To play back the same as it was recorded, the game has to run the same from the same inputs.
+/// Gameplay randomness should come from a generator of its own seeded with `seed`, rather than
+/// from `rand`'s global one, which is stirred by whatever the real pads are doing.
+///
+/// ```ignore
+/// let seed = rand::random_u32();
+/// let mut recorder = Recorder::new(&mut DEMO_BUF, seed);
+/// let mut rng = XorShift32::new(seed);
+/// loop {
+///     let input = manager::player(0).unwrap_or_default();
+///     recorder.record(input.buttons());
+///     // Run the frame with `input` and `rng`.
+/// }
+/// ```
+pub struct Recorder<'a> {
+    buf: &'a mut [u16],
+    len: usize,
+    frames: u32,
+    full: bool,
+}
+
+impl<'a> Recorder<'a> {
+    /// Starts a recording into `buf`, noting `seed` for playback.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` can't hold the header, which is two words.
+    pub fn new(buf: &'a mut [u16], seed: u32) -> Self {
+        if buf.len() < HEADER_WORDS {
+            panic!("demo buffer is too small");
+        }
+        buf[0] = (seed >> 16) as u16;
+        buf[1] = seed as u16;
+        Self { buf, len: HEADER_WORDS, frames: 0, full: false }
+    }
+
+    /// Adds a frame's buttons. Returns false once the buffer is full, after which frames are
+    /// dropped.
+    pub fn record(&mut self, buttons: Buttons) -> bool {
+        if self.full {
+            return false;
+        }
+
+        let bits = buttons.bits();
+        if self.len > HEADER_WORDS {
+            let last = &mut self.buf[self.len - 1];
+            if *last & 0xFFF == bits && (*last >> 12) < (MAX_RUN - 1) as u16 {
+                *last += 1 << 12;
+                self.frames += 1;
+                return true;
+            }
+        }
+        if self.len == self.buf.len() {
+            self.full = true;
+            return false;
+        }
+        self.buf[self.len] = bits;
+        self.len += 1;
+        self.frames += 1;
+        true
+    }
+
+    /// The number of frames recorded.
+    #[inline]
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.full
+    }
+
+    /// The recording so far, which can be given to `Playback::new`.
+    #[inline]
+    pub fn data(&self) -> &[u16] {
+        &self.buf[..self.len]
+    }
+}
+
+/// Plays a recording from `Recorder` back, a frame at a time.
+///
+/// ```ignore
+/// static DEMO: &[u16] = &[/* ... */];
+///
+/// let mut playback = Playback::new(DEMO);
+/// let mut rng = XorShift32::new(playback.seed());
+/// while let Some(input) = playback.next() {
+///     // Run the frame with `input` and `rng`.
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Playback<'a> {
+    data: &'a [u16],
+    pos: usize,
+    /// The frames left in the current word.
+    left: u8,
+    current: Buttons,
+}
+
+impl<'a> Playback<'a> {
+    /// # Panics
+    ///
+    /// Panics if `data` is too short to have come from a `Recorder`.
+    pub const fn new(data: &'a [u16]) -> Self {
+        if data.len() < HEADER_WORDS {
+            panic!("demo recording is too short");
+        }
+        Self { data, pos: HEADER_WORDS, left: 0, current: Buttons::NONE }
+    }
+
+    /// The seed the recording was made with.
+    #[inline]
+    pub const fn seed(&self) -> u32 {
+        ((self.data[0] as u32) << 16) | self.data[1] as u32
+    }
+
+    /// The number of frames in the whole recording.
+    pub fn frames(&self) -> u32 {
+        self.data[HEADER_WORDS..].iter().map(|&word| (word >> 12) as u32 + 1).sum()
+    }
+
+    /// Returns true once every frame has been played.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.left == 0 && self.pos == self.data.len()
+    }
+
+    /// Goes back to the first frame.
+    #[inline]
+    pub fn rewind(&mut self) {
+        self.pos = HEADER_WORDS;
+        self.left = 0;
+        self.current = Buttons::NONE;
+    }
+}
+
+impl Iterator for Playback<'_> {
+    type Item = PadInput;
+
+    /// The next frame's input, with the previous frame's buttons for `just_pressed` and the like.
+    fn next(&mut self) -> Option<PadInput> {
+        let previous = self.current;
+        if self.left == 0 {
+            let &word = self.data.get(self.pos)?;
+            self.pos += 1;
+            self.current = Buttons::from_bits(word);
+            self.left = (word >> 12) as u8 + 1;
+        }
+        self.left -= 1;
+        Some(PadInput::new(self.current, previous))
+    }
+}
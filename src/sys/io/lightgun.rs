@@ -0,0 +1,390 @@
+use core::cell;
+use core::marker::PhantomData;
+
+use critical_section as cs;
+
+use super::port::{self, PortHandle, PortOwner};
+use super::{use_xint, with_paused_z80, IOPort, Player1, Player2};
+use crate::sys::vdp::{Settings, VDP};
+
+pub static P1_GUN: cs::Mutex<cell::Cell<GunState<Player1>>> = cs::Mutex::new(cell::Cell::new(GunState::new(Kind::Menacer)));
+pub static P2_GUN: cs::Mutex<cell::Cell<GunState<Player2>>> = cs::Mutex::new(cell::Cell::new(GunState::new(Kind::Menacer)));
+
+/// The light guns `LightGun` can read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Sega's Menacer, which has a trigger and A, B and Start buttons.
+    Menacer,
+    /// Konami's Justifier, which has a trigger and a Start button. A second gun plugs into the
+    /// first, and `select` picks which one the port reads.
+    Justifier,
+}
+
+/// A light gun's buttons, in the same layout for either gun.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GunButtons(u8);
+
+impl GunButtons {
+    pub const NONE: Self = Self(0x0);
+    pub const TRIGGER: Self = Self(0x1);
+    pub const A: Self = Self(0x2);
+    pub const B: Self = Self(0x4);
+    pub const START: Self = Self(0x8);
+
+    #[inline]
+    pub const fn bits(&self) -> u8 {
+        self.0
+    }
+
+    #[inline]
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// The HV counter value from the last time the gun saw the beam, taken by the external interrupt.
+struct Latch {
+    active: bool,
+    hv: Option<u16>,
+}
+
+static LATCH: cs::Mutex<cell::RefCell<Latch>> = cs::Mutex::new(cell::RefCell::new(Latch {
+    active: false,
+    hv: None,
+}));
+
+/// Returns true if a gun already has the HV counter latch, whether it's a `LightGun` or bound
+/// with `manager::bind`.
+#[inline]
+pub(super) fn is_latch_taken(cs: cs::CriticalSection) -> bool {
+    LATCH.borrow_ref(cs).active
+}
+
+/// Turns the external interrupt and the HV counter latch on or off. This uses critical sections
+/// of its own, so it's called outside of the one a gun is set up in.
+pub(super) fn set_latching(enable: bool) {
+    let mut settings = Settings::current();
+    settings.stop_hv_on_xint(enable);
+    settings.apply::<false>();
+    use_xint(enable);
+}
+
+/// A light gun's position and buttons, as read by `LightGun`, or by the vblank handler once a port
+/// is bound to `Device::LightGun`.
+///
+/// The gun shares the external interrupt with everything else, so its methods take the critical
+/// section they're called in.
+pub struct GunState<P: IOPort> {
+    kind: Kind,
+    enabled: bool,
+    /// Which gun of a pair of Justifiers is being read.
+    second: bool,
+    position: Option<(i16, i16)>,
+    buttons: GunButtons,
+    previous: GunButtons,
+    offset: (i16, i16),
+    port: PhantomData<P>,
+}
+
+// Derived, these would need `P: Copy`, which `LightGun` doesn't ask for.
+impl<P: IOPort> Clone for GunState<P> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P: IOPort> Copy for GunState<P> {}
+
+impl<P: IOPort> GunState<P> {
+    pub const fn new(kind: Kind) -> Self {
+        Self {
+            kind,
+            enabled: false,
+            second: false,
+            position: None,
+            buttons: GunButtons::NONE,
+            previous: GunButtons::NONE,
+            offset: (0, 0),
+            port: PhantomData,
+        }
+    }
+
+    /// Changes the kind of gun, keeping the calibration. Only takes effect on the next `init_in`.
+    #[inline]
+    pub const fn with_kind(mut self, kind: Kind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Configures the port for the gun and takes the HV counter latch. The gun isn't read if
+    /// another one already has the latch. `set_latching` has to be called afterwards.
+    pub fn init_in(mut self, cs: cs::CriticalSection) -> Self {
+        {
+            let mut latch = LATCH.borrow_ref_mut(cs);
+            if latch.active {
+                return self;
+            }
+            *latch = Latch { active: true, hv: None };
+        }
+
+        with_paused_z80(|guard| match self.kind {
+            // TH is an input, with its interrupt on.
+            Kind::Menacer => P::configure(guard, 0x80),
+            // The Justifier takes TH low to be read, and TR picks the gun.
+            Kind::Justifier => {
+                P::configure(guard, 0xE0);
+                P::write(guard, if self.second { 0x20 } else { 0x00 });
+            }
+        });
+        self.enabled = true;
+        self
+    }
+
+    /// Stops reading the gun and lets go of the latch.
+    pub fn shutdown_in(self, cs: cs::CriticalSection) -> Self {
+        if self.enabled {
+            with_paused_z80(|guard| P::configure(guard, 0x00));
+            LATCH.borrow_ref_mut(cs).active = false;
+        }
+        Self::new(self.kind).with_offset(self.offset)
+    }
+
+    #[inline]
+    const fn with_offset(mut self, offset: (i16, i16)) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Reads the position the beam was seen at since the last update, and the buttons.
+    pub fn update_in(mut self, cs: cs::CriticalSection) -> Self {
+        if !self.enabled {
+            return self;
+        }
+
+        let hv = LATCH.borrow_ref_mut(cs).hv.take();
+        self.position = hv.map(|hv| {
+            // The H counter goes up once every two pixels in either width.
+            let x = (hv & 0xFF) as i16 * 2;
+            let y = (hv >> 8) as i16;
+            (x + self.offset.0, y + self.offset.1)
+        });
+
+        let data = with_paused_z80(|guard| P::read(guard));
+        self.previous = self.buttons;
+        self.buttons = match self.kind {
+            Kind::Menacer => GunButtons(
+                flag(data, 0x01, GunButtons::TRIGGER)
+                    | flag(data, 0x02, GunButtons::A)
+                    | flag(data, 0x04, GunButtons::B)
+                    | flag(data, 0x08, GunButtons::START),
+            ),
+            // The Justifier's buttons read low while held.
+            Kind::Justifier => GunButtons(
+                flag(!data, 0x01, GunButtons::TRIGGER) | flag(!data, 0x02, GunButtons::START),
+            ),
+        };
+        self
+    }
+
+    #[inline]
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// With a pair of Justifiers, picks which one is read, starting from the next frame. This
+    /// does nothing for a Menacer.
+    pub fn select(mut self, second: bool) -> Self {
+        if self.kind == Kind::Justifier {
+            self.second = second;
+            if self.enabled {
+                with_paused_z80(|guard| P::write(guard, if second { 0x20 } else { 0x00 }));
+            }
+        }
+        self
+    }
+
+    /// Adds `offset` to every position, to make up for guns and TVs that don't line up.
+    #[inline]
+    pub fn set_calibration(&mut self, offset: (i16, i16)) {
+        self.offset = offset;
+    }
+
+    #[inline]
+    pub fn calibration(&self) -> (i16, i16) {
+        self.offset
+    }
+
+    /// Sets the calibration so the current position reads as `target`, for a calibration screen
+    /// where the player aims at a mark and pulls the trigger. Returns false, and leaves the
+    /// calibration as it was, if the gun didn't see the beam this frame.
+    pub fn calibrate(&mut self, target: (i16, i16)) -> bool {
+        let Some((x, y)) = self.position else { return false };
+        self.offset = (
+            self.offset.0 + target.0 - x,
+            self.offset.1 + target.1 - y,
+        );
+        self.position = Some(target);
+        true
+    }
+
+    /// Where the gun saw the beam in the last frame, in screen pixels, or `None` if it didn't.
+    #[inline]
+    pub fn position(&self) -> Option<(i16, i16)> {
+        self.position
+    }
+
+    #[inline]
+    pub fn buttons(&self) -> GunButtons {
+        self.buttons
+    }
+
+    /// The buttons that went down since the last update.
+    #[inline]
+    pub fn pressed(&self) -> GunButtons {
+        GunButtons(self.buttons.0 & !self.previous.0)
+    }
+
+    #[inline]
+    pub fn is_pressed(&self, buttons: GunButtons) -> bool {
+        self.buttons.contains(buttons)
+    }
+
+    #[inline]
+    pub fn just_pressed(&self, buttons: GunButtons) -> bool {
+        self.buttons.contains(buttons) && !self.previous.contains(buttons)
+    }
+}
+
+/// A light gun in a controller port, usually port 2, that the game reads itself rather than
+/// binding the port to `Device::LightGun`.
+///
+/// When the gun's sensor sees the beam, it pulls TH low, which raises the external interrupt with
+/// the HV counter latched at that point. `update` turns the latched counter into screen
+/// coordinates, and reads the buttons. A frame where the sensor didn't see the beam, because the
+/// gun was pointed away or at something too dark, has no position. Targets are easier to hit when
+/// they're drawn bright, and games often flash the screen white for a frame when the trigger is
+/// pulled.
+///
+/// ```ignore
+/// let mut gun = LightGun::<Player2>::open(Kind::Menacer).unwrap();
+/// loop {
+///     gun.update();
+///     if gun.just_pressed(GunButtons::TRIGGER) {
+///         if let Some((x, y)) = gun.position() {
+///             // Check what's at (x, y).
+///         }
+///     }
+///     VDP::wait_for_vblank(None);
+/// }
+/// ```
+///
+/// Only one gun can be open at a time, since they share the interrupt.
+pub struct LightGun<P: IOPort> {
+    _handle: PortHandle<P>,
+    gun: GunState<P>,
+}
+
+impl<P: IOPort> LightGun<P> {
+    /// Claims port `P` for a gun, and turns on the external interrupt and the HV counter latch.
+    ///
+    /// Returns whoever already has the port if it's claimed. Another gun already reading shows up
+    /// as `PortOwner::LightGun`.
+    pub fn open(kind: Kind) -> Result<Self, PortOwner> {
+        let handle = port::claim::<P>(PortOwner::LightGun)?;
+        let gun = crate::sys::with_cs::<1, 7, _>(|cs| GunState::new(kind).init_in(cs));
+        if !gun.is_enabled() {
+            return Err(PortOwner::LightGun);
+        }
+        set_latching(true);
+        Ok(Self { _handle: handle, gun })
+    }
+
+    #[inline]
+    pub fn kind(&self) -> Kind {
+        self.gun.kind()
+    }
+
+    /// With a pair of Justifiers, picks which one is read, starting from the next frame. This
+    /// does nothing for a Menacer.
+    #[inline]
+    pub fn select(&mut self, second: bool) {
+        self.gun = self.gun.select(second);
+    }
+
+    /// Adds `offset` to every position, to make up for guns and TVs that don't line up.
+    #[inline]
+    pub fn set_calibration(&mut self, offset: (i16, i16)) {
+        self.gun.set_calibration(offset);
+    }
+
+    #[inline]
+    pub fn calibration(&self) -> (i16, i16) {
+        self.gun.calibration()
+    }
+
+    /// Sets the calibration so the current position reads as `target`. See
+    /// `GunState::calibrate`.
+    #[inline]
+    pub fn calibrate(&mut self, target: (i16, i16)) -> bool {
+        self.gun.calibrate(target)
+    }
+
+    /// Reads the position the beam was seen at since the last call, and the buttons. Call this
+    /// once a frame.
+    pub fn update(&mut self) {
+        let gun = self.gun;
+        self.gun = crate::sys::with_cs::<1, 7, _>(|cs| gun.update_in(cs));
+    }
+
+    /// Where the gun saw the beam in the last frame, in screen pixels, or `None` if it didn't.
+    #[inline]
+    pub fn position(&self) -> Option<(i16, i16)> {
+        self.gun.position()
+    }
+
+    #[inline]
+    pub fn buttons(&self) -> GunButtons {
+        self.gun.buttons()
+    }
+
+    #[inline]
+    pub fn is_pressed(&self, buttons: GunButtons) -> bool {
+        self.gun.is_pressed(buttons)
+    }
+
+    #[inline]
+    pub fn just_pressed(&self, buttons: GunButtons) -> bool {
+        self.gun.just_pressed(buttons)
+    }
+
+    /// Stops reading the gun and gives the port back.
+    #[inline]
+    pub fn close(self) {}
+}
+
+impl<P: IOPort> Drop for LightGun<P> {
+    fn drop(&mut self) {
+        set_latching(false);
+        let gun = self.gun;
+        crate::sys::with_cs::<1, 7, _>(|cs| gun.shutdown_in(cs));
+    }
+}
+
+#[inline]
+const fn flag(data: u8, mask: u8, button: GunButtons) -> u8 {
+    if data & mask != 0 { button.0 } else { 0 }
+}
+
+/// Takes the latched HV counter, the first time the gun sees the beam in a frame.
+pub(super) fn on_extint(cs: cs::CriticalSection) {
+    let mut latch = LATCH.borrow_ref_mut(cs);
+    if latch.active && latch.hv.is_none() {
+        latch.hv = Some(VDP::hv_counter());
+    }
+}
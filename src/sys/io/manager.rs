@@ -0,0 +1,506 @@
+use core::cell;
+
+use critical_section as cs;
+
+use super::lightgun::{self, GunButtons, GunState, Kind};
+use super::mouse::{self, MouseState};
+use super::multitap::{self, Multitap};
+use super::port::{self, PortOwner};
+use crate::sys::{timing, vdp};
+
+use super::{Buttons, ControllerState, IOPort, Player1, Player2, P1_CONTROLLER, P2_CONTROLLER};
+
+/// A driver for a device plugged into a controller port.
+pub trait Peripheral: Copy {
+    /// What the device reports each poll.
+    type State: Copy;
+
+    /// Configures the port for the device.
+    fn init(self) -> Self;
+
+    /// Stops using the device, clearing its state.
+    fn shutdown(self) -> Self {
+        self
+    }
+
+    /// Reads the device. Called once per frame from the vblank handler.
+    fn poll(self) -> Self;
+
+    fn state(&self) -> Self::State;
+}
+
+/// A pad's buttons over the last two polls, without the port attached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PadInput {
+    current: Buttons,
+    previous: Buttons,
+}
+
+impl PadInput {
+    #[inline]
+    pub const fn new(current: Buttons, previous: Buttons) -> Self {
+        Self { current, previous }
+    }
+
+    #[inline]
+    pub const fn buttons(&self) -> Buttons {
+        self.current
+    }
+
+    #[inline]
+    pub const fn previous(&self) -> Buttons {
+        self.previous
+    }
+
+    #[inline]
+    pub const fn just_pressed(&self) -> Buttons {
+        self.current.difference(self.previous)
+    }
+
+    #[inline]
+    pub const fn just_released(&self) -> Buttons {
+        self.previous.difference(self.current)
+    }
+
+    #[inline]
+    pub const fn held(&self) -> Buttons {
+        self.current.intersection(self.previous)
+    }
+}
+
+/// What a mouse reported on its last poll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MouseInput {
+    pub dx: i16,
+    pub dy: i16,
+    /// The buttons held down, as `MouseState` button bits.
+    pub buttons: u8,
+    /// The buttons pressed since the previous poll.
+    pub clicked: u8,
+}
+
+impl<P: IOPort + Copy> Peripheral for ControllerState<P> {
+    type State = PadInput;
+
+    #[inline]
+    fn init(self) -> Self {
+        ControllerState::init(self)
+    }
+
+    #[inline]
+    fn shutdown(self) -> Self {
+        Self(0, 0, self.2)
+    }
+
+    #[inline]
+    fn poll(self) -> Self {
+        self.update()
+    }
+
+    #[inline]
+    fn state(&self) -> Self::State {
+        PadInput::new(self.buttons(), self.previous())
+    }
+}
+
+impl<P: IOPort + Copy> Peripheral for MouseState<P> {
+    type State = MouseInput;
+
+    #[inline]
+    fn init(self) -> Self {
+        MouseState::init(self)
+    }
+
+    #[inline]
+    fn shutdown(self) -> Self {
+        MouseState::shutdown(self)
+    }
+
+    #[inline]
+    fn poll(self) -> Self {
+        self.update()
+    }
+
+    #[inline]
+    fn state(&self) -> Self::State {
+        MouseInput {
+            dx: self.dx(),
+            dy: self.dy(),
+            buttons: self.buttons(),
+            clicked: self.clicked(),
+        }
+    }
+}
+
+impl<P: IOPort + Copy> Peripheral for Multitap<P> {
+    type State = [Option<PadInput>; 4];
+
+    #[inline]
+    fn init(self) -> Self {
+        Multitap::init(self)
+    }
+
+    #[inline]
+    fn shutdown(self) -> Self {
+        Multitap::shutdown(self)
+    }
+
+    #[inline]
+    fn poll(self) -> Self {
+        self.update()
+    }
+
+    #[inline]
+    fn state(&self) -> Self::State {
+        core::array::from_fn(|slot| self.pad(slot).map(|pad| PadInput::new(pad.buttons(), pad.previous())))
+    }
+}
+
+/// What a light gun reported on its last poll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GunInput {
+    /// Where the gun saw the beam, in screen pixels, or `None` if it didn't.
+    pub position: Option<(i16, i16)>,
+    pub buttons: GunButtons,
+    /// The buttons pressed since the previous poll.
+    pub pressed: GunButtons,
+}
+
+/// The physical controller ports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    One = 0,
+    Two = 1,
+}
+
+/// Which driver a controller port is bound to.
+///
+/// Keyboard drivers get a variant here once they implement `Peripheral`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Device {
+    /// Nothing is polled.
+    None,
+    /// A 3 or 6 button pad.
+    #[default]
+    Pad,
+    Mouse,
+    Multitap,
+    /// A light gun, which only one port can be bound to at a time since they share the external
+    /// interrupt. A pair of Justifiers is read as the first one.
+    LightGun(Kind),
+}
+
+static BINDINGS: cs::Mutex<cell::Cell<[Device; 2]>> = cs::Mutex::new(cell::Cell::new([Device::Pad; 2]));
+
+/// When the controllers are read each frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PollMode {
+    /// In the vblank handler, once the frame count, raster zones and frame arena have moved on to
+    /// the new frame. This is the first of the `vdp::vblank` callbacks, at `PRIORITY_INPUT`, so it
+    /// comes before the audio and the game's own callbacks.
+    #[default]
+    VBlank,
+    /// From the H-int handler, when the beam reaches this line.
+    ///
+    /// Polling just before the game reads its input, rather than at the start of the previous
+    /// vblank, cuts most of a frame from the worst case latency. Pick a line late enough in the frame
+    /// that the game's update is done with last frame's input by then.
+    Line(u8),
+}
+
+/// When the controllers were read, to within a scanline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Timestamp {
+    /// The frame, as counted by `timing::elapsed_frames`.
+    pub frame: u32,
+    /// The number of lines since the frame's vblank started.
+    pub line: u16,
+}
+
+#[derive(Clone, Copy)]
+struct Latch {
+    mode: PollMode,
+    /// Set once the controllers have been polled this frame, since H-ints can fire more than once.
+    polled: bool,
+    stamp: Timestamp,
+}
+
+static LATCH: cs::Mutex<cell::Cell<Latch>> = cs::Mutex::new(cell::Cell::new(Latch {
+    mode: PollMode::VBlank,
+    polled: false,
+    stamp: Timestamp { frame: 0, line: 0 },
+}));
+
+/// Changes when the controllers are polled.
+///
+/// `PollMode::Line` takes over the VDP's H-int interval, and turns H-ints on. Handlers set with
+/// `VDP::set_hblank_handler` still run, but only as often as polling needs. With raster zones from
+/// `vdp::raster`, the poll line is fitted into their schedule instead.
+pub fn set_poll_mode(mode: PollMode) {
+    super::super::with_cs::<1, 7, _>(|cs| {
+        let cell = LATCH.borrow(cs);
+        cell.set(Latch { mode, polled: false, ..cell.get() });
+        let line = if let PollMode::Line(line) = mode { Some(line) } else { None };
+        vdp::raster::set_poll_line(cs, line);
+    });
+
+    let mut settings = vdp::Settings::current();
+    match mode {
+        PollMode::VBlank => {
+            settings.enable_hint(vdp::raster::is_active());
+            settings.set_hint_interval(0xFF);
+        }
+        PollMode::Line(line) => {
+            settings.enable_hint(true);
+            settings.set_hint_interval(line);
+        }
+    }
+    settings.apply::<false>();
+}
+
+#[inline]
+pub fn poll_mode() -> PollMode {
+    super::super::with_cs::<1, 7, _>(|cs| LATCH.borrow(cs).get().mode)
+}
+
+/// When the controllers were last read, which is when the buttons in `player` and `mouse` were
+/// pressed, give or take the time between polls.
+#[inline]
+pub fn latched_at() -> Timestamp {
+    super::super::with_cs::<1, 7, _>(|cs| LATCH.borrow(cs).get().stamp)
+}
+
+/// Starts a new frame. Returns true if the controllers should be polled now.
+pub(in crate::sys) fn on_vblank(cs: cs::CriticalSection) -> bool {
+    let cell = LATCH.borrow(cs);
+    let mut latch = cell.get();
+    latch.polled = false;
+    let poll = latch.mode == PollMode::VBlank;
+    if poll {
+        latch.stamp = Timestamp { frame: timing::elapsed_frames(), line: 0 };
+    }
+    cell.set(latch);
+    poll
+}
+
+/// Returns true if the controllers should be polled on this H-int.
+pub(in crate::sys) fn on_hblank(cs: cs::CriticalSection) -> bool {
+    let cell = LATCH.borrow(cs);
+    let latch = cell.get();
+    if latch.polled || latch.mode == PollMode::VBlank {
+        return false;
+    }
+    let stamp = Timestamp { frame: timing::elapsed_frames(), line: timing::lines_since_vblank() };
+    cell.set(Latch { polled: true, stamp, ..latch });
+    true
+}
+
+/// The drivers for one port.
+struct PortDrivers<'a, P: IOPort + Copy> {
+    pad: &'a cell::Cell<ControllerState<P>>,
+    mouse: &'a cell::Cell<MouseState<P>>,
+    tap: &'a cell::Cell<Multitap<P>>,
+    gun: &'a cell::Cell<GunState<P>>,
+    owner: Option<PortOwner>,
+    /// The gun shares its latch with the external interrupt, so it's driven in the critical section
+    /// the drivers were borrowed in.
+    cs: cs::CriticalSection<'a>,
+}
+
+/// The drivers for a port, with the port's type erased.
+trait Drivers {
+    fn owner(&self) -> Option<PortOwner>;
+    fn init(&self, device: Device);
+    fn shutdown(&self, device: Device);
+    fn poll(&self, device: Device);
+    fn pads(&self, device: Device) -> [Option<PadInput>; 4];
+    fn mouse(&self) -> MouseInput;
+    fn gun(&self) -> GunInput;
+}
+
+#[inline]
+fn apply<D: Peripheral>(cell: &cell::Cell<D>, f: impl FnOnce(D) -> D) {
+    cell.set(f(cell.get()));
+}
+
+impl<'a, P: IOPort + Copy> Drivers for PortDrivers<'a, P> {
+    #[inline]
+    fn owner(&self) -> Option<PortOwner> {
+        self.owner
+    }
+
+    fn init(&self, device: Device) {
+        match device {
+            Device::None => {}
+            Device::Pad => apply(self.pad, Peripheral::init),
+            Device::Mouse => apply(self.mouse, Peripheral::init),
+            Device::Multitap => apply(self.tap, Peripheral::init),
+            Device::LightGun(kind) => self.gun.set(self.gun.get().with_kind(kind).init_in(self.cs)),
+        }
+    }
+
+    fn shutdown(&self, device: Device) {
+        match device {
+            Device::None => {}
+            Device::Pad => apply(self.pad, Peripheral::shutdown),
+            Device::Mouse => apply(self.mouse, Peripheral::shutdown),
+            Device::Multitap => apply(self.tap, Peripheral::shutdown),
+            Device::LightGun(_) => self.gun.set(self.gun.get().shutdown_in(self.cs)),
+        }
+    }
+
+    fn poll(&self, device: Device) {
+        match device {
+            Device::None => {}
+            Device::Pad => apply(self.pad, Peripheral::poll),
+            Device::Mouse => apply(self.mouse, Peripheral::poll),
+            Device::Multitap => apply(self.tap, Peripheral::poll),
+            Device::LightGun(_) => self.gun.set(self.gun.get().update_in(self.cs)),
+        }
+    }
+
+    fn pads(&self, device: Device) -> [Option<PadInput>; 4] {
+        match device {
+            Device::Pad => [Some(self.pad.get().state()), None, None, None],
+            Device::Multitap => self.tap.get().state(),
+            Device::None | Device::Mouse | Device::LightGun(_) => [None; 4],
+        }
+    }
+
+    #[inline]
+    fn mouse(&self) -> MouseInput {
+        self.mouse.get().state()
+    }
+
+    #[inline]
+    fn gun(&self) -> GunInput {
+        let gun = self.gun.get();
+        GunInput {
+            position: gun.position(),
+            buttons: gun.buttons(),
+            pressed: gun.pressed(),
+        }
+    }
+}
+
+#[inline]
+fn with_drivers<R>(cs: cs::CriticalSection, port: Port, f: impl FnOnce(&dyn Drivers) -> R) -> R {
+    match port {
+        Port::One => f(&PortDrivers::<Player1> {
+            pad: P1_CONTROLLER.borrow(cs),
+            mouse: mouse::P1_MOUSE.borrow(cs),
+            tap: multitap::P1_MULTITAP.borrow(cs),
+            gun: lightgun::P1_GUN.borrow(cs),
+            owner: port::owner_in::<Player1>(cs),
+            cs,
+        }),
+        Port::Two => f(&PortDrivers::<Player2> {
+            pad: P2_CONTROLLER.borrow(cs),
+            mouse: mouse::P2_MOUSE.borrow(cs),
+            tap: multitap::P2_MULTITAP.borrow(cs),
+            gun: lightgun::P2_GUN.borrow(cs),
+            owner: port::owner_in::<Player2>(cs),
+            cs,
+        }),
+    }
+}
+
+/// Returns true if a port claimed by `owner` may still be driven as `device`.
+#[inline]
+fn allowed(owner: Option<PortOwner>, device: Device) -> bool {
+    match owner {
+        None => true,
+        Some(PortOwner::Mouse) => device == Device::Mouse,
+        Some(PortOwner::Multitap) => device == Device::Multitap,
+        Some(_) => false,
+    }
+}
+
+/// Binds `port` to a driver, shutting down whichever one had it before.
+///
+/// Fails with the port's owner if it has been claimed for something else. Binding a light gun
+/// while another gun is being read fails with `PortOwner::LightGun`.
+pub fn bind(port: Port, device: Device) -> Result<(), PortOwner> {
+    let is_gun = |device: Device| matches!(device, Device::LightGun(_));
+    let old = super::super::with_cs::<1, 7, _>(|cs| {
+        let cell = BINDINGS.borrow(cs);
+        let mut bindings = cell.get();
+        let old = bindings[port as usize];
+        if is_gun(device) && !is_gun(old) && lightgun::is_latch_taken(cs) {
+            return Err(PortOwner::LightGun);
+        }
+
+        with_drivers(cs, port, |drivers| {
+            if let Some(owner) = drivers.owner().filter(|_| !allowed(drivers.owner(), device)) {
+                return Err(owner);
+            }
+            if old != device {
+                drivers.shutdown(old);
+                drivers.init(device);
+            }
+            Ok(())
+        })?;
+
+        bindings[port as usize] = device;
+        cell.set(bindings);
+        Ok(old)
+    })?;
+
+    if is_gun(old) != is_gun(device) {
+        lightgun::set_latching(is_gun(device));
+    }
+    Ok(())
+}
+
+/// The driver `port` is bound to.
+#[inline]
+pub fn device(port: Port) -> Device {
+    super::super::with_cs::<1, 7, _>(|cs| BINDINGS.borrow(cs).get()[port as usize])
+}
+
+/// Polls both ports with their bound drivers. Called by the vblank handler.
+pub(in crate::sys) fn poll(cs: cs::CriticalSection) {
+    let bindings = BINDINGS.borrow(cs).get();
+    for (port, device) in [Port::One, Port::Two].into_iter().zip(bindings) {
+        with_drivers(cs, port, |drivers| {
+            if allowed(drivers.owner(), device) {
+                drivers.poll(device);
+            }
+        });
+    }
+}
+
+/// Every connected pad, whether plugged in directly or through a multitap, numbered from port 1's
+/// first slot onwards.
+fn pads(cs: cs::CriticalSection) -> impl Iterator<Item = PadInput> {
+    let bindings = BINDINGS.borrow(cs).get();
+    let one = with_drivers(cs, Port::One, |drivers| drivers.pads(bindings[0]));
+    let two = with_drivers(cs, Port::Two, |drivers| drivers.pads(bindings[1]));
+    one.into_iter().chain(two).flatten()
+}
+
+/// The number of pads connected, counting every pad behind a multitap.
+pub fn player_count() -> u8 {
+    super::super::with_cs::<1, 7, _>(|cs| pads(cs).count() as u8)
+}
+
+/// The pad used by `player`, counting from 0, or `None` if there aren't that many.
+pub fn player(player: u8) -> Option<PadInput> {
+    super::super::with_cs::<1, 7, _>(|cs| pads(cs).nth(player as usize))
+}
+
+/// The light gun on `port`, if one is bound there.
+pub fn gun(port: Port) -> Option<GunInput> {
+    super::super::with_cs::<1, 7, _>(|cs| {
+        let device = BINDINGS.borrow(cs).get()[port as usize];
+        matches!(device, Device::LightGun(_)).then(|| with_drivers(cs, port, |drivers| drivers.gun()))
+    })
+}
+
+/// The mouse on `port`, if one is bound there.
+pub fn mouse(port: Port) -> Option<MouseInput> {
+    super::super::with_cs::<1, 7, _>(|cs| {
+        let device = BINDINGS.borrow(cs).get()[port as usize];
+        (device == Device::Mouse).then(|| with_drivers(cs, port, |drivers| drivers.mouse()))
+    })
+}
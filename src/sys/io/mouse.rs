@@ -0,0 +1,187 @@
+use core::cell;
+use core::marker::PhantomData;
+
+use critical_section as cs;
+
+use super::{handshake_nibble, with_paused_z80, IOPort, Player1, Player2, Z80BusGuard};
+
+pub static P1_MOUSE: cs::Mutex<cell::Cell<MouseState<Player1>>> = cs::Mutex::new(cell::Cell::new(MouseState::new()));
+pub static P2_MOUSE: cs::Mutex<cell::Cell<MouseState<Player2>>> = cs::Mutex::new(cell::Cell::new(MouseState::new()));
+
+/// The state of a Sega Mouse connected to a controller port.
+///
+/// A port only speaks the mouse protocol after `init` has been called on its state. The vblank
+/// handler only polls it while the port is bound to `Device::Mouse` with `manager::bind`, which
+/// calls `init` and `shutdown` itself.
+#[derive(Clone, Copy)]
+pub struct MouseState<P: IOPort> {
+    dx: i16,
+    dy: i16,
+    flags: u8,
+    buttons: u8,
+    prev_buttons: u8,
+    enabled: bool,
+    connected: bool,
+    port: PhantomData<P>,
+}
+
+impl<P: IOPort> MouseState<P> {
+    const X_SIGN: u8 = 0x1;
+    const Y_SIGN: u8 = 0x2;
+    const X_OVERFLOW: u8 = 0x4;
+    const Y_OVERFLOW: u8 = 0x8;
+
+    pub const LEFT: u8 = 0x1;
+    pub const RIGHT: u8 = 0x2;
+    pub const MIDDLE: u8 = 0x4;
+    pub const START: u8 = 0x8;
+
+    pub const fn new() -> Self {
+        Self {
+            dx: 0,
+            dy: 0,
+            flags: 0,
+            buttons: 0,
+            prev_buttons: 0,
+            enabled: false,
+            connected: false,
+            port: PhantomData,
+        }
+    }
+
+    /// Configures the port for the mouse protocol, so `update` reads it.
+    pub fn init(mut self) -> Self {
+        with_paused_z80(|guard| {
+            P::configure(guard, 0x60); // TH and TR are outputs
+            P::write(guard, 0x60); // Idle state, TH = 1, TR = 1
+        });
+        self.enabled = true;
+        self
+    }
+
+    /// Stops reading the mouse. The port must be reinitialized by whichever driver takes it over next.
+    pub fn shutdown(mut self) -> Self {
+        self.enabled = false;
+        self.connected = false;
+        self.dx = 0;
+        self.dy = 0;
+        self.buttons = 0;
+        self.prev_buttons = 0;
+        self
+    }
+
+    #[inline(never)]
+    pub fn update(mut self) -> Self {
+        if !self.enabled {
+            return self;
+        }
+
+        self.prev_buttons = self.buttons;
+
+        match with_paused_z80(|guard| Self::read_packet(guard)) {
+            Some(packet) => {
+                self.connected = true;
+                self.flags = packet[3];
+                self.buttons = packet[4];
+
+                let x = ((packet[5] << 4) | packet[6]) as i16;
+                let y = ((packet[7] << 4) | packet[8]) as i16;
+                self.dx = if self.flags & Self::X_SIGN != 0 { x - 0x100 } else { x };
+                self.dy = if self.flags & Self::Y_SIGN != 0 { y - 0x100 } else { y };
+            },
+            None => {
+                self.connected = false;
+                self.flags = 0;
+                self.buttons = 0;
+                self.dx = 0;
+                self.dy = 0;
+            }
+        }
+
+        self
+    }
+
+    /// Performs the TH/TR handshake, reading the 9 nibbles the mouse sends per packet.
+    fn read_packet(guard: &Z80BusGuard) -> Option<[u8; 9]> {
+        let mut packet = [0u8; 9];
+
+        let mut i = 0usize;
+        while i < packet.len() {
+            packet[i] = handshake_nibble::<P>(guard, i)?;
+            i += 1;
+        }
+
+        P::write(guard, 0x60); // End the transfer
+
+        Some(packet)
+    }
+
+    /// Returns true if `init` has set the port up as a mouse.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns true if a mouse answered the last poll.
+    #[inline]
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// The horizontal movement since the last poll. Positive values move to the right.
+    #[inline]
+    pub fn dx(&self) -> i16 {
+        self.dx
+    }
+
+    /// The vertical movement since the last poll. Positive values move up, so this usually needs to
+    /// be negated before being applied to screen coordinates.
+    #[inline]
+    pub fn dy(&self) -> i16 {
+        self.dy
+    }
+
+    /// Returns true if the horizontal movement was too large to fit in the packet.
+    #[inline]
+    pub fn x_overflow(&self) -> bool {
+        self.flags & Self::X_OVERFLOW != 0
+    }
+
+    /// Returns true if the vertical movement was too large to fit in the packet.
+    #[inline]
+    pub fn y_overflow(&self) -> bool {
+        self.flags & Self::Y_OVERFLOW != 0
+    }
+
+    #[inline]
+    pub fn left(&self) -> bool {
+        self.buttons & Self::LEFT != 0
+    }
+
+    #[inline]
+    pub fn right(&self) -> bool {
+        self.buttons & Self::RIGHT != 0
+    }
+
+    #[inline]
+    pub fn middle(&self) -> bool {
+        self.buttons & Self::MIDDLE != 0
+    }
+
+    #[inline]
+    pub fn start(&self) -> bool {
+        self.buttons & Self::START != 0
+    }
+
+    /// The buttons held down, as a combination of `LEFT`, `RIGHT`, `MIDDLE` and `START`.
+    #[inline]
+    pub fn buttons(&self) -> u8 {
+        self.buttons
+    }
+
+    /// The buttons that were pressed during this poll, but not the one before it.
+    #[inline]
+    pub fn clicked(&self) -> u8 {
+        self.buttons & !self.prev_buttons
+    }
+}
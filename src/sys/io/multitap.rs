@@ -0,0 +1,197 @@
+use core::cell;
+use core::marker::PhantomData;
+
+use critical_section as cs;
+
+use super::{handshake_nibble, with_paused_z80, ControllerState, IOPort, Player1, Player2, Z80BusGuard};
+
+pub static P1_MULTITAP: cs::Mutex<cell::Cell<Multitap<Player1>>> = cs::Mutex::new(cell::Cell::new(Multitap::new()));
+pub static P2_MULTITAP: cs::Mutex<cell::Cell<Multitap<Player2>>> = cs::Mutex::new(cell::Cell::new(Multitap::new()));
+
+/// The kind of device plugged into one of the multitap's slots.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TapDevice {
+    ThreeButton = 0x0,
+    SixButton = 0x1,
+    Mouse = 0x2,
+    #[default]
+    None = 0xF,
+}
+
+impl TapDevice {
+    #[inline]
+    fn from_nibble(nibble: u8) -> Self {
+        match nibble {
+            0x0 => Self::ThreeButton,
+            0x1 => Self::SixButton,
+            0x2 => Self::Mouse,
+            _ => Self::None,
+        }
+    }
+
+    /// The number of nibbles this device sends per poll.
+    #[inline]
+    fn data_nibbles(self) -> usize {
+        match self {
+            Self::ThreeButton => 2,
+            Self::SixButton => 3,
+            Self::Mouse => 6,
+            Self::None => 0,
+        }
+    }
+}
+
+/// The state of a Sega Team Player connected to a controller port, with up to four pads behind it.
+///
+/// Like the mouse, the port only speaks the multitap protocol after `init` has been called on its
+/// state, and the vblank handler only polls it while the port is bound to `Device::Multitap` with
+/// `manager::bind`, which calls `init` and `shutdown` itself.
+#[derive(Clone, Copy)]
+pub struct Multitap<P: IOPort> {
+    devices: [TapDevice; 4],
+    current: [u16; 4],
+    previous: [u16; 4],
+    enabled: bool,
+    connected: bool,
+    port: PhantomData<P>,
+}
+
+impl<P: IOPort> Multitap<P> {
+    /// The ID the multitap reports on the low nibble while TH is high.
+    const ID: u8 = 0x3;
+
+    /// The fixed nibbles sent before the device list.
+    const HEADER: [u8; 3] = [0xF, 0x0, 0x0];
+
+    pub const fn new() -> Self {
+        Self {
+            devices: [TapDevice::None; 4],
+            current: [0; 4],
+            previous: [0; 4],
+            enabled: false,
+            connected: false,
+            port: PhantomData,
+        }
+    }
+
+    /// Configures the port for the multitap protocol, so `update` reads it.
+    pub fn init(mut self) -> Self {
+        with_paused_z80(|guard| {
+            P::configure(guard, 0x60); // TH and TR are outputs
+            P::write(guard, 0x60); // Idle state, TH = 1, TR = 1
+        });
+        self.enabled = true;
+        self
+    }
+
+    /// Stops reading the multitap. The port must be reinitialized by whichever driver takes it over next.
+    pub fn shutdown(mut self) -> Self {
+        self.enabled = false;
+        self.connected = false;
+        self.devices = [TapDevice::None; 4];
+        self.current = [0; 4];
+        self.previous = [0; 4];
+        self
+    }
+
+    #[inline(never)]
+    pub fn update(mut self) -> Self {
+        if !self.enabled {
+            return self;
+        }
+
+        self.previous = self.current;
+
+        if with_paused_z80(|guard| self.read_packet(guard)).is_none() {
+            self.connected = false;
+            self.devices = [TapDevice::None; 4];
+            self.current = [0; 4];
+        } else {
+            self.connected = true;
+        }
+
+        self
+    }
+
+    /// Performs the TH/TR handshake, enumerating the slots and reading every connected pad.
+    fn read_packet(&mut self, guard: &Z80BusGuard) -> Option<()> {
+        P::write(guard, 0x60);
+        if P::read(guard) & 0xF != Self::ID {
+            return None;
+        }
+
+        let mut index = 0usize;
+        let mut next = || {
+            let nibble = handshake_nibble::<P>(guard, index);
+            index += 1;
+            nibble
+        };
+
+        for expected in Self::HEADER {
+            if next()? != expected {
+                P::write(guard, 0x60);
+                return None;
+            }
+        }
+
+        for device in self.devices.iter_mut() {
+            *device = TapDevice::from_nibble(next()?);
+        }
+
+        for (slot, device) in self.devices.iter().enumerate() {
+            let mut bits = 0u16;
+            let mut i = 0usize;
+            while i < device.data_nibbles() {
+                bits |= (next()? as u16) << (i << 2);
+                i += 1;
+            }
+
+            // Pads send RLDU, SACB and MXYZ nibbles, active low, which is exactly the bit layout
+            // `ControllerState` uses. Mice are read to keep the handshake in step, but ignored.
+            self.current[slot] = match device {
+                TapDevice::ThreeButton => !bits & 0xFF,
+                TapDevice::SixButton => !bits & 0xFFF,
+                TapDevice::Mouse | TapDevice::None => 0,
+            };
+        }
+
+        P::write(guard, 0x60); // End the transfer
+
+        Some(())
+    }
+
+    /// Returns true if `init` has set the port up as a multitap.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns true if a multitap answered the last poll.
+    #[inline]
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// The kind of device plugged into `slot` (0 to 3, for A to D).
+    #[inline]
+    pub fn device(&self, slot: usize) -> TapDevice {
+        self.devices.get(slot).copied().unwrap_or_default()
+    }
+
+    /// The number of pads plugged into the multitap.
+    pub fn pad_count(&self) -> u8 {
+        self.devices.iter().filter(|device| matches!(device, TapDevice::ThreeButton | TapDevice::SixButton)).count() as u8
+    }
+}
+
+impl<P: IOPort + Copy> Multitap<P> {
+    /// The buttons of the pad plugged into `slot` (0 to 3, for A to D), or `None` if there isn't one.
+    #[inline]
+    pub fn pad(&self, slot: usize) -> Option<ControllerState<P>> {
+        match self.device(slot) {
+            TapDevice::ThreeButton | TapDevice::SixButton => Some(ControllerState(self.current[slot], self.previous[slot], PhantomData)),
+            TapDevice::Mouse | TapDevice::None => None,
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use core::cell;
+use core::marker::PhantomData;
+
+use critical_section as cs;
+
+use super::{IOPort, Z80BusGuard};
+
+/// The number of ports that can be claimed: both controller ports and the modem port.
+pub const PORT_COUNT: usize = 3;
+
+/// What a port has been claimed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortOwner {
+    Mouse,
+    Multitap,
+    LightGun,
+    Keyboard,
+    Serial,
+    /// Anything else, named for diagnostics.
+    Other(&'static str),
+}
+
+static PORT_OWNERS: cs::Mutex<cell::Cell<[Option<PortOwner>; PORT_COUNT]>> = cs::Mutex::new(cell::Cell::new([None; PORT_COUNT]));
+
+/// Exclusive use of an I/O port, released when dropped.
+///
+/// While a port is claimed, the controller poller leaves it alone, so whoever holds the handle is
+/// free to set its directions and serial mode as they see fit.
+pub struct PortHandle<P: IOPort> {
+    owner: PortOwner,
+    _port: PhantomData<P>,
+}
+
+/// Claims port `P` for `owner`, or returns whoever already has it.
+pub fn claim<P: IOPort>(owner: PortOwner) -> Result<PortHandle<P>, PortOwner> {
+    super::super::with_cs::<1, 7, _>(|cs| {
+        let cell = PORT_OWNERS.borrow(cs);
+        let mut owners = cell.get();
+        if let Some(current) = owners[P::INDEX as usize] {
+            return Err(current);
+        }
+        owners[P::INDEX as usize] = Some(owner);
+        cell.set(owners);
+        Ok(PortHandle { owner, _port: PhantomData })
+    })
+}
+
+/// Whoever has claimed port `P`, if anyone.
+#[inline]
+pub fn owner<P: IOPort>() -> Option<PortOwner> {
+    super::super::with_cs::<1, 7, _>(|cs| owner_in::<P>(cs))
+}
+
+#[inline]
+pub(super) fn owner_in<P: IOPort>(cs: cs::CriticalSection) -> Option<PortOwner> {
+    PORT_OWNERS.borrow(cs).get()[P::INDEX as usize]
+}
+
+impl<P: IOPort> PortHandle<P> {
+    #[inline]
+    pub fn owner(&self) -> PortOwner {
+        self.owner
+    }
+
+    /// Sets which data lines are outputs, along with the TH interrupt enable in bit 7.
+    #[inline]
+    pub fn configure(&self, guard: &Z80BusGuard, directions: u8) {
+        P::configure(guard, directions)
+    }
+
+    #[inline]
+    pub fn read(&self, guard: &Z80BusGuard) -> u8 {
+        P::read(guard)
+    }
+
+    #[inline]
+    pub fn write(&self, guard: &Z80BusGuard, value: u8) {
+        P::write(guard, value)
+    }
+
+    /// Gives the port back, so the controller poller takes it over again.
+    #[inline]
+    pub fn release(self) {}
+}
+
+impl<P: IOPort> Drop for PortHandle<P> {
+    fn drop(&mut self) {
+        super::super::with_cs::<1, 7, _>(|cs| {
+            let cell = PORT_OWNERS.borrow(cs);
+            let mut owners = cell.get();
+            owners[P::INDEX as usize] = None;
+            cell.set(owners);
+        })
+    }
+}
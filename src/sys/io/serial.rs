@@ -0,0 +1,242 @@
+use core::cell;
+
+use critical_section as cs;
+
+use super::port::{self, PortHandle, PortOwner};
+use super::{use_xint, with_paused_z80, IOPort};
+use crate::sys::collections::RingBuffer;
+use crate::sys::delay_us;
+
+/// The number of received bytes held until they're read. Bytes past that are dropped.
+pub const RX_BUFFER_SIZE: usize = 64;
+
+/// How long `write` waits between checks for room to send a byte.
+const TX_POLL_US: u16 = 100;
+
+const SOUT: u8 = 0x10;
+const SIN: u8 = 0x20;
+const RINT: u8 = 0x08;
+const RERR: u8 = 0x04;
+const RRDY: u8 = 0x02;
+const TFUL: u8 = 0x01;
+
+/// The speeds the serial port runs at, in the layout of the top two bits of the serial control
+/// register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Baud {
+    B4800 = 0b00,
+    B2400 = 0b01,
+    B1200 = 0b10,
+    B300 = 0b11,
+}
+
+impl Baud {
+    #[inline]
+    pub const fn bits_per_second(self) -> u16 {
+        match self {
+            Baud::B4800 => 4800,
+            Baud::B2400 => 2400,
+            Baud::B1200 => 1200,
+            Baud::B300 => 300,
+        }
+    }
+
+    /// How long a byte takes to send with its start and stop bits, in microseconds. That's about
+    /// 33ms at 300 baud.
+    #[inline]
+    pub const fn byte_us(self) -> u16 {
+        (10_000_000 / self.bits_per_second() as u32) as u16
+    }
+}
+
+/// Why a byte couldn't be sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The last byte hasn't gone out yet.
+    Busy,
+    /// The port stayed busy until `write` gave up, which usually means nothing is taking the bytes.
+    Timeout,
+}
+
+struct RxBuffer {
+    data: RingBuffer<u8, RX_BUFFER_SIZE>,
+    /// The port the buffer is filled from, or `None` while no port is open.
+    port: Option<u8>,
+    dropped: u16,
+    errors: u16,
+}
+
+static RX: cs::Mutex<cell::RefCell<RxBuffer>> = cs::Mutex::new(cell::RefCell::new(RxBuffer {
+    data: RingBuffer::new(),
+    port: None,
+    dropped: 0,
+    errors: 0,
+}));
+
+impl RxBuffer {
+    fn push(&mut self, byte: u8) {
+        if self.data.push_back(byte).is_err() {
+            self.dropped = self.dropped.saturating_add(1);
+        }
+    }
+}
+
+/// The serial port built into a controller port, for linking two consoles with a cable.
+///
+/// Bytes are sent one at a time, and received bytes raise the external interrupt, which moves them
+/// into a buffer of `RX_BUFFER_SIZE` bytes until they're read. Opening the port turns the external
+/// interrupt on in the VDP, and closing it turns it back off.
+///
+/// ```ignore
+/// let link = Serial::<Player2>::open(Baud::B4800).unwrap();
+/// link.write(&[frame as u8, input.buttons().bits() as u8]).unwrap();
+/// while let Some(byte) = link.read() {
+///     // Handle the other console's byte.
+/// }
+/// ```
+///
+/// Only one port can be open at a time, since they share the interrupt. The interrupt stays on
+/// while a light gun is using it too.
+pub struct Serial<P: IOPort> {
+    _handle: PortHandle<P>,
+    baud: Baud,
+}
+
+impl<P: IOPort> Serial<P> {
+    /// Claims port `P`, and starts its serial port at `baud`.
+    ///
+    /// Returns whoever already has the port if it's claimed. Another port already open for serial
+    /// shows up as `PortOwner::Serial`.
+    pub fn open(baud: Baud) -> Result<Self, PortOwner> {
+        let handle = port::claim::<P>(PortOwner::Serial)?;
+        let opened = crate::sys::with_cs::<1, 7, _>(|cs| {
+            let mut rx = RX.borrow_ref_mut(cs);
+            if rx.port.is_some() {
+                return false;
+            }
+            rx.port = Some(P::INDEX);
+            rx.data.clear();
+            rx.dropped = 0;
+            rx.errors = 0;
+            true
+        });
+        if !opened {
+            return Err(PortOwner::Serial);
+        }
+
+        with_paused_z80(|_| unsafe {
+            // Drain anything left over in the receive register.
+            core::ptr::read_volatile(P::RXDATA as *const u8);
+            core::ptr::write_volatile(P::SCTRL, ((baud as u8) << 6) | SIN | SOUT | RINT);
+        });
+        use_xint(true);
+        Ok(Self { _handle: handle, baud })
+    }
+
+    /// Sends a byte if the port has room for it.
+    pub fn try_send(&self, byte: u8) -> Result<(), Error> {
+        with_paused_z80(|_| unsafe {
+            if core::ptr::read_volatile(P::SCTRL as *const u8) & TFUL != 0 {
+                return Err(Error::Busy);
+            }
+            core::ptr::write_volatile(P::TXDATA, byte);
+            Ok(())
+        })
+    }
+
+    #[inline]
+    pub fn baud(&self) -> Baud {
+        self.baud
+    }
+
+    /// Sends `data`, waiting for each byte to go out before the next. Gives up on a byte if there's
+    /// no room for it after two bytes' time at the port's speed.
+    pub fn write(&self, data: &[u8]) -> Result<(), Error> {
+        let polls = 2 * self.baud.byte_us() as u32 / TX_POLL_US as u32 + 1;
+        for &byte in data {
+            let mut waited = 0;
+            while self.try_send(byte).is_err() {
+                if waited == polls {
+                    return Err(Error::Timeout);
+                }
+                delay_us(TX_POLL_US);
+                waited += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// The oldest byte received and not yet read.
+    #[inline]
+    pub fn read(&self) -> Option<u8> {
+        crate::sys::with_cs::<1, 7, _>(|cs| RX.borrow_ref_mut(cs).data.pop_front())
+    }
+
+    /// Reads received bytes into `buf`, and returns how many there were.
+    pub fn read_into(&self, buf: &mut [u8]) -> usize {
+        crate::sys::with_cs::<1, 7, _>(|cs| {
+            let mut rx = RX.borrow_ref_mut(cs);
+            let mut count = 0;
+            for slot in buf.iter_mut() {
+                let Some(byte) = rx.data.pop_front() else { break };
+                *slot = byte;
+                count += 1;
+            }
+            count
+        })
+    }
+
+    /// The number of received bytes waiting to be read.
+    #[inline]
+    pub fn available(&self) -> usize {
+        crate::sys::with_cs::<1, 7, _>(|cs| RX.borrow_ref(cs).data.len())
+    }
+
+    /// The number of bytes dropped because the buffer was full, and the number received with
+    /// framing errors, since the port was opened or this was last called.
+    pub fn take_errors(&self) -> (u16, u16) {
+        crate::sys::with_cs::<1, 7, _>(|cs| {
+            let mut rx = RX.borrow_ref_mut(cs);
+            let counts = (rx.dropped, rx.errors);
+            rx.dropped = 0;
+            rx.errors = 0;
+            counts
+        })
+    }
+
+    /// Stops the serial port and gives the controller port back.
+    #[inline]
+    pub fn close(self) {}
+}
+
+impl<P: IOPort> Drop for Serial<P> {
+    fn drop(&mut self) {
+        use_xint(false);
+        with_paused_z80(|_| unsafe { core::ptr::write_volatile(P::SCTRL, 0) });
+        crate::sys::with_cs::<1, 7, _>(|cs| RX.borrow_ref_mut(cs).port = None);
+    }
+}
+
+/// Moves a received byte into the buffer, from the external interrupt.
+pub(super) fn on_extint(cs: cs::CriticalSection) {
+    let mut rx = RX.borrow_ref_mut(cs);
+    let (sctrl, rxdata) = match rx.port {
+        Some(0) => (super::Player1::SCTRL, super::Player1::RXDATA),
+        Some(1) => (super::Player2::SCTRL, super::Player2::RXDATA),
+        Some(2) => (super::Modem::SCTRL, super::Modem::RXDATA),
+        _ => return,
+    };
+    let received = with_paused_z80(|_| unsafe {
+        let status = core::ptr::read_volatile(sctrl as *const u8);
+        if status & RRDY == 0 {
+            return None;
+        }
+        Some((core::ptr::read_volatile(rxdata as *const u8), status & RERR != 0))
+    });
+    match received {
+        Some((_, true)) => rx.errors = rx.errors.saturating_add(1),
+        Some((byte, false)) => rx.push(byte),
+        None => {}
+    }
+}
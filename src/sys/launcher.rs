@@ -0,0 +1,72 @@
+use super::vdp::{self, Address, VRAMAddress, Writer};
+#[cfg(feature = "audio")]
+use super::{audio, io};
+
+/// A program that a menu ROM can start, such as one demo in a compilation or one test in a suite.
+#[derive(Debug, Clone, Copy)]
+pub struct Program {
+    pub name: &'static str,
+    pub entry: fn() -> !,
+}
+
+impl Program {
+    #[inline]
+    pub const fn new(name: &'static str, entry: fn() -> !) -> Self {
+        Self { name, entry }
+    }
+
+    /// Starts the program. See `launch`.
+    #[inline]
+    pub fn launch(&self) -> ! {
+        launch(self.entry)
+    }
+}
+
+/// Starts `entry` as though the console had just been switched on, for ROMs that hold several
+/// programs, each with its own entry point, behind a menu.
+///
+/// The stack is thrown away and startup runs again, so `.data` and `.bss` go back to how they
+/// were, the heap is emptied, and the Z80 is reset. Then the VDP is cleared back to the default
+/// settings, with the display blank and every color black, and the sound chips are silenced. The
+/// program has the console to itself, and only needs to know it's been launched to get back to
+/// the menu, which it does by launching the menu's own entry point.
+pub fn launch(entry: fn() -> !) -> ! {
+    unsafe {
+        core::arch::asm!(
+            "move.w #0x2700,%sr",
+            "movea.l {entry},%a2", // A2 is kept across calls.
+            "move.l #0x1000000,%sp",
+            "jsr _init",
+            "jsr _launch_reset",
+            "jmp (%a2)",
+            entry = in(reg_addr) entry,
+            options(noreturn),
+        )
+    }
+}
+
+/// Puts the hardware that startup doesn't touch back to its defaults. Called by `launch` once the
+/// statics are fresh, so there's no queued DMA or handler left over to get in the way.
+#[no_mangle]
+fn _launch_reset() {
+    let mut blank = vdp::Settings::DEFAULT;
+    blank.enable_display(false);
+    blank.apply::<true>();
+
+    super::with_cs::<1, 7, _>(|_| {
+        let zeros = [0u16; 64];
+        for addr in (0..0x10000).step_by(128) {
+            Writer::new(Address::VRAM(VRAMAddress::from_byte_addr(addr))).with_autoinc(2).write::<[u16]>(&zeros);
+        }
+        Writer::new(Address::CRAM(0)).write::<[u16]>(&zeros);
+        Writer::new(Address::VSRAM(0)).write::<[u16]>(&zeros[..40]);
+    });
+
+    #[cfg(feature = "audio")]
+    {
+        io::with_paused_z80(audio::ym::key_off_all);
+        audio::psg::silence();
+    }
+
+    vdp::Settings::DEFAULT.apply::<true>();
+}
@@ -0,0 +1,51 @@
+use core::ops::Range;
+use core::ptr;
+
+/// The sizes from `[package.metadata.mdrs]` in Cargo.toml, written by the build script.
+mod config {
+    include!(concat!(env!("OUT_DIR"), "/layout.rs"));
+}
+
+extern "C" {
+    static mut _heap_start: u8;
+    static mut _heap_end: u8;
+    static mut _persistent_start: u8;
+}
+
+/// The end of the cartridge's ROM space, without a mapper.
+pub const ROM_END: usize = 0x400000;
+
+/// Work RAM, which ends at the top of the address space.
+pub const RAM_START: usize = 0xFF0000;
+pub const RAM_END: usize = 0x1000000;
+
+/// The top of the stack, where it starts from.
+pub const STACK_TOP: usize = RAM_END;
+/// The room the stack has, set with `stack-size`. 8KB unless changed.
+pub const STACK_SIZE: usize = config::STACK_SIZE;
+/// The lowest address the stack can grow down to.
+pub const STACK_BOTTOM: usize = STACK_TOP - STACK_SIZE;
+
+/// The size of the heap set with `heap-size`, or `None` if it takes everything between the
+/// statics and the stack.
+pub const HEAP_SIZE: Option<usize> = config::HEAP_SIZE;
+
+/// The size of the RAM set aside with `persistent-size` that's kept across resets.
+pub const PERSISTENT_SIZE: usize = config::PERSISTENT_SIZE;
+
+/// Where the heap is, as placed by the linker script or `heap_config!`.
+#[inline]
+pub fn heap() -> Range<usize> {
+    (&raw const _heap_start).addr()..(&raw const _heap_end).addr()
+}
+
+/// The RAM set aside with `persistent-size`, which is kept across resets like the `.noinit`
+/// statics. It holds garbage after the console is turned on, which `init::report` tells apart.
+///
+/// # Safety
+///
+/// Nothing else can be using the RAM while the slice is alive.
+#[inline]
+pub unsafe fn persistent() -> &'static mut [u8] {
+    &mut *ptr::slice_from_raw_parts_mut(&raw mut _persistent_start, PERSISTENT_SIZE)
+}
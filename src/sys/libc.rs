@@ -1,410 +1,344 @@
-
-
-// /// This code is shamelessly copied from compiler-builtins 
-// mod impls {
-//     const WORD_SIZE: usize = core::mem::size_of::<usize>();
-//     const WORD_MASK: usize = WORD_SIZE - 1;
-//     const WORD_COPY_THRESHOLD: usize = 2 * WORD_SIZE;
-
-//     /// Loads a `T`-sized chunk from `src` into `dst` at offset `offset`, if that does not exceed
-//     /// `load_sz`. The offset pointers must both be `T`-aligned. Returns the new offset, advanced by the
-//     /// chunk size if a load happened.
-//     #[inline(always)]
-//     unsafe fn load_chunk_aligned<T: Copy>(
-//         src: *const usize,
-//         dst: *mut usize,
-//         load_sz: usize,
-//         offset: usize,
-//     ) -> usize {
-//         let chunk_sz = core::mem::size_of::<T>();
-//         if (load_sz & chunk_sz) != 0 {
-//             *dst.wrapping_byte_add(offset).cast::<T>() = *src.wrapping_byte_add(offset).cast::<T>();
-//             offset | chunk_sz
-//         } else {
-//             offset
-//         }
-//     }
-
-//     /// Load `load_sz` many bytes from `src`, which must be usize-aligned. Acts as if we did a `usize`
-//     /// read with the out-of-bounds part filled with 0s.
-//     /// `load_sz` be strictly less than `WORD_SIZE`.
-//     #[inline(always)]
-//     unsafe fn load_aligned_partial(src: *const usize, load_sz: usize) -> usize {
-//         debug_assert!(load_sz < WORD_SIZE);
-//         // We can read up to 7 bytes here, which is enough for WORD_SIZE of 8
-//         // (since `load_sz < WORD_SIZE`).
-//         const { assert!(WORD_SIZE <= 8) };
-
-//         let mut i = 0;
-//         let mut out = 0usize;
-//         // We load in decreasing order, so the pointers remain sufficiently aligned for the next step.
-//         i = load_chunk_aligned::<u32>(src, &raw mut out, load_sz, i);
-//         i = load_chunk_aligned::<u16>(src, &raw mut out, load_sz, i);
-//         i = load_chunk_aligned::<u8>(src, &raw mut out, load_sz, i);
-//         debug_assert!(i == load_sz);
-//         out
-//     }
-
-//     /// Load `load_sz` many bytes from `src.wrapping_byte_add(WORD_SIZE - load_sz)`. `src` must be
-//     /// `usize`-aligned. The bytes are returned as the *last* bytes of the return value, i.e., this acts
-//     /// as if we had done a `usize` read from `src`, with the out-of-bounds part filled with 0s.
-//     /// `load_sz` be strictly less than `WORD_SIZE`.
-//     #[inline(always)]
-//     unsafe fn load_aligned_end_partial(src: *const usize, load_sz: usize) -> usize {
-//         debug_assert!(load_sz < WORD_SIZE);
-//         // We can read up to 7 bytes here, which is enough for WORD_SIZE of 8
-//         // (since `load_sz < WORD_SIZE`).
-//         const { assert!(WORD_SIZE <= 8) };
-
-//         let mut i = 0;
-//         let mut out = 0usize;
-//         // Obtain pointers pointing to the beginning of the range we want to load.
-//         let src_shifted = src.wrapping_byte_add(WORD_SIZE - load_sz);
-//         let out_shifted = (&raw mut out).wrapping_byte_add(WORD_SIZE - load_sz);
-//         // We load in increasing order, so by the time we reach `u16` things are 2-aligned etc.
-//         i = load_chunk_aligned::<u8>(src_shifted, out_shifted, load_sz, i);
-//         i = load_chunk_aligned::<u16>(src_shifted, out_shifted, load_sz, i);
-//         i = load_chunk_aligned::<u32>(src_shifted, out_shifted, load_sz, i);
-//         debug_assert!(i == load_sz);
-//         out
-//     }
-
-//     #[inline(always)]
-//     pub unsafe fn copy_forward(mut dest: *mut u8, mut src: *const u8, mut n: usize) {
-//         #[inline(always)]
-//         unsafe fn copy_forward_bytes(mut dest: *mut u8, mut src: *const u8, n: usize) {
-//             let dest_end = dest.wrapping_add(n);
-//             while dest < dest_end {
-//                 *dest = *src;
-//                 dest = dest.wrapping_add(1);
-//                 src = src.wrapping_add(1);
-//             }
-//         }
-
-//         #[inline(always)]
-//         unsafe fn copy_forward_aligned_words(dest: *mut u8, src: *const u8, n: usize) {
-//             let mut dest_usize = dest as *mut usize;
-//             let mut src_usize = src as *mut usize;
-//             let dest_end = dest.wrapping_add(n) as *mut usize;
-
-//             while dest_usize < dest_end {
-//                 *dest_usize = *src_usize;
-//                 dest_usize = dest_usize.wrapping_add(1);
-//                 src_usize = src_usize.wrapping_add(1);
-//             }
-//         }
-
-//         /// `n` is in units of bytes, but must be a multiple of the word size and must not be 0.
-//         /// `src` *must not* be `usize`-aligned.
-//         #[inline(always)]
-//         unsafe fn copy_forward_misaligned_words(dest: *mut u8, src: *const u8, n: usize) {
-//             debug_assert!(n > 0 && n % WORD_SIZE == 0);
-//             debug_assert!(src.addr() % WORD_SIZE != 0);
-
-//             let mut dest_usize = dest as *mut usize;
-//             let dest_end = dest.wrapping_add(n) as *mut usize;
-
-//             // Calculate the misalignment offset and shift needed to reassemble value.
-//             // Since `src` is definitely not aligned, `offset` is in the range 1..WORD_SIZE.
-//             let offset = src as usize & WORD_MASK;
-//             let shift = offset * 8;
-
-//             // Realign src
-//             let mut src_aligned = src.wrapping_byte_sub(offset) as *mut usize;
-//             let mut prev_word = load_aligned_end_partial(src_aligned, WORD_SIZE - offset);
-
-//             while dest_usize.wrapping_add(1) < dest_end {
-//                 src_aligned = src_aligned.wrapping_add(1);
-//                 let cur_word = *src_aligned;
-//                 let reassembled = if cfg!(target_endian = "little") {
-//                     prev_word >> shift | cur_word << (WORD_SIZE * 8 - shift)
-//                 } else {
-//                     prev_word << shift | cur_word >> (WORD_SIZE * 8 - shift)
-//                 };
-//                 prev_word = cur_word;
-
-//                 *dest_usize = reassembled;
-//                 dest_usize = dest_usize.wrapping_add(1);
-//             }
-
-//             // There's one more element left to go, and we can't use the loop for that as on the `src` side,
-//             // it is partially out-of-bounds.
-//             src_aligned = src_aligned.wrapping_add(1);
-//             let cur_word = load_aligned_partial(src_aligned, offset);
-//             let reassembled = if cfg!(target_endian = "little") {
-//                 prev_word >> shift | cur_word << (WORD_SIZE * 8 - shift)
-//             } else {
-//                 prev_word << shift | cur_word >> (WORD_SIZE * 8 - shift)
-//             };
-//             // prev_word does not matter any more
-
-//             *dest_usize = reassembled;
-//             // dest_usize does not matter any more
-//         }
-
-//         if n >= WORD_COPY_THRESHOLD {
-//             // Align dest
-//             // Because of n >= 2 * WORD_SIZE, dst_misalignment < n
-//             let dest_misalignment = (dest as usize).wrapping_neg() & WORD_MASK;
-//             copy_forward_bytes(dest, src, dest_misalignment);
-//             dest = dest.wrapping_add(dest_misalignment);
-//             src = src.wrapping_add(dest_misalignment);
-//             n -= dest_misalignment;
-
-//             let n_words = n & !WORD_MASK;
-//             let src_misalignment = src as usize & WORD_MASK;
-//             if core::hint::likely(src_misalignment == 0) {
-//                 copy_forward_aligned_words(dest, src, n_words);
-//             } else {
-//                 copy_forward_misaligned_words(dest, src, n_words);
-//             }
-//             dest = dest.wrapping_add(n_words);
-//             src = src.wrapping_add(n_words);
-//             n -= n_words;
-//         }
-//         copy_forward_bytes(dest, src, n);
-//     }
-
-//     #[inline(always)]
-//     pub unsafe fn copy_backward(dest: *mut u8, src: *const u8, mut n: usize) {
-//         // The following backward copy helper functions uses the pointers past the end
-//         // as their inputs instead of pointers to the start!
-//         #[inline(always)]
-//         unsafe fn copy_backward_bytes(mut dest: *mut u8, mut src: *const u8, n: usize) {
-//             let dest_start = dest.wrapping_sub(n);
-//             while dest_start < dest {
-//                 dest = dest.wrapping_sub(1);
-//                 src = src.wrapping_sub(1);
-//                 *dest = *src;
-//             }
-//         }
-
-//         #[inline(always)]
-//         unsafe fn copy_backward_aligned_words(dest: *mut u8, src: *const u8, n: usize) {
-//             let mut dest_usize = dest as *mut usize;
-//             let mut src_usize = src as *mut usize;
-//             let dest_start = dest.wrapping_sub(n) as *mut usize;
-
-//             while dest_start < dest_usize {
-//                 dest_usize = dest_usize.wrapping_sub(1);
-//                 src_usize = src_usize.wrapping_sub(1);
-//                 *dest_usize = *src_usize;
-//             }
-//         }
-
-//         /// `n` is in units of bytes, but must be a multiple of the word size and must not be 0.
-//         /// `src` *must not* be `usize`-aligned.
-//         #[inline(always)]
-//         unsafe fn copy_backward_misaligned_words(dest: *mut u8, src: *const u8, n: usize) {
-//             debug_assert!(n > 0 && n % WORD_SIZE == 0);
-//             debug_assert!(src.addr() % WORD_SIZE != 0);
-
-//             let mut dest_usize = dest as *mut usize;
-//             let dest_start = dest.wrapping_sub(n) as *mut usize; // we're moving towards the start
-
-//             // Calculate the misalignment offset and shift needed to reassemble value.
-//             // Since `src` is definitely not aligned, `offset` is in the range 1..WORD_SIZE.
-//             let offset = src as usize & WORD_MASK;
-//             let shift = offset * 8;
-
-//             // Realign src
-//             let mut src_aligned = src.wrapping_byte_sub(offset) as *mut usize;
-//             let mut prev_word = load_aligned_partial(src_aligned, offset);
-
-//             while dest_start.wrapping_add(1) < dest_usize {
-//                 src_aligned = src_aligned.wrapping_sub(1);
-//                 let cur_word = *src_aligned;
-//                 let reassembled = if cfg!(target_endian = "little") {
-//                     prev_word << (WORD_SIZE * 8 - shift) | cur_word >> shift
-//                 } else {
-//                     prev_word >> (WORD_SIZE * 8 - shift) | cur_word << shift
-//                 };
-//                 prev_word = cur_word;
-
-//                 dest_usize = dest_usize.wrapping_sub(1);
-//                 *dest_usize = reassembled;
-//             }
-
-//             // There's one more element left to go, and we can't use the loop for that as on the `src` side,
-//             // it is partially out-of-bounds.
-//             src_aligned = src_aligned.wrapping_sub(1);
-//             let cur_word = load_aligned_end_partial(src_aligned, WORD_SIZE - offset);
-//             let reassembled = if cfg!(target_endian = "little") {
-//                 prev_word << (WORD_SIZE * 8 - shift) | cur_word >> shift
-//             } else {
-//                 prev_word >> (WORD_SIZE * 8 - shift) | cur_word << shift
-//             };
-//             // prev_word does not matter any more
-
-//             dest_usize = dest_usize.wrapping_sub(1);
-//             *dest_usize = reassembled;
-//         }
-
-//         let mut dest = dest.wrapping_add(n);
-//         let mut src = src.wrapping_add(n);
-
-//         if n >= WORD_COPY_THRESHOLD {
-//             // Align dest
-//             // Because of n >= 2 * WORD_SIZE, dst_misalignment < n
-//             let dest_misalignment = dest as usize & WORD_MASK;
-//             copy_backward_bytes(dest, src, dest_misalignment);
-//             dest = dest.wrapping_sub(dest_misalignment);
-//             src = src.wrapping_sub(dest_misalignment);
-//             n -= dest_misalignment;
-
-//             let n_words = n & !WORD_MASK;
-//             let src_misalignment = src as usize & WORD_MASK;
-//             if core::hint::likely(src_misalignment == 0) {
-//                 copy_backward_aligned_words(dest, src, n_words);
-//             } else {
-//                 copy_backward_misaligned_words(dest, src, n_words);
-//             }
-//             dest = dest.wrapping_sub(n_words);
-//             src = src.wrapping_sub(n_words);
-//             n -= n_words;
-//         }
-//         copy_backward_bytes(dest, src, n);
-//     }
-
-//     #[inline(always)]
-//     pub unsafe fn set_bytes(mut s: *mut u8, c: u8, mut n: usize) {
-//         #[inline(always)]
-//         pub unsafe fn set_bytes_bytes(mut s: *mut u8, c: u8, n: usize) {
-//             let end = s.wrapping_add(n);
-//             while s < end {
-//                 *s = c;
-//                 s = s.wrapping_add(1);
-//             }
-//         }
-
-//         #[inline(always)]
-//         pub unsafe fn set_bytes_words(s: *mut u8, c: u8, n: usize) {
-//             let mut broadcast = c as usize;
-//             let mut bits = 8;
-//             while bits < WORD_SIZE * 8 {
-//                 broadcast |= broadcast << bits;
-//                 bits <<= 1;
-//             }
-
-//             let mut s_usize = s as *mut usize;
-//             let end = s.wrapping_add(n) as *mut usize;
-
-//             while s_usize < end {
-//                 *s_usize = broadcast;
-//                 s_usize = s_usize.wrapping_add(1);
-//             }
-//         }
-
-//         if core::hint::likely(n >= WORD_COPY_THRESHOLD) {
-//             // Align s
-//             // Because of n >= 2 * WORD_SIZE, dst_misalignment < n
-//             let misalignment = (s as usize).wrapping_neg() & WORD_MASK;
-//             set_bytes_bytes(s, c, misalignment);
-//             s = s.wrapping_add(misalignment);
-//             n -= misalignment;
-
-//             let n_words = n & !WORD_MASK;
-//             set_bytes_words(s, c, n_words);
-//             s = s.wrapping_add(n_words);
-//             n -= n_words;
-//         }
-//         set_bytes_bytes(s, c, n);
-//     }
-
-//     #[inline(always)]
-//     pub unsafe fn compare_bytes(s1: *const u8, s2: *const u8, n: usize) -> u8 {
-//         let mut i = 0;
-//         while i < n {
-//             let a = *s1.wrapping_add(i);
-//             let b = *s2.wrapping_add(i);
-//             if a != b {
-//                 return a - b;
-//             }
-//             i += 1;
-//         }
-//         0
-//     }
-
-//     #[inline(always)]
-//     pub unsafe fn c_string_length(mut s: *const core::ffi::c_char) -> usize {
-//         let mut n = 0;
-//         while *s != 0 {
-//             n += 1;
-//             s = s.wrapping_add(1);
-//         }
-//         n
-//     }
-// }
-
-// #[no_mangle]
-// pub unsafe extern "C" fn memcpy(dest: *mut u8, src: *const u8, n: usize) -> *mut u8 {
-//     impls::copy_forward(dest, src, n);
-//     dest
-// }
-
-// #[no_mangle]
-// pub unsafe extern "C" fn memmove(dest: *mut u8, src: *const u8, n: usize) -> *mut u8 {
-//     if (dest as usize).wrapping_sub(src as usize) >= n {
-//         impls::copy_forward(dest, src, n);
-//     } else {
-//         impls::copy_backward(dest, src, n);
-//     }
-//     dest
-// }
-
-// #[no_mangle]
-// pub unsafe extern "C" fn memset(s: *mut u8, c: core::ffi::c_int, n: usize) -> *mut u8 {
-//     impls::set_bytes(s, c as u8, n);
-//     s
-// }
-
-// #[no_mangle]
-// pub unsafe extern "C" fn memcmp(s1: *const u8, s2: *const u8, n: usize) -> core::ffi::c_int {
-//     impls::compare_bytes(s1, s2, n) as core::ffi::c_int
-// }
-
-// #[no_mangle]
-// pub unsafe extern "C" fn bcmp(s1: *const u8, s2: *const u8, n: usize) -> core::ffi::c_int {
-//     memcmp(s1, s2, n)
-// }
-
-// #[no_mangle]
-// pub unsafe extern "C" fn strlen(s: *const core::ffi::c_char) -> usize {
-//     impls::c_string_length(s)
-// }
-
-// #[no_mangle]
-// pub unsafe extern "C" fn abort() -> ! {
-//     extern "C" {
-//         fn _trap() -> !; // Hook into header trap
-//     }
-//     _trap()
-// }
-
-// #[no_mangle]
-// pub unsafe extern "C" fn __mulsi3(a: u32, b: u32) -> u32 {
-//     if a == 0 || b == 0 {
-//         return 0;
-//     }
-
-//     let al = a as u16;
-//     let ah = ((a as u32) >> 16) as u16;
-//     let bl = b as u16;
-//     let bh = ((b as u32) >> 16) as u16;
-    
-//     let (v0, c0) = al.widening_mul(bl);
-//     let v1 = ah.wrapping_mul(bl);
-//     let v2 = al.wrapping_mul(bh);
-
-//     (
-//         v0 as u32
-//     ).wrapping_add(
-//         (
-//             v1 as u32
-//         ).wrapping_add(
-//             v2 as u32
-//         ).wrapping_add(
-//             c0 as u32
-//         ) << 16
-//     )
-// }
\ No newline at end of file
+// Multiplication and division intrinsics, which the compiler calls for 32 bit `*`, `/` and `%`.
+//
+// The 68000 only multiplies and divides words, so these build the long versions out of as few
+// `mulu.w` and `divu.w` as they can. They follow the C calling convention, with arguments on the
+// stack, results in D0, and D2 saved.
+core::arch::global_asm!(
+    ".global __mulsi3",
+    "__mulsi3:",
+    // Only the low words of the cross products matter, and they land in the high word.
+    "    move.w  4(%sp),%d0",
+    "    mulu.w  10(%sp),%d0",
+    "    move.w  8(%sp),%d1",
+    "    mulu.w  6(%sp),%d1",
+    "    add.w   %d1,%d0",
+    "    swap    %d0",
+    "    clr.w   %d0",
+    "    move.w  6(%sp),%d1",
+    "    mulu.w  10(%sp),%d1",
+    "    add.l   %d1,%d0",
+    "    rts",
+
+    ".global __udivsi3",
+    "__udivsi3:",
+    "    move.l  %d2,-(%sp)",
+    "    movem.l 8(%sp),%d0-%d1",
+    "    bsr.s   .Ludivmod",
+    "    move.l  (%sp)+,%d2",
+    "    rts",
+
+    ".global __umodsi3",
+    "__umodsi3:",
+    "    move.l  %d2,-(%sp)",
+    "    movem.l 8(%sp),%d0-%d1",
+    "    bsr.s   .Ludivmod",
+    "    move.l  %d1,%d0",
+    "    move.l  (%sp)+,%d2",
+    "    rts",
+
+    // The quotient is negative when the signs differ.
+    ".global __divsi3",
+    "__divsi3:",
+    "    move.l  %d2,-(%sp)",
+    "    movem.l 8(%sp),%d0-%d1",
+    "    tst.l   %d0",
+    "    bpl.s   1f",
+    "    neg.l   %d0",
+    "1:  tst.l   %d1",
+    "    bpl.s   2f",
+    "    neg.l   %d1",
+    "2:  bsr.s   .Ludivmod",
+    "    move.l  8(%sp),%d2",
+    "    move.l  12(%sp),%d1",
+    "    eor.l   %d1,%d2",
+    "    bpl.s   3f",
+    "    neg.l   %d0",
+    "3:  move.l  (%sp)+,%d2",
+    "    rts",
+
+    // The remainder takes the sign of the dividend.
+    ".global __modsi3",
+    "__modsi3:",
+    "    move.l  %d2,-(%sp)",
+    "    movem.l 8(%sp),%d0-%d1",
+    "    tst.l   %d0",
+    "    bpl.s   1f",
+    "    neg.l   %d0",
+    "1:  tst.l   %d1",
+    "    bpl.s   2f",
+    "    neg.l   %d1",
+    "2:  bsr.s   .Ludivmod",
+    "    move.l  %d1,%d0",
+    "    tst.l   8(%sp)",
+    "    bpl.s   3f",
+    "    neg.l   %d0",
+    "3:  move.l  (%sp)+,%d2",
+    "    rts",
+
+    // Divides D0 by D1, leaving the quotient in D0 and the remainder in D1. Trashes D2, A0 and A1.
+    ".Ludivmod:",
+    "    cmpi.l  #0x10000,%d1",
+    "    bcc.s   1f",
+    // A word divisor takes two divides, the high word of the dividend and then the low word with
+    // the first remainder above it.
+    "    move.l  %d0,%d2",
+    "    clr.w   %d2",
+    "    swap    %d2",
+    "    divu.w  %d1,%d2",
+    "    move.l  %d2,%a0",
+    "    move.w  %d0,%d2",
+    "    divu.w  %d1,%d2",
+    "    move.l  %a0,%d0",
+    "    swap    %d0",
+    "    move.w  %d2,%d0",
+    "    clr.w   %d2",
+    "    swap    %d2",
+    "    move.l  %d2,%d1",
+    "    rts",
+    // A long divisor means the quotient fits in a word. Shifting both down until the divisor fits
+    // in a word gives it, or one more than it.
+    "1:  move.l  %d0,%a0",
+    "    move.l  %d1,%a1",
+    "2:  lsr.l   #1,%d1",
+    "    lsr.l   #1,%d0",
+    "    cmpi.l  #0x10000,%d1",
+    "    bcc.s   2b",
+    "    divu.w  %d1,%d0",
+    "    andi.l  #0xFFFF,%d0",
+    // The remainder is the dividend less the quotient times the divisor. If the product is bigger
+    // than the dividend, which it can be by a bit more than 32 bits, the quotient was one too many.
+    "3:  move.l  %a1,%d1",
+    "    mulu.w  %d0,%d1",
+    "    move.l  %a1,%d2",
+    "    swap    %d2",
+    "    mulu.w  %d0,%d2",
+    "    swap    %d2",
+    "    tst.w   %d2",
+    "    bne.s   4f",
+    "    add.l   %d2,%d1",
+    "    bcs.s   4f",
+    "    move.l  %a0,%d2",
+    "    sub.l   %d1,%d2",
+    "    bcc.s   5f",
+    "4:  subq.l  #1,%d0",
+    "    bra.s   3b",
+    "5:  move.l  %d2,%d1",
+    "    rts",
+);
+
+// Memory intrinsics, which the compiler calls for copies, fills and comparisons of more than a few
+// bytes, such as staging buffers for VRAM.
+//
+// Copies and fills of 64 bytes or more go 48 bytes at a time through `movem.l`, which moves twelve
+// longs for about the cost of seven `move.l`. That needs both pointers to be even, or both odd
+// once one byte has been moved, so copies between pointers of different alignment go a byte at a
+// time, since the 68000 can't access words at odd addresses.
+core::arch::global_asm!(
+    ".global memcpy",
+    "memcpy:",
+    "    movea.l 4(%sp),%a0",
+    "    movea.l 8(%sp),%a1",
+    "    move.l  12(%sp),%d1",
+    "    bsr.s   .Lcopy_forward",
+    "    move.l  4(%sp),%d0",
+    "    movea.l %d0,%a0",
+    "    rts",
+
+    // Copies backwards when the destination starts inside the source, so the source is read
+    // before it's overwritten.
+    ".global memmove",
+    "memmove:",
+    "    movea.l 4(%sp),%a0",
+    "    movea.l 8(%sp),%a1",
+    "    move.l  12(%sp),%d1",
+    "    move.l  %a0,%d0",
+    "    sub.l   %a1,%d0",
+    "    cmp.l   %d1,%d0",
+    "    bcs.s   1f",
+    "    bsr.s   .Lcopy_forward",
+    "    bra.s   2f",
+    "1:  adda.l  %d1,%a0",
+    "    adda.l  %d1,%a1",
+    "    bsr.w   .Lcopy_backward",
+    "2:  move.l  4(%sp),%d0",
+    "    movea.l %d0,%a0",
+    "    rts",
+
+    // Copies D1 bytes from A1 to A0, leaving both pointers at the end. Trashes D0.
+    ".Lcopy_forward:",
+    // The pointers have the same alignment when their sum is even.
+    "    move.l  %a0,%d0",
+    "    add.l   %a1,%d0",
+    "    btst    #0,%d0",
+    "    bne.s   6f",
+    "    move.l  %a0,%d0",
+    "    btst    #0,%d0",
+    "    beq.s   1f",
+    "    tst.l   %d1",
+    "    beq.s   7f",
+    "    move.b  (%a1)+,(%a0)+",
+    "    subq.l  #1,%d1",
+    "1:  moveq   #64,%d0",
+    "    cmp.l   %d0,%d1",
+    "    bcs.s   3f",
+    "    movem.l %d2-%d7/%a2-%a6,-(%sp)",
+    "2:  movem.l (%a1)+,%d0/%d2-%d7/%a2-%a6",
+    "    movem.l %d0/%d2-%d7/%a2-%a6,(%a0)",
+    "    lea     48(%a0),%a0",
+    "    moveq   #48,%d0",
+    "    sub.l   %d0,%d1",
+    "    cmp.l   %d0,%d1",
+    "    bcc.s   2b",
+    "    movem.l (%sp)+,%d2-%d7/%a2-%a6",
+    // Fewer than 64 bytes are left, so the rest can be counted in words.
+    "3:  move.w  %d1,%d0",
+    "    lsr.w   #2,%d0",
+    "    bra.s   5f",
+    "4:  move.l  (%a1)+,(%a0)+",
+    "5:  dbra    %d0,4b",
+    "    btst    #1,%d1",
+    "    beq.s   8f",
+    "    move.w  (%a1)+,(%a0)+",
+    "8:  btst    #0,%d1",
+    "    beq.s   7f",
+    "    move.b  (%a1)+,(%a0)+",
+    "    rts",
+    "6:  tst.l   %d1",
+    "    beq.s   7f",
+    "9:  move.b  (%a1)+,(%a0)+",
+    "    subq.l  #1,%d1",
+    "    bne.s   9b",
+    "7:  rts",
+
+    // Copies D1 bytes backwards from the end of A1 to the end of A0, which the pointers start at.
+    // Trashes D0.
+    ".Lcopy_backward:",
+    "    move.l  %a0,%d0",
+    "    add.l   %a1,%d0",
+    "    btst    #0,%d0",
+    "    bne.s   6f",
+    "    move.l  %a0,%d0",
+    "    btst    #0,%d0",
+    "    beq.s   1f",
+    "    tst.l   %d1",
+    "    beq.s   7f",
+    "    move.b  -(%a1),-(%a0)",
+    "    subq.l  #1,%d1",
+    "1:  moveq   #64,%d0",
+    "    cmp.l   %d0,%d1",
+    "    bcs.s   3f",
+    "    movem.l %d2-%d7/%a2-%a6,-(%sp)",
+    "2:  lea     -48(%a1),%a1",
+    "    movem.l (%a1),%d0/%d2-%d7/%a2-%a6",
+    "    movem.l %d0/%d2-%d7/%a2-%a6,-(%a0)",
+    "    moveq   #48,%d0",
+    "    sub.l   %d0,%d1",
+    "    cmp.l   %d0,%d1",
+    "    bcc.s   2b",
+    "    movem.l (%sp)+,%d2-%d7/%a2-%a6",
+    "3:  move.w  %d1,%d0",
+    "    lsr.w   #2,%d0",
+    "    bra.s   5f",
+    "4:  move.l  -(%a1),-(%a0)",
+    "5:  dbra    %d0,4b",
+    "    btst    #1,%d1",
+    "    beq.s   8f",
+    "    move.w  -(%a1),-(%a0)",
+    "8:  btst    #0,%d1",
+    "    beq.s   7f",
+    "    move.b  -(%a1),-(%a0)",
+    "    rts",
+    "6:  tst.l   %d1",
+    "    beq.s   7f",
+    "9:  move.b  -(%a1),-(%a0)",
+    "    subq.l  #1,%d1",
+    "    bne.s   9b",
+    "7:  rts",
+
+    ".global memset",
+    "memset:",
+    "    movea.l 4(%sp),%a0",
+    // The value goes in every byte of D0.
+    "    move.b  11(%sp),%d0",
+    "    lsl.w   #8,%d0",
+    "    move.b  11(%sp),%d0",
+    "    move.w  %d0,%d1",
+    "    swap    %d0",
+    "    move.w  %d1,%d0",
+    "    move.l  12(%sp),%d1",
+    "    btst    #0,7(%sp)",
+    "    beq.s   1f",
+    "    tst.l   %d1",
+    "    beq.s   7f",
+    "    move.b  %d0,(%a0)+",
+    "    subq.l  #1,%d1",
+    "1:  cmpi.l  #64,%d1",
+    "    bcs.s   3f",
+    "    movem.l %d2-%d7/%a2-%a6,-(%sp)",
+    "    move.l  %d0,%d2",
+    "    move.l  %d0,%d3",
+    "    move.l  %d0,%d4",
+    "    move.l  %d0,%d5",
+    "    move.l  %d0,%d6",
+    "    move.l  %d0,%d7",
+    "    movea.l %d0,%a2",
+    "    movea.l %d0,%a3",
+    "    movea.l %d0,%a4",
+    "    movea.l %d0,%a5",
+    "    movea.l %d0,%a6",
+    "2:  movem.l %d0/%d2-%d7/%a2-%a6,(%a0)",
+    "    lea     48(%a0),%a0",
+    "    subi.l  #48,%d1",
+    "    cmpi.l  #48,%d1",
+    "    bcc.s   2b",
+    "    movem.l (%sp)+,%d2-%d7/%a2-%a6",
+    "    bra.s   3f",
+    "4:  move.l  %d0,(%a0)+",
+    "    subq.w  #4,%d1",
+    "3:  cmpi.w  #4,%d1",
+    "    bcc.s   4b",
+    "    btst    #1,%d1",
+    "    beq.s   5f",
+    "    move.w  %d0,(%a0)+",
+    "5:  btst    #0,%d1",
+    "    beq.s   7f",
+    "    move.b  %d0,(%a0)+",
+    "7:  move.l  4(%sp),%d0",
+    "    movea.l %d0,%a0",
+    "    rts",
+
+    // Compares a long at a time when both pointers are even, which works because the 68000 is big
+    // endian, so the first byte that differs decides which long is bigger.
+    ".global memcmp",
+    ".global bcmp",
+    "memcmp:",
+    "bcmp:",
+    "    movea.l 4(%sp),%a0",
+    "    movea.l 8(%sp),%a1",
+    "    move.l  12(%sp),%d1",
+    "    move.l  %a0,%d0",
+    "    btst    #0,%d0",
+    "    bne.s   3f",
+    "    move.l  %a1,%d0",
+    "    btst    #0,%d0",
+    "    bne.s   3f",
+    "    bra.s   2f",
+    "1:  cmpm.l  (%a1)+,(%a0)+",
+    "    bne.s   5f",
+    "2:  subq.l  #4,%d1",
+    "    bcc.s   1b",
+    "    addq.l  #4,%d1",
+    "3:  tst.l   %d1",
+    "    beq.s   6f",
+    "4:  cmpm.b  (%a1)+,(%a0)+",
+    "    bne.s   5f",
+    "    subq.l  #1,%d1",
+    "    bne.s   4b",
+    "6:  moveq   #0,%d0",
+    "    rts",
+    "5:  bhi.s   7f",
+    "    moveq   #-1,%d0",
+    "    rts",
+    "7:  moveq   #1,%d0",
+    "    rts",
+);
@@ -0,0 +1,188 @@
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+
+/// An immutable map stored in ROM as an array of `(key, value)` pairs, sorted by key.
+///
+/// Lookups are binary searches, so there's no heap usage and no hashing. Maps are normally built
+/// with `rom_map!`, which checks that the keys are sorted at compile time.
+#[derive(Clone, Copy)]
+pub struct RomMap<K: 'static, V: 'static> {
+    entries: &'static [(K, V)],
+}
+
+impl<K: 'static, V: 'static> RomMap<K, V> {
+    /// Wraps an array of entries. They must be sorted by key with no duplicates, otherwise lookups
+    /// may fail.
+    pub const fn new_unchecked(entries: &'static [(K, V)]) -> Self {
+        Self { entries }
+    }
+
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[inline]
+    pub const fn entries(&self) -> &'static [(K, V)] {
+        self.entries
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &'static (K, V)> {
+        self.entries.iter()
+    }
+
+    #[inline]
+    pub fn keys(&self) -> impl Iterator<Item = &'static K> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+}
+
+impl<K: Ord + 'static, V: 'static> RomMap<K, V> {
+    /// The position of `key` in the map, which makes for a compact id.
+    pub fn index_of<Q: Ord + ?Sized>(&self, key: &Q) -> Option<usize> where K: Borrow<Q> {
+        self.entries.binary_search_by(|(k, _)| k.borrow().cmp(key)).ok()
+    }
+
+    #[inline]
+    pub fn get<Q: Ord + ?Sized>(&self, key: &Q) -> Option<&'static V> where K: Borrow<Q> {
+        self.index_of(key).map(|index| &self.entries[index].1)
+    }
+
+    #[inline]
+    pub fn contains_key<Q: Ord + ?Sized>(&self, key: &Q) -> bool where K: Borrow<Q> {
+        self.index_of(key).is_some()
+    }
+}
+
+/// An immutable table stored in ROM, indexed directly by a dense id.
+#[derive(Clone, Copy)]
+pub struct RomTable<T: 'static> {
+    entries: &'static [T],
+}
+
+impl<T: 'static> RomTable<T> {
+    pub const fn new(entries: &'static [T]) -> Self {
+        Self { entries }
+    }
+
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[inline]
+    pub fn get(&self, id: u16) -> Option<&'static T> {
+        self.entries.get(id as usize)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &'static T> {
+        self.entries.iter()
+    }
+}
+
+impl<T: 'static> core::ops::Index<u16> for RomTable<T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, id: u16) -> &Self::Output {
+        &self.entries[id as usize]
+    }
+}
+
+#[doc(hidden)]
+pub const fn str_cmp(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let mut i = 0;
+    while i < a.len() && i < b.len() {
+        if a[i] != b[i] {
+            return if a[i] < b[i] { Ordering::Less } else { Ordering::Greater };
+        }
+        i += 1;
+    }
+    if a.len() == b.len() {
+        Ordering::Equal
+    } else if a.len() < b.len() {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
+}
+
+#[doc(hidden)]
+pub const fn str_keys_sorted<V>(entries: &[(&str, V)]) -> bool {
+    let mut i = 1;
+    while i < entries.len() {
+        if !matches!(str_cmp(entries[i - 1].0, entries[i].0), Ordering::Less) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+#[doc(hidden)]
+pub const fn u16_keys_sorted<V>(entries: &[(u16, V)]) -> bool {
+    let mut i = 1;
+    while i < entries.len() {
+        if entries[i - 1].0 >= entries[i].0 {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+#[doc(hidden)]
+pub const fn u32_keys_sorted<V>(entries: &[(u32, V)]) -> bool {
+    let mut i = 1;
+    while i < entries.len() {
+        if entries[i - 1].0 >= entries[i].0 {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Builds a `RomMap` from a list of entries, failing compilation if the keys aren't sorted and unique.
+///
+/// Keys can be `str`, `u16` or `u32`:
+///
+/// ```ignore
+/// static ITEMS: RomMap<&str, u16> = rom_map!(str => u16 {
+///     "potion" => 0,
+///     "shield" => 1,
+///     "sword" => 2,
+/// });
+/// ```
+#[macro_export]
+macro_rules! rom_map {
+    (str => $v:ty { $($key:expr => $value:expr),* $(,)? }) => {
+        $crate::rom_map!(@build &'static str, $v, str_keys_sorted, { $($key => $value),* })
+    };
+    (u16 => $v:ty { $($key:expr => $value:expr),* $(,)? }) => {
+        $crate::rom_map!(@build u16, $v, u16_keys_sorted, { $($key => $value),* })
+    };
+    (u32 => $v:ty { $($key:expr => $value:expr),* $(,)? }) => {
+        $crate::rom_map!(@build u32, $v, u32_keys_sorted, { $($key => $value),* })
+    };
+    (@build $k:ty, $v:ty, $check:ident, { $($key:expr => $value:expr),* }) => {
+        const {
+            const ENTRIES: &[($k, $v)] = &[$(($key, $value)),*];
+            assert!($crate::sys::lookup::$check(ENTRIES), "rom_map! keys must be sorted and unique");
+            $crate::sys::lookup::RomMap::new_unchecked(ENTRIES)
+        }
+    };
+}
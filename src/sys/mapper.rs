@@ -0,0 +1,151 @@
+use core::{cell, ptr};
+
+use critical_section as cs;
+
+use crate::sys;
+
+/// The bank register for slot 1. The registers for slots 1 to 7 are on consecutive odd bytes.
+const BANK_REGS: *mut u8 = 0xA130F3 as *mut _;
+
+/// The size of each slot in the 68k's address space, and of each ROM bank.
+pub const SLOT_SIZE: usize = 0x80000;
+/// The number of slots in the first 4MB of the address space.
+pub const SLOTS: u8 = 8;
+/// The number of banks the mapper can address, for up to 32MB of ROM.
+pub const MAX_BANKS: u8 = 64;
+
+#[derive(Clone, Copy)]
+struct Mapper {
+    /// The bank mapped into each slot. The registers are write only, so this shadows them.
+    banks: [u8; SLOTS as usize],
+    /// Slots with a live `BankWindow`, as bits indexed by slot.
+    claimed: u8,
+}
+
+static MAPPER: cs::Mutex<cell::Cell<Mapper>> = cs::Mutex::new(cell::Cell::new(Mapper {
+    banks: [0, 1, 2, 3, 4, 5, 6, 7],
+    claimed: 0,
+}));
+
+#[inline]
+unsafe fn write_bank_reg(slot: u8, bank: u8) {
+    ptr::write_volatile(BANK_REGS.add(((slot - 1) as usize) << 1), bank);
+}
+
+/// The bank mapped into `slot`.
+#[inline]
+pub fn bank(slot: u8) -> u8 {
+    sys::with_cs::<1, 7, _>(|cs| MAPPER.borrow(cs).get().banks[(slot % SLOTS) as usize])
+}
+
+/// One of the mapper's 512KB slots, claimed so its bank can be switched.
+///
+/// Slot 0 holds the vector table and can't be switched, so windows use slots 1 to 7. Nothing else
+/// should live in a claimed slot's default bank: any code or data the program uses from there is
+/// unreachable while another bank is mapped. Slices borrowed from a window keep it from being
+/// remapped, and the slot's default bank is mapped back when the window is dropped.
+///
+/// On cartridges without a mapper, `map` does nothing and every slot keeps its default bank.
+pub struct BankWindow {
+    slot: u8,
+}
+
+impl BankWindow {
+    /// Claims `slot`. Returns `None` if it's slot 0, out of range, or already claimed.
+    pub fn claim(slot: u8) -> Option<Self> {
+        if slot == 0 || slot >= SLOTS {
+            return None;
+        }
+
+        sys::with_cs::<1, 7, _>(|cs| {
+            let cell = MAPPER.borrow(cs);
+            let mut mapper = cell.get();
+            if mapper.claimed & (1 << slot) != 0 {
+                return None;
+            }
+            mapper.claimed |= 1 << slot;
+            cell.set(mapper);
+            Some(Self { slot })
+        })
+    }
+
+    #[inline]
+    pub fn slot(&self) -> u8 {
+        self.slot
+    }
+
+    #[inline]
+    pub fn bank(&self) -> u8 {
+        bank(self.slot)
+    }
+
+    /// Maps `bank` into the window.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bank` is `MAX_BANKS` or over.
+    pub fn map(&mut self, bank: u8) {
+        if bank >= MAX_BANKS {
+            panic!("ROM bank out of range");
+        }
+
+        sys::with_cs::<1, 7, _>(|cs| {
+            let cell = MAPPER.borrow(cs);
+            let mut mapper = cell.get();
+            mapper.banks[self.slot as usize] = bank;
+            cell.set(mapper);
+            unsafe { write_bank_reg(self.slot, bank); }
+        })
+    }
+
+    /// Maps in the bank holding `len` bytes of ROM at `rom_addr`, and returns them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bytes cross a bank boundary, or are past the end of the mapper's range.
+    pub fn map_rom(&mut self, rom_addr: u32, len: usize) -> &[u8] {
+        let bank = rom_addr as usize / SLOT_SIZE;
+        let offset = rom_addr as usize % SLOT_SIZE;
+        if offset + len > SLOT_SIZE {
+            panic!("ROM range crosses a bank boundary");
+        }
+        if bank >= MAX_BANKS as usize {
+            panic!("ROM bank out of range");
+        }
+
+        self.map(bank as u8);
+        self.slice(offset, len)
+    }
+
+    /// The start of the window in the address space.
+    #[inline]
+    pub fn base(&self) -> *const u8 {
+        (self.slot as usize * SLOT_SIZE) as *const u8
+    }
+
+    /// The whole of the mapped bank.
+    #[inline]
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.base(), SLOT_SIZE) }
+    }
+
+    /// `len` bytes of the mapped bank, starting `offset` bytes in.
+    #[inline]
+    pub fn slice(&self, offset: usize, len: usize) -> &[u8] {
+        &self.as_slice()[offset..offset + len]
+    }
+}
+
+impl Drop for BankWindow {
+    fn drop(&mut self) {
+        let slot = self.slot;
+        sys::with_cs::<1, 7, _>(|cs| {
+            let cell = MAPPER.borrow(cs);
+            let mut mapper = cell.get();
+            mapper.banks[slot as usize] = slot;
+            mapper.claimed &= !(1 << slot);
+            cell.set(mapper);
+            unsafe { write_bank_reg(slot, slot); }
+        })
+    }
+}
@@ -0,0 +1,244 @@
+use core::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+use fixed::types::{I16F16, I8F8};
+
+use super::fixed::{FixedCordic, FixedCordicMath};
+
+/// A 2D vector, for positions, velocities and directions.
+///
+/// `Vec2<I16F16>` has room for positions across a large level, while `Vec2<I8F8>` is cheaper on
+/// the 68000 and enough for velocities. Angles are in radians, turning from the x axis towards the
+/// y axis, which is clockwise on screen since y points down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Vec2<T> {
+    pub x: T,
+    pub y: T,
+}
+
+pub type Vec2F16 = Vec2<I16F16>;
+pub type Vec2F8 = Vec2<I8F8>;
+
+impl<T> Vec2<T> {
+    #[inline]
+    pub const fn new(x: T, y: T) -> Self {
+        Self { x, y }
+    }
+}
+
+impl<T: FixedCordicMath> Vec2<T> {
+    pub const ZERO: Self = Self::new(T::ZERO, T::ZERO);
+    pub const X: Self = Self::new(T::ONE, T::ZERO);
+    pub const Y: Self = Self::new(T::ZERO, T::ONE);
+
+    /// A vector of length one at `angle`.
+    #[inline]
+    pub fn from_angle(angle: T) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new(cos, sin)
+    }
+
+    /// A vector of length `len` at `angle`.
+    #[inline]
+    pub fn from_polar(len: T, angle: T) -> Self {
+        Self::from_angle(angle) * len
+    }
+
+    #[inline]
+    pub fn dot(self, other: Self) -> T {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// The z of the 3D cross product, which is positive when `other` is turned towards the y axis
+    /// from `self`.
+    #[inline]
+    pub fn cross(self, other: Self) -> T {
+        self.x * other.y - self.y * other.x
+    }
+
+    /// The squared length, which is much cheaper than `length` for comparing distances.
+    #[inline]
+    pub fn length_squared(self) -> T {
+        self.dot(self)
+    }
+
+    /// The length, worked out with CORDIC. See `FixedCordicMath::hypot` for the range.
+    #[inline]
+    pub fn length(self) -> T {
+        self.x.hypot(self.y)
+    }
+
+    /// The angle, from -pi to pi.
+    #[inline]
+    pub fn angle(self) -> T {
+        self.y.atan2(self.x)
+    }
+
+    /// The length and angle together, for the cost of one of them.
+    #[inline]
+    pub fn to_polar(self) -> (T, T) {
+        T::to_polar(self.x, self.y)
+    }
+
+    /// A vector of length one in the same direction, or zero for the zero vector.
+    pub fn normalize(self) -> Self {
+        let (len, angle) = self.to_polar();
+        if len == T::ZERO {
+            Self::ZERO
+        } else {
+            Self::from_angle(angle)
+        }
+    }
+
+    /// The vector turned a quarter turn, from the x axis towards the y axis.
+    #[inline]
+    pub fn perp(self) -> Self {
+        Self::new(-self.y, self.x)
+    }
+
+    #[inline]
+    pub fn rotate(self, angle: T) -> Self {
+        Mat2::rotation(angle) * self
+    }
+
+    /// The point `t` of the way from `self` to `other`.
+    #[inline]
+    pub fn lerp(self, other: Self, t: T) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl<T: FixedCordic> Add for Vec2<T> {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl<T: FixedCordic> Sub for Vec2<T> {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl<T: FixedCordic> AddAssign for Vec2<T> {
+    #[inline]
+    fn add_assign(&mut self, rhs: Self) {
+        self.x += rhs.x;
+        self.y += rhs.y;
+    }
+}
+
+impl<T: FixedCordic> SubAssign for Vec2<T> {
+    #[inline]
+    fn sub_assign(&mut self, rhs: Self) {
+        self.x -= rhs.x;
+        self.y -= rhs.y;
+    }
+}
+
+impl<T: FixedCordic> Neg for Vec2<T> {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y)
+    }
+}
+
+impl<T: FixedCordic> Mul<T> for Vec2<T> {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: T) -> Self {
+        Self::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+/// A 2x2 matrix, for rotating and scaling vectors, such as sprite offsets around a pivot.
+///
+/// Matrices are stored as their columns, which are where the x and y axes end up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mat2<T> {
+    pub x: Vec2<T>,
+    pub y: Vec2<T>,
+}
+
+pub type Mat2F16 = Mat2<I16F16>;
+pub type Mat2F8 = Mat2<I8F8>;
+
+impl<T> Mat2<T> {
+    #[inline]
+    pub const fn from_cols(x: Vec2<T>, y: Vec2<T>) -> Self {
+        Self { x, y }
+    }
+}
+
+impl<T: FixedCordicMath> Mat2<T> {
+    pub const IDENTITY: Self = Self::from_cols(Vec2::X, Vec2::Y);
+
+    /// Rotates by `angle`, from the x axis towards the y axis.
+    #[inline]
+    pub fn rotation(angle: T) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::from_cols(Vec2::new(cos, sin), Vec2::new(-sin, cos))
+    }
+
+    #[inline]
+    pub fn scale(x: T, y: T) -> Self {
+        Self::from_cols(Vec2::new(x, T::ZERO), Vec2::new(T::ZERO, y))
+    }
+
+    /// Rotates by `angle`, then scales by `scale` along both axes.
+    #[inline]
+    pub fn rotation_scale(angle: T, scale: T) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        let (sin, cos) = (sin * scale, cos * scale);
+        Self::from_cols(Vec2::new(cos, sin), Vec2::new(-sin, cos))
+    }
+
+    #[inline]
+    pub fn transpose(self) -> Self {
+        Self::from_cols(Vec2::new(self.x.x, self.y.x), Vec2::new(self.x.y, self.y.y))
+    }
+
+    #[inline]
+    pub fn determinant(self) -> T {
+        self.x.cross(self.y)
+    }
+
+    /// The matrix that undoes this one, or `None` if it squashes everything onto a line.
+    pub fn inverse(self) -> Option<Self> {
+        let det = self.determinant();
+        if det == T::ZERO {
+            return None;
+        }
+        Some(Self::from_cols(
+            Vec2::new(self.y.y / det, -self.x.y / det),
+            Vec2::new(-self.y.x / det, self.x.x / det),
+        ))
+    }
+}
+
+impl<T: FixedCordic> Mul<Vec2<T>> for Mat2<T> {
+    type Output = Vec2<T>;
+
+    #[inline]
+    fn mul(self, rhs: Vec2<T>) -> Vec2<T> {
+        self.x * rhs.x + self.y * rhs.y
+    }
+}
+
+/// Applies `rhs` first, then `self`.
+impl<T: FixedCordic> Mul for Mat2<T> {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: Self) -> Self {
+        Self::from_cols(self * rhs.x, self * rhs.y)
+    }
+}
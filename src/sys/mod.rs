@@ -4,10 +4,44 @@ pub mod libc;
 pub mod alloc;
 pub mod io;
 pub mod fixed;
+pub mod collections;
+pub mod math;
+pub mod sram;
+pub mod suspend;
+pub mod launcher;
+pub mod eeprom;
+pub mod header;
+pub mod init;
+pub mod layout;
+#[cfg(feature = "mapper")]
+pub mod mapper;
+pub mod flags;
+pub mod rom;
+pub mod lookup;
+pub mod timing;
+pub mod game_loop;
+pub mod region;
+pub mod rand;
+pub mod fmt;
+pub mod debug;
+pub mod exceptions;
+pub mod audio;
+pub mod scd;
+pub mod x32;
+mod delay;
+mod crash;
+#[cfg(feature = "integrity")]
+pub mod integrity;
+
+pub use collections::RingBuffer;
+pub use delay::{delay_lines, delay_us};
+pub use game_loop::{run_game_loop, Commit, Frame};
 
 use critical_section as cs;
 
 use crate::sys::alloc::MDSpecializeAlloc;
+#[cfg(feature = "frame-arena")]
+use crate::sys::alloc::BumpArena;
 
 extern "C" {
     static _data_src: u8;
@@ -43,20 +77,56 @@ const fn bss_dst_ptr() -> *mut u8 {
 }
 
 
+/// Logs the panic, then shows the crash screen with the message, registers and top of the stack.
 #[panic_handler]
 pub fn panic_handler(info: &core::panic::PanicInfo) -> ! {
-    vdp::VDP::debug_alert(info.message().as_str().unwrap_or("(panic message needs formatting)").as_bytes());
-    vdp::VDP::debug_halt();
-    extern "C" {
-        fn abort() -> !;
-    }
+    crate::error!("{}", info.message());
+    crash::panic(info)
+}
+
+/// The pattern work RAM is filled with at startup by the `ram-fill` feature, so memory that's read
+/// before it's written stands out in a memory viewer or crash dump.
+#[cfg(feature = "ram-fill")]
+pub const RAM_FILL_PATTERN: u32 = 0xDEADBEEF;
+
+/// Fills work RAM with `RAM_FILL_PATTERN`, up to just below the stack.
+///
+/// Stack that's never been used keeps the pattern too, which shows how deep the stack has gone.
+/// The RAM kept across resets is left alone, so warm boots are still seen.
+#[cfg(all(feature = "ram-fill", debug_assertions))]
+#[inline(always)]
+unsafe fn fill_ram() {
+    const RAM_START: usize = 0xFF0000;
+    /// Room left for `_init`'s own stack frame.
+    const STACK_MARGIN: usize = 0x100;
+
+    let sp: usize;
+    core::arch::asm!("move.l %sp,{sp}", sp = out(reg_addr) sp);
 
-    unsafe { abort() };
+    let end = (sp - STACK_MARGIN) & !3;
+    let skip = init::noinit_range();
+    let mut addr = RAM_START;
+    while addr < end {
+        if !skip.contains(&addr) {
+            core::ptr::write_volatile(addr as *mut u32, RAM_FILL_PATTERN);
+        }
+        addr += 4;
+    }
 }
 
 /// Runs as soon as the console starts up, and before main() runs.
 #[no_mangle]
 pub unsafe fn _init() {
+    let warm = init::check_warm();
+
+    #[cfg(feature = "boot-memtest")]
+    let mut memtest = init::MemTest::default();
+    #[cfg(feature = "boot-memtest")]
+    init::test_ram(&mut memtest);
+
+    #[cfg(all(feature = "ram-fill", debug_assertions))]
+    fill_ram();
+
     {
         const TMSS_REG: *mut u32 = 0xA14000 as _;
         const TMSS_VAL: u32 = 0x53454741u32; // "SEGA" as a single long
@@ -73,6 +143,17 @@ pub unsafe fn _init() {
 
     ALLOCATOR.init();
 
+    #[cfg(feature = "boot-memtest")]
+    init::test_vdp(&mut memtest);
+    init::finish(
+        warm,
+        #[cfg(feature = "boot-memtest")]
+        memtest,
+    );
+
+    // The Z80 starts out held in reset, where bus requests are never granted.
+    audio::z80::load(audio::z80::IDLE_PROGRAM);
+
     with_cs::<1, 7, _>(|cs| {
         let p1 = io::P1_CONTROLLER.borrow(cs);
         let p2 = io::P2_CONTROLLER.borrow(cs);
@@ -84,6 +165,42 @@ pub unsafe fn _init() {
 #[global_allocator]
 static ALLOCATOR: MDSpecializeAlloc = MDSpecializeAlloc::new();
 
+/// Frees any blocks whose deallocation was deferred. Call this once per frame from the main loop.
+#[inline]
+pub fn drain_deferred_frees() {
+    ALLOCATOR.drain_deferred();
+}
+
+/// The size of `frame_arena`, in bytes.
+#[cfg(feature = "frame-arena")]
+pub const FRAME_ARENA_SIZE: usize = 0x400;
+
+#[cfg(feature = "frame-arena")]
+static mut FRAME_ARENA_BUF: [u8; FRAME_ARENA_SIZE] = [0; FRAME_ARENA_SIZE];
+
+#[cfg(feature = "frame-arena")]
+static FRAME_ARENA: BumpArena = unsafe { BumpArena::new_in("frame", &raw mut FRAME_ARENA_BUF) };
+
+/// An arena for scratch allocations that only last until the end of the frame, which is reset at
+/// the start of every vblank. Allocations still live by then, such as a `Vec` that was kept around,
+/// hold off the reset until they're dropped.
+///
+/// ```ignore
+/// let mut sprites = Vec::with_capacity_in(16, sys::frame_arena());
+/// ```
+#[cfg(feature = "frame-arena")]
+#[inline]
+pub fn frame_arena() -> &'static BumpArena {
+    &FRAME_ARENA
+}
+
+/// Resets `frame_arena`. Called by the vblank handler.
+#[cfg(feature = "frame-arena")]
+#[inline]
+pub(in crate::sys) fn reset_frame_arena(cs: cs::CriticalSection) {
+    FRAME_ARENA.reset_in(cs);
+}
+
 /// Sets the 68k's interrupt mask bits to the specified constant.
 /// 
 /// Unfortunately, due to an LLVM compiler bug, we have to use a temporary register here. See issue [#165077](https://github.com/llvm/llvm-project/issues/165077).
@@ -123,6 +240,65 @@ pub fn with_cs<const OUTER: u8, const INNER: u8, R>(f: impl FnOnce(cs::CriticalS
     unsafe { f(cs::CriticalSection::new()) }
 }
 
+/// Which interrupts a subsystem's critical sections hold off, picked at compile time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsMask {
+    /// Every interrupt, as with `with_cs::<1, 7, _>`.
+    All,
+    /// Vblank and external interrupts, but not hblank, as with `with_cs_audio_safe`.
+    KeepHblank,
+}
+
+/// Execute closure `f` with vblank and external interrupts held off, but hblank left running, so
+/// raster effects stay on their lines however long `f` takes.
+///
+/// Vblank has a higher level than hblank, so it can't be masked on its own. Instead it's turned
+/// off at the VDP while `f` runs. A vblank that comes in meanwhile is held pending, and taken as
+/// soon as `f` returns.
+///
+/// Nesting critical sections is NOT allowed.
+///
+/// # Safety
+///
+/// Nothing `f` borrows through the critical section may be touched by the hblank handler, which
+/// can interrupt `f`.
+#[inline]
+pub unsafe fn with_cs_audio_safe<R>(f: impl FnOnce(cs::CriticalSection) -> R) -> R {
+    struct Guard;
+
+    impl Drop for Guard {
+        #[inline(always)]
+        fn drop(&mut self) {
+            unsafe {
+                set_int_level::<7>();
+                vdp::hold_vint(cs::CriticalSection::new(), false);
+                set_int_level::<1>();
+            }
+        }
+    }
+
+    set_int_level::<7>();
+    vdp::hold_vint(cs::CriticalSection::new(), true);
+    let _guard = Guard;
+    // External interrupts are level 2, and hblank is 4.
+    set_int_level::<3>();
+
+    f(cs::CriticalSection::new())
+}
+
+/// Execute closure `f` in a critical section that holds off the interrupts `mask` says to.
+///
+/// # Safety
+///
+/// With `CsMask::KeepHblank`, the same as `with_cs_audio_safe`.
+#[inline(always)]
+pub unsafe fn with_cs_masked<R>(mask: CsMask, f: impl FnOnce(cs::CriticalSection) -> R) -> R {
+    match mask {
+        CsMask::All => with_cs::<1, 7, _>(f),
+        CsMask::KeepHblank => with_cs_audio_safe(f),
+    }
+}
+
 #[repr(C)] // guarantee 'bytes' comes after '_align'
 pub struct AlignedAs<Align, Bytes: ?Sized> {
     pub _align: [Align; 0],
@@ -131,7 +307,7 @@ pub struct AlignedAs<Align, Bytes: ?Sized> {
 
 #[macro_export]
 macro_rules! include_bytes_aligned_as {
-    ($align_ty:ty, $path:literal) => {
+    ($align_ty:ty, $path:expr) => {
         const {  // const block expression to encapsulate the static
             use $crate::sys::AlignedAs;
             
@@ -146,6 +322,30 @@ macro_rules! include_bytes_aligned_as {
     };
 }
 
+/// Like `include_bytes_aligned_as!`, but fails to compile if the file isn't a whole number of
+/// `$align_ty`, or, given a count, if it isn't exactly that many.
+///
+/// ```ignore
+/// const LEVEL_MAP: &[u16] = include_bytes_aligned_as_checked!(u16, "level1.map", 64 * 32);
+/// ```
+#[macro_export]
+macro_rules! include_bytes_aligned_as_checked {
+    ($align_ty:ty, $path:expr) => {{
+        const _: () = assert!(
+            include_bytes!($path).len() % core::mem::size_of::<$align_ty>() == 0,
+            concat!("the size of ", stringify!($path), " isn't a multiple of the size of ", stringify!($align_ty)),
+        );
+        $crate::include_bytes_aligned_as!($align_ty, $path)
+    }};
+    ($align_ty:ty, $path:expr, $count:expr) => {{
+        const _: () = assert!(
+            include_bytes!($path).len() == $count * core::mem::size_of::<$align_ty>(),
+            concat!(stringify!($path), " doesn't hold ", stringify!($count), " of ", stringify!($align_ty)),
+        );
+        $crate::include_bytes_aligned_as!($align_ty, $path)
+    }};
+}
+
 // #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 // pub struct AtomicFlag<const BIT: u8 = 0u8>(u8);
 
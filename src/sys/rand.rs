@@ -0,0 +1,204 @@
+use core::cell;
+use core::ops::Range;
+
+use critical_section as cs;
+
+use super::vdp::VDP;
+
+/// A 32 bit xorshift generator, using the (13, 17, 5) triple.
+///
+/// Every step is three shifts and three xors, which is about as cheap as a decent generator gets on
+/// the 68000. It's not suitable for anything cryptographic.
+#[derive(Debug, Clone, Copy)]
+pub struct XorShift32(u32);
+
+impl XorShift32 {
+    /// Creates a generator from `seed`. A seed of zero is replaced, since xorshift can't leave zero.
+    #[inline]
+    pub const fn new(seed: u32) -> Self {
+        Self(if seed == 0 { 0x2545F491 } else { seed })
+    }
+
+    #[inline]
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    /// The high half of the next output, which is better mixed than the low half.
+    #[inline]
+    pub fn next_u16(&mut self) -> u16 {
+        (self.next_u32() >> 16) as u16
+    }
+
+    /// A number in `range`, or `range.start` if it's empty.
+    #[inline]
+    pub fn range(&mut self, range: Range<u16>) -> u16 {
+        scale(self.next_u16(), range)
+    }
+
+    /// Stirs `value` into the state.
+    #[inline]
+    pub fn mix(&mut self, value: u32) {
+        *self = Self::new(self.0 ^ value.rotate_left(16));
+        self.next_u32();
+    }
+}
+
+/// A 16 bit xorshift generator, using the (7, 9, 8) triple.
+///
+/// Smaller and faster than `XorShift32`, but it repeats after 65535 numbers.
+#[derive(Debug, Clone, Copy)]
+pub struct XorShift16(u16);
+
+impl XorShift16 {
+    #[inline]
+    pub const fn new(seed: u16) -> Self {
+        Self(if seed == 0 { 0xACE1 } else { seed })
+    }
+
+    #[inline]
+    pub fn next_u16(&mut self) -> u16 {
+        let mut x = self.0;
+        x ^= x << 7;
+        x ^= x >> 9;
+        x ^= x << 8;
+        self.0 = x;
+        x
+    }
+
+    #[inline]
+    pub fn range(&mut self, range: Range<u16>) -> u16 {
+        scale(self.next_u16(), range)
+    }
+}
+
+/// Maps `x` onto `range` with a single 16x16 multiply rather than a division.
+#[inline]
+fn scale(x: u16, range: Range<u16>) -> u16 {
+    let span = range.end.wrapping_sub(range.start);
+    if range.end <= range.start {
+        return range.start;
+    }
+    range.start + ((x as u32 * span as u32) >> 16) as u16
+}
+
+static RNG: cs::Mutex<cell::Cell<XorShift32>> = cs::Mutex::new(cell::Cell::new(XorShift32::new(0)));
+
+/// Reseeds the global generator.
+#[inline]
+pub fn seed(seed: u32) {
+    super::with_cs::<1, 7, _>(|cs| RNG.borrow(cs).set(XorShift32::new(seed)))
+}
+
+/// Stirs the HV counter and frame count into the global generator.
+///
+/// The HV counter depends on exactly when this is called, so calling it after waiting on the
+/// player (e.g. when Start is pressed on the title screen) gives a different sequence every time.
+pub fn seed_from_hardware() {
+    let value = ((VDP::hv_counter() as u32) << 16) | (super::timing::elapsed_frames() & 0xFFFF);
+    super::with_cs::<1, 7, _>(|cs| stir(cs, value))
+}
+
+/// Stirs `value` into the global generator. Called from the vblank handler with input timings.
+#[inline]
+pub(super) fn stir(cs: cs::CriticalSection, value: u32) {
+    let cell = RNG.borrow(cs);
+    let mut rng = cell.get();
+    rng.mix(value);
+    cell.set(rng);
+}
+
+#[inline]
+fn with_rng<R>(f: impl FnOnce(&mut XorShift32) -> R) -> R {
+    super::with_cs::<1, 7, _>(|cs| {
+        let cell = RNG.borrow(cs);
+        let mut rng = cell.get();
+        let out = f(&mut rng);
+        cell.set(rng);
+        out
+    })
+}
+
+#[inline]
+pub fn random_u32() -> u32 {
+    with_rng(XorShift32::next_u32)
+}
+
+#[inline]
+pub fn random_u16() -> u16 {
+    with_rng(XorShift32::next_u16)
+}
+
+/// A number in `range` from the global generator, or `range.start` if it's empty.
+#[inline]
+pub fn range(range: Range<u16>) -> u16 {
+    with_rng(|rng| rng.range(range))
+}
+
+/// The global generator, as a handle for code written against `rand_core`.
+#[cfg(feature = "rand_core")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GlobalRng;
+
+#[cfg(feature = "rand_core")]
+impl rand_core::RngCore for GlobalRng {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        random_u32()
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_u32(self)
+    }
+
+    #[inline]
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        rand_core::impls::fill_bytes_via_next(self, dest)
+    }
+
+    #[inline]
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rand_core")]
+impl rand_core::RngCore for XorShift32 {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        XorShift32::next_u32(self)
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_u32(self)
+    }
+
+    #[inline]
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        rand_core::impls::fill_bytes_via_next(self, dest)
+    }
+
+    #[inline]
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rand_core")]
+impl rand_core::SeedableRng for XorShift32 {
+    type Seed = [u8; 4];
+
+    #[inline]
+    fn from_seed(seed: Self::Seed) -> Self {
+        Self::new(u32::from_be_bytes(seed))
+    }
+}
@@ -0,0 +1,227 @@
+use core::cell;
+
+use critical_section as cs;
+use fixed::types::{U16F16, U8F8};
+
+use super::header;
+use super::timing;
+use super::vdp::Settings;
+
+/// The kind of console the program is running on, from its version register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// A Japanese Mega Drive, at 60Hz.
+    Japan,
+    /// A domestic console at 50Hz, as sold in parts of Asia.
+    Asia,
+    /// A Genesis, or an overseas 60Hz Mega Drive such as a Brazilian one.
+    Americas,
+    /// A European or Australian Mega Drive, at 50Hz.
+    Europe,
+}
+
+impl Region {
+    /// The region of the console the program is running on.
+    #[inline]
+    pub fn current() -> Self {
+        let version = super::io::version();
+        match (version.is_overseas(), version.is_pal()) {
+            (false, false) => Region::Japan,
+            (false, true) => Region::Asia,
+            (true, false) => Region::Americas,
+            (true, true) => Region::Europe,
+        }
+    }
+
+    #[inline]
+    pub const fn is_pal(self) -> bool {
+        matches!(self, Region::Asia | Region::Europe)
+    }
+
+    #[inline]
+    pub const fn is_overseas(self) -> bool {
+        matches!(self, Region::Americas | Region::Europe)
+    }
+
+    /// The timing constants for the region's refresh rate, with the 224 line display. `timing`
+    /// also checks which display is in use.
+    #[inline]
+    pub const fn timing(self) -> &'static Timing {
+        if self.is_pal() { &Timing::PAL } else { &Timing::NTSC }
+    }
+
+    /// The header region a ROM has to list to be meant for this console. Domestic 50Hz consoles
+    /// count as Japan, which is the letter Asian releases used.
+    #[inline]
+    pub const fn header_region(self) -> header::Region {
+        match self {
+            Region::Japan | Region::Asia => header::Region::JAPAN,
+            Region::Americas => header::Region::AMERICAS,
+            Region::Europe => header::Region::EUROPE,
+        }
+    }
+
+    /// Returns true if the running ROM's header lists this region.
+    #[inline]
+    pub fn is_supported(self) -> bool {
+        header::header().region().contains(self.header_region())
+    }
+
+    /// The language a game shows by default in the region.
+    #[inline]
+    pub const fn default_language(self) -> Language {
+        match self {
+            Region::Japan => Language::Japanese,
+            _ => Language::English,
+        }
+    }
+}
+
+/// The language a game shows its text in, chosen at boot by `RegionConfig::apply`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Japanese,
+    English,
+    French,
+    German,
+    Spanish,
+    Italian,
+}
+
+/// Timing constants for one refresh rate, so code can look them up once instead of checking for PAL
+/// each time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    /// The vertical refresh rate, in Hz.
+    pub frame_rate: U8F8,
+    /// The length of a frame, in seconds.
+    pub frame_time: U16F16,
+    /// The number of scanlines in a frame, including vblank.
+    pub lines: u16,
+    /// The number of active display lines, 240 with the V30 display on a 50Hz console and 224
+    /// otherwise.
+    pub active_lines: u16,
+    /// The number of frames in a second, rounded to the nearest frame, for timers counted in
+    /// frames.
+    pub frames_per_second: u16,
+}
+
+impl Timing {
+    pub const NTSC: Self = Self {
+        frame_rate: timing::NTSC_FRAME_RATE,
+        frame_time: timing::NTSC_FRAME_TIME,
+        lines: timing::NTSC_LINES,
+        active_lines: 224,
+        frames_per_second: 60,
+    };
+
+    pub const PAL: Self = Self {
+        frame_rate: timing::PAL_FRAME_RATE,
+        frame_time: timing::PAL_FRAME_TIME,
+        lines: timing::PAL_LINES,
+        active_lines: 224,
+        frames_per_second: 50,
+    };
+
+    /// `PAL` with the 240 line display, as `RegionConfig::apply` sets up unless told not to.
+    pub const PAL_V30: Self = Self {
+        active_lines: 240,
+        ..Self::PAL
+    };
+
+    /// Scales a speed tuned at 60Hz, in units per frame, so things move as fast in real time at
+    /// this refresh rate.
+    #[inline]
+    pub fn scale_speed(&self, per_ntsc_frame: U16F16) -> U16F16 {
+        if self.frames_per_second == 60 {
+            per_ntsc_frame
+        } else {
+            per_ntsc_frame.saturating_mul(U16F16::lit("1.2"))
+        }
+    }
+}
+
+/// The timing constants for the console the program is running on, and the display height the
+/// VDP is set to.
+#[inline]
+pub fn timing() -> &'static Timing {
+    let region = Region::current();
+    if region.is_pal() && Settings::current().is_v30() { &Timing::PAL_V30 } else { region.timing() }
+}
+
+static LANGUAGE: cs::Mutex<cell::Cell<Option<Language>>> = cs::Mutex::new(cell::Cell::new(None));
+
+/// The language picked at boot, or the region's default if `RegionConfig::apply` hasn't run.
+#[inline]
+pub fn language() -> Language {
+    super::with_cs::<1, 7, _>(|cs| LANGUAGE.borrow(cs).get()).unwrap_or_else(|| Region::current().default_language())
+}
+
+/// Changes the language, such as from an options menu.
+#[inline]
+pub fn set_language(language: Language) {
+    super::with_cs::<1, 7, _>(|cs| LANGUAGE.borrow(cs).set(Some(language)));
+}
+
+/// How a game sets itself up for the console's region at boot, built up with the `with_*` methods.
+///
+/// ```ignore
+/// fn locked_out(region: Region) -> ! {
+///     // Draw a "this game isn't made for your console" screen.
+///     loop {}
+/// }
+///
+/// const REGION: RegionConfig = RegionConfig::DEFAULT.with_lockout(locked_out);
+///
+/// let mut settings = Settings::DEFAULT;
+/// let region = REGION.apply(&mut settings);
+/// settings.apply::<true>();
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct RegionConfig {
+    /// Called if the header doesn't list the console's region.
+    pub lockout: Option<fn(Region) -> !>,
+    /// Picks the language at boot, such as from a menu or a save. Without it, the region's default
+    /// is used.
+    pub language: Option<fn(Region) -> Language>,
+    /// Uses the 240 line display on 50Hz consoles, to fill the taller PAL screen.
+    pub v30_on_pal: bool,
+}
+
+impl RegionConfig {
+    pub const DEFAULT: Self = Self {
+        lockout: None,
+        language: None,
+        v30_on_pal: true,
+    };
+
+    pub const fn with_lockout(mut self, lockout: fn(Region) -> !) -> Self {
+        self.lockout = Some(lockout);
+        self
+    }
+
+    pub const fn with_language(mut self, language: fn(Region) -> Language) -> Self {
+        self.language = Some(language);
+        self
+    }
+
+    pub const fn with_v30_on_pal(mut self, enable: bool) -> Self {
+        self.v30_on_pal = enable;
+        self
+    }
+
+    /// Checks the console's region against the header, picks the language, and sets the display
+    /// height in `settings` to suit the refresh rate. The settings still need to be applied.
+    pub fn apply(&self, settings: &mut Settings) -> Region {
+        let region = Region::current();
+        if let Some(lockout) = self.lockout {
+            if !region.is_supported() {
+                lockout(region);
+            }
+        }
+        let language = self.language.map_or(region.default_language(), |pick| pick(region));
+        set_language(language);
+        settings.enable_v30(self.v30_on_pal && region.is_pal());
+        region
+    }
+}
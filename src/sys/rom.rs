@@ -0,0 +1,126 @@
+use crate::sys::io;
+#[cfg(feature = "mapper")]
+use crate::sys::mapper::{BankWindow, SLOT_SIZE};
+
+/// The part of the ROM the 68000 can see without the mapper.
+pub const DIRECT_SIZE: u32 = 0x400000;
+
+/// How much `copy_to_ram_exclusive` copies each time it stops the Z80, in bytes. At about 4 bytes
+/// every 20 cycles, that's a little under 0.7ms.
+pub const EXCLUSIVE_CHUNK: usize = 1024;
+
+/// Copies `src`, usually data in ROM, into `dst` in RAM.
+///
+/// This goes through `memcpy`, which moves 48 bytes at a time with `movem` once the pointers are
+/// both even, or both odd. Keeping ROM data and RAM buffers word aligned keeps it on that path.
+///
+/// # Panics
+///
+/// Panics if the slices are different lengths.
+#[inline]
+pub fn copy_to_ram(src: &[u8], dst: &mut [u8]) {
+    dst.copy_from_slice(src);
+}
+
+/// Like `copy_to_ram`, with the Z80 stopped while it copies, in chunks of `EXCLUSIVE_CHUNK` bytes.
+///
+/// Every time the Z80 reads the 68000's bus, such as a sample driver streaming from ROM, the 68000
+/// is held up for it, which makes large copies slower and their timing unpredictable. Stopping the
+/// Z80 takes that away, at the cost of a gap in whatever it's playing, so this is for loading
+/// between scenes rather than during gameplay.
+///
+/// # Panics
+///
+/// Panics if the slices are different lengths.
+pub fn copy_to_ram_exclusive(src: &[u8], dst: &mut [u8]) {
+    if src.len() != dst.len() {
+        panic!("source and destination are different lengths");
+    }
+    for (src, dst) in src.chunks(EXCLUSIVE_CHUNK).zip(dst.chunks_mut(EXCLUSIVE_CHUNK)) {
+        io::with_paused_z80(|_| dst.copy_from_slice(src));
+    }
+}
+
+/// A range of the ROM image, by its offset from the start, which can be past the 4MB the 68000 sees
+/// directly.
+///
+/// Data past the first 4MB is reached through a `BankWindow`, which `copy_to` maps each bank the
+/// range covers into in turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomSlice {
+    addr: u32,
+    len: u32,
+}
+
+impl RomSlice {
+    #[inline]
+    pub const fn new(addr: u32, len: u32) -> Self {
+        Self { addr, len }
+    }
+
+    /// The range of ROM `data` is in.
+    #[inline]
+    pub fn from_static(data: &'static [u8]) -> Self {
+        Self::new(data.as_ptr().addr() as u32, data.len() as u32)
+    }
+
+    #[inline]
+    pub const fn addr(&self) -> u32 {
+        self.addr
+    }
+
+    #[inline]
+    pub const fn len(&self) -> u32 {
+        self.len
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// `len` bytes of the range, starting `offset` bytes in.
+    ///
+    /// # Panics
+    ///
+    /// Panics if that runs past the end of the range.
+    #[inline]
+    pub const fn slice(&self, offset: u32, len: u32) -> Self {
+        if offset + len > self.len {
+            panic!("ROM slice out of range");
+        }
+        Self::new(self.addr + offset, len)
+    }
+
+    /// The bytes, if they're all in the first 4MB. With the mapper, that assumes the banks there
+    /// are the ones the ROM starts with.
+    #[inline]
+    pub fn as_direct(&self) -> Option<&'static [u8]> {
+        if self.addr + self.len > DIRECT_SIZE {
+            return None;
+        }
+        Some(unsafe { core::slice::from_raw_parts(self.addr as usize as *const u8, self.len as usize) })
+    }
+
+    /// Copies the range into `dst`, mapping each bank it covers into `window` in turn. The window
+    /// is left with the last bank mapped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dst` isn't the length of the range, or the range is past the mapper's reach.
+    #[cfg(feature = "mapper")]
+    pub fn copy_to(&self, window: &mut BankWindow, dst: &mut [u8]) {
+        if dst.len() != self.len as usize {
+            panic!("destination is the wrong length");
+        }
+        let mut addr = self.addr;
+        let mut dst = dst;
+        while !dst.is_empty() {
+            let in_bank = SLOT_SIZE - addr as usize % SLOT_SIZE;
+            let (part, rest) = dst.split_at_mut(in_bank.min(dst.len()));
+            copy_to_ram(window.map_rom(addr, part.len()), part);
+            addr += part.len() as u32;
+            dst = rest;
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use core::ptr;
+
+/// Where the Mega CD's BIOS header shows up when the console boots from the cartridge, which is
+/// known as mode 1.
+const BIOS_SIGNATURE: *const [u8; 4] = 0x400100 as _;
+
+/// Reset and bus control for the sub CPU, and the level 2 interrupt it's sent.
+const RESET_HIGH: *mut u8 = 0xA12000 as _;
+const RESET_LOW: *mut u8 = 0xA12001 as _;
+/// PRG-RAM write protection, in 512 byte units.
+const WRITE_PROTECT: *mut u8 = 0xA12002 as _;
+/// The PRG-RAM bank and Word RAM ownership.
+const MEMORY_MODE: *mut u8 = 0xA12003 as _;
+const MAIN_FLAGS: *mut u8 = 0xA1200E as _;
+const SUB_FLAGS: *const u8 = 0xA1200F as _;
+const COMMANDS: *mut u16 = 0xA12010 as _;
+const STATUSES: *const u16 = 0xA12020 as _;
+
+/// The number of command words, and of status words.
+pub const COMM_WORDS: usize = 8;
+
+/// The 128KB window into PRG-RAM, in mode 1. Which quarter of PRG-RAM it shows is set with
+/// `set_prg_bank`, and it can only be written while the sub CPU is held with `hold_sub`.
+pub const PRG_RAM: *mut u8 = 0x420000 as _;
+pub const PRG_RAM_WINDOW: usize = 0x20000;
+/// Word RAM, in mode 1. In 2M mode it's all here while the main CPU has it, and in 1M mode this
+/// is the main CPU's half.
+pub const WORD_RAM: *mut u8 = 0x600000 as _;
+pub const WORD_RAM_SIZE: usize = 0x40000;
+
+/// How Word RAM is split between the CPUs, which is chosen by the sub CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordRamMode {
+    /// All 256KB belongs to one CPU at a time, and is handed back and forth.
+    TwoMeg,
+    /// Each CPU has one 128KB bank, and the banks are swapped between them.
+    OneMeg,
+}
+
+/// Returns true if a Mega CD is attached.
+///
+/// The program is running from the cartridge, so this looks for the CD BIOS where it's mapped in
+/// mode 1.
+#[inline]
+pub fn is_present() -> bool {
+    unsafe { ptr::read_volatile(BIOS_SIGNATURE) == *b"SEGA" }
+}
+
+#[inline]
+fn read(reg: *const u8) -> u8 {
+    unsafe { ptr::read_volatile(reg) }
+}
+
+#[inline]
+fn write(reg: *mut u8, value: u8) {
+    unsafe { ptr::write_volatile(reg, value) }
+}
+
+/// Sets or clears bits in the reset register's low byte. Its two bits read back as they are, so
+/// the one not being changed is kept.
+#[inline]
+fn modify_reset(mask: u8, set: bool) {
+    let value = read(RESET_LOW) & 0x03;
+    write(RESET_LOW, if set { value | mask } else { value & !mask });
+}
+
+/// Returns true while the sub CPU is running, rather than held in reset.
+#[inline]
+pub fn is_sub_running() -> bool {
+    read(RESET_LOW) & 0x01 != 0
+}
+
+/// Holds the sub CPU in reset, or lets it run from its reset vector.
+#[inline]
+pub fn set_sub_reset(reset: bool) {
+    modify_reset(0x01, !reset);
+    while is_sub_running() == reset {}
+}
+
+/// Sends the sub CPU its level 2 interrupt, which the BIOS passes on to the sub program's
+/// handler. Programs usually use it to say the main CPU has finished a frame.
+#[inline]
+pub fn interrupt_sub() {
+    write(RESET_HIGH, 0x01);
+}
+
+/// Keeps the sub CPU off its bus, so the main CPU can get at PRG-RAM. See `hold_sub`.
+#[must_use]
+pub struct SubHold(());
+
+/// Asks the sub CPU for its bus, and waits until it's given up. The sub CPU carries on when the
+/// returned guard is dropped.
+pub fn hold_sub() -> SubHold {
+    modify_reset(0x02, true);
+    while read(RESET_LOW) & 0x02 == 0 {}
+    SubHold(())
+}
+
+impl SubHold {
+    /// Copies `data` into PRG-RAM at `offset` in the window, such as the sub CPU's program. The
+    /// BIOS expects the program at 0x6000.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` runs past the end of the window.
+    pub fn write_prg(&self, offset: usize, data: &[u8]) {
+        if offset + data.len() > PRG_RAM_WINDOW {
+            panic!("write past the end of PRG-RAM window");
+        }
+        for (i, &byte) in data.iter().enumerate() {
+            unsafe { ptr::write_volatile(PRG_RAM.add(offset + i), byte) };
+        }
+    }
+}
+
+impl Drop for SubHold {
+    fn drop(&mut self) {
+        modify_reset(0x02, false);
+    }
+}
+
+/// Shows one of the four 128KB banks of PRG-RAM in the window at `PRG_RAM`.
+#[inline]
+pub fn set_prg_bank(bank: u8) {
+    // DMNA is left clear, which doesn't touch Word RAM.
+    write(MEMORY_MODE, (bank & 0x3) << 6);
+}
+
+/// Protects PRG-RAM from the sub CPU's writes below `units` times 512 bytes, which keeps a
+/// program from overwriting the BIOS's area.
+#[inline]
+pub fn set_write_protect(units: u8) {
+    write(WRITE_PROTECT, units);
+}
+
+#[inline]
+pub fn word_ram_mode() -> WordRamMode {
+    if read(MEMORY_MODE) & 0x04 != 0 { WordRamMode::OneMeg } else { WordRamMode::TwoMeg }
+}
+
+/// In 2M mode, returns true while the main CPU has Word RAM. In 1M mode, returns true once a swap
+/// asked for with `give_word_ram` is done.
+#[inline]
+pub fn has_word_ram() -> bool {
+    let mode = read(MEMORY_MODE);
+    match word_ram_mode() {
+        WordRamMode::TwoMeg => mode & 0x01 != 0,
+        WordRamMode::OneMeg => mode & 0x02 == 0,
+    }
+}
+
+/// In 2M mode, hands Word RAM over to the sub CPU, which gives it back when it's done with it.
+/// In 1M mode, asks for the banks to be swapped.
+///
+/// Either way, `has_word_ram` turns true once the main CPU can use it again, which
+/// `wait_word_ram` waits for.
+#[inline]
+pub fn give_word_ram() {
+    write(MEMORY_MODE, (read(MEMORY_MODE) & 0xC0) | 0x02);
+}
+
+/// Waits until the main CPU can use Word RAM.
+#[inline]
+pub fn wait_word_ram() {
+    while !has_word_ram() {}
+}
+
+/// The main CPU's eight communication flags, which the sub CPU can read.
+#[inline]
+pub fn main_flags() -> u8 {
+    read(MAIN_FLAGS)
+}
+
+#[inline]
+pub fn set_main_flags(flags: u8) {
+    write(MAIN_FLAGS, flags);
+}
+
+/// The sub CPU's eight communication flags.
+#[inline]
+pub fn sub_flags() -> u8 {
+    read(SUB_FLAGS)
+}
+
+/// Sets one of the command words the sub CPU reads, from 0 to 7.
+#[inline]
+pub fn set_command(index: usize, value: u16) {
+    unsafe { ptr::write_volatile(COMMANDS.add(index % COMM_WORDS), value) }
+}
+
+/// One of the status words the sub CPU writes, from 0 to 7.
+#[inline]
+pub fn status(index: usize) -> u16 {
+    unsafe { ptr::read_volatile(STATUSES.add(index % COMM_WORDS)) }
+}
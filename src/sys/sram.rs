@@ -0,0 +1,80 @@
+use core::ptr;
+
+/// The first byte of battery backed SRAM. SRAM is only wired to the odd bytes of the cartridge bus.
+const SRAM_BASE: *mut u8 = 0x200001 as *mut _;
+/// The mapper register that switches SRAM into the address space, replacing ROM above 2 MB.
+const SRAM_CTRL: *mut u8 = 0xA130F1 as *mut _;
+
+const SRAM_ENABLE: u8 = 0x1;
+const SRAM_WRITE_PROTECT: u8 = 0x2;
+
+#[inline]
+pub unsafe fn enable_sram(writable: bool) {
+    ptr::write_volatile(SRAM_CTRL, if writable { SRAM_ENABLE } else { SRAM_ENABLE | SRAM_WRITE_PROTECT });
+}
+
+#[inline]
+pub unsafe fn disable_sram() {
+    ptr::write_volatile(SRAM_CTRL, 0);
+}
+
+/// A structure used to guard SRAM access.
+///
+/// SRAM is switched out of the address space when this guard is dropped, so ROM above 2 MB is
+/// readable again.
+pub struct SramGuard<'a>(core::marker::PhantomData<&'a ()>);
+
+impl<'a> SramGuard<'a> {
+    #[inline(always)]
+    pub unsafe fn new() -> Self {
+        unsafe { enable_sram(true); }
+        Self(core::marker::PhantomData)
+    }
+
+    /// Reads `buf.len()` bytes starting at the byte offset `offset`.
+    #[inline]
+    pub fn read(&self, offset: u16, buf: &mut [u8]) {
+        let mut src = unsafe { SRAM_BASE.add((offset as usize) << 1) };
+        for byte in buf {
+            unsafe {
+                *byte = ptr::read_volatile(src);
+                src = src.add(2);
+            }
+        }
+    }
+
+    /// Writes `data` starting at the byte offset `offset`.
+    #[inline]
+    pub fn write(&self, offset: u16, data: &[u8]) {
+        let mut dst = unsafe { SRAM_BASE.add((offset as usize) << 1) };
+        for &byte in data {
+            unsafe {
+                ptr::write_volatile(dst, byte);
+                dst = dst.add(2);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn read_byte(&self, offset: u16) -> u8 {
+        unsafe { ptr::read_volatile(SRAM_BASE.add((offset as usize) << 1)) }
+    }
+
+    #[inline]
+    pub fn write_byte(&self, offset: u16, value: u8) {
+        unsafe { ptr::write_volatile(SRAM_BASE.add((offset as usize) << 1), value) }
+    }
+}
+
+impl<'a> Drop for SramGuard<'a> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        unsafe { disable_sram(); }
+    }
+}
+
+#[inline]
+pub fn with_sram<T, F: FnOnce(&SramGuard<'_>) -> T>(f: F) -> T {
+    let guard = unsafe { SramGuard::new() };
+    f(&guard)
+}
@@ -0,0 +1,326 @@
+use super::header;
+use super::sram::{self, SramGuard};
+use super::vdp::{self, Address, Reader, VRAMAddress, Writer, VDP};
+
+const MAGIC: [u8; 4] = *b"SUSP";
+
+/// Magic, ROM checksum, payload length, payload checksum and game state length.
+const HEADER_LEN: u16 = 12;
+
+const VRAM_BYTES: u32 = 0x10000;
+const CRAM_BYTES: u32 = 0x80;
+const VSRAM_BYTES: u32 = 0x50;
+
+/// VDP memory is copied through a buffer of this many words at a time, with interrupts masked.
+const CHUNK_WORDS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The snapshot doesn't fit in the space given. Nothing was saved.
+    TooBig,
+    /// There's no snapshot to resume from.
+    NoSnapshot,
+    /// The snapshot was saved by a different build of the game.
+    WrongRom,
+    /// The snapshot doesn't match its checksum, or its game state is a different size.
+    Corrupt,
+}
+
+/// Packs bytes with PackBits run length encoding, straight into SRAM.
+struct Packer<'a> {
+    sram: &'a SramGuard<'a>,
+    pos: u16,
+    end: u16,
+    overflow: bool,
+    sum: u16,
+    literal: [u8; 128],
+    literal_len: u8,
+    run_byte: u8,
+    run_len: u8,
+}
+
+impl<'a> Packer<'a> {
+    fn new(sram: &'a SramGuard<'a>, start: u16, end: u16) -> Self {
+        Self {
+            sram,
+            pos: start,
+            end,
+            overflow: false,
+            sum: 0,
+            literal: [0; 128],
+            literal_len: 0,
+            run_byte: 0,
+            run_len: 0,
+        }
+    }
+
+    fn emit(&mut self, byte: u8) {
+        if self.pos >= self.end {
+            self.overflow = true;
+            return;
+        }
+        self.sram.write_byte(self.pos, byte);
+        self.sum = self.sum.wrapping_add(byte as u16);
+        self.pos += 1;
+    }
+
+    fn flush_literal(&mut self) {
+        if self.literal_len == 0 {
+            return;
+        }
+        self.emit(self.literal_len - 1);
+        for i in 0..self.literal_len as usize {
+            self.emit(self.literal[i]);
+        }
+        self.literal_len = 0;
+    }
+
+    fn flush_run(&mut self) {
+        if self.run_len >= 3 {
+            self.flush_literal();
+            self.emit((257 - self.run_len as u16) as u8);
+            self.emit(self.run_byte);
+        } else {
+            // Runs this short are cheaper as part of a literal.
+            for _ in 0..self.run_len {
+                if self.literal_len == 128 {
+                    self.flush_literal();
+                }
+                self.literal[self.literal_len as usize] = self.run_byte;
+                self.literal_len += 1;
+            }
+        }
+        self.run_len = 0;
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.run_len != 0 && byte == self.run_byte && self.run_len < 128 {
+            self.run_len += 1;
+            return;
+        }
+        self.flush_run();
+        self.run_byte = byte;
+        self.run_len = 1;
+    }
+
+    fn push_all(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.push(byte);
+        }
+    }
+
+    fn finish(&mut self) {
+        self.flush_run();
+        self.flush_literal();
+    }
+}
+
+/// Unpacks PackBits data from SRAM.
+struct Unpacker<'a> {
+    sram: &'a SramGuard<'a>,
+    pos: u16,
+    end: u16,
+    literal_left: u8,
+    run_left: u8,
+    run_byte: u8,
+}
+
+impl<'a> Unpacker<'a> {
+    fn new(sram: &'a SramGuard<'a>, start: u16, end: u16) -> Self {
+        Self {
+            sram,
+            pos: start,
+            end,
+            literal_left: 0,
+            run_left: 0,
+            run_byte: 0,
+        }
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        if self.pos >= self.end {
+            return None;
+        }
+        let byte = self.sram.read_byte(self.pos);
+        self.pos += 1;
+        Some(byte)
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        loop {
+            if self.run_left != 0 {
+                self.run_left -= 1;
+                return Some(self.run_byte);
+            }
+            if self.literal_left != 0 {
+                self.literal_left -= 1;
+                return self.read();
+            }
+
+            match self.read()? {
+                128 => {}
+                header if header < 128 => self.literal_left = header + 1,
+                header => {
+                    self.run_left = (257 - header as u16) as u8;
+                    self.run_byte = self.read()?;
+                }
+            }
+        }
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        for byte in buf {
+            *byte = self.next().ok_or(Error::Corrupt)?;
+        }
+        Ok(())
+    }
+}
+
+#[inline]
+fn read_u16(sram: &SramGuard<'_>, offset: u16) -> u16 {
+    u16::from_be_bytes([sram.read_byte(offset), sram.read_byte(offset + 1)])
+}
+
+#[inline]
+fn write_u16(sram: &SramGuard<'_>, offset: u16, value: u16) {
+    sram.write(offset, &value.to_be_bytes());
+}
+
+/// Masks interrupts and waits out any DMA, so nothing else touches the VDP's ports.
+#[inline]
+fn with_vdp<R>(f: impl FnOnce() -> R) -> R {
+    super::with_cs::<1, 7, _>(|_| {
+        while VDP::status().dma_in_progress() {
+            core::hint::spin_loop();
+        }
+        f()
+    })
+}
+
+/// Every region of VDP memory that's saved, in order.
+fn regions() -> [(fn(u32) -> Address, u32); 3] {
+    [
+        (|addr| Address::VRAM(VRAMAddress::from_byte_addr(addr)), VRAM_BYTES),
+        (|addr| Address::CRAM(addr as u8), CRAM_BYTES),
+        (|addr| Address::VSRAM(addr as u8), VSRAM_BYTES),
+    ]
+}
+
+/// Saves the whole of VRAM, CRAM and VSRAM, the VDP settings, and the game's own `state`, to SRAM
+/// at `offset`, packed with run length encoding so a typical screen fits on a 32KB cartridge. At
+/// most `capacity` bytes of SRAM are used. Returns how many were.
+///
+/// This takes a few frames, during which interrupts are held off for a few lines at a time, so
+/// the music should be paused first. The snapshot is only marked valid once it's complete, so
+/// losing power partway through leaves nothing to resume rather than a broken snapshot.
+pub fn suspend(offset: u16, capacity: u16, state: &[u8]) -> Result<u16, Error> {
+    if capacity < HEADER_LEN {
+        return Err(Error::TooBig);
+    }
+
+    sram::with_sram(|sram| {
+        // Invalidate any older snapshot first.
+        sram.write(offset, &[0; 4]);
+
+        let start = offset + HEADER_LEN;
+        let mut packer = Packer::new(sram, start, offset.saturating_add(capacity));
+        let mut words = [0u16; CHUNK_WORDS];
+        for (address, len) in regions() {
+            let mut addr = 0;
+            while addr < len && !packer.overflow {
+                let chunk = &mut words[..(((len - addr) >> 1) as usize).min(CHUNK_WORDS)];
+                with_vdp(|| Reader::new(address(addr)).with_autoinc(2).read(chunk));
+                for word in chunk.iter() {
+                    packer.push_all(&word.to_be_bytes());
+                }
+                addr += (chunk.len() << 1) as u32;
+            }
+        }
+        packer.push_all(&vdp::Settings::current().to_bytes());
+        packer.push_all(state);
+        packer.finish();
+
+        if packer.overflow {
+            return Err(Error::TooBig);
+        }
+
+        let payload_len = packer.pos - start;
+        write_u16(sram, offset + 4, header::header().checksum());
+        write_u16(sram, offset + 6, payload_len);
+        write_u16(sram, offset + 8, packer.sum);
+        write_u16(sram, offset + 10, state.len() as u16);
+        sram.write(offset, &MAGIC);
+        Ok(HEADER_LEN + payload_len)
+    })
+}
+
+/// Returns true if there's a snapshot at `offset` to resume from.
+pub fn is_suspended(offset: u16) -> bool {
+    sram::with_sram(|sram| {
+        let mut magic = [0; 4];
+        sram.read(offset, &mut magic);
+        magic == MAGIC
+    })
+}
+
+/// Throws away the snapshot at `offset`, if there is one.
+pub fn discard(offset: u16) {
+    sram::with_sram(|sram| sram.write(offset, &[0; 4]))
+}
+
+/// Restores a snapshot saved by `suspend`, filling in `state` with the game's own state, then
+/// discards it so the game can't be resumed twice from the same point.
+///
+/// Everything is checked before the VDP is touched, so on error the screen is left as it was. The
+/// display is best turned off while restoring, since VRAM fills in over a few frames.
+pub fn resume(offset: u16, state: &mut [u8]) -> Result<(), Error> {
+    sram::with_sram(|sram| {
+        let mut magic = [0; 4];
+        sram.read(offset, &mut magic);
+        if magic != MAGIC {
+            return Err(Error::NoSnapshot);
+        }
+        if read_u16(sram, offset + 4) != header::header().checksum() {
+            return Err(Error::WrongRom);
+        }
+
+        let start = offset + HEADER_LEN;
+        let end = start.checked_add(read_u16(sram, offset + 6)).ok_or(Error::Corrupt)?;
+        let sum = (start..end).fold(0u16, |sum, pos| sum.wrapping_add(sram.read_byte(pos) as u16));
+        if sum != read_u16(sram, offset + 8) || read_u16(sram, offset + 10) as usize != state.len() {
+            return Err(Error::Corrupt);
+        }
+
+        // Settings and state come after VDP memory, so they're unpacked and checked up front.
+        let mut unpacker = Unpacker::new(sram, start, end);
+        let mut skip = VRAM_BYTES + CRAM_BYTES + VSRAM_BYTES;
+        while skip != 0 {
+            unpacker.next().ok_or(Error::Corrupt)?;
+            skip -= 1;
+        }
+        let mut settings = [0; vdp::Settings::BYTES];
+        unpacker.fill(&mut settings)?;
+        let settings = vdp::Settings::from_bytes(&settings).ok_or(Error::Corrupt)?;
+        unpacker.fill(state)?;
+
+        let mut unpacker = Unpacker::new(sram, start, end);
+        let mut bytes = [0u8; CHUNK_WORDS * 2];
+        let mut words = [0u16; CHUNK_WORDS];
+        for (address, len) in regions() {
+            let mut addr = 0;
+            while addr < len {
+                let count = (((len - addr) >> 1) as usize).min(CHUNK_WORDS);
+                unpacker.fill(&mut bytes[..count << 1])?;
+                for (word, pair) in words.iter_mut().zip(bytes.chunks_exact(2)) {
+                    *word = u16::from_be_bytes([pair[0], pair[1]]);
+                }
+                with_vdp(|| Writer::new(address(addr)).with_autoinc(2).write::<[u16]>(&words[..count]));
+                addr += (count << 1) as u32;
+            }
+        }
+        settings.apply::<true>();
+
+        sram.write(offset, &[0; 4]);
+        Ok(())
+    })
+}
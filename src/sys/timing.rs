@@ -0,0 +1,274 @@
+#[cfg(feature = "audio")]
+use core::cell;
+use core::ptr;
+
+#[cfg(feature = "audio")]
+use critical_section as cs;
+use fixed::types::{U16F16, U8F8};
+
+#[cfg(feature = "audio")]
+use super::{audio::ym, io};
+use super::vdp::VDP;
+
+/// The NTSC vertical refresh rate, in Hz.
+pub const NTSC_FRAME_RATE: U8F8 = U8F8::lit("59.922743");
+/// The PAL vertical refresh rate, in Hz.
+pub const PAL_FRAME_RATE: U8F8 = U8F8::lit("49.701459");
+
+/// The length of an NTSC frame, in seconds.
+pub const NTSC_FRAME_TIME: U16F16 = U16F16::lit("0.016688155");
+/// The length of a PAL frame, in seconds.
+pub const PAL_FRAME_TIME: U16F16 = U16F16::lit("0.020120141");
+
+/// The number of scanlines in an NTSC frame, including vblank.
+pub const NTSC_LINES: u16 = 262;
+/// The number of scanlines in a PAL frame, including vblank.
+pub const PAL_LINES: u16 = 313;
+
+/// The number of active display lines, assuming the usual 224 line mode.
+const ACTIVE_LINES: u16 = 224;
+
+/// The number of vblanks since startup. Only written by the vblank handler.
+static mut FRAME_COUNT: u32 = 0;
+
+/// Counts a frame. Called from the vblank handler.
+#[inline]
+pub(super) fn tick() {
+    unsafe { ptr::write_volatile(&raw mut FRAME_COUNT, ptr::read_volatile(&raw const FRAME_COUNT).wrapping_add(1)) }
+}
+
+/// The number of frames since startup. Wraps around after about 2.2 years on NTSC.
+#[inline]
+pub fn elapsed_frames() -> u32 {
+    // Longword moves can't be split by an interrupt, so no critical section is needed here.
+    unsafe { ptr::read_volatile(&raw const FRAME_COUNT) }
+}
+
+/// The number of frames elapsed since `since`, a value earlier returned by `elapsed_frames`.
+#[inline]
+pub fn frames_since(since: u32) -> u32 {
+    elapsed_frames().wrapping_sub(since)
+}
+
+/// Waits until `n` more vblanks have happened.
+#[inline(never)]
+pub fn wait_frames(n: u32) {
+    let start = elapsed_frames();
+    while frames_since(start) < n {
+        core::hint::spin_loop();
+    }
+}
+
+/// Returns true if the console is running at PAL timings.
+#[inline]
+pub fn is_pal() -> bool {
+    VDP::status().is_pal()
+}
+
+/// The vertical refresh rate the console is running at, in Hz.
+#[inline]
+pub fn frame_rate() -> U8F8 {
+    if is_pal() { PAL_FRAME_RATE } else { NTSC_FRAME_RATE }
+}
+
+/// The length of a frame at the current refresh rate, in seconds. This is the delta time for code
+/// that runs once per frame.
+#[inline]
+pub fn frame_time() -> U16F16 {
+    if is_pal() { PAL_FRAME_TIME } else { NTSC_FRAME_TIME }
+}
+
+/// Converts a number of frames to seconds at the current refresh rate, saturating at about 18 hours.
+#[inline]
+pub fn frames_to_seconds(frames: u32) -> U16F16 {
+    frame_time().saturating_mul_int(frames)
+}
+
+/// Converts a number of seconds to frames at the current refresh rate, rounding down.
+#[inline]
+pub fn seconds_to_frames(seconds: U16F16) -> u32 {
+    // Done in two halves, to keep to 32 bit multiplies.
+    let rate = frame_rate().to_bits() as u32;
+    let bits = seconds.to_bits();
+    (((bits >> 16) * rate) >> 8) + (((bits & 0xFFFF) * rate) >> 24)
+}
+
+/// The number of scanlines in a frame at the current refresh rate.
+#[inline]
+pub fn lines_per_frame() -> u16 {
+    if is_pal() { PAL_LINES } else { NTSC_LINES }
+}
+
+/// The number of scanlines since vblank last started, read from the VDP's V counter.
+///
+/// The counter wraps partway through vblank, so lines read there are only approximate. In the 240
+/// line mode, lines in the active display come out 16 lines, or about 1ms, late.
+pub fn lines_since_vblank() -> u16 {
+    let line = (VDP::hv_counter() >> 8) as u16;
+    let total = lines_per_frame();
+    if line >= ACTIVE_LINES {
+        (line - ACTIVE_LINES).min(total - ACTIVE_LINES - 1)
+    } else {
+        line + total - ACTIVE_LINES
+    }
+}
+
+/// The YM2612's internal sample rate in Hz, which timer A counts at, on NTSC and PAL consoles.
+#[cfg(feature = "audio")]
+const FM_TIMER_RATE: [u32; 2] = [7_670_453 / 144, 7_600_489 / 144];
+
+/// The timer control bits written to the YM2612: load in bits 0-1 and flag enable in bits 2-3.
+#[cfg(feature = "audio")]
+static FM_TIMER_CTRL: cs::Mutex<cell::Cell<u8>> = cs::Mutex::new(cell::Cell::new(0));
+#[cfg(feature = "audio")]
+static FM_TIMER_CALLBACKS: cs::Mutex<cell::Cell<[Option<fn(cs::CriticalSection)>; 2]>> =
+    cs::Mutex::new(cell::Cell::new([None; 2]));
+
+/// One of the YM2612's two timers, as a timing source finer than a frame.
+///
+/// Timer A counts in steps of about 19µs, up to about 19ms. Timer B counts in steps of about
+/// 300µs, up to about 77ms. Each raises a flag when it runs out and starts over, which can be
+/// polled, or checked by the vblank handler, which runs a callback for it.
+///
+/// ```ignore
+/// io::with_paused_z80(|bus| {
+///     FmTimer::A.set_period_us(bus, 1000);
+///     FmTimer::A.start(bus);
+/// });
+/// loop {
+///     if io::with_paused_z80(|bus| FmTimer::A.poll(bus)) {
+///         // A millisecond has gone by.
+///     }
+/// }
+/// ```
+///
+/// The timers share a register with channel 3's special mode, which is left off, so music that
+/// uses the special mode will stop the timers.
+#[cfg(feature = "audio")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FmTimer {
+    A = 0,
+    B = 1,
+}
+
+#[cfg(feature = "audio")]
+impl FmTimer {
+    /// The timer's bit in the control and status registers.
+    #[inline]
+    const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    /// The number of steps the timer can count.
+    #[inline]
+    pub const fn max_count(self) -> u16 {
+        match self {
+            FmTimer::A => 1024,
+            FmTimer::B => 256,
+        }
+    }
+
+    #[inline]
+    fn write_ctrl(bus: &io::Z80BusGuard<'_>, ctrl: u8) {
+        ym::write(bus, 0, ym::REG_TIMER_CTRL, ctrl);
+        ym::select(bus, ym::REG_DAC);
+    }
+
+    /// Sets the timer to run out every `count` steps, from 1 to `max_count`.
+    pub fn set_count(self, bus: &io::Z80BusGuard<'_>, count: u16) {
+        let value = self.max_count() - count.clamp(1, self.max_count());
+        match self {
+            FmTimer::A => {
+                ym::write(bus, 0, 0x24, (value >> 2) as u8);
+                ym::write(bus, 0, 0x25, value as u8 & 3);
+            }
+            FmTimer::B => ym::write(bus, 0, 0x26, value as u8),
+        }
+        ym::select(bus, ym::REG_DAC);
+    }
+
+    /// Sets the timer to run out as close to every `us` microseconds as it can count, and returns
+    /// the count used.
+    pub fn set_period_us(self, bus: &io::Z80BusGuard<'_>, us: u32) -> u16 {
+        let rate = FM_TIMER_RATE[is_pal() as usize];
+        let count = match self {
+            FmTimer::A => us.min(20_000) * rate / 1_000_000,
+            FmTimer::B => us.min(80_000) * (rate / 16) / 1_000_000,
+        };
+        let count = (count as u16).clamp(1, self.max_count());
+        self.set_count(bus, count);
+        count
+    }
+
+    /// Starts the timer counting, with its flag cleared.
+    pub fn start(self, bus: &io::Z80BusGuard<'_>) {
+        super::with_cs::<1, 7, _>(|cs| {
+            let ctrl = FM_TIMER_CTRL.borrow(cs);
+            ctrl.set(ctrl.get() | self.bit() | self.bit() << 2);
+            Self::write_ctrl(bus, ctrl.get() | self.bit() << 4);
+        })
+    }
+
+    pub fn stop(self, bus: &io::Z80BusGuard<'_>) {
+        super::with_cs::<1, 7, _>(|cs| {
+            let ctrl = FM_TIMER_CTRL.borrow(cs);
+            ctrl.set(ctrl.get() & !(self.bit() | self.bit() << 2));
+            Self::write_ctrl(bus, ctrl.get() | self.bit() << 4);
+        })
+    }
+
+    /// Returns true if the timer has run out since its flag was last cleared.
+    #[inline]
+    pub fn expired(self, bus: &io::Z80BusGuard<'_>) -> bool {
+        ym::status(bus) & self.bit() != 0
+    }
+
+    /// Clears the timer's flag. The timer carries on counting.
+    pub fn clear(self, bus: &io::Z80BusGuard<'_>) {
+        let ctrl = super::with_cs::<1, 7, _>(|cs| FM_TIMER_CTRL.borrow(cs).get());
+        Self::write_ctrl(bus, ctrl | self.bit() << 4);
+    }
+
+    /// Returns true if the timer has run out, and clears its flag if it has.
+    #[inline]
+    pub fn poll(self, bus: &io::Z80BusGuard<'_>) -> bool {
+        let expired = self.expired(bus);
+        if expired {
+            self.clear(bus);
+        }
+        expired
+    }
+
+    /// Sets a function for the vblank handler to run when it finds the timer has run out, or
+    /// clears it. This only checks once a frame, so it suits timers slower than the frame rate.
+    pub fn set_callback(self, callback: Option<fn(cs::CriticalSection)>) {
+        super::with_cs::<1, 7, _>(|cs| {
+            let cell = FM_TIMER_CALLBACKS.borrow(cs);
+            let mut callbacks = cell.get();
+            callbacks[self as usize] = callback;
+            cell.set(callbacks);
+        })
+    }
+}
+
+/// Runs the callbacks of timers that have run out. Called from the vblank handler.
+#[cfg(feature = "audio")]
+pub(super) fn fm_timer_tick(cs: cs::CriticalSection) {
+    let callbacks = FM_TIMER_CALLBACKS.borrow(cs).get();
+    if callbacks == [None; 2] {
+        return;
+    }
+    let ctrl = FM_TIMER_CTRL.borrow(cs).get();
+    let status = io::with_paused_z80(|bus| {
+        let status = ym::status(bus) & ctrl;
+        if status != 0 {
+            FmTimer::write_ctrl(bus, ctrl | status << 4);
+        }
+        status
+    });
+    for timer in [FmTimer::A, FmTimer::B] {
+        if let (true, Some(callback)) = (status & timer.bit() != 0, callbacks[timer as usize]) {
+            callback(cs);
+        }
+    }
+}
@@ -5,6 +5,22 @@ use core::cell;
 
 use critical_section as cs;
 
+use super::layout::ROM_END;
+
+pub mod palette;
+pub mod vblank;
+pub mod raster;
+pub mod tiles;
+pub mod interlace;
+pub mod shadow;
+mod plane;
+mod scroll;
+
+pub use palette::{Color, Palette};
+pub use plane::{PlaneBuffer, PlaneOps};
+pub use tiles::{TileAllocator, TileHandle};
+pub use scroll::{HScrollTable, ParallaxBand, ParallaxLayers, Scroller};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub struct VRAMAddress(u16);
 
@@ -19,9 +35,21 @@ impl VRAMAddress {
         Self(addr)
     }
 
+    /// The byte address. Without the `vram-128k` feature, it wraps at 64KB.
     #[inline]
     pub const fn byte_addr(self) -> u32 {
-        (self.0 << 1) as u32
+        if cfg!(feature = "vram-128k") {
+            (self.0 as u32) << 1
+        } else {
+            (self.0 << 1) as u32
+        }
+    }
+
+    /// Returns true if the address is in the upper 64KB, which is only there in 128KB mode.
+    #[cfg(feature = "vram-128k")]
+    #[inline]
+    pub const fn is_extended(self) -> bool {
+        self.0 & 0x8000 != 0
     }
 
     #[inline]
@@ -29,12 +57,26 @@ impl VRAMAddress {
         self.0
     }
 
+    /// The address of tile `index`, 16 words per tile. Without the `vram-128k` feature, it wraps
+    /// at 64KB, or 2048 tiles.
     #[inline]
     pub const fn from_tile_index(index: u16) -> Self {
-        Self((index & 0x7FF) << 5)
+        if cfg!(feature = "vram-128k") {
+            Self((index & 0xFFF) << 4)
+        } else {
+            Self((index & 0x7FF) << 4)
+        }
     }
 }
 
+// Tiles are 32 bytes apart, and wrap at the end of VRAM.
+const _: () = assert!(VRAMAddress::from_tile_index(1).byte_addr() == 32);
+const _: () = assert!(VRAMAddress::from_tile_index(0x7FF).byte_addr() == 0xFFE0);
+#[cfg(feature = "vram-128k")]
+const _: () = assert!(VRAMAddress::from_tile_index(0x800).byte_addr() == 0x10000);
+#[cfg(not(feature = "vram-128k"))]
+const _: () = assert!(VRAMAddress::from_tile_index(0x800).byte_addr() == 0);
+
 // impl const From<u16> for VRAMAddress {
 //     fn from(value: u16) -> Self {
 //         Self(value)
@@ -124,6 +166,16 @@ impl Address {
         }
     }
 
+    /// The address `bytes` further on, wrapping around the end of the memory.
+    #[inline]
+    const fn offset(self, bytes: u32) -> Self {
+        match self {
+            Address::VRAM(addr) => Address::VRAM(VRAMAddress::from_byte_addr(addr.byte_addr() + bytes)),
+            Address::CRAM(addr) => Address::CRAM(addr.wrapping_add(bytes as u8)),
+            Address::VSRAM(addr) => Address::VSRAM(addr.wrapping_add(bytes as u8)),
+        }
+    }
+
     #[inline]
     pub fn cram_line(line: u8) -> Self {
         Self::CRAM((line & 0x3) << 4)
@@ -159,6 +211,50 @@ impl WindowClip {
             WindowClip::After(v) => 0x80 | (v & 0x1f),
         }
     }
+
+    fn from_raw_value(raw: u8) -> Self {
+        if raw & 0x80 != 0 {
+            WindowClip::After(raw & 0x1f)
+        } else {
+            WindowClip::Before(raw & 0x1f)
+        }
+    }
+
+    /// The column or row the clip splits the screen at, whichever side the window is on.
+    #[inline]
+    const fn split(self) -> u8 {
+        match self {
+            WindowClip::Before(v) | WindowClip::After(v) => v,
+        }
+    }
+
+    /// Returns true if the window covers none of the axis, given how many units it's split in.
+    #[inline]
+    const fn is_empty(self, units: u8) -> bool {
+        match self {
+            WindowClip::Before(v) => v == 0,
+            WindowClip::After(v) => v >= units,
+        }
+    }
+}
+
+/// Why a window configuration won't display as intended. The setters mask values into the bits
+/// the registers have, which silently turns these into a different configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowError {
+    /// The window table isn't on a 2KB boundary, or a 4KB boundary in H40, where the VDP ignores
+    /// the lowest bit of its base.
+    Misaligned,
+    /// The horizontal clip is past the right of the screen. It counts in pairs of tiles, so it
+    /// goes up to 16 in H32 and 20 in H40.
+    XClipOutOfRange,
+    /// The vertical clip is past the bottom of the screen, which is 28 tiles, or 30 in V30.
+    YClipOutOfRange,
+    /// The window is shown, and its table shares VRAM with plane A's.
+    OverlapsPlaneA,
+    OverlapsPlaneB,
+    OverlapsSprites,
+    OverlapsHScroll,
 }
 
 /// This enumeration is for configuring how vertical scrolling works.
@@ -180,6 +276,16 @@ pub enum HScrollMode {
     Lines = 0b11,
 }
 
+/// One of the two scrolling background planes.
+///
+/// The discriminant is the plane's word offset within each entry of the scroll tables.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Plane {
+    A = 0,
+    B = 1,
+}
+
 /// The interlacing rendering mode.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -397,7 +503,99 @@ pub type Tile = [u32; 8];
 #[macro_export]
 macro_rules! include_tiles {
     ($path:literal) => {
-        include_bytes_aligned_as!($crate::sys::vdp::Tile, $path)
+        $crate::include_bytes_aligned_as!($crate::sys::vdp::Tile, $path)
+    };
+}
+
+/// Like `include_tiles!`, but fails to compile if the file isn't a whole number of tiles, or, given
+/// a count, if it isn't exactly that many tiles.
+#[macro_export]
+macro_rules! include_tiles_checked {
+    ($path:literal) => {{
+        const _: () = assert!(include_bytes!($path).len() % 32 == 0, concat!($path, " isn't a whole number of tiles"));
+        $crate::include_bytes_aligned_as!($crate::sys::vdp::Tile, $path)
+    }};
+    ($path:literal, $count:expr) => {{
+        const _: () = assert!(include_bytes!($path).len() == $count * 32, concat!($path, " isn't ", stringify!($count), " tiles"));
+        $crate::include_bytes_aligned_as!($crate::sys::vdp::Tile, $path)
+    }};
+}
+
+/// Includes a palette line from a file of 16 big-endian CRAM words, failing to compile if it's any
+/// other size.
+///
+/// ```ignore
+/// const PLAYER_PALETTE: &Palette = include_palette!("../assets/player.pal");
+/// ```
+#[macro_export]
+macro_rules! include_palette {
+    ($path:literal) => {{
+        const _: () = assert!(include_bytes!($path).len() == 32, concat!($path, " isn't a palette of 16 colors"));
+        &$crate::include_bytes_aligned_as!($crate::sys::vdp::Palette, $path)[0]
+    }};
+}
+
+/// Tiles and a palette converted from an image by the build script.
+#[derive(Clone, Copy)]
+pub struct Image {
+    /// The image's tiles, in row-major order.
+    pub tiles: &'static [Tile],
+    pub palette: Palette,
+    /// The width of the image in tiles.
+    pub width: u16,
+    /// The height of the image in tiles.
+    pub height: u16,
+}
+
+impl Image {
+    /// Assembles an image from the build script's output. Used by `include_image!`.
+    #[doc(hidden)]
+    pub const fn from_parts(tiles: &'static [Tile], meta: &[u8]) -> Self {
+        const fn word(bytes: &[u8], index: usize) -> u16 {
+            u16::from_be_bytes([bytes[index * 2], bytes[index * 2 + 1]])
+        }
+
+        let mut palette = [Color::BLACK; 16];
+        let mut i = 0;
+        while i < 16 {
+            palette[i] = Color::from_bits(word(meta, i + 2));
+            i += 1;
+        }
+
+        Self {
+            tiles,
+            palette,
+            width: word(meta, 0),
+            height: word(meta, 1),
+        }
+    }
+
+    /// The tile at (`x`, `y`), counted in tiles.
+    #[inline]
+    pub fn tile(&self, x: u16, y: u16) -> &'static Tile {
+        &self.tiles[(y as usize * self.width as usize) + x as usize]
+    }
+}
+
+/// Includes a PNG from `src/assets` as an `Image`, converted to 4bpp tiles by the build script.
+///
+/// Indexed images with up to 16 colors keep their palette as-is, with index 0 being transparent.
+/// Anything else is quantized to its 15 most common colors, and transparent pixels use index 0.
+/// Images that aren't a whole number of tiles are padded with transparent pixels.
+///
+/// Tiles are in row-major order, which suits planes. Sprites bigger than a tile want column-major
+/// order instead.
+///
+/// ```ignore
+/// const PLAYER: vdp::Image = include_image!("player.png");
+/// ```
+#[macro_export]
+macro_rules! include_image {
+    ($path:literal) => {
+        $crate::sys::vdp::Image::from_parts(
+            $crate::include_bytes_aligned_as!($crate::sys::vdp::Tile, concat!(env!("OUT_DIR"), "/images/", $path, ".tiles")),
+            include_bytes!(concat!(env!("OUT_DIR"), "/images/", $path, ".pal")),
+        )
     };
 }
 
@@ -665,6 +863,88 @@ impl VRAMData for [Sprite] {
     }
 }
 
+/// Data that can be read back from VDP memory in place.
+pub trait VRAMDataMut: VRAMData {
+    fn as_words_mut(&mut self) -> &mut [u16];
+}
+
+impl<T: Send + Sync + 'static, const N: usize> VRAMDataMut for [T; N] where [T]: VRAMDataMut {
+    #[inline]
+    fn as_words_mut(&mut self) -> &mut [u16] {
+        VRAMDataMut::as_words_mut(self.as_mut_slice())
+    }
+}
+
+impl VRAMDataMut for u16 {
+    #[inline]
+    fn as_words_mut(&mut self) -> &mut [u16] {
+        core::slice::from_mut(self)
+    }
+}
+
+impl VRAMDataMut for [u16] {
+    #[inline]
+    fn as_words_mut(&mut self) -> &mut [u16] {
+        self
+    }
+}
+
+impl VRAMDataMut for i16 {
+    #[inline]
+    fn as_words_mut(&mut self) -> &mut [u16] {
+        unsafe { core::slice::from_raw_parts_mut((&raw mut *self).cast::<u16>(), 1) }
+    }
+}
+
+impl VRAMDataMut for [i16] {
+    #[inline]
+    fn as_words_mut(&mut self) -> &mut [u16] {
+        unsafe { core::slice::from_raw_parts_mut(self.as_mut_ptr().cast::<u16>(), self.len()) }
+    }
+}
+
+impl VRAMDataMut for TileFlags {
+    #[inline]
+    fn as_words_mut(&mut self) -> &mut [u16] {
+        unsafe { core::slice::from_raw_parts_mut((&raw mut *self).cast::<u16>(), 1) }
+    }
+}
+
+impl VRAMDataMut for [TileFlags] {
+    #[inline]
+    fn as_words_mut(&mut self) -> &mut [u16] {
+        unsafe { core::slice::from_raw_parts_mut(self.as_mut_ptr().cast::<u16>(), self.len()) }
+    }
+}
+
+impl VRAMDataMut for Tile {
+    #[inline]
+    fn as_words_mut(&mut self) -> &mut [u16] {
+        unsafe { core::slice::from_raw_parts_mut((&raw mut *self).cast::<u16>(), 16) }
+    }
+}
+
+impl VRAMDataMut for [Tile] {
+    #[inline]
+    fn as_words_mut(&mut self) -> &mut [u16] {
+        unsafe { core::slice::from_raw_parts_mut(self.as_mut_ptr().cast::<u16>(), self.len() << 4) }
+    }
+}
+
+impl VRAMDataMut for Sprite {
+    #[inline]
+    fn as_words_mut(&mut self) -> &mut [u16] {
+        unsafe { core::slice::from_raw_parts_mut((&raw mut *self).cast::<u16>(), 4) }
+    }
+}
+
+impl VRAMDataMut for [Sprite] {
+    #[inline]
+    fn as_words_mut(&mut self) -> &mut [u16] {
+        unsafe { core::slice::from_raw_parts_mut(self.as_mut_ptr().cast::<u16>(), self.len() << 2) }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Status(u16);
 
@@ -749,6 +1029,9 @@ impl Default for Settings {
 }
 
 impl Settings {
+    /// The size of the settings saved by `to_bytes`.
+    pub(in crate::sys) const BYTES: usize = 14;
+
     pub const DEFAULT: Self = Self {
         mode: 0x81007404,
         plane_a_base: 0x30,
@@ -776,6 +1059,50 @@ impl Settings {
         })
     }
 
+    /// The settings as they're written to the registers, for saving them.
+    pub(in crate::sys) fn to_bytes(&self) -> [u8; Self::BYTES] {
+        let mode = self.mode.to_be_bytes();
+        [
+            mode[0], mode[1], mode[2], mode[3],
+            self.sprites_base,
+            self.plane_a_base,
+            self.plane_b_base,
+            self.window_base,
+            self.hscroll_base,
+            self.plane_size as u8,
+            self.window_x_clip.raw_value(),
+            self.window_y_clip.raw_value(),
+            self.background_color,
+            self.hint_interval,
+        ]
+    }
+
+    /// Reads back settings saved with `to_bytes`. Returns `None` if they aren't valid.
+    pub(in crate::sys) fn from_bytes(bytes: &[u8; Self::BYTES]) -> Option<Self> {
+        let plane_size = [
+            PlaneSize::Size32x32,
+            PlaneSize::Size64x32,
+            PlaneSize::Size128x32,
+            PlaneSize::Size32x64,
+            PlaneSize::Size64x64,
+            PlaneSize::Size32x128,
+        ].into_iter().find(|&size| size as u8 == bytes[9])?;
+
+        Some(Self {
+            mode: u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            sprites_base: bytes[4],
+            plane_a_base: bytes[5],
+            plane_b_base: bytes[6],
+            window_base: bytes[7],
+            hscroll_base: bytes[8],
+            plane_size,
+            window_x_clip: WindowClip::from_raw_value(bytes[10]),
+            window_y_clip: WindowClip::from_raw_value(bytes[11]),
+            background_color: bytes[12],
+            hint_interval: bytes[13],
+        })
+    }
+
     #[inline(never)]
     pub fn apply<const FORCE: bool>(self) {
         super::with_cs::<1, 7, _>(|cs| {
@@ -848,37 +1175,67 @@ impl Settings {
     }
 
     #[inline]
-    pub fn modify_mode(&mut self, mode: u32, mask: u32) {
+    pub const fn modify_mode(&mut self, mode: u32, mask: u32) {
         self.mode = (self.mode & !mask) | (mode & mask)
     }
 
     #[inline]
-    pub fn set_scroll_mode(&mut self, hscroll: HScrollMode, vscroll: VScrollMode) {
+    pub const fn set_scroll_mode(&mut self, hscroll: HScrollMode, vscroll: VScrollMode) {
         self.modify_mode(((hscroll as u32) << 16) | ((vscroll as u32) << 18), 0x70000);
     }
 
     #[inline]
-    pub fn set_interlace_mode(&mut self, mode: InterlaceMode) {
+    pub const fn set_interlace_mode(&mut self, mode: InterlaceMode) {
         self.modify_mode((mode as u32) << 25, 0x6000000);
     }
 
+    /// Switches the VDP to 128KB VRAM mode. Only consoles with their VRAM upgraded, and some
+    /// emulators, have the memory for it, so use `VDP::detect_vram_128k` rather than turning it on
+    /// blind.
+    #[cfg(feature = "vram-128k")]
+    #[inline]
+    pub const fn enable_vram_128k(&mut self, enable: bool) {
+        self.modify_mode(flag_u32!(0x8000, enable), 0x8000);
+    }
+
+    #[cfg(feature = "vram-128k")]
+    #[inline]
+    pub const fn vram_128k(&self) -> bool {
+        self.mode & 0x8000 != 0
+    }
+
+    #[inline]
+    pub const fn interlace_mode(&self) -> InterlaceMode {
+        match (self.mode >> 25) & 0x3 {
+            0b01 => InterlaceMode::Interlace,
+            0b11 => InterlaceMode::DoubleRes,
+            _ => InterlaceMode::None,
+        }
+    }
+
     #[inline] 
-    pub fn set_background_color(&mut self, line: u8, index: u8) {
+    pub const fn set_background_color(&mut self, line: u8, index: u8) {
         self.background_color = ((line & 0x3) << 4) | (index & 0xF);
     }
 
+    /// The palette line and index of the backdrop color.
     #[inline]
-    pub fn enable_display(&mut self, enable: bool) {
+    pub const fn background_color(&self) -> (u8, u8) {
+        (self.background_color >> 4, self.background_color & 0xF)
+    }
+
+    #[inline]
+    pub const fn enable_display(&mut self, enable: bool) {
         self.modify_mode(flag_u32!(0x4000, enable), 0x4000);
     }
 
     // #[inline]
-    // pub fn enable_mode5(&mut self, enable: bool) {
+    // pub const fn enable_mode5(&mut self, enable: bool) {
     //     self.modify_mode(flag_u32!(0x400, enable), 0x400);
     // }
 
     #[inline]
-    pub fn enable_interrupts(&mut self, vint: bool, hint: bool, xint: bool) {
+    pub const fn enable_interrupts(&mut self, vint: bool, hint: bool, xint: bool) {
         self.modify_mode(
             flag_u32!(0x2000, vint) | flag_u32!(0x10, hint) | flag_u32!(0x80000, xint), 
             0x82010
@@ -886,242 +1243,527 @@ impl Settings {
     }
 
     #[inline]
-    pub fn stop_hv_on_xint(&mut self, stop: bool) {
+    pub const fn enable_hint(&mut self, enable: bool) {
+        self.modify_mode(flag_u32!(0x10, enable), 0x10);
+    }
+
+    #[inline]
+    pub const fn stop_hv_on_xint(&mut self, stop: bool) {
         self.modify_mode(flag_u32!(0x2, stop), 0x2);
     }
 
     #[inline]
-    pub fn enable_dma(&mut self, enable: bool) {
+    pub const fn enable_dma(&mut self, enable: bool) {
         self.modify_mode(flag_u32!(0x1000, enable), 0x1000);
     }
 
     #[inline]
-    pub fn enable_h40(&mut self, enable: bool) {
+    pub const fn enable_h40(&mut self, enable: bool) {
         self.modify_mode(flag_u32!(0x81000000, enable), 0x81000000);
     }
 
     #[inline]
-    pub fn enable_v30(&mut self, enable: bool) {
+    pub const fn enable_v30(&mut self, enable: bool) {
         self.modify_mode(flag_u32!(0x800, enable), 0x800);
     }
 
     #[inline]
-    pub fn enable_shadow_highlight(&mut self, enable: bool) {
+    pub const fn enable_shadow_highlight(&mut self, enable: bool) {
         self.modify_mode(flag_u32!(0x8000000, enable), 0x8000000);
     }
 
     #[inline]
-    pub fn set_hint_interval(&mut self, interval: u8) {
+    pub const fn set_hint_interval(&mut self, interval: u8) {
         self.hint_interval = interval;
     }
 
     #[inline]
-    pub fn set_plane_a_base(&mut self, addr: VRAMAddress) {
+    pub const fn set_plane_a_base(&mut self, addr: VRAMAddress) {
         self.plane_a_base = ((addr.word_addr() >> 9) as u8) & 0x78;
     }
 
     #[inline]
-    pub fn plane_a_base(&self) -> VRAMAddress {
+    pub const fn plane_a_base(&self) -> VRAMAddress {
         VRAMAddress::from_word_addr((self.plane_a_base as u16) << 9)
     }
 
     #[inline]
-    pub fn set_plane_b_base(&mut self, addr: VRAMAddress) {
+    pub const fn set_plane_b_base(&mut self, addr: VRAMAddress) {
         self.plane_b_base = ((addr.word_addr() >> 12) as u8) & 0xF;
     }
 
     #[inline]
-    pub fn plane_b_base(&self) -> VRAMAddress {
+    pub const fn plane_b_base(&self) -> VRAMAddress {
         VRAMAddress::from_word_addr((self.plane_b_base as u16) << 12)
     } 
 
     #[inline]
-    pub fn set_sprites_base(&mut self, addr: VRAMAddress) {
+    pub const fn set_sprites_base(&mut self, addr: VRAMAddress) {
         self.sprites_base = ((addr.word_addr() >> 8) as u8) & 0xFF;
     }
 
     #[inline]
-    pub fn sprites_base(&self) -> VRAMAddress {
+    pub const fn sprites_base(&self) -> VRAMAddress {
         VRAMAddress::from_word_addr((self.sprites_base as u16) << 8)
     }
 
     #[inline]
-    pub fn set_window_base(&mut self, addr: VRAMAddress) {
+    pub const fn set_window_base(&mut self, addr: VRAMAddress) {
         self.window_base = ((addr.word_addr() >> 9) as u8) & 0x7E;
     }
 
+    /// Sets the window base, or returns `WindowError::Misaligned` and leaves it as it was if the
+    /// address isn't one the register can hold in the current mode.
+    #[inline]
+    pub const fn try_set_window_base(&mut self, addr: VRAMAddress) -> Result<(), WindowError> {
+        if addr.word_addr() & (self.window_align_words() - 1) != 0 {
+            return Err(WindowError::Misaligned);
+        }
+        self.set_window_base(addr);
+        Ok(())
+    }
+
     #[inline]
-    pub fn window_base(&self) -> VRAMAddress {
+    pub const fn window_base(&self) -> VRAMAddress {
         VRAMAddress::from_word_addr((self.window_base as u16) << 9)
     }
 
     #[inline]
-    pub fn set_hscroll_base(&mut self, addr: VRAMAddress) {
+    pub const fn set_hscroll_base(&mut self, addr: VRAMAddress) {
         self.hscroll_base = ((addr.word_addr() >> 9) as u8) & 0x7F;
     }
 
     #[inline]
-    pub fn hscroll_base(&self) -> VRAMAddress {
+    pub const fn hscroll_base(&self) -> VRAMAddress {
         VRAMAddress::from_word_addr((self.hscroll_base as u16) << 9)
     }
 
     #[inline]
-    pub fn set_plane_size(&mut self, size: PlaneSize) {
+    pub const fn set_plane_size(&mut self, size: PlaneSize) {
         self.plane_size = size;
     }
 
     #[inline]
-    pub fn plane_size(&self) -> PlaneSize {
+    pub const fn plane_size(&self) -> PlaneSize {
         self.plane_size
     }
 
     #[inline]
-    pub fn set_window_clip(&mut self, x_clip: WindowClip, y_clip: WindowClip) {
+    pub const fn set_window_clip(&mut self, x_clip: WindowClip, y_clip: WindowClip) {
         self.window_x_clip = x_clip;
         self.window_y_clip = y_clip;
     }
 
+    /// Sets the window clip, or returns an error and leaves it as it was if either is off the
+    /// screen in the current mode.
+    #[inline]
+    pub const fn try_set_window_clip(&mut self, x_clip: WindowClip, y_clip: WindowClip) -> Result<(), WindowError> {
+        if x_clip.split() > self.window_x_units() {
+            return Err(WindowError::XClipOutOfRange);
+        }
+        if y_clip.split() > self.window_y_units() {
+            return Err(WindowError::YClipOutOfRange);
+        }
+        self.set_window_clip(x_clip, y_clip);
+        Ok(())
+    }
+
     #[inline] 
-    pub fn window_x_clip(&self) -> WindowClip {
+    pub const fn window_x_clip(&self) -> WindowClip {
         self.window_x_clip
     }
 
     #[inline] 
-    pub fn window_y_clip(&self) -> WindowClip {
+    pub const fn window_y_clip(&self) -> WindowClip {
         self.window_y_clip
     }
 
+    /// Returns true if the display is 40 tiles wide rather than 32.
     #[inline]
-    pub fn plane_a_tile(&self, x: u8, y: u8) -> VRAMAddress {
-        self.plane_size.tile_offset_from(self.plane_a_base(), x, y)
+    pub const fn is_h40(&self) -> bool {
+        self.mode & 0x81000000 == 0x81000000
     }
 
+    /// Returns true if the display is set to 30 tiles tall rather than 28, which only works on PAL.
     #[inline]
-    pub fn plane_b_tile(&self, x: u8, y: u8) -> VRAMAddress {
-        self.plane_size.tile_offset_from(self.plane_b_base(), x, y)
+    pub const fn is_v30(&self) -> bool {
+        self.mode & 0x800 != 0
     }
 
+    /// The width of the display.
     #[inline]
-    pub fn window_tile(&self, x: u8, y: u8) -> VRAMAddress {
-        self.plane_size.tile_offset_from(self.window_base(), x, y)
+    pub const fn display_width(&self) -> Extent {
+        if self.is_h40() { Extent::tiles(40) } else { Extent::tiles(32) }
     }
-}
-
-static GLOBAL_SETTINGS: cs::Mutex<cell::Cell<Settings>> = cs::Mutex::new(cell::Cell::new(Settings::DEFAULT));
 
-const VDP_DATA_PORT: *mut () = 0xC00000 as _;
-const VDP_CTRL_PORT: *mut () = 0xC00004 as _;
+    /// The height of the display. V30 mode only takes effect on PAL consoles, so this is 28 tiles
+    /// on NTSC whatever the setting.
+    #[inline]
+    pub fn display_height(&self) -> Extent {
+        if self.is_v30() && super::timing::is_pal() { Extent::tiles(30) } else { Extent::tiles(28) }
+    }
 
-#[repr(transparent)]
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub struct WordCmd(pub u16);
+    /// The width of the window's table in tiles, which is fixed by the display width rather than
+    /// following the plane size.
+    #[inline]
+    pub const fn window_width_tiles(&self) -> u8 {
+        if self.is_h40() { 64 } else { 32 }
+    }
 
-impl WordCmd {
-    const NULL: Self = Self(0);
+    #[inline]
+    const fn window_align_words(&self) -> u16 {
+        if self.is_h40() { 0x800 } else { 0x400 }
+    }
 
+    /// How many pairs of columns the horizontal clip can split the screen at.
     #[inline]
-    pub const fn set_reg(reg: u8, val: u8) -> Self {
-        Self(0x8000 | (((reg & 0x1F) as u16) << 8) | (val as u16))
+    const fn window_x_units(&self) -> u8 {
+        if self.is_h40() { 20 } else { 16 }
     }
 
     #[inline]
-    pub fn execute(self) {
-        unsafe {
-            // core::arch::asm!(
-            //     "move.w {cmd},({port})",
-            //     cmd = in(reg_data) self.0,
-            //     port = in(reg_addr) VDP_CTRL_PORT,
-            // );
-            ptr::write_volatile(VDP_CTRL_PORT as *mut u16, self.0);
-        }
+    const fn window_y_units(&self) -> u8 {
+        if self.is_v30() { 30 } else { 28 }
     }
-}
 
-#[repr(transparent)]
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub struct LongCmd(pub u32);
+    /// Returns true if the window covers any of the screen.
+    #[inline]
+    pub const fn is_window_shown(&self) -> bool {
+        !(self.window_x_clip.is_empty(self.window_x_units()) && self.window_y_clip.is_empty(self.window_y_units()))
+    }
 
-impl LongCmd {
+    /// Checks the window's base and clip against the rest of the settings, which can go wrong
+    /// after they're set, for instance by switching to H40 afterwards. Overlaps are only errors
+    /// while the window is shown, since a hidden window's table is never read.
+    ///
+    /// This is a `const fn`, so fixed settings can be checked when the game is built:
+    ///
+    /// ```ignore
+    /// const _: () = assert!(SETTINGS.check_window().is_ok());
+    /// ```
+    pub const fn check_window(&self) -> Result<(), WindowError> {
+        let base = self.window_base().word_addr() as u32;
+        if base & (self.window_align_words() as u32 - 1) != 0 {
+            return Err(WindowError::Misaligned);
+        }
+        if self.window_x_clip.split() > self.window_x_units() {
+            return Err(WindowError::XClipOutOfRange);
+        }
+        if self.window_y_clip.split() > self.window_y_units() {
+            return Err(WindowError::YClipOutOfRange);
+        }
+        if !self.is_window_shown() {
+            return Ok(());
+        }
 
-    #[inline]
-    pub const fn set_addr_w(addr: Address, dma: bool, copy: bool) -> Self {
-        let mut ctrl = match addr {
-            Address::VRAM(_) => 0x40000000,
-            Address::CRAM(_) => 0xC0000000,
-            Address::VSRAM(_) => 0x40000010,
-        };
-        let addr = addr.byte_addr();
-        if dma {
-            ctrl |= 0x80;
+        // Everything in words, which the whole of VRAM fits in.
+        const fn overlaps(a: u32, a_len: u32, b: u32, b_len: u32) -> bool {
+            a < b + b_len && b < a + a_len
         }
-        if copy {
-            ctrl |= 0x40;
+        let len = (self.window_width_tiles() as u32) << 5;
+        let plane_len = (self.plane_size.width_tiles() as u32) * (self.plane_size.height_tiles() as u32);
+        let sprites_len = if self.is_h40() { 80 * 4 } else { 64 * 4 };
+        let hscroll_len = match (self.mode >> 16) & 0x3 {
+            0b00 => 2,
+            _ => (self.window_y_units() as u32) << 4,
+        };
+
+        if overlaps(base, len, self.plane_a_base().word_addr() as u32, plane_len) {
+            Err(WindowError::OverlapsPlaneA)
+        } else if overlaps(base, len, self.plane_b_base().word_addr() as u32, plane_len) {
+            Err(WindowError::OverlapsPlaneB)
+        } else if overlaps(base, len, self.sprites_base().word_addr() as u32, sprites_len) {
+            Err(WindowError::OverlapsSprites)
+        } else if overlaps(base, len, self.hscroll_base().word_addr() as u32, hscroll_len) {
+            Err(WindowError::OverlapsHScroll)
+        } else {
+            Ok(())
         }
-        Self(((addr & 0x1C000) >> 14) | ((addr & 0x3FFF) << 16) | ctrl)
     }
 
+    /// Starts building settings from `DEFAULT`, for configuration that lives in a `const`.
     #[inline]
-    pub const fn set_addr_r(addr: Address, dma: bool, copy: bool) -> Self {
-        let mut ctrl = match addr {
-            Address::VRAM(_) => 0x00000000,
-            Address::CRAM(_) => 0x00000020,
-            Address::VSRAM(_) => 0x00000010,
-        };
-        let addr = addr.byte_addr();
-        if dma {
-            ctrl |= 0x80;
-        }
-        if copy {
-            ctrl |= 0x40;
-        }
-        Self(((addr & 0x1C000) >> 14) | ((addr & 0x3FFF) << 16) | ctrl)
+    pub const fn builder() -> SettingsBuilder {
+        SettingsBuilder(Self::DEFAULT)
     }
 
     #[inline]
-    pub const fn from_words(first: WordCmd, second: WordCmd) -> Self {
-        Self(((first.0 as u32) << 16) | (second.0 as u32))
+    pub fn plane_a_tile(&self, x: u8, y: u8) -> VRAMAddress {
+        self.plane_size.tile_offset_from(self.plane_a_base(), x, y)
     }
 
     #[inline]
-    pub fn execute(self) {
-        unsafe {
-            // core::arch::asm!(
-            //     "move.l {cmd},({port})",
-            //     cmd = in(reg_data) self.0,
-            //     port = in(reg_addr) VDP_CTRL_PORT,
-            // );
-            ptr::write_volatile(VDP_CTRL_PORT as *mut u32, self.0);
-        }
+    pub fn plane_b_tile(&self, x: u8, y: u8) -> VRAMAddress {
+        self.plane_size.tile_offset_from(self.plane_b_base(), x, y)
     }
 
     #[inline]
-    pub fn execute_dma(self) {
-        unsafe {
-            // core::arch::asm!(
-            //     "move.l {cmd},{scratch}",
-            //     "move.l {scratch},({port})",
-            //     cmd = in(reg_data) self.0,
-            //     port = in(reg_addr) VDP_CTRL_PORT,
-            //     scratch = sym SCRATCH,
-            // );
-            ptr::write_volatile(&raw mut LONG_CMD_SCRATCH, mem::MaybeUninit::new(self.0));
-            ptr::write_volatile(VDP_CTRL_PORT as *mut u32, ptr::read_volatile(&raw const LONG_CMD_SCRATCH).assume_init());
-        }
+    pub fn window_tile(&self, x: u8, y: u8) -> VRAMAddress {
+        let x = (x & (self.window_width_tiles() - 1)) as u16;
+        let y = (y & 0x1F) as u16;
+        VRAMAddress(self.window_base().0 + y * self.window_width_tiles() as u16 + x)
     }
 }
 
-static mut LONG_CMD_SCRATCH: mem::MaybeUninit<u32> = const { mem::MaybeUninit::uninit() };
-
-#[derive(Clone)]
-pub struct Writer(Address, Option<u8>);
+/// A length on screen, in pixels and in tiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Extent {
+    pub pixels: u16,
+    pub tiles: u8,
+}
 
-impl Writer {
+impl Extent {
     #[inline]
-    pub const fn new(addr: Address) -> Self {
-        Self(addr, None)
+    pub const fn tiles(tiles: u8) -> Self {
+        Self { pixels: (tiles as u16) << 3, tiles }
     }
+}
 
-    #[inline]
+/// Builds `Settings` in a `const`, starting from `Settings::DEFAULT`.
+///
+/// ```ignore
+/// const SETTINGS: Settings = Settings::builder()
+///     .with_h40(true)
+///     .with_plane_size(PlaneSize::Size64x64)
+///     .build();
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct SettingsBuilder(Settings);
+
+impl SettingsBuilder {
+    #[inline]
+    pub const fn with_scroll_mode(mut self, hscroll: HScrollMode, vscroll: VScrollMode) -> Self {
+        self.0.set_scroll_mode(hscroll, vscroll);
+        self
+    }
+
+    #[inline]
+    pub const fn with_interlace_mode(mut self, mode: InterlaceMode) -> Self {
+        self.0.set_interlace_mode(mode);
+        self
+    }
+
+    #[inline]
+    pub const fn with_background_color(mut self, line: u8, index: u8) -> Self {
+        self.0.set_background_color(line, index);
+        self
+    }
+
+    #[inline]
+    pub const fn with_display(mut self, enable: bool) -> Self {
+        self.0.enable_display(enable);
+        self
+    }
+
+    #[inline]
+    pub const fn with_interrupts(mut self, vint: bool, hint: bool, xint: bool) -> Self {
+        self.0.enable_interrupts(vint, hint, xint);
+        self
+    }
+
+    #[inline]
+    pub const fn with_dma(mut self, enable: bool) -> Self {
+        self.0.enable_dma(enable);
+        self
+    }
+
+    #[inline]
+    pub const fn with_h40(mut self, enable: bool) -> Self {
+        self.0.enable_h40(enable);
+        self
+    }
+
+    #[inline]
+    pub const fn with_v30(mut self, enable: bool) -> Self {
+        self.0.enable_v30(enable);
+        self
+    }
+
+    #[inline]
+    pub const fn with_shadow_highlight(mut self, enable: bool) -> Self {
+        self.0.enable_shadow_highlight(enable);
+        self
+    }
+
+    #[inline]
+    pub const fn with_hint_interval(mut self, interval: u8) -> Self {
+        self.0.set_hint_interval(interval);
+        self
+    }
+
+    #[inline]
+    pub const fn with_plane_a_base(mut self, addr: VRAMAddress) -> Self {
+        self.0.set_plane_a_base(addr);
+        self
+    }
+
+    #[inline]
+    pub const fn with_plane_b_base(mut self, addr: VRAMAddress) -> Self {
+        self.0.set_plane_b_base(addr);
+        self
+    }
+
+    #[inline]
+    pub const fn with_sprites_base(mut self, addr: VRAMAddress) -> Self {
+        self.0.set_sprites_base(addr);
+        self
+    }
+
+    #[inline]
+    pub const fn with_window_base(mut self, addr: VRAMAddress) -> Self {
+        self.0.set_window_base(addr);
+        self
+    }
+
+    #[inline]
+    pub const fn with_hscroll_base(mut self, addr: VRAMAddress) -> Self {
+        self.0.set_hscroll_base(addr);
+        self
+    }
+
+    #[inline]
+    pub const fn with_plane_size(mut self, size: PlaneSize) -> Self {
+        self.0.set_plane_size(size);
+        self
+    }
+
+    #[inline]
+    pub const fn with_window_clip(mut self, x_clip: WindowClip, y_clip: WindowClip) -> Self {
+        self.0.set_window_clip(x_clip, y_clip);
+        self
+    }
+
+    #[inline]
+    pub const fn build(self) -> Settings {
+        self.0
+    }
+
+    /// Builds the settings, or returns what's wrong with the window. See `Settings::check_window`.
+    #[inline]
+    pub const fn try_build(self) -> Result<Settings, WindowError> {
+        match self.0.check_window() {
+            Ok(()) => Ok(self.0),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+static GLOBAL_SETTINGS: cs::Mutex<cell::Cell<Settings>> = cs::Mutex::new(cell::Cell::new(Settings::DEFAULT));
+
+const VDP_DATA_PORT: *mut () = 0xC00000 as _;
+const VDP_CTRL_PORT: *mut () = 0xC00004 as _;
+const VDP_HV_COUNTER: *mut () = 0xC00008 as _;
+
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct WordCmd(pub u16);
+
+impl WordCmd {
+    const NULL: Self = Self(0);
+
+    #[inline]
+    pub const fn set_reg(reg: u8, val: u8) -> Self {
+        Self(0x8000 | (((reg & 0x1F) as u16) << 8) | (val as u16))
+    }
+
+    #[inline]
+    pub fn execute(self) {
+        unsafe {
+            // core::arch::asm!(
+            //     "move.w {cmd},({port})",
+            //     cmd = in(reg_data) self.0,
+            //     port = in(reg_addr) VDP_CTRL_PORT,
+            // );
+            ptr::write_volatile(VDP_CTRL_PORT as *mut u16, self.0);
+        }
+    }
+}
+
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct LongCmd(pub u32);
+
+impl LongCmd {
+
+    #[inline]
+    pub const fn set_addr_w(addr: Address, dma: bool, copy: bool) -> Self {
+        let mut ctrl = match addr {
+            Address::VRAM(_) => 0x40000000,
+            Address::CRAM(_) => 0xC0000000,
+            Address::VSRAM(_) => 0x40000010,
+        };
+        let addr = addr.byte_addr();
+        if dma {
+            ctrl |= 0x80;
+        }
+        if copy {
+            ctrl |= 0x40;
+        }
+        Self(((addr & 0x1C000) >> 14) | ((addr & 0x3FFF) << 16) | ctrl)
+    }
+
+    #[inline]
+    pub const fn set_addr_r(addr: Address, dma: bool, copy: bool) -> Self {
+        let mut ctrl = match addr {
+            Address::VRAM(_) => 0x00000000,
+            Address::CRAM(_) => 0x00000020,
+            Address::VSRAM(_) => 0x00000010,
+        };
+        let addr = addr.byte_addr();
+        if dma {
+            ctrl |= 0x80;
+        }
+        if copy {
+            ctrl |= 0x40;
+        }
+        Self(((addr & 0x1C000) >> 14) | ((addr & 0x3FFF) << 16) | ctrl)
+    }
+
+    #[inline]
+    pub const fn from_words(first: WordCmd, second: WordCmd) -> Self {
+        Self(((first.0 as u32) << 16) | (second.0 as u32))
+    }
+
+    #[inline]
+    pub fn execute(self) {
+        unsafe {
+            // core::arch::asm!(
+            //     "move.l {cmd},({port})",
+            //     cmd = in(reg_data) self.0,
+            //     port = in(reg_addr) VDP_CTRL_PORT,
+            // );
+            ptr::write_volatile(VDP_CTRL_PORT as *mut u32, self.0);
+        }
+    }
+
+    #[inline]
+    pub fn execute_dma(self) {
+        unsafe {
+            // core::arch::asm!(
+            //     "move.l {cmd},{scratch}",
+            //     "move.l {scratch},({port})",
+            //     cmd = in(reg_data) self.0,
+            //     port = in(reg_addr) VDP_CTRL_PORT,
+            //     scratch = sym SCRATCH,
+            // );
+            ptr::write_volatile(&raw mut LONG_CMD_SCRATCH, mem::MaybeUninit::new(self.0));
+            ptr::write_volatile(VDP_CTRL_PORT as *mut u32, ptr::read_volatile(&raw const LONG_CMD_SCRATCH).assume_init());
+        }
+    }
+}
+
+static mut LONG_CMD_SCRATCH: mem::MaybeUninit<u32> = const { mem::MaybeUninit::uninit() };
+
+#[derive(Clone)]
+pub struct Writer(Address, Option<u8>);
+
+impl Writer {
+    #[inline]
+    pub const fn new(addr: Address) -> Self {
+        Self(addr, None)
+    }
+
+    #[inline]
     pub fn with_autoinc(mut self, autoinc: impl Into<Option<u8>>) -> Self {
         self.1 = autoinc.into();
         self
@@ -1150,6 +1792,54 @@ impl Writer {
         }
     }
 
+    /// Like `write`, but runs `idle` while the VDP's write FIFO is full, instead of stalling on the
+    /// next write until a slot frees up.
+    ///
+    /// The FIFO holds four words, and during the active display the VDP only takes one off it every
+    /// few pixels, so a long write spends most of its time with the 68000 held up. `idle` gets that
+    /// time back. The FIFO is checked before every word, and `idle` is called once each time it's
+    /// found full, so it should do a small piece of work, such as stepping a decompressor, and must
+    /// not touch the VDP itself.
+    ///
+    /// With `ACTIVE_DISPLAY` false, the checks are compiled out, for writes made during vblank,
+    /// where the FIFO empties faster than the 68000 can fill it.
+    ///
+    /// ```ignore
+    /// Writer::new(Address::VRAM(0x2000)).write_throttled::<true, [u16]>(&tiles, || {
+    ///     decoder.step();
+    /// });
+    /// ```
+    #[inline]
+    pub fn write_throttled<const ACTIVE_DISPLAY: bool, T: VRAMData + ?Sized>(
+        self,
+        data: impl AsRef<T>,
+        mut idle: impl FnMut(),
+    ) {
+        self.begin();
+        let mut wait = || {
+            if ACTIVE_DISPLAY {
+                while VDP::status().fifo_full() {
+                    idle();
+                }
+            }
+        };
+        unsafe {
+            let (pairs, extra) = data.as_ref().as_word_pairs();
+            // A long write would stall on its second word with only one slot free, so the words go
+            // one at a time.
+            for &[high, low] in pairs {
+                wait();
+                ptr::write_volatile(VDP_DATA_PORT as *mut u16, high);
+                wait();
+                ptr::write_volatile(VDP_DATA_PORT as *mut u16, low);
+            }
+            if let Some(&extra) = extra {
+                wait();
+                ptr::write_volatile(VDP_DATA_PORT as *mut u16, extra);
+            }
+        }
+    }
+
     #[inline]
     pub fn write_iter<T: VRAMData + ?Sized>(self, iter: impl IntoIterator<Item = impl AsRef<T>>) {
         self.begin();
@@ -1179,6 +1869,65 @@ impl Writer {
     }
 }
 
+/// Reads data back from VRAM, CRAM or VSRAM into RAM.
+///
+/// Reads mustn't overlap a DMA. Only the color bits of CRAM words are meaningful, the rest read back
+/// as garbage.
+pub struct Reader(Address, Option<u8>);
+
+impl Reader {
+    #[inline]
+    pub const fn new(addr: Address) -> Self {
+        Self(addr, None)
+    }
+
+    #[inline]
+    pub fn with_autoinc(mut self, autoinc: impl Into<Option<u8>>) -> Self {
+        self.1 = autoinc.into();
+        self
+    }
+
+    #[inline]
+    fn begin(&self) {
+        if let Some(autoinc) = self.1 {
+            WordCmd::set_reg(0xF, autoinc).execute();
+        }
+
+        LongCmd::set_addr_r(self.0, false, false).execute();
+    }
+
+    /// Fills `data` with words read from VDP memory.
+    #[inline]
+    pub fn read<T: VRAMDataMut + ?Sized>(self, data: &mut T) {
+        self.begin();
+        let words = data.as_words_mut();
+        let (pairs, extra) = words.as_chunks_mut::<2>();
+        unsafe {
+            for pair in pairs {
+                *pair = ptr::read_volatile(VDP_DATA_PORT as *const [u16; 2]);
+            }
+            if let Some(extra) = extra.first_mut() {
+                *extra = ptr::read_volatile(VDP_DATA_PORT as *const u16);
+            }
+        }
+    }
+
+    /// Reads a single word.
+    #[inline]
+    pub fn read_word(self) -> u16 {
+        self.begin();
+        unsafe { ptr::read_volatile(VDP_DATA_PORT as *const u16) }
+    }
+
+    /// Reads a value of any type that can be read in place, like a `Tile` or a palette line.
+    #[inline]
+    pub fn read_value<T: VRAMDataMut + Default>(self) -> T {
+        let mut value = T::default();
+        self.read(&mut value);
+        value
+    }
+}
+
 pub struct VDP;
 
 impl VDP {
@@ -1208,25 +1957,28 @@ impl VDP {
         }
     }
 
+    /// Waits for the next vblank, running `handler` in it at `vblank::PRIORITY_WAIT`.
     #[inline]
     pub fn wait_for_vblank(handler: Option<fn(cs::CriticalSection)>) {
         fn null_handler(_cs: cs::CriticalSection) {}
-        unsafe {
-            Self::set_vint_handler(handler.unwrap_or(null_handler));
-            Self::vint_wait();
-        }
+        vblank::wait(handler.unwrap_or(null_handler));
     }
 
+    /// Sets the function run on every horizontal interrupt, or clears it.
+    ///
+    /// H-ints also need to be turned on with `Settings::enable_interrupts`, and fire every
+    /// `Settings::set_hint_interval` + 1 lines.
     #[inline]
-    unsafe fn set_vint_handler(handler: fn(cs::CriticalSection)) {
-        // We use volatile reads to force the compiler to not optimize or reorder things.
-        ptr::write_volatile(&raw mut VINT_HANDLER, Some(handler));
+    pub fn set_hblank_handler(handler: Option<fn()>) {
+        unsafe { ptr::write_volatile(&raw mut HINT_HANDLER, handler) }
     }
 
-    #[inline(never)]
-    unsafe fn vint_wait() {
-        while ptr::read_volatile(&raw const VINT_HANDLER).is_some() {
-            core::hint::spin_loop();
+    /// Reads the HV counter, with the vertical position in the high byte and the horizontal
+    /// position in the low byte.
+    #[inline]
+    pub fn hv_counter() -> u16 {
+        unsafe {
+            ptr::read_volatile(VDP_HV_COUNTER as *mut u16)
         }
     }
 
@@ -1296,24 +2048,235 @@ impl VDP {
     pub fn debug_halt() {
         WordCmd::set_reg(29, 0).execute();
     }
+
+    /// Turns on 128KB VRAM mode if the console has the memory for it, and returns whether it did.
+    ///
+    /// This writes a test word to the first word of the upper 64KB, and checks that it reads back
+    /// without landing on the first word of VRAM, which is put back afterwards. Without the memory,
+    /// the mode is turned back off, and everything carries on in 64KB. Call this with the display
+    /// off, before anything is loaded into VRAM.
+    #[cfg(feature = "vram-128k")]
+    pub fn detect_vram_128k() -> bool {
+        const TEST: u16 = 0xA55A;
+        let low = Address::VRAM(VRAMAddress::from_word_addr(0));
+        let high = Address::VRAM(VRAMAddress::from_word_addr(0x8000));
+
+        let mut settings = Settings::current();
+        settings.enable_vram_128k(true);
+        settings.apply::<false>();
+
+        let saved = Reader::new(low).read_word();
+        Writer::new(low).write::<[u16]>([!TEST]);
+        Writer::new(high).write::<[u16]>([TEST]);
+        let found = Reader::new(high).read_word() == TEST && Reader::new(low).read_word() == !TEST;
+        Writer::new(low).write::<[u16]>([saved]);
+
+        if !found {
+            settings.enable_vram_128k(false);
+            settings.apply::<false>();
+        }
+        crate::info!("vdp: 128KB VRAM {}", if found { "found" } else { "not found" });
+        found
+    }
+
+    /// How many words of DMA can be sent in one vblank with the current settings. Queued commands
+    /// past this are held over to the next vblank, so they don't run into the active display.
+    #[inline]
+    pub fn dma_budget() -> u16 {
+        dma_budget(&Settings::current())
+    }
+
+    /// Sets when queued DMA holds the Z80 off the 68k's bus.
+    #[inline]
+    pub fn set_z80_dma_policy(policy: Z80DmaPolicy) {
+        super::with_cs::<1, 7, _>(|cs| Z80_DMA_POLICY.borrow(cs).set(policy))
+    }
+
+    #[inline]
+    pub fn z80_dma_policy() -> Z80DmaPolicy {
+        super::with_cs::<1, 7, _>(|cs| Z80_DMA_POLICY.borrow(cs).get())
+    }
+
+    /// The total size of the commands waiting in the DMA queue, in words.
+    pub fn dma_queued_words() -> u32 {
+        super::with_cs::<1, 7, _>(|cs| {
+            let queue = DMA_QUEUE.borrow_ref(cs);
+            queue.iter().map(|cmd| cmd.words as u32).sum()
+        })
+    }
+}
+
+/// When the vblank handler takes the Z80's bus for the length of a queued transfer.
+///
+/// A Z80 reading from the 68k's bus, like the `pcm` driver does for samples in ROM, while a transfer
+/// is running can corrupt the transfer. Holding the Z80 off the bus avoids that, but stalls it, which
+/// a sample being played turns into an audible click for larger transfers. Fills and copies don't
+/// use the 68k's bus, so they never hold the Z80.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Z80DmaPolicy {
+    /// Leave the Z80 running. This is the default unless the `pcm` driver is enabled.
+    Never,
+    /// Hold the Z80 around transfers of at least this many words. The default with the `pcm` driver
+    /// is `Z80DmaPolicy::PCM_DEFAULT`.
+    From(u16),
+    /// Hold the Z80 around transfers from ROM, which is where a Z80 reading through its bank
+    /// window collides with them, and leave it running for transfers from RAM.
+    Rom,
+    Always,
+}
+
+impl Z80DmaPolicy {
+    /// Small transfers are over in a few scanlines, which the driver's sample timing can absorb.
+    pub const PCM_DEFAULT: Self = Self::From(0x200);
+
+    const DEFAULT: Self = if cfg!(feature = "pcm") { Self::PCM_DEFAULT } else { Self::Never };
+
+    #[inline]
+    fn holds_for(self, cmd: &DMACommand) -> bool {
+        match self {
+            Z80DmaPolicy::Never => false,
+            Z80DmaPolicy::From(min) => cmd.words >= min,
+            Z80DmaPolicy::Rom => cmd.is_from_rom(),
+            Z80DmaPolicy::Always => true,
+        }
+    }
+}
+
+impl Default for Z80DmaPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+static Z80_DMA_POLICY: cs::Mutex<cell::Cell<Z80DmaPolicy>> = cs::Mutex::new(cell::Cell::new(Z80DmaPolicy::DEFAULT));
+
+/// Words of DMA that fit in a line of vblank, in H32 and H40 modes.
+const DMA_WORDS_PER_LINE_H32: u16 = 80;
+const DMA_WORDS_PER_LINE_H40: u16 = 99;
+
+/// Lines of vblank set aside for the vblank handler's own work before the queue is sent.
+const DMA_RESERVED_LINES: u16 = 4;
+
+fn dma_budget(settings: &Settings) -> u16 {
+    let lines = super::timing::lines_per_frame() - settings.display_height().pixels - DMA_RESERVED_LINES;
+    let per_line = if settings.is_h40() { DMA_WORDS_PER_LINE_H40 } else { DMA_WORDS_PER_LINE_H32 };
+    lines * per_line
+}
+
+/// The size of the DMA staging buffer, in words.
+#[cfg(feature = "dma-staging")]
+pub const DMA_STAGING_WORDS: usize = 256;
+
+#[cfg(feature = "dma-staging")]
+struct Staging {
+    buf: [u16; DMA_STAGING_WORDS],
+    used: u16,
+}
+
+/// Copies of sources for `DMACommand::schedule_staged`, kept until the queue has been sent.
+#[cfg(feature = "dma-staging")]
+static DMA_STAGING: cs::Mutex<cell::RefCell<Staging>> = cs::Mutex::new(cell::RefCell::new(Staging {
+    buf: [0; DMA_STAGING_WORDS],
+    used: 0,
+}));
+
+/// Why `DMACommand::schedule_staged` couldn't queue a transfer.
+#[cfg(feature = "dma-staging")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StagingError {
+    /// The source doesn't fit in what's left of the staging buffer. It empties once the queue has
+    /// been sent.
+    Full,
+    QueueFull,
+}
+
+/// The VDP's DMA source address doesn't carry over from one 128KB block to the next.
+const TRANSFER_BOUNDARY: usize = 0x20000;
+/// The most words one command can send.
+const MAX_TRANSFER_WORDS: usize = 0xFFFF;
+
+/// Why a transfer couldn't be built or queued.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferError {
+    /// The source doesn't start on a word boundary, or has an odd number of bytes.
+    Unaligned,
+    /// The source is empty. A length of 0 tells the VDP to send 64K words instead.
+    Empty,
+    /// The source crosses a 128KB boundary, which one command can't do.
+    CrossesBoundary,
+    /// The source is more than 0xFFFF words.
+    TooLong,
+    /// The DMA queue hasn't got room for every part of the transfer.
+    QueueFull,
+}
+
+/// The commands of a transfer split up by `DMACommand::split_transfer`, in order.
+#[derive(Clone)]
+pub struct TransferChunks {
+    src: usize,
+    end: usize,
+    dst: Address,
+    autoinc: u8,
+}
+
+impl Iterator for TransferChunks {
+    type Item = DMACommand;
+
+    fn next(&mut self) -> Option<DMACommand> {
+        if self.src >= self.end {
+            return None;
+        }
+        let boundary = (self.src / TRANSFER_BOUNDARY + 1) * TRANSFER_BOUNDARY;
+        let bytes = (self.end.min(boundary) - self.src).min(MAX_TRANSFER_WORDS * 2);
+        let words = (bytes >> 1) as u16;
+        let cmd = DMACommand::transfer_from(self.src, words, self.dst, self.autoinc);
+        self.src += bytes;
+        self.dst = self.dst.offset(words as u32 * self.autoinc as u32);
+        Some(cmd)
+    }
 }
 
+/// A command for the VDP's DMA, which is either run straight away with `execute`, or queued for
+/// the next vblank with `schedule`.
+///
+/// A transfer only keeps the address of its source, which the VDP reads when the command runs, so
+/// a queued transfer's source has to outlive the queue. Locals on the stack are gone by then, and
+/// whatever has since reused their space is sent instead. `new_static_transfer` rules that out at
+/// compile time for sources in ROM or in statics, and with the `dma-staging` feature,
+/// `schedule_staged` takes a copy of short-lived sources. In debug builds, `schedule` panics if a
+/// transfer's source is on the stack.
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct DMACommand {
     cmds: [LongCmd; 4],
+    /// How much of the vblank's DMA bandwidth the command takes, in words.
+    words: u16,
+    /// Set for transfers, which read from the 68k's bus. Fills and copies stay inside the VDP.
+    from_68k: bool,
+    /// Whether to hold the Z80 off the bus while the command runs, or `None` to follow the
+    /// `Z80DmaPolicy`.
+    hold_z80: Option<bool>,
 }
 
 impl DMACommand {
+    /// Creates a transfer of `src` to `dst`.
+    ///
+    /// The VDP can't carry its source address over a 128KB boundary, and counts at most 0xFFFF
+    /// words, so a source that does either comes out broken. `try_new_transfer` checks for this,
+    /// and `schedule_transfer` splits such sources up.
     #[inline]
     pub fn new_transfer<T: VRAMData>(
         src: &[T],
         dst: Address,
         autoinc: Option<NonZero<u8>>,
     ) -> Self {
-        let autoinc = autoinc.map_or(2, NonZero::get);
-        let addr = (src.as_ptr().addr() >> 1) as u32;
         let len = ((src.len() * mem::size_of::<T>()) >> 1) as u16;
+        Self::transfer_from(src.as_ptr().addr(), len, dst, autoinc.map_or(2, NonZero::get))
+    }
+
+    #[inline]
+    fn transfer_from(src: usize, len: u16, dst: Address, autoinc: u8) -> Self {
+        let addr = (src >> 1) as u32;
         let cmds = [
             LongCmd::from_words(WordCmd::set_reg(0x0F, autoinc), WordCmd::set_reg(0x17, (addr >> 16) as u8)),
             LongCmd::from_words(WordCmd::set_reg(0x16, (addr >> 8) as u8), WordCmd::set_reg(0x15, addr as u8)),
@@ -1322,7 +2285,122 @@ impl DMACommand {
         ];
         Self {
             cmds,
+            words: len,
+            from_68k: true,
+            hold_z80: None,
+        }
+    }
+
+    /// Like `new_transfer`, but returns an error instead of a command that wouldn't send `src`
+    /// as it is.
+    pub fn try_new_transfer<T: VRAMData>(
+        src: &[T],
+        dst: Address,
+        autoinc: Option<NonZero<u8>>,
+    ) -> Result<Self, TransferError> {
+        let (start, bytes) = Self::check_transfer(src)?;
+        if bytes > MAX_TRANSFER_WORDS * 2 {
+            return Err(TransferError::TooLong);
+        }
+        if start / TRANSFER_BOUNDARY != (start + bytes - 1) / TRANSFER_BOUNDARY {
+            return Err(TransferError::CrossesBoundary);
+        }
+        Ok(Self::new_transfer(src, dst, autoinc))
+    }
+
+    /// Splits a transfer of `src` to `dst` into as many commands as it needs, breaking it at 128KB
+    /// boundaries in the source and where it's too long for one command.
+    pub fn split_transfer<T: VRAMData>(
+        src: &[T],
+        dst: Address,
+        autoinc: Option<NonZero<u8>>,
+    ) -> Result<TransferChunks, TransferError> {
+        let (start, bytes) = Self::check_transfer(src)?;
+        Ok(TransferChunks {
+            src: start,
+            end: start + bytes,
+            dst,
+            autoinc: autoinc.map_or(2, NonZero::get),
+        })
+    }
+
+    /// Queues a transfer of `src` to `dst` for the next vblank, split up as `split_transfer` does.
+    /// Either every part is queued, or none are.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, panics if `src` is on the stack, as `schedule` does.
+    pub fn schedule_transfer<T: VRAMData>(
+        src: &[T],
+        dst: Address,
+        autoinc: Option<NonZero<u8>>,
+    ) -> Result<(), TransferError> {
+        let chunks = Self::split_transfer(src, dst, autoinc)?;
+        if let Some(first) = chunks.clone().next() {
+            first.check_source();
         }
+        super::with_cs::<1, 7, _>(|cs| {
+            let mut queue = DMA_QUEUE.borrow_ref_mut(cs);
+            if queue.len() + chunks.clone().count() > DMA_QUEUE_LEN {
+                crate::warn!("dma: queue full");
+                return Err(TransferError::QueueFull);
+            }
+            for cmd in chunks {
+                let _ = queue.push_back(cmd);
+            }
+            Ok(())
+        })
+    }
+
+    /// The byte address and length of a transfer's source, if it can be sent at all.
+    #[inline]
+    fn check_transfer<T: VRAMData>(src: &[T]) -> Result<(usize, usize), TransferError> {
+        let start = src.as_ptr().addr();
+        let bytes = src.len() * mem::size_of::<T>();
+        if start & 1 != 0 || bytes & 1 != 0 {
+            return Err(TransferError::Unaligned);
+        }
+        if bytes == 0 {
+            return Err(TransferError::Empty);
+        }
+        Ok((start, bytes))
+    }
+
+    /// Like `new_transfer`, for a source that's there for the whole program, such as data in ROM
+    /// or a static, so it's always safe to queue.
+    #[inline]
+    pub fn new_static_transfer<T: VRAMData>(
+        src: &'static [T],
+        dst: Address,
+        autoinc: Option<NonZero<u8>>,
+    ) -> Self {
+        Self::new_transfer(src, dst, autoinc)
+    }
+
+    /// Copies `src` into the staging buffer, and queues a transfer from the copy, so `src` can be
+    /// a local that's gone before the queue is sent. The buffer holds `DMA_STAGING_WORDS` words,
+    /// and empties once the queue has been sent, which makes this for small, short-lived data.
+    #[cfg(feature = "dma-staging")]
+    pub fn schedule_staged<T: VRAMData>(
+        src: &[T],
+        dst: Address,
+        autoinc: Option<NonZero<u8>>,
+    ) -> Result<(), StagingError> {
+        let words = (src.len() * mem::size_of::<T>()) >> 1;
+        super::with_cs::<1, 7, _>(|cs| {
+            let mut staging = DMA_STAGING.borrow_ref_mut(cs);
+            let start = staging.used as usize;
+            let Some(copy) = staging.buf.get_mut(start..start + words) else {
+                return Err(StagingError::Full);
+            };
+            unsafe {
+                ptr::copy_nonoverlapping(src.as_ptr() as *const u16, copy.as_mut_ptr(), words);
+            }
+            let cmd = Self::new_transfer(copy, dst, autoinc);
+            DMA_QUEUE.borrow_ref_mut(cs).push_back(cmd).map_err(|_| StagingError::QueueFull)?;
+            staging.used += words as u16;
+            Ok(())
+        })
     }
 
     #[inline]
@@ -1341,20 +2419,28 @@ impl DMACommand {
             LongCmd::from_words(WordCmd::NULL, WordCmd((val as u16) << 8))
         ];
         Self {
-            cmds
+            cmds,
+            words: len.div_ceil(2),
+            from_68k: false,
+            hold_z80: None,
         }
     }
 
+    /// Copies `len` bytes of VRAM from `src` to `dst`. The VDP counts copies in bytes and takes
+    /// a byte source address, unlike transfers.
     #[inline]
-    pub fn new_copy(
+    pub const fn new_copy(
         src: VRAMAddress,
         dst: VRAMAddress,
         len: usize,
         autoinc: Option<NonZero<u8>>,
     ) -> Self {
-        let autoinc = autoinc.map_or(1, NonZero::get);
-        let addr = src.word_addr();
-        let len = (len >> 1) as u16;
+        let autoinc = match autoinc {
+            Some(n) => n.get(),
+            None => 1,
+        };
+        let addr = src.byte_addr() as u16;
+        let len = len as u16;
         let cmds = [
             LongCmd::from_words(WordCmd::set_reg(0x0F, autoinc), WordCmd::set_reg(0x17, 0xC0)),
             LongCmd::from_words(WordCmd::set_reg(0x16, (addr >> 8) as u8), WordCmd::set_reg(0x15, addr as u8)),
@@ -1362,15 +2448,77 @@ impl DMACommand {
             LongCmd::set_addr_w(Address::VRAM(dst), true, true)
         ];
         Self {
-            cmds
+            cmds,
+            // Copies move a byte in about the time a transfer moves a word.
+            words: len,
+            from_68k: false,
+            hold_z80: None,
+        }
+    }
+
+    /// How much of the vblank's DMA bandwidth the command takes, in words.
+    #[inline]
+    pub fn words(&self) -> u16 {
+        self.words
+    }
+
+    /// Holds the Z80 off the bus while this command runs from the queue, or leaves it running,
+    /// whatever the `Z80DmaPolicy` says. Only transfers use the bus, so this does nothing for fills
+    /// and copies.
+    ///
+    /// ```ignore
+    /// DMACommand::new_static_transfer(LEVEL_TILES, Address::VRAM(base), None).with_z80_held(true).schedule()?;
+    /// ```
+    #[inline]
+    pub const fn with_z80_held(mut self, hold: bool) -> Self {
+        self.hold_z80 = Some(hold);
+        self
+    }
+
+    /// Returns true if the command is a transfer from cartridge ROM.
+    #[inline]
+    pub fn is_from_rom(&self) -> bool {
+        self.source().is_some_and(|src| src < ROM_END)
+    }
+
+    /// The byte address a transfer reads from.
+    #[inline]
+    fn source(&self) -> Option<usize> {
+        if !self.from_68k {
+            return None;
+        }
+        let (high, low) = (self.cmds[0].0 & 0x7F, self.cmds[1].0);
+        Some((((high << 16) | ((low >> 8) & 0xFF00) | (low & 0xFF)) << 1) as usize)
+    }
+
+    /// Panics in debug builds if the command is a transfer from the stack.
+    #[inline]
+    fn check_source(&self) {
+        #[cfg(debug_assertions)]
+        if let Some(src) = self.source() {
+            if (super::layout::STACK_BOTTOM..super::layout::STACK_TOP).contains(&src) {
+                panic!("DMA source is on the stack");
+            }
         }
     }
 
+    /// Queues the command for the next vblank.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, panics if the command is a transfer from the stack. See the type's
+    /// documentation.
     #[inline]
     pub fn schedule(self) -> Result<(), Self> {
-        super::with_cs::<1, 7, _>(|cs| {
+        self.check_source();
+        let result = super::with_cs::<1, 7, _>(|cs| {
             DMA_QUEUE.borrow_ref_mut(cs).push_back(self)
-        })
+        });
+        match result {
+            Ok(()) => crate::trace!("dma: queued {} words", super::fmt::Int(self.words)),
+            Err(_) => crate::warn!("dma: queue full"),
+        }
+        result
     }
 
     #[inline]
@@ -1396,139 +2544,21 @@ impl DMACommand {
     }
 }
 
-#[repr(C)]
-struct DmaQueue<const N: usize> {
-    head: u8,
-    tail: u8,
-    full: bool,
-    data: [mem::MaybeUninit<DMACommand>; N]
-}
-
-impl<const N: usize> DmaQueue<N> {
-    pub const INIT: Self = Self {
-        head: 0,
-        tail: 0,
-        full: false,
-        data: const { [mem::MaybeUninit::uninit(); N] },
-    };
+// A copy of 0x100 bytes from byte 0x1234 loads the source and length registers in bytes.
+const _: () = {
+    let cmd = DMACommand::new_copy(VRAMAddress::from_byte_addr(0x1234), VRAMAddress::from_byte_addr(0), 0x100, None);
+    assert!(cmd.cmds[1].0 == 0x9612_9534);
+    assert!(cmd.cmds[2].0 == 0x9401_9300);
+    assert!(cmd.words == 0x100);
+};
 
-    #[inline]
-    pub fn is_empty(&self) -> bool {
-        self.head == self.tail && !self.full
-    }
+/// The commands waiting for vblank, oldest first.
+type DmaQueue<const N: usize> = super::collections::RingBuffer<DMACommand, N>;
 
-    #[inline]
-    pub fn is_full(&self) -> bool {
-        self.full
-    }
+/// The number of commands the DMA queue holds.
+const DMA_QUEUE_LEN: usize = 32;
 
-    #[inline]
-    pub fn increment(&self, i: u8) -> u8 {
-        unsafe {
-            let out: u8;
-            core::arch::asm!(
-                "add.b  #1,{i}",
-                "cmpi.b  #{N},{i}",
-                "bne    2f",
-                "move.b #0,{i}",
-                "2:",
-                i = inout(reg_data) i => out,
-                N = const N,
-            );
-            out
-        }
-    }
-
-    #[inline]
-    pub fn decrement(&self, i: u8) -> u8 {
-        unsafe {
-            let out: u8;
-            core::arch::asm!(
-                "sub.b  #1,{i}",
-                "bcc    2f",
-                "move.b #{Nm1},{i}",
-                "2:",
-                i = inout(reg_data) i => out,
-                Nm1 = const N-1,
-            );
-            out
-        }
-    }
-
-    #[inline]
-    pub fn pop_front(&mut self) -> Option<DMACommand> {
-        if self.is_empty() {
-            None
-        } else {
-            Some(unsafe { self.pop_front_unchecked() })
-        }
-    }
-
-    #[inline]
-    pub fn pop_back(&mut self) -> Option<DMACommand> {
-        if self.is_empty() {
-            None
-        } else {
-            Some(unsafe { self.pop_back_unchecked() })
-        }
-    }
-
-    #[inline]
-    pub fn push_front(&mut self, value: DMACommand) -> Result<(), DMACommand> {
-        if self.is_full() {
-            Err(value)
-        } else {
-            unsafe { self.push_front_unchecked(value) }
-            Ok(())
-        }
-    }
-
-    #[inline]
-    pub fn push_back(&mut self, value: DMACommand) -> Result<(), DMACommand> {
-        if self.is_full() {
-            Err(value)
-        } else {
-            unsafe { self.push_back_unchecked(value) }
-            Ok(())
-        }
-    }
-
-    #[inline]
-    pub unsafe fn pop_front_unchecked(&mut self) -> DMACommand {
-        let index = self.head as usize;
-        self.full = false;
-        self.head = self.increment(self.head);
-        self.data.get_unchecked_mut(index).assume_init_read()
-    }
-
-    #[inline]
-    pub unsafe fn pop_back_unchecked(&mut self) -> DMACommand {
-        self.full = false;
-        self.tail = self.decrement(self.tail);
-        self.data.get_unchecked_mut(self.tail as usize).assume_init_read()
-    }
-
-    #[inline]
-    pub unsafe fn push_front_unchecked(&mut self, value: DMACommand) {
-        let index = self.decrement(self.head) as usize;
-        self.data.get_unchecked_mut(index).write(value);
-        self.head = index as u8;
-        if self.head == self.tail {
-            self.full = true;
-        }
-    }
-
-    #[inline]
-    pub unsafe fn push_back_unchecked(&mut self, value: DMACommand) {
-        self.data.get_unchecked_mut(self.tail as usize).write(value);
-        self.tail = self.increment(self.tail);
-        if self.head == self.tail {
-            self.full = true;
-        }
-    }
-}
-
-static DMA_QUEUE: cs::Mutex<cell::RefCell<DmaQueue<32>>> = cs::Mutex::new(cell::RefCell::new(DmaQueue::INIT));
+static DMA_QUEUE: cs::Mutex<cell::RefCell<DmaQueue<DMA_QUEUE_LEN>>> = cs::Mutex::new(cell::RefCell::new(DmaQueue::new()));
 
 #[repr(C)]
 struct VIntData {
@@ -1536,14 +2566,12 @@ struct VIntData {
     vtable: mem::MaybeUninit<ptr::DynMetadata<dyn FnOnce(cs::CriticalSection)>>
 }
 
-/// The static storage for the vertical interrupt handler. Should this be bounded by some kind of mutex? Yes. Do I care right now? No.
-static mut VINT_HANDLER: Option<fn(cs::CriticalSection)> = None;
-
 static mut HINT_HANDLER: Option<fn()> = None;
 
 /// The vertical interrupt handler. 
 /// 
 /// This is called whenever the electron beam finishes the last scanline, and has entered the vertical blanking period.
+/// Once the frame has moved on, everything else, down to sending queued DMA, runs from the `vblank` registry.
 #[no_mangle]
 unsafe fn _vblank() {
     while !VDP::status().in_vblank() {
@@ -1551,48 +2579,91 @@ unsafe fn _vblank() {
     }
 
     super::with_cs::<1, 7, _>(|cs| {
-        {
-            let p1 = super::io::P1_CONTROLLER.borrow(cs);
-            let p2 = super::io::P2_CONTROLLER.borrow(cs);
-            p1.set(p1.get().update());
-            p2.set(p2.get().update());
-        }
-
-        if VDP::status().dma_in_progress() {
-            return;
-        }
-
-        let handler = ptr::read_volatile(&raw const VINT_HANDLER); // Read the handler pointer
-        if let Some(handler) = handler {
+        super::timing::tick();
+        raster::on_vblank(cs);
+        #[cfg(feature = "frame-arena")]
+        super::reset_frame_arena(cs);
+        vblank::run(cs);
+    });
+}
 
-            handler(cs);
-            
-            // Set handler to null to indicate vblank has happened
-            ptr::write_volatile(&raw mut VINT_HANDLER, None);
-        }
-        let mut queue = DMA_QUEUE.borrow_ref_mut(cs);
-        'queue_loop: loop {
-            loop {
-                let status = VDP::status();
-                if !status.in_vblank() {
-                    break 'queue_loop;
-                }
-                if !status.dma_in_progress() {
-                    break;
-                }
-                core::arch::asm!("nop","nop","nop","nop"); // Waste a bunch of time
+/// Sends as much of the DMA queue as fits in this vblank. Run from the vblank registry at
+/// `vblank::PRIORITY_DMA`.
+fn flush_dma(cs: cs::CriticalSection) {
+    if VDP::status().dma_in_progress() {
+        return;
+    }
+
+    let mut budget = dma_budget(&GLOBAL_SETTINGS.borrow(cs).get());
+    let mut sent = false;
+    let z80_policy = Z80_DMA_POLICY.borrow(cs).get();
+    let mut queue = DMA_QUEUE.borrow_ref_mut(cs);
+    shadow::flush(cs, &mut queue);
+    'queue_loop: loop {
+        loop {
+            let status = VDP::status();
+            if !status.in_vblank() {
+                break 'queue_loop;
             }
-            if let Some(cmd) = queue.pop_front() {
-                cmd.execute();
-            } else {
+            if !status.dma_in_progress() {
                 break;
             }
+            unsafe { core::arch::asm!("nop","nop","nop","nop") }; // Waste a bunch of time
         }
-    });
+        let Some(cmd) = queue.pop_front() else { break };
+        // Whatever doesn't fit waits for the next vblank. A command bigger than the whole budget
+        // still goes first, or it would never be sent.
+        if sent && cmd.words > budget {
+            // It was just popped, so there's room for it.
+            unsafe { queue.push_front_unchecked(cmd) };
+            crate::debug!("dma: budget spent, holding {} words over", super::fmt::Int(cmd.words));
+            break;
+        }
+        budget = budget.saturating_sub(cmd.words);
+        sent = true;
+
+        // If something else already has the bus, it's held for the whole transfer anyway.
+        let hold = cmd.from_68k
+            && cmd.hold_z80.unwrap_or_else(|| z80_policy.holds_for(&cmd))
+            && !super::io::z80_bus_granted();
+        if hold {
+            unsafe { super::io::pause_z80() };
+            while !super::io::z80_bus_granted() {}
+        }
+        // Transfers from the 68k's bus halt the 68k until they're done.
+        cmd.execute();
+        if hold {
+            unsafe { super::io::unpause_z80() };
+        }
+    }
+    #[cfg(feature = "dma-staging")]
+    if queue.is_empty() {
+        DMA_STAGING.borrow_ref_mut(cs).used = 0;
+    }
+}
+
+/// Turns the vblank interrupt off at the VDP, or back on if the settings have it on, without
+/// changing the settings. Used by `with_cs_audio_safe`.
+#[inline]
+pub(in crate::sys) fn hold_vint(cs: cs::CriticalSection, hold: bool) {
+    let mode = (GLOBAL_SETTINGS.borrow(cs).get().mode >> 8) as u8;
+    WordCmd::set_reg(1, if hold { mode & !0x20 } else { mode }).execute();
 }
 
 #[no_mangle]
 unsafe fn _hblank() {
+    // H-ints come in at level 4, so that's what the mask goes back to.
+    let due = super::with_cs::<4, 7, _>(|cs| {
+        let due = raster::on_hblank(cs);
+        if due.poll {
+            super::io::poll_hblank(cs);
+        }
+        due
+    });
+    for handler in due.handlers() {
+        handler();
+    }
+
     let handler = ptr::read_volatile(&raw const HINT_HANDLER);
     if let Some(handler) = handler {
         handler();
@@ -1601,5 +2672,8 @@ unsafe fn _hblank() {
 
 #[no_mangle]
 unsafe fn _extint() {
-    
+    // External interrupts come in at level 2.
+    super::with_cs::<2, 7, _>(|cs| {
+        super::io::on_extint(cs);
+    });
 }
\ No newline at end of file
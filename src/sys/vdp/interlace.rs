@@ -0,0 +1,107 @@
+use super::{InterlaceMode, Settings, Sprite, TileFlags, VRAMAddress, VDP};
+
+/// Which of an interlaced frame's two fields is being shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// The field with the even lines, counting from 0.
+    Even,
+    Odd,
+}
+
+/// The field the VDP is showing. In double resolution, the lines drawn this frame are the ones in
+/// this field.
+#[inline]
+pub fn field() -> Field {
+    if VDP::status().odd_interlace_frame() { Field::Odd } else { Field::Even }
+}
+
+/// The interlace mode in the current settings.
+#[inline]
+pub fn current_mode() -> InterlaceMode {
+    Settings::current().interlace_mode()
+}
+
+/// How many lines of the screen each line of a sprite's or plane's Y coordinate covers. In double
+/// resolution every frame line is two lines, one from each field, and tiles are 8x16.
+#[inline]
+pub const fn line_scale(mode: InterlaceMode) -> i16 {
+    match mode {
+        InterlaceMode::DoubleRes => 2,
+        _ => 1,
+    }
+}
+
+/// The bytes of VRAM each tile index covers, which is a pair of 8x8 tiles in double resolution.
+#[inline]
+pub const fn tile_bytes(mode: InterlaceMode) -> u32 {
+    32 * line_scale(mode) as u32
+}
+
+/// The address of the tile `index` refers to in name tables and sprites.
+#[inline]
+pub const fn tile_address(mode: InterlaceMode, index: u16) -> VRAMAddress {
+    VRAMAddress::from_byte_addr((index & 0x7FF) as u32 * tile_bytes(mode))
+}
+
+/// Sprites and name table entries whose meaning changes in double resolution interlace.
+///
+/// In double resolution, tiles are 8x16, and each is a pair of 8x8 tiles one after the other in
+/// VRAM, so entries number tiles in pairs. The methods here take and give the index of the first
+/// 8x8 tile of the pair, as it'd be loaded with a `TileAllocator`, so the same art can be drawn in
+/// either mode. In other modes they're the same as the plain versions.
+///
+/// ```ignore
+/// let mode = interlace::current_mode();
+/// let mut sprite = Sprite::with_flags(TileFlags::ZEROED, SpriteSize::Size2x2);
+/// sprite.set_tile_interlaced(mode, allocator.base(&player));
+/// sprite.set_screen_y(mode, 300);
+/// ```
+pub trait InterlaceAware {
+    /// Sets the tile shown to the one at 8x8 tile index `tile_index`, which has to be even in
+    /// double resolution.
+    fn set_tile_interlaced(&mut self, mode: InterlaceMode, tile_index: u16);
+
+    /// The 8x8 tile index of the tile shown.
+    fn tile_interlaced(&self, mode: InterlaceMode) -> u16;
+}
+
+impl InterlaceAware for TileFlags {
+    #[inline]
+    fn set_tile_interlaced(&mut self, mode: InterlaceMode, tile_index: u16) {
+        self.set_tile_index(tile_index / line_scale(mode) as u16);
+    }
+
+    #[inline]
+    fn tile_interlaced(&self, mode: InterlaceMode) -> u16 {
+        self.tile_index() * line_scale(mode) as u16
+    }
+}
+
+impl InterlaceAware for Sprite {
+    #[inline]
+    fn set_tile_interlaced(&mut self, mode: InterlaceMode, tile_index: u16) {
+        self.flags.set_tile_interlaced(mode, tile_index);
+    }
+
+    #[inline]
+    fn tile_interlaced(&self, mode: InterlaceMode) -> u16 {
+        self.flags.tile_interlaced(mode)
+    }
+}
+
+impl Sprite {
+    /// Puts the sprite's top at screen line `y`. In double resolution, lines count both fields,
+    /// so the screen is 448 or 480 lines tall.
+    #[inline]
+    pub fn set_screen_y(&mut self, mode: InterlaceMode, y: i16) {
+        // Double resolution has an extra bit of Y, and the offset to the top of the screen doubles.
+        let mask = if mode == InterlaceMode::DoubleRes { 0x3FF } else { 0x1FF };
+        self.y = ((y + 128 * line_scale(mode)) as u16) & mask;
+    }
+
+    /// The screen line of the sprite's top, counted as for `set_screen_y`.
+    #[inline]
+    pub fn screen_y(&self, mode: InterlaceMode) -> i16 {
+        self.y as i16 - 128 * line_scale(mode)
+    }
+}
@@ -0,0 +1,322 @@
+use super::{Address, DMACommand, VRAMData, VRAMDataMut};
+
+/// The number of palette lines in CRAM.
+pub const LINES: usize = 4;
+
+/// The number of colors in each palette line.
+pub const LINE_COLORS: usize = 16;
+
+/// A color in CRAM's format: `0000BBB0GGG0RRR0`, three bits per component.
+///
+/// In shadow/highlight mode the VDP can also show each color at half brightness (shadow) or at half
+/// brightness plus a half (highlight), giving three shades per CRAM entry without using any more of
+/// it. Those shades are made by the VDP at display time, and never stored in CRAM, so `shadowed` and
+/// `highlighted` only approximate them for code that needs to know what's on screen.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Color(u16);
+
+impl Color {
+    pub const BLACK: Self = Self::new(0, 0, 0);
+    pub const WHITE: Self = Self::new(7, 7, 7);
+    pub const RED: Self = Self::new(7, 0, 0);
+    pub const GREEN: Self = Self::new(0, 7, 0);
+    pub const BLUE: Self = Self::new(0, 0, 7);
+
+    const MASK: u16 = 0x0EEE;
+
+    /// Creates a color from 3 bit components.
+    #[inline]
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self((((b & 0x7) as u16) << 9) | (((g & 0x7) as u16) << 5) | (((r & 0x7) as u16) << 1))
+    }
+
+    /// Creates a color from 8 bit components, keeping the top 3 bits of each.
+    #[inline]
+    pub const fn from_rgb8(r: u8, g: u8, b: u8) -> Self {
+        Self::new(r >> 5, g >> 5, b >> 5)
+    }
+
+    /// Creates a color from a raw CRAM word, ignoring the unused bits.
+    #[inline]
+    pub const fn from_bits(bits: u16) -> Self {
+        Self(bits & Self::MASK)
+    }
+
+    #[inline]
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    #[inline]
+    pub const fn r(self) -> u8 {
+        ((self.0 >> 1) & 0x7) as u8
+    }
+
+    #[inline]
+    pub const fn g(self) -> u8 {
+        ((self.0 >> 5) & 0x7) as u8
+    }
+
+    #[inline]
+    pub const fn b(self) -> u8 {
+        ((self.0 >> 9) & 0x7) as u8
+    }
+
+    /// Scales the color's brightness, from black at 0 to unchanged at 255.
+    #[inline]
+    pub const fn scale(self, level: u8) -> Self {
+        let level = level as u16 + 1;
+        Self::new(
+            ((self.r() as u16 * level) >> 8) as u8,
+            ((self.g() as u16 * level) >> 8) as u8,
+            ((self.b() as u16 * level) >> 8) as u8,
+        )
+    }
+
+    /// Interpolates between two colors, component by component, `frame` frames into a fade lasting
+    /// `frames` frames.
+    #[inline]
+    pub const fn lerp(self, to: Self, frame: u8, frames: u8) -> Self {
+        if frames == 0 || self.0 == to.0 {
+            return to;
+        }
+
+        let mut out = 0u16;
+        let mut shift = 1u8;
+        while shift < 12 {
+            let a = ((self.0 >> shift) & 0x7) as i16;
+            let b = ((to.0 >> shift) & 0x7) as i16;
+            let c = a + (b - a) * (frame as i16) / (frames as i16);
+            out |= (c as u16) << shift;
+            shift += 4;
+        }
+        Self(out)
+    }
+
+    /// Roughly how this color looks when shadowed.
+    #[inline]
+    pub const fn shadowed(self) -> Self {
+        Self((self.0 >> 1) & Self::MASK)
+    }
+
+    /// Roughly how this color looks when highlighted.
+    #[inline]
+    pub const fn highlighted(self) -> Self {
+        Self(((self.0 >> 1) & Self::MASK) + 0x0666)
+    }
+}
+
+impl From<Color> for u16 {
+    #[inline]
+    fn from(color: Color) -> Self {
+        color.0
+    }
+}
+
+impl VRAMData for Color {
+    #[inline]
+    fn as_words(&self) -> &[u16] {
+        core::slice::from_ref(&self.0)
+    }
+}
+
+impl VRAMData for [Color] {
+    #[inline]
+    fn as_words(&self) -> &[u16] {
+        unsafe { core::slice::from_raw_parts(self.as_ptr().cast::<u16>(), self.len()) }
+    }
+}
+
+impl VRAMDataMut for Color {
+    #[inline]
+    fn as_words_mut(&mut self) -> &mut [u16] {
+        core::slice::from_mut(&mut self.0)
+    }
+}
+
+impl VRAMDataMut for [Color] {
+    #[inline]
+    fn as_words_mut(&mut self) -> &mut [u16] {
+        unsafe { core::slice::from_raw_parts_mut(self.as_mut_ptr().cast::<u16>(), self.len()) }
+    }
+}
+
+/// A palette line.
+pub type Palette = [Color; LINE_COLORS];
+
+const BLACK: [Palette; LINES] = [[Color::BLACK; LINE_COLORS]; LINES];
+const WHITE: [Palette; LINES] = [[Color::WHITE; LINE_COLORS]; LINES];
+
+/// Fades the whole of CRAM between palettes over a number of frames.
+///
+/// The fader keeps RAM copies of all four palette lines, which are sent with a queued DMA whenever
+/// they change, so it must stay alive until the next vblank after every `step`. In practice this
+/// means keeping it in a static.
+pub struct Fader {
+    current: [Palette; LINES],
+    from: [Palette; LINES],
+    to: [Palette; LINES],
+    frame: u8,
+    frames: u8,
+    dirty: bool,
+}
+
+impl Fader {
+    pub const fn new() -> Self {
+        Self {
+            current: BLACK,
+            from: BLACK,
+            to: BLACK,
+            frame: 0,
+            frames: 0,
+            dirty: false,
+        }
+    }
+
+    /// The colors currently being shown.
+    #[inline]
+    pub fn palette(&self, line: u8) -> &Palette {
+        &self.current[(line & 0x3) as usize]
+    }
+
+    /// Sets a palette line immediately, cancelling any fade in progress on it.
+    pub fn set_palette(&mut self, line: u8, colors: &Palette) {
+        let line = (line & 0x3) as usize;
+        self.current[line] = *colors;
+        self.from[line] = *colors;
+        self.to[line] = *colors;
+        self.dirty = true;
+    }
+
+    /// Starts fading every palette line to `target` over `frames` frames.
+    pub fn fade_to(&mut self, target: &[Palette; LINES], frames: u8) {
+        self.from = self.current;
+        self.to = *target;
+        self.frame = 0;
+        self.frames = frames;
+        if frames == 0 {
+            self.current = *target;
+            self.dirty = true;
+        }
+    }
+
+    /// Starts fading a single palette line to `target` over `frames` frames, leaving the others as
+    /// they are.
+    pub fn fade_line_to(&mut self, line: u8, target: &Palette, frames: u8) {
+        let mut to = self.current;
+        to[(line & 0x3) as usize] = *target;
+        self.fade_to(&to, frames);
+    }
+
+    #[inline]
+    pub fn fade_to_black(&mut self, frames: u8) {
+        self.fade_to(&BLACK, frames);
+    }
+
+    #[inline]
+    pub fn fade_to_white(&mut self, frames: u8) {
+        self.fade_to(&WHITE, frames);
+    }
+
+    #[inline]
+    pub fn is_fading(&self) -> bool {
+        self.frame < self.frames
+    }
+
+    /// Advances the fade by a frame and queues the new colors for upload.
+    ///
+    /// This should be called once per frame. If the DMA queue is full, the upload is retried on the
+    /// next call.
+    pub fn step(&mut self) {
+        if self.is_fading() {
+            self.frame += 1;
+            for ((current, from), to) in self.current.iter_mut().zip(self.from.iter()).zip(self.to.iter()) {
+                for ((c, &a), &b) in current.iter_mut().zip(from.iter()).zip(to.iter()) {
+                    *c = a.lerp(b, self.frame, self.frames);
+                }
+            }
+            self.dirty = true;
+        }
+
+        if self.dirty {
+            self.dirty = DMACommand::new_transfer(self.current.as_flattened(), Address::CRAM(0), None)
+                .schedule()
+                .is_err();
+        }
+    }
+}
+
+impl Default for Fader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Rotates a range of colors in a palette line every few frames, for waterfalls, conveyor belts and
+/// other art drawn to be animated by its colors.
+///
+/// The colors go through the CRAM shadow in `vdp::shadow`, so the line has to be set there, with
+/// `shadow::set_palette`, rather than through a `Fader`.
+///
+/// ```ignore
+/// shadow::set_palette(1, &LEVEL_PALETTE);
+/// let mut water = Cycler::new(1, 8, 12, 6);
+/// loop {
+///     water.step();
+///     VDP::wait_for_vblank(None);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Cycler {
+    line: u8,
+    start: u8,
+    end: u8,
+    period: u8,
+    counter: u8,
+    reverse: bool,
+}
+
+impl Cycler {
+    /// Rotates colors `start` up to but not including `end` of palette line `line` along by one
+    /// every `period` frames.
+    pub const fn new(line: u8, start: u8, end: u8, period: u8) -> Self {
+        Self { line, start, end, period, counter: 0, reverse: false }
+    }
+
+    /// Rotates the other way, with the first color going round to the end.
+    #[inline]
+    pub const fn with_reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    /// Sets how many frames go between rotations. A period of 0 stops the cycling.
+    #[inline]
+    pub fn set_period(&mut self, period: u8) {
+        self.period = period;
+        self.counter = self.counter.min(period);
+    }
+
+    #[inline]
+    pub fn period(&self) -> u8 {
+        self.period
+    }
+
+    /// Counts a frame, and rotates the colors once every `period` frames. Call this once a frame.
+    pub fn step(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+        self.counter += 1;
+        if self.counter < self.period {
+            return;
+        }
+        self.counter = 0;
+        if self.reverse {
+            super::shadow::rotate_colors_back(self.line, self.start, self.end);
+        } else {
+            super::shadow::rotate_colors(self.line, self.start, self.end);
+        }
+    }
+}
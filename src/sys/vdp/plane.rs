@@ -0,0 +1,233 @@
+use alloc::boxed::Box;
+use alloc::vec;
+
+use core::num::NonZero;
+
+use super::{Address, DMACommand, Plane, PlaneSize, Settings, TileFlags, VRAMAddress, Writer, VDP};
+
+/// A RAM copy of a plane's name table.
+///
+/// Tiles are edited in RAM, and `flush` sends only the rows that changed to VRAM using queued DMA,
+/// so editing the plane doesn't compete with the display for VDP bandwidth. Coordinates wrap around
+/// the plane, just like they do in VRAM.
+pub struct PlaneBuffer {
+    base: VRAMAddress,
+    size: PlaneSize,
+    tiles: Box<[TileFlags]>,
+    dirty: [u32; 4],
+}
+
+impl PlaneBuffer {
+    /// Creates a blank buffer for the name table at `base`.
+    pub fn new(base: VRAMAddress, size: PlaneSize) -> Self {
+        let len = (size.width_tiles() as usize) << size.height_shift();
+        Self {
+            base,
+            size,
+            tiles: vec![TileFlags::ZEROED; len].into_boxed_slice(),
+            dirty: [0; 4],
+        }
+    }
+
+    /// Creates a blank buffer for one of the scrolling planes, as configured in `settings`.
+    pub fn for_plane(settings: &Settings, plane: Plane) -> Self {
+        let base = match plane {
+            Plane::A => settings.plane_a_base(),
+            Plane::B => settings.plane_b_base(),
+        };
+        Self::new(base, settings.plane_size())
+    }
+
+    #[inline]
+    pub fn base(&self) -> VRAMAddress {
+        self.base
+    }
+
+    #[inline]
+    pub fn size(&self) -> PlaneSize {
+        self.size
+    }
+
+    #[inline]
+    fn mark_dirty(&mut self, y: u8) {
+        self.dirty[(y >> 5) as usize] |= 1 << (y & 31);
+    }
+
+    /// Marks every row to be sent on the next `flush`.
+    #[inline]
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty = [u32::MAX; 4];
+    }
+
+    #[inline]
+    pub fn get(&self, x: u8, y: u8) -> TileFlags {
+        self.tiles[self.size.tile_offset(x, y) as usize]
+    }
+
+    #[inline]
+    pub fn set(&mut self, x: u8, y: u8, tile: TileFlags) {
+        let y = y & self.size.y_mask();
+        self.tiles[self.size.tile_offset(x, y) as usize] = tile;
+        self.mark_dirty(y);
+    }
+
+    /// The tiles of a whole row.
+    #[inline]
+    pub fn row(&self, y: u8) -> &[TileFlags] {
+        let start = self.size.tile_offset(0, y) as usize;
+        &self.tiles[start..start + self.size.width_tiles() as usize]
+    }
+
+    /// Fills a `w` by `h` rectangle of tiles with `tile`.
+    pub fn fill_rect(&mut self, x: u8, y: u8, w: u8, h: u8, tile: TileFlags) {
+        for row in 0..h {
+            let ty = y.wrapping_add(row) & self.size.y_mask();
+            for col in 0..w {
+                self.tiles[self.size.tile_offset(x.wrapping_add(col), ty) as usize] = tile;
+            }
+            self.mark_dirty(ty);
+        }
+    }
+
+    /// Copies a `w` tiles wide block of tiles to (`x`, `y`). `src` holds the block's rows one after
+    /// the other, and its length decides the block's height.
+    pub fn blit(&mut self, x: u8, y: u8, w: u8, src: &[TileFlags]) {
+        if w == 0 {
+            return;
+        }
+
+        for (row, tiles) in src.chunks(w as usize).enumerate() {
+            let ty = y.wrapping_add(row as u8) & self.size.y_mask();
+            for (col, &tile) in tiles.iter().enumerate() {
+                self.tiles[self.size.tile_offset(x.wrapping_add(col as u8), ty) as usize] = tile;
+            }
+            self.mark_dirty(ty);
+        }
+    }
+
+    /// Queues every changed row to be sent to VRAM during the next vblank.
+    ///
+    /// Rows that don't fit in the DMA queue stay marked, and are sent by a later flush. The buffer
+    /// must stay alive until the next vblank after flushing.
+    pub fn flush(&mut self) {
+        let width = self.size.width_tiles() as usize;
+        let height = self.size.height_tiles();
+
+        for y in 0..height {
+            let (word, bit) = ((y >> 5) as usize, 1u32 << (y & 31));
+            if self.dirty[word] & bit == 0 {
+                continue;
+            }
+
+            let start = (y as usize) << self.size.pitch_shift();
+            let addr = self.size.tile_offset_from(self.base, 0, y);
+            if DMACommand::new_transfer(&self.tiles[start..start + width], Address::VRAM(addr), None).schedule().is_err() {
+                break;
+            }
+            self.dirty[word] &= !bit;
+        }
+    }
+}
+
+/// Fills and copies rectangles of tiles straight in VRAM, with DMA fills and copies, so the CPU
+/// doesn't have to send every tile. Coordinates wrap around the plane, as with `PlaneBuffer`.
+///
+/// These run right away rather than being queued, one DMA for each stretch of a row that doesn't
+/// wrap, waiting for each to finish. That's a lot quicker with the display off, such as while a
+/// scene is being set up, than during the active display, where the VDP only has a few slots a
+/// line for DMA. DMA has to be enabled in `Settings`.
+pub struct PlaneOps;
+
+impl PlaneOps {
+    /// The name table and size of `plane`, as currently set.
+    fn layout(plane: Plane) -> (VRAMAddress, PlaneSize) {
+        let settings = Settings::current();
+        let base = match plane {
+            Plane::A => settings.plane_a_base(),
+            Plane::B => settings.plane_b_base(),
+        };
+        (base, settings.plane_size())
+    }
+
+    /// Runs a fill or copy, and waits for it to finish, since the 68000 isn't held up for them.
+    /// Only the command writes are locked, so the vblank handler isn't held off for the wait.
+    fn run(cmd: DMACommand) {
+        crate::sys::with_cs::<1, 7, _>(|_| cmd.execute());
+        while VDP::status().dma_in_progress() {}
+    }
+
+    /// Fills a `w` by `h` rectangle of `plane` with `tile`.
+    pub fn fill_rect(plane: Plane, x: u8, y: u8, w: u8, h: u8, tile: TileFlags) {
+        let (base, size) = Self::layout(plane);
+        let width = size.width_tiles();
+        let [high, low] = tile.0.to_be_bytes();
+        let step = NonZero::new(2);
+
+        for row in 0..h {
+            let ty = y.wrapping_add(row);
+            let mut col = 0;
+            while col < w {
+                let tx = x.wrapping_add(col) & size.x_mask();
+                let len = (w - col).min(width - tx);
+                let addr = size.tile_offset_from(base, tx, ty);
+                if high == low {
+                    Self::run(DMACommand::new_fill(addr, len as usize * 2, high, None));
+                } else {
+                    // Fills write single bytes, so the two halves of each entry go in separately,
+                    // and the first entry, which the fills' setup writes over, is put right after.
+                    let odd = VRAMAddress::from_byte_addr(addr.byte_addr() + 1);
+                    Self::run(DMACommand::new_fill(addr, len as usize, high, step));
+                    Self::run(DMACommand::new_fill(odd, len as usize, low, step));
+                    Writer::new(Address::VRAM(addr)).write::<[TileFlags]>([tile]);
+                }
+                col += len;
+            }
+        }
+    }
+
+    /// Copies a `w` by `h` rectangle of `plane` from (`src_x`, `src_y`) to (`dst_x`, `dst_y`). The
+    /// rectangles can overlap.
+    pub fn copy_rect(plane: Plane, src_x: u8, src_y: u8, dst_x: u8, dst_y: u8, w: u8, h: u8) {
+        let (base, size) = Self::layout(plane);
+        let width = size.width_tiles();
+        let (x_mask, y_mask) = (size.x_mask(), size.y_mask());
+        let (src_x, src_y, dst_x, dst_y) = (src_x & x_mask, src_y & y_mask, dst_x & x_mask, dst_y & y_mask);
+
+        // Copies go upwards through VRAM, so when the destination is further on, rows and the
+        // stretches within them go from the end backwards, to copy each tile before it's written
+        // over.
+        let backwards_rows = dst_y > src_y;
+        let backwards_cols = dst_y == src_y && dst_x > src_x;
+        // Moving right within a row, a stretch can't be longer than the move.
+        let max_len = if backwards_cols { dst_x - src_x } else { w };
+
+        for i in 0..h {
+            let row = if backwards_rows { h - 1 - i } else { i };
+            let (sy, dy) = (src_y.wrapping_add(row), dst_y.wrapping_add(row));
+            let mut done = 0;
+            while done < w {
+                let len;
+                let col;
+                if backwards_cols {
+                    let end = w - done;
+                    let sx = src_x.wrapping_add(end - 1) & x_mask;
+                    let dx = dst_x.wrapping_add(end - 1) & x_mask;
+                    len = end.min(sx + 1).min(dx + 1).min(max_len);
+                    col = end - len;
+                } else {
+                    let sx = src_x.wrapping_add(done) & x_mask;
+                    let dx = dst_x.wrapping_add(done) & x_mask;
+                    len = (w - done).min(width - sx).min(width - dx).min(max_len);
+                    col = done;
+                }
+                Self::run(DMACommand::new_copy(
+                    size.tile_offset_from(base, src_x.wrapping_add(col), sy),
+                    size.tile_offset_from(base, dst_x.wrapping_add(col), dy),
+                    len as usize * 2,
+                    None,
+                ));
+                done += len;
+            }
+        }
+    }
+}
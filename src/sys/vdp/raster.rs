@@ -0,0 +1,283 @@
+use core::cell;
+
+use critical_section as cs;
+
+use crate::sys;
+use crate::sys::io::manager::{self, PollMode};
+
+use super::{Settings, WordCmd, GLOBAL_SETTINGS};
+
+/// The maximum number of zones that can be added at once.
+pub const MAX_ZONES: usize = 8;
+
+/// An entry and an exit for each zone, and the controller poll.
+const MAX_EVENTS: usize = 2 * MAX_ZONES + 1;
+/// The events, and the two H-ints every schedule starts with.
+const MAX_HINTS: usize = MAX_EVENTS + 2;
+
+/// A band of scanlines with its own effect, such as a status bar with its own scroll, or water
+/// with its own palette.
+///
+/// `enter` runs from the H-int at the end of line `start`, just before line `start + 1` is drawn,
+/// and `exit`, if there is one, at the end of line `end`. Both run with interrupts enabled, in
+/// the short time before the next line starts, so they should do little more than write a few
+/// VDP registers or colors. Where one zone ends on the line another starts, the exit runs first.
+#[derive(Debug, Clone, Copy)]
+pub struct Zone {
+    pub start: u8,
+    pub end: u8,
+    pub enter: fn(),
+    pub exit: Option<fn()>,
+}
+
+impl Zone {
+    #[inline]
+    pub const fn new(start: u8, end: u8, enter: fn()) -> Self {
+        Self { start, end, enter, exit: None }
+    }
+
+    #[inline]
+    pub const fn with_exit(mut self, exit: fn()) -> Self {
+        self.exit = Some(exit);
+        self
+    }
+}
+
+/// Removes a zone when passed to `remove`.
+#[must_use]
+#[derive(Debug, PartialEq, Eq)]
+pub struct ZoneToken(u8);
+
+/// An H-int in the frame's schedule.
+#[derive(Clone, Copy)]
+struct Hint {
+    line: u8,
+    /// What register 10 is set to in this H-int, which the counter only picks up at the next one.
+    next: u8,
+    /// The range of `Raster::handlers` run by this H-int.
+    first: u8,
+    len: u8,
+    poll: bool,
+}
+
+impl Hint {
+    const EMPTY: Self = Self { line: 0, next: 0xFF, first: 0, len: 0, poll: false };
+}
+
+struct Raster {
+    zones: [(u8, Zone); MAX_ZONES],
+    len: u8,
+    next_id: u8,
+    hints: [Hint; MAX_HINTS],
+    hints_len: u8,
+    handlers: [fn(); MAX_EVENTS],
+    /// The next H-int this frame.
+    cursor: u8,
+    poll: Option<u8>,
+    /// Set when the zones or poll line have changed since the schedule was compiled, which is
+    /// left to the next vblank so the current frame keeps the schedule it started with.
+    dirty: bool,
+}
+
+fn nothing() {}
+
+static RASTER: cs::Mutex<cell::RefCell<Raster>> = cs::Mutex::new(cell::RefCell::new(Raster {
+    zones: [(0, Zone::new(0, 0, nothing)); MAX_ZONES],
+    len: 0,
+    next_id: 0,
+    hints: [Hint::EMPTY; MAX_HINTS],
+    hints_len: 0,
+    handlers: [nothing; MAX_EVENTS],
+    cursor: 0,
+    poll: None,
+    dirty: false,
+}));
+
+impl Raster {
+    /// Turns the zones and the poll line into the frame's H-ints.
+    ///
+    /// The counter is reloaded from register 10 when an H-int fires, so a new value written by the
+    /// handler only takes effect after the following H-int. Each H-int therefore sets the gap
+    /// after the next one. To get the first two gaps right too, every schedule starts with H-ints
+    /// at lines 0 and 1, from register 10 being 0 through vblank.
+    fn compile(&mut self) {
+        // (line, exits first, handler), sorted.
+        let mut events = [(0u8, false, None::<fn()>); MAX_EVENTS];
+        let mut count = 0;
+        for &(_, zone) in &self.zones[..self.len as usize] {
+            events[count] = (zone.start, true, Some(zone.enter));
+            count += 1;
+            if let Some(exit) = zone.exit {
+                events[count] = (zone.end, false, Some(exit));
+                count += 1;
+            }
+        }
+        if let Some(line) = self.poll {
+            events[count] = (line, false, None);
+            count += 1;
+        }
+        events[..count].sort_unstable_by_key(|&(line, enter, _)| (line, enter));
+
+        self.hints[0] = Hint { line: 0, ..Hint::EMPTY };
+        self.hints[1] = Hint { line: 1, ..Hint::EMPTY };
+        let mut hints = 2;
+        let mut handlers = 0;
+        for &(line, _, handler) in &events[..count] {
+            // Events on lines 0 and 1 go on the H-ints that are there anyway.
+            let index = if line <= 1 {
+                line as usize
+            } else {
+                if line != self.hints[hints - 1].line {
+                    self.hints[hints] = Hint { line, ..Hint::EMPTY };
+                    hints += 1;
+                }
+                hints - 1
+            };
+            let hint = &mut self.hints[index];
+            match handler {
+                Some(handler) => {
+                    if hint.len == 0 {
+                        hint.first = handlers as u8;
+                    }
+                    self.handlers[handlers] = handler;
+                    handlers += 1;
+                    hint.len += 1;
+                }
+                None => hint.poll = true,
+            }
+        }
+
+        for i in 0..hints {
+            self.hints[i].next = if i + 2 < hints {
+                self.hints[i + 2].line - self.hints[i + 1].line - 1
+            } else {
+                0xFF
+            };
+        }
+        self.hints_len = hints as u8;
+    }
+}
+
+/// Adds a zone, which takes effect from the next frame.
+///
+/// While there are any zones, they have the VDP's H-int interval to themselves, and H-ints are
+/// kept on. `Settings::set_hint_interval` is ignored until the last zone is removed. Controller
+/// polling with `PollMode::Line` and the handler from `VDP::set_hblank_handler` keep working, the
+/// latter running on every H-int in the schedule.
+///
+/// Returns `None` if `MAX_ZONES` zones have already been added.
+pub fn add(zone: Zone) -> Option<ZoneToken> {
+    let token = sys::with_cs::<1, 7, _>(|cs| {
+        let mut raster = RASTER.borrow_ref_mut(cs);
+        let len = raster.len as usize;
+        if len == MAX_ZONES {
+            return None;
+        }
+
+        let mut id = raster.next_id;
+        while raster.zones[..len].iter().any(|&(other, _)| other == id) {
+            id = id.wrapping_add(1);
+        }
+        raster.next_id = id.wrapping_add(1);
+        raster.zones[len] = (id, zone);
+        raster.len += 1;
+        raster.dirty = true;
+        Some(ZoneToken(id))
+    })?;
+
+    let mut settings = Settings::current();
+    settings.enable_hint(true);
+    settings.apply::<false>();
+    Some(token)
+}
+
+/// Removes a zone, from the next frame. Once the last zone is gone, the H-int interval goes back
+/// to the one in the settings, and H-ints are turned off unless controllers are polled from one.
+pub fn remove(token: ZoneToken) {
+    let empty = sys::with_cs::<1, 7, _>(|cs| {
+        let mut raster = RASTER.borrow_ref_mut(cs);
+        let len = raster.len as usize;
+        if let Some(index) = raster.zones[..len].iter().position(|&(id, _)| id == token.0) {
+            raster.zones.copy_within(index + 1..len, index);
+            raster.len -= 1;
+        }
+        raster.dirty = true;
+        if raster.len == 0 {
+            WordCmd::set_reg(10, GLOBAL_SETTINGS.borrow(cs).get().hint_interval).execute();
+        }
+        raster.len == 0
+    });
+
+    if empty && manager::poll_mode() == PollMode::VBlank {
+        let mut settings = Settings::current();
+        settings.enable_hint(false);
+        settings.apply::<false>();
+    }
+}
+
+/// Returns true if any zones have been added.
+#[inline]
+pub fn is_active() -> bool {
+    sys::with_cs::<1, 7, _>(|cs| RASTER.borrow_ref(cs).len != 0)
+}
+
+/// Puts the controller poll line into the schedule. Called when the poll mode changes.
+pub(in crate::sys) fn set_poll_line(cs: cs::CriticalSection, line: Option<u8>) {
+    let mut raster = RASTER.borrow_ref_mut(cs);
+    raster.poll = line;
+    raster.dirty = true;
+}
+
+/// Starts the schedule over. Called from the vblank handler, while the counter is being reloaded
+/// on every line.
+pub(in crate::sys) fn on_vblank(cs: cs::CriticalSection) {
+    let mut raster = RASTER.borrow_ref_mut(cs);
+    if raster.dirty {
+        raster.compile();
+        raster.dirty = false;
+    }
+    raster.cursor = 0;
+    if raster.len != 0 {
+        WordCmd::set_reg(10, 0).execute();
+    }
+}
+
+/// What an H-int has to do, once its critical section is over.
+pub(in crate::sys) struct Due {
+    handlers: [fn(); MAX_EVENTS],
+    len: u8,
+    /// Whether controllers are polled on this H-int.
+    pub poll: bool,
+}
+
+impl Due {
+    #[inline]
+    pub fn handlers(&self) -> &[fn()] {
+        &self.handlers[..self.len as usize]
+    }
+}
+
+/// Sets up the H-int after next, and returns what's due on this one. Called from the H-int
+/// handler. With no zones, every H-int is left to the poll line.
+pub(in crate::sys) fn on_hblank(cs: cs::CriticalSection) -> Due {
+    let mut due = Due { handlers: [nothing; MAX_EVENTS], len: 0, poll: true };
+    let mut raster = RASTER.borrow_ref_mut(cs);
+    if raster.len == 0 {
+        return due;
+    }
+
+    let cursor = raster.cursor as usize;
+    if cursor >= raster.hints_len as usize {
+        due.poll = false;
+        return due;
+    }
+    let hint = raster.hints[cursor];
+    WordCmd::set_reg(10, hint.next).execute();
+    raster.cursor += 1;
+
+    let first = hint.first as usize;
+    due.handlers[..hint.len as usize].copy_from_slice(&raster.handlers[first..first + hint.len as usize]);
+    due.len = hint.len;
+    due.poll = hint.poll;
+    due
+}
@@ -0,0 +1,291 @@
+use core::num::NonZero;
+
+use fixed::types::I8F8;
+
+use super::{Address, DMACommand, Plane, Settings, VRAMAddress, Writer};
+use crate::sys::timing;
+
+/// The whole-screen scroll of both planes, kept in RAM and sent to the scroll tables during vblank.
+///
+/// Writing the scroll tables while the display is drawing shifts the planes partway down the
+/// screen, so `set_plane_scroll` only changes the copy in RAM, and `flush` queues a DMA for the
+/// next vblank. Like other queued DMA sources, the scroller must stay alive until then, which in
+/// practice means keeping it in a static.
+///
+/// This is for `HScrollMode::Screen` and `VScrollMode::Screen`. The associated functions write the
+/// tables directly, for the other modes.
+#[derive(Debug, Clone)]
+pub struct Scroller {
+    /// The horizontal scroll of planes A and B, in the order of the hscroll table.
+    hscroll: [i16; 2],
+    /// The vertical scroll of planes A and B, in the order of VSRAM.
+    vscroll: [i16; 2],
+    hscroll_dirty: bool,
+    vscroll_dirty: bool,
+}
+
+impl Scroller {
+    /// Both planes unscrolled. The first `flush` sends them, whatever the tables held before.
+    pub const fn new() -> Self {
+        Self {
+            hscroll: [0; 2],
+            vscroll: [0; 2],
+            hscroll_dirty: true,
+            vscroll_dirty: true,
+        }
+    }
+
+    /// Sets the values for `plane` in the scroll tables. Raising `x` moves the plane right, and
+    /// raising `y` moves it up.
+    #[inline]
+    pub fn set_plane_scroll(&mut self, plane: Plane, x: i16, y: i16) {
+        self.set_hscroll(plane, x);
+        self.set_vscroll(plane, y);
+    }
+
+    #[inline]
+    pub fn set_hscroll(&mut self, plane: Plane, x: i16) {
+        let entry = &mut self.hscroll[plane as usize];
+        self.hscroll_dirty |= *entry != x;
+        *entry = x;
+    }
+
+    #[inline]
+    pub fn set_vscroll(&mut self, plane: Plane, y: i16) {
+        let entry = &mut self.vscroll[plane as usize];
+        self.vscroll_dirty |= *entry != y;
+        *entry = y;
+    }
+
+    /// The horizontal and vertical scroll of `plane`, as last set.
+    #[inline]
+    pub fn plane_scroll(&self, plane: Plane) -> (i16, i16) {
+        (self.hscroll[plane as usize], self.vscroll[plane as usize])
+    }
+
+    /// Queues whatever has changed to be sent during the next vblank. Call this once a frame.
+    ///
+    /// If the DMA queue is full, the rest is sent by a later flush.
+    pub fn flush(&mut self) -> Result<(), DMACommand> {
+        if self.hscroll_dirty {
+            DMACommand::new_transfer(&self.hscroll, Address::VRAM(Self::hscroll_entry(Plane::A)), None).schedule()?;
+            self.hscroll_dirty = false;
+        }
+        if self.vscroll_dirty {
+            DMACommand::new_transfer(&self.vscroll, Address::VSRAM(0), None).schedule()?;
+            self.vscroll_dirty = false;
+        }
+        Ok(())
+    }
+
+    /// The number of entries in a line scroll table, one per visible line in V28 mode.
+    pub const LINES: usize = 224;
+    /// The number of 2-tile columns with their own vertical scroll in `VScrollMode::Columns`, which
+    /// is the width of the screen in H40. H32 only uses the first 16.
+    pub const COLUMNS: usize = 20;
+
+    /// The address of a plane's first entry in the horizontal scroll table.
+    #[inline]
+    fn hscroll_entry(plane: Plane) -> VRAMAddress {
+        VRAMAddress(Settings::current().hscroll_base().0 + plane as u16)
+    }
+
+    /// Writes a full line scroll table for `plane`, for use with `HScrollMode::Lines`.
+    ///
+    /// The hscroll table interleaves both planes, so this writes every other word.
+    #[inline]
+    pub fn line_scroll(plane: Plane, table: &[i16; Self::LINES]) {
+        Writer::new(Address::VRAM(Self::hscroll_entry(plane)))
+            .with_autoinc(4)
+            .write::<[i16]>(table)
+    }
+
+    /// The VSRAM address of a column's entry for `plane`. VSRAM interleaves the planes, with plane A
+    /// on even words and plane B on odd ones, so each column takes a long.
+    #[inline]
+    fn vscroll_entry(plane: Plane, column: usize) -> Address {
+        Address::VSRAM(((column % Self::COLUMNS) * 4 + plane as usize * 2) as u8)
+    }
+
+    /// Sets the vertical scroll of one 2-tile column of `plane`, for use with
+    /// `VScrollMode::Columns`. Column 0 is the leftmost two tiles on screen.
+    #[inline]
+    pub fn column_scroll(plane: Plane, column: usize, value: i16) {
+        Writer::new(Self::vscroll_entry(plane, column)).write::<[i16]>([value])
+    }
+
+    /// Sets the vertical scroll of every column of `plane`, for use with `VScrollMode::Columns`,
+    /// leaving the other plane's as they are.
+    #[inline]
+    pub fn set_columns(plane: Plane, columns: &[i16; Self::COLUMNS]) {
+        Writer::new(Self::vscroll_entry(plane, 0))
+            .with_autoinc(4)
+            .write::<[i16]>(columns)
+    }
+}
+
+impl Default for Scroller {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A horizontal band of scanlines that scrolls at a fraction of the camera's speed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParallaxBand {
+    start: u8,
+    end: u8,
+    speed: I8F8,
+}
+
+impl ParallaxBand {
+    /// Creates a band covering the lines `start..end`, moving `speed` pixels per pixel of camera movement.
+    pub const fn new(start: u8, end: u8, speed: I8F8) -> Self {
+        Self { start, end, speed }
+    }
+}
+
+/// Generates a line scroll table from a set of parallax bands.
+///
+/// The table is sent with a queued DMA, so this must stay alive (and unmodified) until the next vblank,
+/// which in practice means keeping it in a static.
+pub struct ParallaxLayers<const N: usize> {
+    plane: Plane,
+    bands: [ParallaxBand; N],
+    table: [i16; Scroller::LINES],
+}
+
+impl<const N: usize> ParallaxLayers<N> {
+    pub const fn new(plane: Plane, bands: [ParallaxBand; N]) -> Self {
+        Self {
+            plane,
+            bands,
+            table: [0; Scroller::LINES],
+        }
+    }
+
+    #[inline]
+    pub fn bands_mut(&mut self) -> &mut [ParallaxBand; N] {
+        &mut self.bands
+    }
+
+    #[inline]
+    pub fn table(&self) -> &[i16; Scroller::LINES] {
+        &self.table
+    }
+
+    /// Regenerates the table for a camera at horizontal position `camera_x`.
+    ///
+    /// Lines not covered by any band are left as they were.
+    pub fn update(&mut self, camera_x: i16) {
+        for band in self.bands.iter() {
+            let offset = -(((camera_x as i32) * (band.speed.to_bits() as i32)) >> 8) as i16;
+            let end = (band.end as usize).min(Scroller::LINES);
+            if let Some(lines) = self.table.get_mut(band.start as usize..end) {
+                lines.fill(offset);
+            }
+        }
+    }
+
+    /// Queues the table to be sent to VRAM during the next vblank.
+    #[inline]
+    pub fn schedule(&self) -> Result<(), DMACommand> {
+        DMACommand::new_transfer(
+            &self.table,
+            Address::VRAM(Scroller::hscroll_entry(self.plane)),
+            NonZero::new(4),
+        ).schedule()
+    }
+}
+
+/// A double buffered line scroll table for both planes, for use with `HScrollMode::Lines`.
+///
+/// Writing the scroll table while the display is drawing it shears the picture partway down, so
+/// edits go to a back buffer, which `swap` sends in one DMA during the next vblank. The buffer being
+/// sent is left alone until then, so the back buffer can be edited again right away. Like other
+/// queued DMA sources, the table must stay alive until the next vblank after a swap, which in
+/// practice means keeping it in a static.
+pub struct HScrollTable {
+    /// Both buffers, with the two planes' entries for each line interleaved, as in VRAM.
+    buffers: [[[i16; 2]; Scroller::LINES]; 2],
+    back: u8,
+    /// The frame of the last swap, whose DMA might still be waiting.
+    swapped_on: u32,
+}
+
+impl HScrollTable {
+    pub const fn new() -> Self {
+        Self {
+            buffers: [[[0; 2]; Scroller::LINES]; 2],
+            back: 0,
+            swapped_on: u32::MAX,
+        }
+    }
+
+    /// The table being edited, one `[plane A, plane B]` pair per line.
+    #[inline]
+    pub fn back_mut(&mut self) -> &mut [[i16; 2]; Scroller::LINES] {
+        &mut self.buffers[self.back as usize]
+    }
+
+    /// The table last sent, which is what's on screen from the vblank after the swap onwards.
+    #[inline]
+    pub fn front(&self) -> &[[i16; 2]; Scroller::LINES] {
+        &self.buffers[(self.back ^ 1) as usize]
+    }
+
+    /// Sets a line's scroll for `plane` in the back buffer.
+    #[inline]
+    pub fn set(&mut self, plane: Plane, line: usize, x: i16) {
+        self.back_mut()[line][plane as usize] = x;
+    }
+
+    /// Sets the scroll of a range of lines for `plane` in the back buffer.
+    pub fn fill(&mut self, plane: Plane, lines: core::ops::Range<usize>, x: i16) {
+        let end = lines.end.min(Scroller::LINES);
+        for entry in self.back_mut().get_mut(lines.start..end).into_iter().flatten() {
+            entry[plane as usize] = x;
+        }
+    }
+
+    /// Copies a plane's scroll from a `ParallaxLayers` table into the back buffer.
+    pub fn copy_from<const N: usize>(&mut self, plane: Plane, layers: &ParallaxLayers<N>) {
+        for (entry, &x) in self.back_mut().iter_mut().zip(layers.table()) {
+            entry[plane as usize] = x;
+        }
+    }
+
+    /// Queues the back buffer to be sent during the next vblank, and makes it the front buffer. The
+    /// new back buffer starts off as a copy of it.
+    ///
+    /// Returns false, without swapping, if the table was already swapped since the last vblank, or
+    /// if the DMA queue is full.
+    pub fn swap(&mut self) -> bool {
+        let frame = timing::elapsed_frames();
+        if frame == self.swapped_on {
+            return false;
+        }
+
+        let back = &self.buffers[self.back as usize];
+        let queued = DMACommand::new_transfer(
+            back.as_flattened(),
+            Address::VRAM(Scroller::hscroll_entry(Plane::A)),
+            None,
+        ).schedule().is_ok();
+        if !queued {
+            return false;
+        }
+
+        self.swapped_on = frame;
+        self.back ^= 1;
+        self.buffers[self.back as usize] = self.buffers[(self.back ^ 1) as usize];
+        true
+    }
+}
+
+impl Default for HScrollTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
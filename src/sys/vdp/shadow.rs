@@ -0,0 +1,192 @@
+use core::cell;
+
+use critical_section as cs;
+
+use super::palette::{Color, Palette, LINES, LINE_COLORS};
+use super::{Address, DMACommand, DmaQueue};
+
+/// The number of colors in CRAM.
+pub const CRAM_COLORS: usize = LINES * LINE_COLORS;
+/// The number of words in VSRAM: one for each plane in each of the 20 two-tile columns.
+pub const VSRAM_WORDS: usize = 40;
+
+/// A range of entries changed since the last flush, from `start` up to but not including `end`.
+#[derive(Clone, Copy)]
+struct Dirty {
+    start: u8,
+    end: u8,
+}
+
+impl Dirty {
+    const CLEAN: Self = Self { start: u8::MAX, end: 0 };
+
+    #[inline]
+    fn add(&mut self, start: usize, end: usize) {
+        self.start = self.start.min(start as u8);
+        self.end = self.end.max(end as u8);
+    }
+
+    #[inline]
+    fn range(&self) -> Option<(usize, usize)> {
+        (self.start < self.end).then_some((self.start as usize, self.end as usize))
+    }
+}
+
+// Statics, since the queued DMA reads them at vblank.
+static mut CRAM: [Color; CRAM_COLORS] = [Color::BLACK; CRAM_COLORS];
+static mut VSRAM: [i16; VSRAM_WORDS] = [0; VSRAM_WORDS];
+
+static DIRTY: cs::Mutex<cell::Cell<(Dirty, Dirty)>> = cs::Mutex::new(cell::Cell::new((Dirty::CLEAN, Dirty::CLEAN)));
+
+#[inline]
+fn mark_cram(cs: cs::CriticalSection, start: usize, end: usize) {
+    let cell = DIRTY.borrow(cs);
+    let (mut cram, vsram) = cell.get();
+    cram.add(start, end);
+    cell.set((cram, vsram));
+}
+
+#[inline]
+fn mark_vsram(cs: cs::CriticalSection, start: usize, end: usize) {
+    let cell = DIRTY.borrow(cs);
+    let (cram, mut vsram) = cell.get();
+    vsram.add(start, end);
+    cell.set((cram, vsram));
+}
+
+/// Sets color `index` of palette line `line` in the shadow. The colors changed in a frame are sent
+/// to CRAM in the next vblank, with one DMA covering all of them.
+///
+/// The shadow doesn't see colors written any other way, such as by a `Fader`, so a game should
+/// keep to one or the other for each line. Shadow colors are sent after anything else queued for
+/// the same vblank.
+#[inline]
+pub fn set_color(line: u8, index: u8, color: Color) {
+    let i = (line as usize % LINES) * LINE_COLORS + index as usize % LINE_COLORS;
+    super::super::with_cs::<1, 7, _>(|cs| {
+        unsafe { (&mut *(&raw mut CRAM))[i] = color };
+        mark_cram(cs, i, i + 1);
+    })
+}
+
+/// The color in the shadow, which is what CRAM will have after the next vblank.
+#[inline]
+pub fn color(line: u8, index: u8) -> Color {
+    let i = (line as usize % LINES) * LINE_COLORS + index as usize % LINE_COLORS;
+    super::super::with_cs::<1, 7, _>(|_| unsafe { (&*(&raw const CRAM))[i] })
+}
+
+/// Sets a whole palette line in the shadow.
+pub fn set_palette(line: u8, colors: &Palette) {
+    let start = (line as usize % LINES) * LINE_COLORS;
+    super::super::with_cs::<1, 7, _>(|cs| {
+        unsafe { (&mut *(&raw mut CRAM))[start..start + LINE_COLORS].copy_from_slice(colors) };
+        mark_cram(cs, start, start + LINE_COLORS);
+    })
+}
+
+pub fn palette(line: u8) -> Palette {
+    let start = (line as usize % LINES) * LINE_COLORS;
+    super::super::with_cs::<1, 7, _>(|_| {
+        let mut colors = [Color::BLACK; LINE_COLORS];
+        colors.copy_from_slice(unsafe { &(&*(&raw const CRAM))[start..start + LINE_COLORS] });
+        colors
+    })
+}
+
+/// Moves colors `start` up to `end` of palette line `line` along by one, in the shadow, with the
+/// last color going round to the start. This is the usual palette cycling effect for water, lava
+/// and the like, which `Cycler` runs every few frames.
+#[inline]
+pub fn rotate_colors(line: u8, start: u8, end: u8) {
+    rotate(line, start, end, true);
+}
+
+/// Like `rotate_colors`, the other way, with the first color going round to the end.
+#[inline]
+pub fn rotate_colors_back(line: u8, start: u8, end: u8) {
+    rotate(line, start, end, false);
+}
+
+fn rotate(line: u8, start: u8, end: u8, forward: bool) {
+    let base = (line as usize % LINES) * LINE_COLORS;
+    let (start, end) = (base + start as usize % LINE_COLORS, base + (end as usize).min(LINE_COLORS));
+    if start >= end {
+        return;
+    }
+    super::super::with_cs::<1, 7, _>(|cs| {
+        let colors = unsafe { &mut (&mut *(&raw mut CRAM))[start..end] };
+        if forward {
+            colors.rotate_right(1);
+        } else {
+            colors.rotate_left(1);
+        }
+        mark_cram(cs, start, end);
+    })
+}
+
+/// Sets VSRAM word `index` in the shadow. In full screen vertical scroll, words 0 and 1 are planes
+/// A and B. In two-tile column scroll, each column has a word for each plane, A first.
+#[inline]
+pub fn set_vscroll(index: u8, value: i16) {
+    let i = index as usize % VSRAM_WORDS;
+    super::super::with_cs::<1, 7, _>(|cs| {
+        unsafe { (&mut *(&raw mut VSRAM))[i] = value };
+        mark_vsram(cs, i, i + 1);
+    })
+}
+
+#[inline]
+pub fn vscroll(index: u8) -> i16 {
+    let i = index as usize % VSRAM_WORDS;
+    super::super::with_cs::<1, 7, _>(|_| unsafe { (&*(&raw const VSRAM))[i] })
+}
+
+/// Sets VSRAM words from `start` on in the shadow.
+///
+/// # Panics
+///
+/// Panics if `values` runs past the end of VSRAM.
+pub fn set_vscroll_words(start: u8, values: &[i16]) {
+    let (start, end) = (start as usize, start as usize + values.len());
+    if end > VSRAM_WORDS {
+        panic!("write past the end of VSRAM");
+    }
+    super::super::with_cs::<1, 7, _>(|cs| {
+        unsafe { (&mut *(&raw mut VSRAM))[start..end].copy_from_slice(values) };
+        mark_vsram(cs, start, end);
+    })
+}
+
+/// Marks the whole of both shadows to be sent in the next vblank, such as after something else
+/// has written over CRAM.
+pub fn mark_all_dirty() {
+    super::super::with_cs::<1, 7, _>(|cs| {
+        mark_cram(cs, 0, CRAM_COLORS);
+        mark_vsram(cs, 0, VSRAM_WORDS);
+    })
+}
+
+/// Queues the changed parts of the shadows. Whatever doesn't fit in the queue stays marked for the
+/// next vblank.
+///
+/// This is called by the vertical interrupt handler.
+pub(super) fn flush<const N: usize>(cs: cs::CriticalSection, queue: &mut DmaQueue<N>) {
+    let cell = DIRTY.borrow(cs);
+    let (mut cram, mut vsram) = cell.get();
+    if let Some((start, end)) = cram.range() {
+        let colors = unsafe { &(&*(&raw const CRAM))[start..end] };
+        let cmd = DMACommand::new_transfer(colors, Address::CRAM((start * 2) as u8), None);
+        if queue.push_back(cmd).is_ok() {
+            cram = Dirty::CLEAN;
+        }
+    }
+    if let Some((start, end)) = vsram.range() {
+        let words = unsafe { &(&*(&raw const VSRAM))[start..end] };
+        let cmd = DMACommand::new_transfer(words, Address::VSRAM((start * 2) as u8), None);
+        if queue.push_back(cmd).is_ok() {
+            vsram = Dirty::CLEAN;
+        }
+    }
+    cell.set((cram, vsram));
+}
@@ -0,0 +1,161 @@
+use super::{DMACommand, VRAMAddress};
+
+/// The size of a tile in VRAM, in bytes.
+pub const TILE_BYTES: usize = 32;
+
+/// A range of tiles allocated from a `TileAllocator`, which must be given back with `free`.
+///
+/// Defragmenting can move the range, so its tiles should be looked up with `TileAllocator::base`
+/// whenever they're drawn, rather than stored.
+#[must_use]
+#[derive(Debug, PartialEq, Eq)]
+pub struct TileHandle(u8);
+
+/// A range of tiles moved by `TileAllocator::defrag_step`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Relocation {
+    pub from: u16,
+    pub to: u16,
+    pub len: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Range {
+    start: u16,
+    len: u16,
+}
+
+/// Hands out ranges of tile indices in a region of VRAM, for sprite sheets and other art that's
+/// loaded and unloaded as the game goes on.
+///
+/// Ranges are placed as low as they'll fit, and up to `N` can be allocated at once. Loading and
+/// unloading in a different order leaves gaps behind, which `defrag_step` closes up.
+pub struct TileAllocator<const N: usize> {
+    first: u16,
+    end: u16,
+    ranges: [Option<Range>; N],
+}
+
+impl<const N: usize> TileAllocator<N> {
+    /// An allocator for the `count` tiles starting at tile index `first`.
+    pub const fn new(first: u16, count: u16) -> Self {
+        assert!(N <= u8::MAX as usize, "too many tile ranges");
+        Self {
+            first,
+            end: first + count,
+            ranges: [None; N],
+        }
+    }
+
+    #[inline]
+    fn ranges(&self) -> impl Iterator<Item = &Range> {
+        self.ranges.iter().flatten()
+    }
+
+    /// The lowest tile index at or after `from` with `len` free tiles.
+    fn find_gap(&self, from: u16, len: u16) -> Option<u16> {
+        let mut start = from;
+        while let Some(end) = self.ranges()
+            .filter(|r| r.start < start + len && start < r.start + r.len)
+            .map(|r| r.start + r.len)
+            .max()
+        {
+            start = end;
+        }
+        (start + len <= self.end).then_some(start)
+    }
+
+    /// Allocates `len` tiles. Returns `None` if there's no gap big enough, or no free handles.
+    pub fn alloc(&mut self, len: u16) -> Option<TileHandle> {
+        let slot = self.ranges.iter().position(Option::is_none)?;
+        let start = self.find_gap(self.first, len)?;
+        self.ranges[slot] = Some(Range { start, len });
+        Some(TileHandle(slot as u8))
+    }
+
+    /// Gives a range back. Its tiles are left in VRAM until something else is allocated over them.
+    #[inline]
+    pub fn free(&mut self, handle: TileHandle) {
+        self.ranges[handle.0 as usize] = None;
+    }
+
+    /// The first tile index of a range.
+    #[inline]
+    pub fn base(&self, handle: &TileHandle) -> u16 {
+        self.range(handle).start
+    }
+
+    /// The address of a range's first tile, for uploading to it.
+    #[inline]
+    pub fn address(&self, handle: &TileHandle) -> VRAMAddress {
+        VRAMAddress::from_tile_index(self.base(handle))
+    }
+
+    #[inline]
+    pub fn len(&self, handle: &TileHandle) -> u16 {
+        self.range(handle).len
+    }
+
+    #[inline]
+    fn range(&self, handle: &TileHandle) -> Range {
+        match self.ranges[handle.0 as usize] {
+            Some(range) => range,
+            None => panic!("tile handle was freed"),
+        }
+    }
+
+    /// The number of tiles not allocated, whether or not they're in one piece.
+    pub fn free_tiles(&self) -> u16 {
+        self.end - self.first - self.ranges().map(|r| r.len).sum::<u16>()
+    }
+
+    /// The size of the largest range that can be allocated right now.
+    pub fn largest_free(&self) -> u16 {
+        let mut largest = 0;
+        let mut start = self.first;
+        while start < self.end {
+            let next = self.ranges().map(|r| r.start).filter(|&s| s >= start).min().unwrap_or(self.end);
+            largest = largest.max(next - start);
+            start = self.ranges().filter(|r| r.start == next).map(|r| r.start + r.len).max().unwrap_or(self.end);
+        }
+        largest
+    }
+
+    /// Moves the lowest range that has a gap below it down into the gap, with a VRAM to VRAM copy
+    /// queued for the next vblank. Ranges over `max_len` tiles are left where they are, to keep the
+    /// copy within the vblank's DMA bandwidth.
+    ///
+    /// Call this once per frame while nothing is being shown that the move could tear, such as
+    /// during a palette fade to black or while the game is paused, until it returns `None`. The
+    /// copy and the uploads that use the new position land in the same vblank, so anything drawn
+    /// with `base` after this returns picks up the move.
+    ///
+    /// Returns `None` if there's nothing left to move, or the DMA queue is full.
+    pub fn defrag_step(&mut self, max_len: u16) -> Option<Relocation> {
+        let mut packed = self.first;
+        loop {
+            let (slot, range) = self.ranges.iter().enumerate()
+                .filter_map(|(slot, range)| range.map(|range| (slot, range)))
+                .filter(|(_, range)| range.start >= packed)
+                .min_by_key(|(_, range)| range.start)?;
+
+            if range.start == packed || range.len > max_len {
+                packed = range.start + range.len;
+                continue;
+            }
+
+            // Copies go upwards through VRAM, so moving down is safe even when the ranges overlap.
+            DMACommand::new_copy(
+                VRAMAddress::from_tile_index(range.start),
+                VRAMAddress::from_tile_index(packed),
+                range.len as usize * TILE_BYTES,
+                None,
+            )
+            .schedule()
+            .ok()?;
+
+            self.ranges[slot] = Some(Range { start: packed, ..range });
+            return Some(Relocation { from: range.start, to: packed, len: range.len });
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use core::cell;
+
+use critical_section as cs;
+
+use crate::sys;
+
+use super::VDP;
+
+/// The maximum number of vblank callbacks that can be registered at once, on top of the built-in
+/// ones.
+pub const MAX_CALLBACKS: usize = 8;
+
+/// Suggested priorities. Callbacks with lower priorities run first.
+pub const PRIORITY_HIGH: u8 = 0x00;
+pub const PRIORITY_NORMAL: u8 = 0x80;
+pub const PRIORITY_LOW: u8 = 0xFF;
+
+/// The priorities of the built-in callbacks. Callbacks registered with the same priority run after
+/// the built-in one.
+pub const PRIORITY_INPUT: u8 = 0x00;
+pub const PRIORITY_AUDIO: u8 = 0x40;
+pub const PRIORITY_WAIT: u8 = 0xF0;
+pub const PRIORITY_DMA: u8 = 0xF8;
+
+/// Unregisters a vblank callback when passed to `unregister`.
+#[must_use]
+#[derive(Debug, PartialEq, Eq)]
+pub struct VBlankToken(u8);
+
+#[derive(Clone, Copy)]
+struct Entry {
+    id: u8,
+    priority: u8,
+    callback: fn(cs::CriticalSection),
+}
+
+impl Entry {
+    const EMPTY: Self = Self {
+        id: 0,
+        priority: 0,
+        callback: |_| {},
+    };
+}
+
+/// The work every vblank does, as priorities and callbacks. These have no tokens, so they stay
+/// registered.
+const BUILTINS: &[(u8, fn(cs::CriticalSection))] = &[
+    (PRIORITY_INPUT, sys::io::poll),
+    #[cfg(feature = "audio")]
+    (PRIORITY_AUDIO, tick_audio),
+    (PRIORITY_WAIT, run_wait_handler),
+    (PRIORITY_DMA, super::flush_dma),
+];
+
+const CAPACITY: usize = BUILTINS.len() + MAX_CALLBACKS;
+
+struct Registry {
+    entries: [Entry; CAPACITY],
+    len: u8,
+    next_id: u8,
+}
+
+impl Registry {
+    const fn new() -> Self {
+        let mut entries = [Entry::EMPTY; CAPACITY];
+        let mut i = 0;
+        while i < BUILTINS.len() {
+            let (priority, callback) = BUILTINS[i];
+            entries[i] = Entry { id: i as u8, priority, callback };
+            i += 1;
+        }
+        Self {
+            entries,
+            len: BUILTINS.len() as u8,
+            next_id: BUILTINS.len() as u8,
+        }
+    }
+}
+
+static REGISTRY: cs::Mutex<cell::RefCell<Registry>> = cs::Mutex::new(cell::RefCell::new(Registry::new()));
+
+/// The handler `VDP::wait_for_vblank` is waiting on, cleared once it's been run.
+static WAIT_HANDLER: cs::Mutex<cell::Cell<Option<fn(cs::CriticalSection)>>> = cs::Mutex::new(cell::Cell::new(None));
+
+/// Registers a callback to run in every vblank, until it's unregistered.
+///
+/// Where it runs among the built-in callbacks is set by its priority: input is polled at
+/// `PRIORITY_INPUT`, the music and sound effects step at `PRIORITY_AUDIO`, the handler passed to
+/// `VDP::wait_for_vblank` runs at `PRIORITY_WAIT`, and queued DMA is sent at `PRIORITY_DMA`.
+/// Anything scheduled by a callback that runs after that is sent in the next vblank. Callbacks
+/// with the same priority run in the order they were registered.
+///
+/// Returns `None` if `MAX_CALLBACKS` callbacks are already registered.
+pub fn register(priority: u8, callback: fn(cs::CriticalSection)) -> Option<VBlankToken> {
+    sys::with_cs::<1, 7, _>(|cs| {
+        let mut registry = REGISTRY.borrow_ref_mut(cs);
+        let len = registry.len as usize;
+        if len == CAPACITY {
+            return None;
+        }
+
+        // Skip ids still held by a long lived callback.
+        let mut id = registry.next_id;
+        while registry.entries[..len].iter().any(|entry| entry.id == id) {
+            id = id.wrapping_add(1);
+        }
+        registry.next_id = id.wrapping_add(1);
+
+        let index = registry.entries[..len].iter().position(|entry| entry.priority > priority).unwrap_or(len);
+        registry.entries.copy_within(index..len, index + 1);
+        registry.entries[index] = Entry { id, priority, callback };
+        registry.len += 1;
+
+        Some(VBlankToken(id))
+    })
+}
+
+/// Unregisters a callback, so it won't run from the next vblank onward.
+pub fn unregister(token: VBlankToken) {
+    sys::with_cs::<1, 7, _>(|cs| {
+        let mut registry = REGISTRY.borrow_ref_mut(cs);
+        let len = registry.len as usize;
+        if let Some(index) = registry.entries[..len].iter().position(|entry| entry.id == token.0) {
+            registry.entries.copy_within(index + 1..len, index);
+            registry.len -= 1;
+        }
+    })
+}
+
+/// Runs every registered callback. Called from the vblank handler.
+pub(super) fn run(cs: cs::CriticalSection) {
+    // Callbacks are run from a copy, so they're free to register and unregister callbacks.
+    let (entries, len) = {
+        let registry = REGISTRY.borrow_ref(cs);
+        (registry.entries, registry.len as usize)
+    };
+
+    for entry in &entries[..len] {
+        (entry.callback)(cs);
+    }
+}
+
+/// Has `handler` run in the next vblank, at `PRIORITY_WAIT`, and waits until it has.
+pub(super) fn wait(handler: fn(cs::CriticalSection)) {
+    sys::with_cs::<1, 7, _>(|cs| WAIT_HANDLER.borrow(cs).set(Some(handler)));
+    while sys::with_cs::<1, 7, _>(|cs| WAIT_HANDLER.borrow(cs).get().is_some()) {
+        core::hint::spin_loop();
+    }
+}
+
+fn run_wait_handler(cs: cs::CriticalSection) {
+    // The handler may write to the VDP, which would upset a fill or copy that's still going, so
+    // it waits for a vblank where the VDP is free.
+    if VDP::status().dma_in_progress() {
+        return;
+    }
+    if let Some(handler) = WAIT_HANDLER.borrow(cs).take() {
+        handler(cs);
+    }
+}
+
+#[cfg(feature = "audio")]
+fn tick_audio(cs: cs::CriticalSection) {
+    sys::audio::music::tick(cs);
+    sys::audio::sfx::tick(cs);
+    sys::audio::levels::tick(cs);
+    sys::timing::fm_timer_tick(cs);
+}
@@ -0,0 +1,226 @@
+use core::ptr;
+
+/// Reads "MARS" when a 32X is attached.
+const ID: *const [u8; 4] = 0xA130EC as _;
+
+/// The adapter control register: the adapter's enable, the SH2s' reset, and who has the 32X's VDP.
+const ADAPTER_CTRL: *mut u16 = 0xA15100 as _;
+/// Raises the command interrupt on either SH2.
+const INT_CTRL: *mut u16 = 0xA15102 as _;
+/// Which 1MB bank of the cartridge shows at 0x900000 once the adapter is on.
+const BANK_SET: *mut u16 = 0xA15104 as _;
+/// The SH2s' DREQ control, whose RV bit puts the cartridge back at 0 for the 68000.
+const DREQ_CTRL: *mut u16 = 0xA15106 as _;
+const COMM: *mut u16 = 0xA15120 as _;
+
+/// The number of communication words shared with the SH2s.
+pub const COMM_WORDS: usize = 8;
+
+const ADEN: u16 = 0x0001;
+const RES: u16 = 0x0002;
+const FM: u16 = 0x8000;
+const RV: u16 = 0x0001;
+
+/// How many times `init` checks for the SH2s before giving up, which is a little over a second.
+const INIT_TIMEOUT: u32 = 0x20000;
+
+/// Room on the stack for the part of `init` that can't run from the cartridge, which is 24 words.
+const BOOT_CODE_WORDS: usize = 32;
+
+/// Why `init` couldn't bring the 32X up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    NotPresent,
+    /// The SH2s didn't report in. Usually their programs didn't pass the boot ROM's checks.
+    Timeout,
+}
+
+/// Which side can get at the 32X's VDP registers, palette and framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VdpAccess {
+    /// The 68000, at 0xA15180 and 0x840000.
+    Md,
+    Sh2,
+}
+
+/// Returns true if a 32X is attached.
+#[inline]
+pub fn is_present() -> bool {
+    unsafe { ptr::read_volatile(ID) == *b"MARS" }
+}
+
+#[inline]
+fn adapter_ctrl() -> u16 {
+    unsafe { ptr::read_volatile(ADAPTER_CTRL) }
+}
+
+#[inline]
+fn modify_adapter_ctrl(mask: u16, set: bool) {
+    let value = adapter_ctrl();
+    unsafe { ptr::write_volatile(ADAPTER_CTRL, if set { value | mask } else { value & !mask }) }
+}
+
+/// Returns true once the adapter is on, and the 32X is taking part.
+#[inline]
+pub fn is_adapter_enabled() -> bool {
+    adapter_ctrl() & ADEN != 0
+}
+
+/// Turns the adapter on.
+///
+/// # Safety
+///
+/// The cartridge moves to 0x880000, and the 32X's own vector table takes its place at 0, so this
+/// has to be called from code running in the cartridge's mirror at 0x880000, with the vector table
+/// set up for it. Sega's startup code does this itself.
+#[inline]
+pub unsafe fn enable_adapter() {
+    modify_adapter_ctrl(ADEN, true);
+}
+
+/// Holds both SH2s in reset, or lets them run.
+#[inline]
+pub fn set_sh2_reset(reset: bool) {
+    modify_adapter_ctrl(RES, !reset);
+}
+
+/// Gives the 32X's VDP to one side or the other. The framebuffer has to be handed to the SH2s
+/// before they draw into it, and back before the 68000 touches it.
+#[inline]
+pub fn set_vdp_access(access: VdpAccess) {
+    modify_adapter_ctrl(FM, access == VdpAccess::Sh2);
+}
+
+#[inline]
+pub fn vdp_access() -> VdpAccess {
+    if adapter_ctrl() & FM != 0 { VdpAccess::Sh2 } else { VdpAccess::Md }
+}
+
+/// Raises the command interrupt on the master SH2.
+#[inline]
+pub fn interrupt_master() {
+    unsafe { ptr::write_volatile(INT_CTRL, ptr::read_volatile(INT_CTRL) | 0x0001) }
+}
+
+/// Raises the command interrupt on the slave SH2.
+#[inline]
+pub fn interrupt_slave() {
+    unsafe { ptr::write_volatile(INT_CTRL, ptr::read_volatile(INT_CTRL) | 0x0002) }
+}
+
+/// Shows 1MB bank `bank` of the cartridge at 0x900000, which is how the 68000 reaches ROM past the
+/// first megabyte once the adapter is on.
+#[inline]
+pub fn set_rom_bank(bank: u8) {
+    unsafe { ptr::write_volatile(BANK_SET, (bank & 0x3) as u16) }
+}
+
+/// One of the communication words shared with the SH2s, from 0 to 7.
+#[inline]
+pub fn comm(index: usize) -> u16 {
+    unsafe { ptr::read_volatile(COMM.add(index % COMM_WORDS)) }
+}
+
+#[inline]
+pub fn set_comm(index: usize, value: u16) {
+    unsafe { ptr::write_volatile(COMM.add(index % COMM_WORDS), value) }
+}
+
+/// Whether the cartridge shows at 0 for the 68000 while the adapter is on. The SH2s can't read
+/// the cartridge while it does.
+#[inline]
+pub fn is_rom_at_zero() -> bool {
+    unsafe { ptr::read_volatile(DREQ_CTRL) & RV != 0 }
+}
+
+/// The 68000's half of bringing a 32X up.
+///
+/// Checks for "MARS", clears communication words 0 to 3, then turns the adapter on and takes the
+/// SH2s out of reset. It waits for them to report in the way Sega's SH2 startup code does, with
+/// "M_OK" in words 0 and 1 and "S_OK" in words 2 and 3, clears the words again, and leaves the
+/// 68000 with the VDP.
+///
+/// Turning the adapter on moves the cartridge to 0x880000, which mdrs isn't linked to run from, so
+/// that part runs from the stack with interrupts off. The SH2s read their programs from the
+/// cartridge while they start, and once they've reported in, RV is set to bring the cartridge back
+/// to 0. From then on the SH2s can't read the cartridge, so anything they need later has to be
+/// handed over through their SDRAM or the communication words.
+///
+/// The cartridge still needs the 32X's own header at 0x3C0 and Sega's security code for the SH2
+/// boot ROM to start their programs, which mdrs doesn't provide.
+pub fn init() -> Result<(), Error> {
+    if !is_present() {
+        return Err(Error::NotPresent);
+    }
+
+    for word in 0..4 {
+        set_comm(word, 0);
+    }
+    let reported = if is_adapter_enabled() {
+        set_sh2_reset(false);
+        let reported = |word: usize, tag: &[u8; 4]| {
+            comm(word) == u16::from_be_bytes([tag[0], tag[1]])
+                && comm(word + 1) == u16::from_be_bytes([tag[2], tag[3]])
+        };
+        let both = || reported(0, b"M_OK") && reported(2, b"S_OK");
+        (0..INIT_TIMEOUT).any(|_| both())
+    } else {
+        super::with_cs::<1, 7, _>(|_| boot_from_stack())
+    };
+    if !reported {
+        return Err(Error::Timeout);
+    }
+
+    for word in 0..4 {
+        set_comm(word, 0);
+    }
+    set_vdp_access(VdpAccess::Md);
+    Ok(())
+}
+
+/// Copies the code after `2` onto the stack and runs it there, since the cartridge is gone from 0
+/// between setting ADEN and setting RV. Returns false if the SH2s didn't report in.
+#[inline(never)]
+fn boot_from_stack() -> bool {
+    let mut code = [0u16; BOOT_CODE_WORDS];
+    let timeout: u32;
+    unsafe {
+        core::arch::asm!(
+            "lea    (2f,%pc),{src}",
+            "move.l {buf},{dst}",
+            "move.w #{words},{count}",
+            "1:",
+            "move.w ({src})+,({dst})+",
+            "sub.w  #1,{count}",
+            "bne    1b",
+            "jsr    ({buf})",
+            "bra    6f",
+            // ADEN, then RES to let the SH2s run.
+            "2:",
+            "or.b   #1,(1,{ctrl})",
+            "or.b   #2,(1,{ctrl})",
+            // "M_OK" and "S_OK" in the communication words.
+            "3:",
+            "cmpi.l #0x4D5F4F4B,(0x20,{ctrl})",
+            "bne    4f",
+            "cmpi.l #0x535F4F4B,(0x24,{ctrl})",
+            "beq    5f",
+            "4:",
+            "sub.l  #1,{timeout}",
+            "bne    3b",
+            // RV, which brings the cartridge back before returning to it.
+            "5:",
+            "or.b   #1,(7,{ctrl})",
+            "rts",
+            "6:",
+            ctrl = in(reg_addr) ADAPTER_CTRL,
+            buf = in(reg_addr) code.as_mut_ptr(),
+            words = const BOOT_CODE_WORDS,
+            timeout = inout(reg_data) INIT_TIMEOUT => timeout,
+            src = out(reg_addr) _,
+            dst = out(reg_addr) _,
+            count = out(reg_data) _,
+        );
+    }
+    timeout != 0
+}
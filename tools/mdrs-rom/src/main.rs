@@ -0,0 +1,66 @@
+//! Turns a linked mdrs game into a ROM ready to flash.
+//!
+//! ```text
+//! mdrs-rom <ELF or raw binary> [ROM]
+//! ```
+//!
+//! An ELF is stripped down to a raw binary with the m68k `objcopy` first. The ROM is then padded
+//! with 0xFF to a power of two, at least 128KB, has its size and checksum filled in, and has the
+//! titles and regions from `MDRS_ROM_TITLE` and `MDRS_ROM_REGION` written into its header. It's
+//! written to `ROM`, or next to the input with a `.bin` extension.
+//!
+//! Cargo passes the ELF as the first argument to a runner, so setting this as the runner for the
+//! m68k target makes `cargo run --release` build a finished ROM, and `cargo run --release --
+//! game.bin` write it to `game.bin`.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+mod rom;
+mod toolchain;
+
+use rom::HeaderPatch;
+use toolchain::Toolchain;
+
+/// The first bytes of every ELF file.
+const ELF_MAGIC: &[u8] = b"\x7FELF";
+
+fn main() -> ExitCode {
+    let mut args = env::args_os().skip(1);
+    let Some(input) = args.next().map(PathBuf::from) else {
+        eprintln!("usage: mdrs-rom <ELF or raw binary> [ROM]");
+        return ExitCode::FAILURE;
+    };
+    let rom = args.next().map(PathBuf::from).unwrap_or_else(|| input.with_extension("bin"));
+
+    match finish(&input, &rom) {
+        Ok(size) => {
+            println!("wrote {} ({}KB)", rom.display(), size / 1024);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{}: {}", input.display(), err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Writes the finished ROM for `input` to `rom`, and returns its size.
+fn finish(input: &Path, rom: &Path) -> Result<usize, String> {
+    let mut data = fs::read(input).map_err(|err| err.to_string())?;
+    if data.starts_with(ELF_MAGIC) {
+        if let Some(dir) = rom.parent() {
+            fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        Toolchain::find().run("objcopy", |objcopy| {
+            objcopy.args(["-O", "binary"]).arg(input).arg(rom);
+        });
+        data = fs::read(rom).map_err(|err| err.to_string())?;
+    }
+
+    rom::finish_rom(&mut data, &HeaderPatch::from_env())?;
+    fs::write(rom, &data).map_err(|err| err.to_string())?;
+    Ok(data.len())
+}
@@ -0,0 +1,76 @@
+use std::env;
+
+/// Override the titles and the region letters, such as "JU", in the ROMs `finish_rom` makes.
+pub const TITLE_VAR: &str = "MDRS_ROM_TITLE";
+pub const REGION_VAR: &str = "MDRS_ROM_REGION";
+
+/// Where the header's fields live in a raw ROM, and where the checksummed part starts.
+const DOMESTIC_TITLE_OFFSET: usize = 0x120;
+const OVERSEAS_TITLE_OFFSET: usize = 0x150;
+const TITLE_LEN: usize = 48;
+const CHECKSUM_OFFSET: usize = 0x18E;
+const ROM_END_OFFSET: usize = 0x1A4;
+const REGION_OFFSET: usize = 0x1F0;
+const REGION_LEN: usize = 16;
+const CHECKSUM_START: usize = 0x200;
+
+/// The smallest ROM `finish_rom` pads to, the smallest size of cartridge flash commonly sold.
+const MIN_ROM_SIZE: usize = 0x20000;
+
+/// Header fields to overwrite in a linked ROM, so one build can be retitled or released for other
+/// regions without recompiling.
+pub struct HeaderPatch {
+    title: Option<String>,
+    region: Option<String>,
+}
+
+impl HeaderPatch {
+    /// The title and region set in `MDRS_ROM_TITLE` and `MDRS_ROM_REGION`, if any.
+    pub fn from_env() -> Self {
+        Self {
+            title: env::var(TITLE_VAR).ok(),
+            region: env::var(REGION_VAR).ok(),
+        }
+    }
+}
+
+/// Writes `text` into a space padded header field.
+fn set_field(data: &mut [u8], offset: usize, len: usize, text: &str, name: &str) -> Result<(), String> {
+    if !text.is_ascii() || text.len() > len {
+        return Err(format!("the {} has to be at most {} ASCII characters", name, len));
+    }
+    let field = &mut data[offset..offset + len];
+    field.fill(b' ');
+    field[..text.len()].copy_from_slice(text.as_bytes());
+    Ok(())
+}
+
+/// Makes a raw ROM ready to flash: applies `patch` to the header, pads the ROM with 0xFF up to a
+/// power of two and records the new size in the header, then writes the sum of every word after
+/// the header into it, as `sys::header::rom_checksum_valid` expects.
+pub fn finish_rom(data: &mut Vec<u8>, patch: &HeaderPatch) -> Result<(), String> {
+    if data.len() < CHECKSUM_START {
+        return Err("too small to have a header".into());
+    }
+
+    if let Some(title) = &patch.title {
+        set_field(data, DOMESTIC_TITLE_OFFSET, TITLE_LEN, title, "title")?;
+        set_field(data, OVERSEAS_TITLE_OFFSET, TITLE_LEN, title, "title")?;
+    }
+    if let Some(region) = &patch.region {
+        if region.is_empty() || !region.chars().all(|c| "JUE".contains(c)) {
+            return Err(format!("the region has to be made of J, U and E, not {:?}", region));
+        }
+        set_field(data, REGION_OFFSET, REGION_LEN, region, "region")?;
+    }
+
+    let size = data.len().next_power_of_two().max(MIN_ROM_SIZE);
+    data.resize(size, 0xFF);
+    data[ROM_END_OFFSET..ROM_END_OFFSET + 4].copy_from_slice(&(size as u32 - 1).to_be_bytes());
+
+    let checksum = data[CHECKSUM_START..].chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], word.get(1).copied().unwrap_or(0)]))
+        .fold(0u16, u16::wrapping_add);
+    data[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 2].copy_from_slice(&checksum.to_be_bytes());
+    Ok(())
+}
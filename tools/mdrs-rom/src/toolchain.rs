@@ -0,0 +1,50 @@
+use std::env;
+use std::process::Command;
+
+/// The prefix of the m68k GCC and binutils to use, such as `m68k-elf-`, if they aren't one of
+/// `PREFIXES`.
+pub const PREFIX_VAR: &str = "MDRS_M68K_PREFIX";
+
+/// The prefixes the usual cross toolchains are installed with, tried in order.
+const PREFIXES: &[&str] = &["m68k-linux-gnu-", "m68k-elf-", "m68k-unknown-elf-", "m68k-none-elf-"];
+
+/// The m68k GCC and binutils, which assemble the vector table and turn ELFs into ROMs.
+pub struct Toolchain {
+    prefix: String,
+}
+
+impl Toolchain {
+    /// Uses the prefix in `MDRS_M68K_PREFIX`, or the first of `PREFIXES` whose GCC is on the
+    /// `PATH`.
+    pub fn find() -> Self {
+        if let Ok(prefix) = env::var(PREFIX_VAR) {
+            return Self { prefix };
+        }
+        let paths = env::var_os("PATH").unwrap_or_default();
+        let found = PREFIXES.iter().find(|prefix| {
+            let gcc = format!("{}gcc{}", prefix, env::consts::EXE_SUFFIX);
+            env::split_paths(&paths).any(|dir| dir.join(&gcc).is_file())
+        });
+        match found {
+            Some(prefix) => Self { prefix: prefix.to_string() },
+            None => panic!(
+                "couldn't find an m68k GCC on the PATH, tried {}; install one, or set {} to its prefix",
+                PREFIXES.iter().map(|prefix| format!("{}gcc", prefix)).collect::<Vec<_>>().join(", "),
+                PREFIX_VAR,
+            ),
+        }
+    }
+
+    /// Runs one of the tools, such as `"ar"`, set up by `configure`, and panics if it can't be run
+    /// or doesn't succeed.
+    pub fn run(&self, tool: &str, configure: impl FnOnce(&mut Command)) {
+        let name = format!("{}{}", self.prefix, tool);
+        let mut command = Command::new(&name);
+        configure(&mut command);
+        match command.status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => panic!("{} failed with {}", name, status),
+            Err(err) => panic!("couldn't run {}: {}", name, err),
+        }
+    }
+}